@@ -1,6 +1,10 @@
-use std::{env, error::Error, process::Command};
+use std::{env, error::Error, fs, path::Path, process::Command};
 use time::OffsetDateTime;
 
+// The same scanner the emulator uses to find its own unimplemented functions
+#[path = "../gm8emulator/kernel_functions.rs"]
+mod kernel_functions;
+
 fn main() -> Result<(), Box<dyn Error>> {
     // build date
     let time = OffsetDateTime::now_utc();
@@ -15,6 +19,9 @@ fn main() -> Result<(), Box<dyn Error>> {
     let target_triple = env::var("TARGET")?;
     println!("cargo:rustc-env=TARGET_TRIPLE={}", target_triple);
 
+    // GML functions known to GM8Emulator, for the inspect subcommand
+    emulator_functions()?;
+
     // icon
     #[cfg(target_os = "windows")]
     {
@@ -25,3 +32,33 @@ fn main() -> Result<(), Box<dyn Error>> {
 
     Ok(())
 }
+
+/// Writes out which GML functions GM8Emulator has mappings for, and which of those are still `unimplemented!`,
+/// by scanning its source. If the emulator's source can't be read, both lists are left empty.
+fn emulator_functions() -> Result<(), Box<dyn Error>> {
+    let src = Path::new("../gm8emulator/src");
+    let scanned = kernel_functions::scan(src);
+    let available = scanned.is_ok();
+    let (known, unimplemented) = scanned.unwrap_or_default();
+    if available {
+        println!("cargo:rerun-if-changed={}", src.join("gml/mappings.rs").display());
+        println!("cargo:rerun-if-changed={}", src.join("gml/kernel.rs").display());
+        println!("cargo:rerun-if-changed=../gm8emulator/kernel_functions.rs");
+        // Emitting the above stops cargo from rerunning this whenever anything in the package changes, so keep that
+        println!("cargo:rerun-if-changed=.");
+    }
+
+    let out_path = Path::new(&env::var("OUT_DIR")?).join("emulator_functions.rs");
+    fs::write(
+        out_path,
+        format!(
+            concat!(
+                "pub const AVAILABLE: bool = {};\n",
+                "pub static KNOWN: &[&str] = &{:?};\n",
+                "pub static UNIMPLEMENTED: &[&str] = &{:?};\n",
+            ),
+            available, known, unimplemented,
+        ),
+    )?;
+    Ok(())
+}
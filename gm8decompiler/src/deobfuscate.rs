@@ -1,8 +1,7 @@
 // Deobfuscates games processed with Schreib's GM Obfuscator.
 //
-// This entire file is manually writing an AST back to GML,
-// and is really disgusting - read at your own risk.
-// You have been warned.
+// Code is parsed and printed back out by gml_parser's printer, with a Rewrite
+// which simplifies constants and renames fields, scripts and assets as it goes.
 
 use crate::mappings;
 use gm8exe::{
@@ -11,12 +10,10 @@ use gm8exe::{
 };
 use gml_parser::{
     ast::{self, AST},
+    printer::{Name, Place, Printer, Rewrite, Style},
     token::Operator,
 };
-use std::{
-    collections::{HashMap, HashSet},
-    io::Write,
-};
+use std::collections::{HashMap, HashSet};
 
 #[derive(Clone, Copy, Eq, PartialEq)]
 pub enum Mode {
@@ -31,15 +28,10 @@ struct DeobfState {
     vars: HashSet<&'static [u8]>,
 }
 
-struct ExprWriter<'a, 'b, 'c> {
+/// Simplifies constant expressions and renames obfuscated names as code is printed.
+struct Renamer<'a, 'b> {
     assets: &'a GameAssets,
     deobf: &'b mut DeobfState,
-    indent: usize,
-    indent_str: String,
-    output: &'c mut Vec<u8>,
-
-    is_gml_expr: bool,        // whether we're in a gml expr
-    group_skip_newline: bool, // overrides writing a newline after a group
 }
 
 pub fn process(assets: &mut GameAssets) {
@@ -256,42 +248,21 @@ pub fn process(assets: &mut GameAssets) {
 
 impl DeobfState {
     pub fn process_gml(&mut self, input: &[u8], assets: &GameAssets) -> Result<Vec<u8>, ast::Error> {
-        let mut output = Vec::new();
         let ast = AST::new(input)?;
-
-        let mut writer = ExprWriter {
-            assets,
-            deobf: self,
-            indent: 0,
-            indent_str: "    ".into(),
-            output: &mut output,
-
-            is_gml_expr: false,
-            group_skip_newline: false,
-        };
-
-        for expr in ast {
-            writer.process_expr(&expr);
-        }
-
-        Ok(output)
+        let style = Style::default();
+        let mut renamer = Renamer { assets, deobf: self };
+        let mut printer = Printer::new(&style, &mut renamer);
+        printer.statements(&ast);
+        Ok(printer.finish())
     }
 
     pub fn process_expression(&mut self, input: &[u8], assets: &GameAssets) -> Result<Vec<u8>, ast::Error> {
-        let mut output = Vec::new();
         let expr = AST::expression(input)?;
-        let mut writer = ExprWriter {
-            assets,
-            deobf: self,
-            indent: 0,
-            indent_str: "    ".into(),
-            output: &mut output,
-
-            is_gml_expr: true,
-            group_skip_newline: false,
-        };
-        writer.process_expr(&expr);
-        Ok(output)
+        let style = Style::default();
+        let mut renamer = Renamer { assets, deobf: self };
+        let mut printer = Printer::new(&style, &mut renamer);
+        printer.expression(&expr);
+        Ok(printer.finish())
     }
 
     pub fn register_field(&mut self, field: &[u8]) -> usize {
@@ -358,438 +329,79 @@ impl DeobfState {
     }
 }
 
-impl<'a, 'b, 'c> ExprWriter<'a, 'b, 'c> {
-    pub fn process_expr(&mut self, ex: &'_ ast::Expr) {
-        macro_rules! push_str {
-            ($lit: literal) => {{
-                self.output.extend_from_slice(($lit).as_bytes());
-            }};
-        }
-
-        fn write_wrapped(writer: &mut ExprWriter, expr: &ast::Expr) {
-            writer.output.push(b'(');
-            writer.process_expr(expr);
-            writer.output.push(b')');
-        }
-
-        match ex {
-            ast::Expr::LiteralIdentifier(expr) => {
-                if let Some(simple) = self.deobf.simplify(&ast::Expr::LiteralIdentifier(expr), self.assets) {
-                    let _ = write!(self.output, "{}", simple);
-                } else if self.deobf.vars.get(expr).is_some() || expr == b"pi" {
-                    self.output.extend_from_slice(expr);
-                } else {
-                    self.write_field(expr);
-                }
-            },
-            ast::Expr::LiteralReal(real) => {
-                let _ = write!(self.output, "{}", real);
-            },
-            ast::Expr::LiteralString(string) => {
-                let quote = if string.iter().any(|&x| x == b'"') { b'\'' } else { b'"' };
-                self.output.push(quote);
-                self.output.extend_from_slice(string);
-                self.output.push(quote);
-            },
-            ast::Expr::Unary(expr) => {
-                let op = op_to_str(expr.op);
-                self.output.extend_from_slice(op);
-                let prev_state = self.is_gml_expr;
-                self.is_gml_expr = true;
-                if let Some(simple) = self.deobf.simplify(&expr.child, self.assets) {
-                    self.process_expr(&ast::Expr::LiteralReal(simple));
-                } else {
-                    match &expr.child {
-                        ast::Expr::Binary(b) if !matches!(b.op, Operator::Deref | Operator::Index) => {
-                            write_wrapped(self, &expr.child)
-                        },
-                        _ => self.process_expr(&expr.child),
-                    }
-                }
-                self.is_gml_expr = prev_state;
-            },
-            ast::Expr::Binary(expr) => {
-                let prev_state = self.is_gml_expr;
-                self.is_gml_expr = true;
-                if let Some(simple) = self.deobf.simplify(ex, self.assets) {
-                    self.process_expr(&ast::Expr::LiteralReal(simple));
-                } else if expr.op == Operator::Index {
-                    // array indexing
-                    self.process_expr(&expr.left);
-                    self.output.push(b'[');
-                    if let ast::Expr::Group(group) = &expr.right {
-                        for (i, expr) in group.iter().enumerate() {
-                            if i != 0 {
-                                push_str!(", ");
-                            }
-                            if let Some(simple) = self.deobf.simplify(expr, &self.assets) {
-                                self.process_expr(&ast::Expr::LiteralReal(simple));
-                            } else {
-                                self.process_expr(expr);
-                            }
-                        }
-                    } else {
-                        panic!("index rhs wasn't a group");
-                    }
-                    self.output.push(b']');
-                } else if expr.op == Operator::Deref {
-                    // Deref operator - lots of special cases here
-                    // If LHS can be simplified,
-                    if let Some(simple) = self.deobf.simplify(&expr.left, self.assets) {
-                        // If the simplified number is the ID of an object,
-                        let simple_int = simple as i32;
-                        if simple_int >= 0
-                            && self.assets.objects.get(simple_int as usize).is_some()
-                            && simple.fract() == 0.0
-                        {
-                            // Write eg "object123"
-                            let _ = write!(self.output, "object{}", simple_int);
-                        } else if simple.fract() == 0.0 {
-                            // Special cases for certain keywords, otherwise just write eg "(123)"
-                            match simple_int {
-                                -1 => self.output.extend_from_slice(b"self"),
-                                -2 => self.output.extend_from_slice(b"other"),
-                                -5 => self.output.extend_from_slice(b"global"),
-                                -7 => self.output.extend_from_slice(b"local"),
-                                i => {
-                                    let _ = write!(self.output, "({})", i);
-                                },
-                            }
-                        } else {
-                            // Write the whole LHS expression normally
-                            write_wrapped(self, &expr.left);
-                        }
-                    } else {
-                        // Write the LHS expression normally, wrapping it only if necessary
-                        match &expr.left {
-                            ast::Expr::LiteralIdentifier(_) => {
-                                self.process_expr(&expr.left);
-                            },
-                            ast::Expr::Binary(b) if matches!(b.op, Operator::Index | Operator::Deref) => {
-                                self.process_expr(&expr.left);
-                            },
-                            _ => {
-                                write_wrapped(self, &expr.left);
-                            },
-                        }
-                    }
-
-                    self.output.push(b'.');
-                    self.process_expr(&expr.right);
-                } else {
-                    // This is a "normal" binary expression with an operator between two things
-                    // Helper fn: write one side of the expr, deciding whether to paren-wrap it or not
-                    fn write_side(writer: &mut ExprWriter, expr: &ast::Expr, can_wrap: bool) {
-                        if let Some(simple) = writer.deobf.simplify(expr, writer.assets) {
-                            writer.process_expr(&ast::Expr::LiteralReal(simple));
-                        } else if can_wrap {
-                            match expr {
-                                ast::Expr::LiteralIdentifier(_)
-                                | ast::Expr::LiteralReal(_)
-                                | ast::Expr::LiteralString(_)
-                                | ast::Expr::Unary(_)
-                                | ast::Expr::Function(_) => {
-                                    writer.process_expr(expr);
-                                },
-                                ast::Expr::Binary(b) if matches!(b.op, Operator::Index | Operator::Deref) => {
-                                    writer.process_expr(expr);
-                                },
-                                _ => {
-                                    write_wrapped(writer, expr);
-                                },
-                            }
-                        } else {
-                            writer.process_expr(expr);
-                        }
-                    }
-
-                    let is_assign = matches!(
-                        expr.op,
-                        Operator::Assign
-                            | Operator::AssignAdd
-                            | Operator::AssignSubtract
-                            | Operator::AssignMultiply
-                            | Operator::AssignDivide
-                            | Operator::AssignBitwiseAnd
-                            | Operator::AssignBitwiseOr
-                            | Operator::AssignBitwiseXor
-                    );
-                    write_side(self, &expr.left, !is_assign);
-                    self.output.push(b' ');
-                    self.output.extend_from_slice(op_to_str(expr.op));
-                    self.output.push(b' ');
-                    write_side(self, &expr.right, !is_assign);
-                }
-                self.is_gml_expr = prev_state;
-                if !self.is_gml_expr {
-                    push_str!(";\r\n");
-                }
-            },
-            ast::Expr::DoUntil(expr) => {
-                push_str!("do ");
-                self.write_expr_grouped(&expr.body, false);
-                push_str!("until (");
-                self.is_gml_expr = true;
-                self.process_expr(&expr.cond);
-                self.is_gml_expr = false;
-                push_str!(");\r\n");
-            },
-            ast::Expr::For(expr) => {
-                fn remove_truncate(x: &mut Vec<u8>, pat: &[u8]) {
-                    if x.ends_with(pat) {
-                        x.truncate(x.len() - pat.len());
-                    }
-                }
-
-                push_str!("for (");
-                self.is_gml_expr = true;
-                self.process_expr(&expr.start);
-                remove_truncate(&mut self.output, b"\r\n");
-                push_str!("; ");
-                self.process_expr(&expr.cond);
-                push_str!("; ");
-                self.process_expr(&expr.step);
-                remove_truncate(&mut self.output, b"\r\n");
-                remove_truncate(&mut self.output, b";");
-                push_str!(") ");
-                self.is_gml_expr = false;
-                self.write_expr_grouped(&expr.body, true);
-            },
-            ast::Expr::Function(expr) => {
-                if let Some(idx) = self
-                    .assets
-                    .scripts
-                    .iter()
-                    .enumerate()
-                    .filter_map(|(i, o)| o.as_ref().map(|x| (i, x)))
-                    .find(|(_, scr)| &*scr.name.0 == expr.name)
-                    .map(|(i, _)| i)
-                {
-                    let _ = write!(self.output, "script{}", idx);
-                } else {
-                    self.output.extend_from_slice(expr.name);
-                }
-                self.output.push(b'(');
-                let prev_expr_state = self.is_gml_expr;
-                self.is_gml_expr = true;
-                for (i, param) in expr.params.iter().enumerate() {
-                    if i != 0 {
-                        push_str!(", ");
-                    }
-                    self.process_expr(param);
-                }
-                self.is_gml_expr = prev_expr_state;
-                if self.is_gml_expr {
-                    push_str!(")");
-                } else {
-                    push_str!(");\r\n");
-                }
-            },
-            ast::Expr::Group(exprs) => {
-                let skip_newline = self.group_skip_newline;
-                self.group_skip_newline = false;
-                push_str!("{\r\n");
-                self.indent += 1;
-                let mut is_case = false;
-                for expr in exprs {
-                    if matches!(expr, ast::Expr::Case(_) | ast::Expr::Default) {
-                        if is_case {
-                            self.indent -= 1;
-                        } else {
-                            is_case = true;
-                        }
-                        self.write_indent();
-                        self.process_expr(expr);
-                        self.indent += 1;
-                    } else {
-                        self.write_indent();
-                        self.process_expr(expr);
-                    }
-                }
-                if is_case {
-                    self.indent -= 1;
-                }
-                self.indent -= 1;
-                self.write_indent();
-                if skip_newline {
-                    self.output.push(b'}');
-                } else {
-                    push_str!("}\r\n");
-                }
-            },
-            ast::Expr::If(expr) => {
-                push_str!("if (");
-                self.is_gml_expr = true;
-                self.process_expr(&expr.cond);
-                self.is_gml_expr = false;
-                push_str!(") ");
-
-                self.write_expr_grouped(&expr.body, false);
-
-                if let Some(expr_else) = &expr.else_body {
-                    push_str!(" else ");
-                    if matches!(expr_else, ast::Expr::If(_)) {
-                        self.process_expr(expr_else);
+impl Rewrite for Renamer<'_, '_> {
+    fn expr(&mut self, expr: &ast::Expr, place: Place) -> Option<Vec<u8>> {
+        match place {
+            Place::Value => self.value(expr),
+            Place::Assigned | Place::Field => match expr {
+                ast::Expr::LiteralIdentifier(name) => self.identifier(name),
+                _ => None,
+            },
+            Place::Owner => match self.deobf.simplify(expr, self.assets) {
+                Some(simple) if simple.fract() == 0.0 => {
+                    // Write eg "object123" if it's the ID of an object, otherwise special cases for certain keywords
+                    let simple_int = simple as i32;
+                    let text = if simple_int >= 0 && self.assets.objects.get(simple_int as usize).is_some() {
+                        format!("object{}", simple_int)
                     } else {
-                        self.write_expr_grouped(expr_else, true);
-                    }
-                } else {
-                    push_str!("\r\n");
-                }
-            },
-            ast::Expr::Repeat(expr) => {
-                push_str!("repeat (");
-                self.is_gml_expr = true;
-                self.process_expr(&expr.count);
-                self.is_gml_expr = false;
-                push_str!(") ");
-                self.write_expr_grouped(&expr.body, true);
-            },
-            ast::Expr::Switch(expr) => {
-                push_str!("switch (");
-                self.is_gml_expr = true;
-                self.process_expr(&expr.input);
-                self.is_gml_expr = false;
-                push_str!(") ");
-                self.write_expr_grouped(&expr.body, true);
-            },
-            ast::Expr::Var(expr) => {
-                if !expr.vars.is_empty() {
-                    push_str!("var ");
-                    for (i, name) in expr.vars.iter().enumerate() {
-                        if i != 0 {
-                            push_str!(", ");
-                        }
-                        self.write_field(name);
-                    }
-                    push_str!(";\r\n");
-                }
-            },
-            ast::Expr::GlobalVar(expr) => {
-                if !expr.vars.is_empty() {
-                    push_str!("globalvar ");
-                    for (i, name) in expr.vars.iter().enumerate() {
-                        if i != 0 {
-                            push_str!(", ");
+                        match simple_int {
+                            -1 => "self".into(),
+                            -2 => "other".into(),
+                            -5 => "global".into(),
+                            -7 => "local".into(),
+                            i => format!("({})", i),
                         }
-                        self.write_field(name);
-                    }
-                    push_str!(";\r\n");
-                }
+                    };
+                    Some(text.into_bytes())
+                },
+                Some(simple) => Some(format!("({})", simple).into_bytes()),
+                None => self.value(expr),
             },
-            ast::Expr::With(expr) => {
-                push_str!("with (");
-                self.is_gml_expr = true;
-                if let Some(simple) = self.deobf.simplify(&expr.target, self.assets) {
+            Place::WithTarget => match self.deobf.simplify(expr, self.assets) {
+                Some(simple) if simple.fract() == 0.0 => {
                     let simple_int = simple as i32;
-                    if simple_int >= 0
-                        && self.assets.objects.get(simple_int as usize).is_some()
-                        && simple.fract() == 0.0
-                    {
-                        let _ = write!(self.output, "object{}", simple_int);
-                    } else if simple.fract() == 0.0 {
-                        let _ = write!(self.output, "{}", simple_int);
+                    if simple_int >= 0 && self.assets.objects.get(simple_int as usize).is_some() {
+                        Some(format!("object{}", simple_int).into_bytes())
                     } else {
-                        self.process_expr(&expr.target);
+                        Some(simple_int.to_string().into_bytes())
                     }
-                } else {
-                    self.process_expr(&expr.target);
-                }
-                self.is_gml_expr = false;
-                push_str!(") ");
-                self.write_expr_grouped(&expr.body, true);
-            },
-            ast::Expr::While(expr) => {
-                push_str!("while (");
-                self.is_gml_expr = true;
-                self.process_expr(&expr.cond);
-                self.is_gml_expr = false;
-                push_str!(") ");
-                self.write_expr_grouped(&expr.body, true);
-            },
-            ast::Expr::Case(expr) => {
-                push_str!("case ");
-                self.is_gml_expr = true;
-                self.process_expr(expr);
-                self.is_gml_expr = false;
-                push_str!(":\r\n");
-            },
-            ast::Expr::Default => push_str!("default:\r\n"),
-            ast::Expr::Continue => push_str!("continue;\r\n"),
-            ast::Expr::Break => push_str!("break;\r\n"),
-            ast::Expr::Exit => push_str!("exit;\r\n"),
-            ast::Expr::Return(expr) => {
-                push_str!("return ");
-                self.is_gml_expr = true;
-                self.process_expr(expr);
-                self.is_gml_expr = false;
-                push_str!(";\r\n");
+                },
+                _ => self.value(expr),
             },
         }
     }
 
-    pub fn write_expr_grouped(&mut self, expr: &ast::Expr, newline: bool) {
-        if matches!(expr, ast::Expr::Group(_)) {
-            if !newline {
-                self.group_skip_newline = true;
-            }
-            self.process_expr(expr);
-        } else {
-            self.output.extend_from_slice(b"{\r\n");
-            self.indent += 1;
-            self.write_indent();
-            self.process_expr(expr);
-            self.indent -= 1;
-            self.write_indent();
-            self.output.extend_from_slice(if newline { b"}\r\n" } else { b"}" });
+    fn name(&mut self, name: &[u8], kind: Name) -> Option<Vec<u8>> {
+        match kind {
+            Name::Function => self
+                .assets
+                .scripts
+                .iter()
+                .position(|o| o.as_ref().map(|scr| &*scr.name.0 == name).unwrap_or(false))
+                .map(|idx| format!("script{}", idx).into_bytes()),
+            Name::Declared => Some(self.field(name)),
         }
     }
+}
 
-    pub fn write_field(&mut self, ident: &[u8]) {
-        let field_number = self.deobf.register_field(ident);
-        let _ = write!(self.output, "field{}", field_number);
+impl Renamer<'_, '_> {
+    /// Anywhere a value is read, constant expressions are written as plain numbers.
+    fn value(&mut self, expr: &ast::Expr) -> Option<Vec<u8>> {
+        if let Some(simple) = self.deobf.simplify(expr, self.assets) {
+            Some(simple.to_string().into_bytes())
+        } else if let ast::Expr::LiteralIdentifier(name) = expr {
+            self.identifier(name)
+        } else {
+            None
+        }
     }
 
-    pub fn write_indent(&mut self) {
-        for _ in 0..self.indent {
-            self.output.extend_from_slice(self.indent_str.as_bytes());
-        }
+    /// Renames an identifier unless it's a builtin variable.
+    fn identifier(&mut self, name: &[u8]) -> Option<Vec<u8>> {
+        if self.deobf.vars.contains(name) || name == b"pi" { None } else { Some(self.field(name)) }
     }
-}
 
-fn op_to_str(op: Operator) -> &'static [u8] {
-    match op {
-        Operator::Add => b"+",
-        Operator::Subtract => b"-",
-        Operator::Multiply => b"*",
-        Operator::Divide => b"/",
-        Operator::IntDivide => b"div",
-        Operator::BitwiseAnd => b"&",
-        Operator::BitwiseOr => b"|",
-        Operator::BitwiseXor => b"^",
-        Operator::Assign => b"=",
-        Operator::Not => b"!",
-        Operator::LessThan => b"<",
-        Operator::GreaterThan => b">",
-        Operator::AssignAdd => b"+=",
-        Operator::AssignSubtract => b"-=",
-        Operator::AssignMultiply => b"*=",
-        Operator::AssignDivide => b"/=",
-        Operator::AssignBitwiseAnd => b"&=",
-        Operator::AssignBitwiseOr => b"|=",
-        Operator::AssignBitwiseXor => b"^=",
-        Operator::Equal => b"==",
-        Operator::NotEqual => b"!=",
-        Operator::LessThanOrEqual => b"<=",
-        Operator::GreaterThanOrEqual => b">=",
-        Operator::Modulo => b"mod",
-        Operator::And => b"&&",
-        Operator::Or => b"||",
-        Operator::Xor => b"^^",
-        Operator::BinaryShiftLeft => b"<<",
-        Operator::BinaryShiftRight => b">>",
-        Operator::Complement => b"~",
-        Operator::Deref => b".",
-        Operator::Index => panic!("index op passed to op_to_str"),
+    fn field(&mut self, name: &[u8]) -> Vec<u8> {
+        format!("field{}", self.deobf.register_field(name)).into_bytes()
     }
 }
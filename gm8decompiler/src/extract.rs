@@ -0,0 +1,236 @@
+//! Pulls individual assets out of a game without decompiling the whole thing.
+
+use crate::{
+    collision::{self, Shape},
+    unpacked::{bgra_to_rgba, sanitize, stems, write_png},
+};
+use gm8exe::{asset::extension::FileKind, GameAssets};
+use std::{
+    fs,
+    io::{self, Write},
+    path::Path,
+};
+
+/// Asset types which can be extracted, as they're named on the command line.
+pub const KINDS: [&str; 6] = ["sprites", "backgrounds", "sounds", "scripts", "included", "extensions"];
+
+/// Which assets to extract.
+pub struct Filter {
+    /// Asset types to extract, from `KINDS`. Empty means all of them.
+    pub kinds: Vec<String>,
+
+    /// Glob pattern which asset names have to match, using `*` and `?`.
+    pub pattern: Option<String>,
+}
+
+impl Filter {
+    fn wants(&self, kind: &str) -> bool {
+        self.kinds.is_empty() || self.kinds.iter().any(|x| x == kind)
+    }
+
+    fn matches(&self, name: &str) -> bool {
+        self.pattern.as_ref().map_or(true, |pattern| glob_match(pattern, name))
+    }
+}
+
+/// Case-insensitive glob match, where `*` matches any run of characters and `?` matches any single character.
+pub fn glob_match(pattern: &str, name: &str) -> bool {
+    let pattern = pattern.to_lowercase().chars().collect::<Vec<_>>();
+    let name = name.to_lowercase().chars().collect::<Vec<_>>();
+    // Standard greedy matching, backtracking to the last star on a mismatch
+    let (mut p, mut n) = (0, 0);
+    let mut star = None;
+    while n < name.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == name[n]) {
+            p += 1;
+            n += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            star = Some((p, n));
+            p += 1;
+        } else if let Some((star_p, star_n)) = star {
+            p = star_p + 1;
+            n = star_n + 1;
+            star = Some((star_p, star_n + 1));
+        } else {
+            return false
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+/// Writes every asset matching the filter into a folder per asset type. Returns how many files were written.
+pub fn extract(assets: &GameAssets, dir: &Path, filter: &Filter) -> io::Result<usize> {
+    let mut count = 0;
+
+    if filter.wants("sprites") {
+        let dir = dir.join("sprites");
+        let names = stems(assets.sprites.iter().map(|x| x.as_ref().map(|x| &x.name)));
+        for (sprite, stem) in assets.sprites.iter().zip(names).filter_map(|(a, s)| Some((a.as_deref()?, s?))) {
+            if !filter.matches(&sprite.name.to_string()) {
+                continue
+            }
+            fs::create_dir_all(&dir)?;
+            for (i, frame) in sprite.frames.iter().enumerate() {
+                let rgba = bgra_to_rgba(&frame.data);
+                write_png(&dir.join(format!("{}_{}.png", stem, i)), frame.width, frame.height, &rgba)?;
+                count += 1;
+            }
+            for (i, mask) in sprite.colliders.iter().enumerate() {
+                let rgba = mask.data.iter().flat_map(|&x| if x { [255; 4] } else { [0; 4] }).collect::<Vec<_>>();
+                write_png(&dir.join(format!("{}_mask_{}.png", stem, i)), mask.width, mask.height, &rgba)?;
+                count += 1;
+            }
+
+            // Describe the mask the way the GM8 sprite editor would show it
+            let mut out = Vec::new();
+            writeln!(out, "origin = {}, {}", sprite.origin_x, sprite.origin_y)?;
+            if let Some(map) = collision::resolve_map(sprite) {
+                let shape = match map.shape {
+                    Shape::Precise => "precise",
+                    Shape::Rectangle => "rectangle",
+                    Shape::Disk => "disk",
+                    Shape::Diamond => "diamond",
+                };
+                writeln!(out, "shape = {}", shape)?;
+                writeln!(out, "alpha_tolerance = {}", map.alpha_tolerance)?;
+                writeln!(out, "per_frame_masks = {}", sprite.per_frame_colliders)?;
+                writeln!(out, "bbox = {} {} {} {}", map.bbox_left, map.bbox_top, map.bbox_right, map.bbox_bottom)?;
+            }
+            fs::write(dir.join(format!("{}.txt", stem)), out)?;
+            count += 1;
+        }
+    }
+
+    if filter.wants("backgrounds") {
+        let dir = dir.join("backgrounds");
+        let names = stems(assets.backgrounds.iter().map(|x| x.as_ref().map(|x| &x.name)));
+        for (background, stem) in assets.backgrounds.iter().zip(names).filter_map(|(a, s)| Some((a.as_deref()?, s?))) {
+            if let (true, Some(data)) = (filter.matches(&background.name.to_string()), &background.data) {
+                fs::create_dir_all(&dir)?;
+                let path = dir.join(format!("{}.png", stem));
+                write_png(&path, background.width, background.height, &bgra_to_rgba(data))?;
+                count += 1;
+            }
+        }
+    }
+
+    if filter.wants("sounds") {
+        let dir = dir.join("sounds");
+        let names = stems(assets.sounds.iter().map(|x| x.as_ref().map(|x| &x.name)));
+        for (sound, stem) in assets.sounds.iter().zip(names).filter_map(|(a, s)| Some((a.as_deref()?, s?))) {
+            if let (true, Some(data)) = (filter.matches(&sound.name.to_string()), &sound.data) {
+                fs::create_dir_all(&dir)?;
+                let extension = sanitize(&sound.extension.to_string());
+                let extension = extension.trim_start_matches('.');
+                let extension = if extension.is_empty() { "bin" } else { extension };
+                fs::write(dir.join(format!("{}.{}", stem, extension)), data)?;
+                count += 1;
+            }
+        }
+    }
+
+    if filter.wants("scripts") {
+        let dir = dir.join("scripts");
+        let names = stems(assets.scripts.iter().map(|x| x.as_ref().map(|x| &x.name)));
+        for (script, stem) in assets.scripts.iter().zip(names).filter_map(|(a, s)| Some((a.as_deref()?, s?))) {
+            if filter.matches(&script.name.to_string()) {
+                fs::create_dir_all(&dir)?;
+                fs::write(dir.join(format!("{}.gml", stem)), &script.source.0)?;
+                count += 1;
+            }
+        }
+    }
+
+    if filter.wants("included") {
+        let dir = dir.join("included");
+        let names = stems(assets.included_files.iter().map(|x| Some(&x.file_name)));
+        for (file, stem) in assets.included_files.iter().zip(names.into_iter().flatten()) {
+            if let (true, Some(data)) = (filter.matches(&file.file_name.to_string()), &file.embedded_data) {
+                fs::create_dir_all(&dir)?;
+                fs::write(dir.join(stem), data)?;
+                count += 1;
+            }
+        }
+    }
+
+    if filter.wants("extensions") {
+        let names = stems(assets.extensions.iter().map(|x| Some(&x.name)));
+        for (extension, stem) in assets.extensions.iter().zip(names.into_iter().flatten()) {
+            let dir = dir.join("extensions").join(stem);
+            let files = extension.files.iter().filter(|f| f.kind == FileKind::DynamicLibrary);
+            for file in files.filter(|f| filter.matches(&f.name.to_string())) {
+                fs::create_dir_all(&dir)?;
+                fs::write(dir.join(sanitize(&file.name.to_string())), &file.contents)?;
+                count += 1;
+            }
+        }
+    }
+
+    Ok(count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn glob_literals() {
+        assert!(glob_match("spr_player", "spr_player"));
+        assert!(!glob_match("spr_player", "spr_players"));
+        assert!(!glob_match("spr_players", "spr_player"));
+    }
+
+    #[test]
+    fn glob_star() {
+        assert!(glob_match("spr_*", "spr_player"));
+        assert!(glob_match("spr_*", "spr_"));
+        assert!(glob_match("*_wall", "obj_wall"));
+        assert!(glob_match("*", "anything"));
+        assert!(glob_match("**", "anything"));
+        assert!(glob_match("a*b*c", "a__b__c"));
+        assert!(!glob_match("spr_*", "obj_player"));
+    }
+
+    #[test]
+    fn glob_question_mark() {
+        assert!(glob_match("bg?", "bg1"));
+        assert!(!glob_match("bg?", "bg"));
+        assert!(!glob_match("bg?", "bg12"));
+        assert!(glob_match("?*", "x"));
+        assert!(!glob_match("?*", ""));
+    }
+
+    #[test]
+    fn glob_backtracking() {
+        assert!(glob_match("a*b", "aXbYb"));
+        assert!(!glob_match("a*b", "aXbYc"));
+        assert!(glob_match("*ab", "aab"));
+        assert!(glob_match("a*?c", "abbc"));
+        assert!(glob_match("*x*y", "xxyxy"));
+        assert!(!glob_match("*x*y", "xxyx"));
+    }
+
+    #[test]
+    fn glob_empty() {
+        assert!(glob_match("", ""));
+        assert!(!glob_match("", "a"));
+        assert!(!glob_match("a", ""));
+        assert!(glob_match("*", ""));
+    }
+
+    #[test]
+    fn glob_ignores_case() {
+        assert!(glob_match("SPR_*", "spr_Player"));
+        assert!(glob_match("spr_?layer", "SPR_PLAYER"));
+        assert!(glob_match("ÜBER*", "über_boss"));
+    }
+
+    #[test]
+    fn filter() {
+        let filter = Filter { kinds: vec![], pattern: None };
+        assert!(filter.wants("sprites") && filter.matches("anything"));
+        let filter = Filter { kinds: vec!["sounds".into()], pattern: Some("snd_*".into()) };
+        assert!(filter.wants("sounds") && !filter.wants("sprites"));
+        assert!(filter.matches("SND_jump") && !filter.matches("spr_jump"));
+    }
+}
@@ -0,0 +1,537 @@
+//! Machine-readable report about a game: its version, protection, settings, assets, extensions,
+//! and which functions its code calls, including which of those GM8Emulator can't run yet.
+
+use gm8exe::{
+    asset::{
+        extension::{CallingConvention, FileKind, FunctionValueKind},
+        included_file::ExportSetting,
+        CodeAction, PascalString,
+    },
+    reader::Protection,
+    GameAssets, GameVersion,
+};
+use gml_parser::ast::{self, Expr};
+use std::{
+    collections::{BTreeMap, HashSet},
+    fmt,
+};
+
+/// Lists of GML functions taken from GM8Emulator's source at build time.
+mod emulator {
+    include!(concat!(env!("OUT_DIR"), "/emulator_functions.rs"));
+}
+
+/// A JSON value. Objects keep their keys in insertion order.
+pub enum Json {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>),
+}
+
+impl From<bool> for Json {
+    fn from(x: bool) -> Self {
+        Json::Bool(x)
+    }
+}
+
+impl From<&str> for Json {
+    fn from(x: &str) -> Self {
+        Json::String(x.into())
+    }
+}
+
+impl From<String> for Json {
+    fn from(x: String) -> Self {
+        Json::String(x)
+    }
+}
+
+impl From<&PascalString> for Json {
+    fn from(x: &PascalString) -> Self {
+        Json::String(x.to_string())
+    }
+}
+
+macro_rules! json_number {
+    ($($t: ty),*) => {
+        $(impl From<$t> for Json {
+            fn from(x: $t) -> Self {
+                Json::Number(x as f64)
+            }
+        })*
+    };
+}
+json_number!(i32, u32, usize, f64);
+
+impl<T: Into<Json>> From<Option<T>> for Json {
+    fn from(x: Option<T>) -> Self {
+        x.map(Into::into).unwrap_or(Json::Null)
+    }
+}
+
+impl<T: Into<Json>> From<Vec<T>> for Json {
+    fn from(x: Vec<T>) -> Self {
+        Json::Array(x.into_iter().map(Into::into).collect())
+    }
+}
+
+/// Shorthand for building a JSON object out of key-value pairs.
+macro_rules! object {
+    ($($key: expr => $value: expr),* $(,)?) => {
+        Json::Object(vec![$(($key.into(), Json::from($value))),*])
+    };
+}
+
+impl Json {
+    fn write(&self, f: &mut fmt::Formatter, indent: usize) -> fmt::Result {
+        match self {
+            Json::Null => write!(f, "null"),
+            Json::Bool(x) => write!(f, "{}", x),
+            Json::Number(x) if x.is_finite() => write!(f, "{}", x),
+            Json::Number(_) => write!(f, "null"),
+            Json::String(x) => write_string(f, x),
+            Json::Array(items) if items.is_empty() => write!(f, "[]"),
+            Json::Array(items) => {
+                writeln!(f, "[")?;
+                for (i, item) in items.iter().enumerate() {
+                    write!(f, "{:1$}", "", indent + 2)?;
+                    item.write(f, indent + 2)?;
+                    writeln!(f, "{}", if i + 1 < items.len() { "," } else { "" })?;
+                }
+                write!(f, "{:1$}]", "", indent)
+            },
+            Json::Object(fields) if fields.is_empty() => write!(f, "{{}}"),
+            Json::Object(fields) => {
+                writeln!(f, "{{")?;
+                for (i, (key, value)) in fields.iter().enumerate() {
+                    write!(f, "{:1$}", "", indent + 2)?;
+                    write_string(f, key)?;
+                    write!(f, ": ")?;
+                    value.write(f, indent + 2)?;
+                    writeln!(f, "{}", if i + 1 < fields.len() { "," } else { "" })?;
+                }
+                write!(f, "{:1$}}}", "", indent)
+            },
+        }
+    }
+}
+
+fn write_string(f: &mut fmt::Formatter, s: &str) -> fmt::Result {
+    write!(f, "\"")?;
+    for c in s.chars() {
+        match c {
+            '"' => write!(f, "\\\"")?,
+            '\\' => write!(f, "\\\\")?,
+            '\n' => write!(f, "\\n")?,
+            '\r' => write!(f, "\\r")?,
+            '\t' => write!(f, "\\t")?,
+            c if u32::from(c) < 0x20 => write!(f, "\\u{:04x}", u32::from(c))?,
+            c => write!(f, "{}", c)?,
+        }
+    }
+    write!(f, "\"")
+}
+
+impl fmt::Display for Json {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.write(f, 0)
+    }
+}
+
+/// Builds the full report for a game.
+pub fn report(assets: &GameAssets, protection: &Protection) -> Json {
+    let version = match assets.version {
+        GameVersion::GameMaker8_0 => "8.0",
+        GameVersion::GameMaker8_1 => "8.1",
+    };
+    let [a, b, c, d] = assets.guid;
+    let antidec = protection.antidec.map(|x| {
+        object! {
+            "exe_load_offset" => x.exe_load_offset,
+            "header_start" => x.header_start,
+            "xor_mask" => x.xor_mask,
+            "add_mask" => x.add_mask,
+            "sub_mask" => x.sub_mask,
+        }
+    });
+    fn count<T>(list: &[Option<T>]) -> usize {
+        list.iter().filter(|x| x.is_some()).count()
+    }
+
+    object! {
+        "version" => version,
+        "game_id" => assets.game_id,
+        "guid" => format!("{:08X}{:08X}{:08X}{:08X}", a, b, c, d),
+        "protection" => object! {
+            "upx" => protection.upx,
+            "antidec" => antidec,
+        },
+        "settings" => settings(assets),
+        "asset_counts" => object! {
+            "sprites" => count(&assets.sprites),
+            "sounds" => count(&assets.sounds),
+            "backgrounds" => count(&assets.backgrounds),
+            "paths" => count(&assets.paths),
+            "scripts" => count(&assets.scripts),
+            "fonts" => count(&assets.fonts),
+            "timelines" => count(&assets.timelines),
+            "objects" => count(&assets.objects),
+            "rooms" => count(&assets.rooms),
+            "triggers" => count(&assets.triggers),
+            "constants" => assets.constants.len(),
+            "included_files" => assets.included_files.len(),
+            "extensions" => assets.extensions.len(),
+        },
+        "extensions" => assets.extensions.iter().map(extension).collect::<Vec<_>>(),
+        "included_files" => assets.included_files.iter().map(|file| {
+            let export = match &file.export_settings {
+                ExportSetting::NoExport => "none".into(),
+                ExportSetting::TempFolder => "temp".into(),
+                ExportSetting::GameFolder => "game".into(),
+                ExportSetting::CustomFolder(folder) => format!("custom: {}", folder),
+            };
+            object! {
+                "name" => &file.file_name,
+                "size" => file.embedded_data.as_ref().map(|x| x.len()),
+                "export" => export,
+                "free_memory" => file.free_memory,
+                "remove_at_end" => file.remove_at_end,
+            }
+        }).collect::<Vec<_>>(),
+        "functions" => functions(assets),
+    }
+}
+
+fn settings(assets: &GameAssets) -> Json {
+    let settings = &assets.settings;
+    macro_rules! fields {
+        ($($field: ident),* $(,)?) => {
+            object! { $(stringify!($field) => settings.$field),* }
+        };
+    }
+    fields!(
+        fullscreen,
+        scaling,
+        interpolate_pixels,
+        clear_colour,
+        allow_resize,
+        window_on_top,
+        dont_draw_border,
+        dont_show_buttons,
+        display_cursor,
+        freeze_on_lose_focus,
+        disable_screensaver,
+        force_cpu_render,
+        set_resolution,
+        colour_depth,
+        resolution,
+        frequency,
+        vsync,
+        esc_close_game,
+        treat_close_as_esc,
+        f1_help_menu,
+        f4_fullscreen_toggle,
+        f5_save_f6_load,
+        f9_screenshot,
+        priority,
+        transparent,
+        translucency,
+        loading_bar,
+        scale_progress_bar,
+        show_error_messages,
+        log_errors,
+        always_abort,
+        zero_uninitialized_vars,
+        error_on_uninitialized_args,
+        swap_creation_events,
+    )
+}
+
+fn extension(extension: &gm8exe::asset::Extension) -> Json {
+    let value_kind = |kind: FunctionValueKind| match kind {
+        FunctionValueKind::GMString => "string",
+        FunctionValueKind::GMReal => "real",
+    };
+    let files = extension.files.iter().map(|file| {
+        let kind = match file.kind {
+            FileKind::DynamicLibrary => "dll",
+            FileKind::GmlScript => "gml",
+            FileKind::ActionLibrary => "lib",
+            FileKind::Other => "other",
+        };
+        let functions = file.functions.iter().map(|function| {
+            let convention = match function.convention {
+                CallingConvention::Gml => "gml",
+                CallingConvention::Stdcall => "stdcall",
+                CallingConvention::Cdecl => "cdecl",
+                CallingConvention::Unknown => "unknown",
+            };
+            let args = usize::try_from(function.arg_count)
+                .ok()
+                .map(|count| function.arg_types.iter().take(count).map(|&x| value_kind(x)).collect::<Vec<_>>());
+            object! {
+                "name" => &function.name,
+                "external_name" => &function.external_name,
+                "convention" => convention,
+                "args" => args,
+                "returns" => value_kind(function.return_type),
+            }
+        });
+        object! {
+            "name" => &file.name,
+            "kind" => kind,
+            "size" => file.contents.len(),
+            "functions" => functions.collect::<Vec<_>>(),
+        }
+    });
+    object! {
+        "name" => &extension.name,
+        "files" => files.collect::<Vec<_>>(),
+    }
+}
+
+/// Counts every function call in the game's code and works out what each one refers to.
+fn functions(assets: &GameAssets) -> Json {
+    let mut calls = BTreeMap::new();
+    let mut errors = Vec::new();
+    let mut scan = |location: String, code: &PascalString| match ast::AST::new(&code.0) {
+        Ok(ast) => ast.iter().for_each(|expr| count_calls(expr, &mut calls)),
+        Err(e) => errors.push(object! {
+            "location" => location,
+            "message" => e.message,
+            "line" => e.span.line,
+            "column" => e.span.column,
+        }),
+    };
+
+    for script in assets.scripts.iter().flatten() {
+        scan(format!("script {}", script.name), &script.source);
+    }
+    for room in assets.rooms.iter().flatten() {
+        scan(format!("room {} creation code", room.name), &room.creation_code);
+        for instance in &room.instances {
+            scan(format!("room {} instance {} creation code", room.name, instance.id), &instance.creation_code);
+        }
+    }
+    let mut action_calls = Vec::new();
+    let mut scan_actions = |location: String, actions: &[CodeAction]| {
+        for (i, action) in actions.iter().enumerate() {
+            if action.action_kind == 7 && action.execution_type == 2 {
+                scan(format!("{} action {}", location, i), &action.param_strings[0]);
+            } else if action.execution_type == 1 {
+                // Drag-and-drop actions which call a function directly, such as action_move
+                action_calls.push(action.fn_name.to_string());
+            }
+        }
+    };
+    for object in assets.objects.iter().flatten() {
+        for (ev_type, events) in object.events.iter().enumerate() {
+            for (sub, actions) in events {
+                scan_actions(format!("object {} event {} {}", object.name, ev_type, sub), actions);
+            }
+        }
+    }
+    for timeline in assets.timelines.iter().flatten() {
+        for (moment, actions) in &timeline.moments {
+            scan_actions(format!("timeline {} moment {}", timeline.name, moment), actions);
+        }
+    }
+
+    // Trigger conditions are expressions rather than code
+    for trigger in assets.triggers.iter().flatten() {
+        match ast::AST::expression(&trigger.condition.0) {
+            Ok(expr) => count_calls(&expr, &mut calls),
+            Err(e) => errors.push(object! {
+                "location" => format!("trigger {}", trigger.name),
+                "message" => e.message,
+                "line" => e.span.line,
+                "column" => e.span.column,
+            }),
+        }
+    }
+    for name in action_calls {
+        *calls.entry(name).or_insert(0) += 1;
+    }
+
+    let scripts = assets.scripts.iter().flatten().map(|x| x.name.to_string()).collect::<HashSet<_>>();
+    let extension_functions = assets
+        .extensions
+        .iter()
+        .flat_map(|x| x.files.iter().flat_map(|x| x.functions.iter().map(|x| x.name.to_string())))
+        .collect::<HashSet<_>>();
+
+    // Everything which isn't a script or an extension function is expected to be built into GM8
+    let mut unimplemented = Vec::new();
+    let mut unknown = Vec::new();
+    let called = calls
+        .into_iter()
+        .map(|(name, count)| {
+            let (kind, status) = if scripts.contains(&name) {
+                ("script", None)
+            } else if extension_functions.contains(&name) {
+                ("extension", None)
+            } else if !emulator::AVAILABLE {
+                ("kernel", None)
+            } else if emulator::UNIMPLEMENTED.contains(&name.as_str()) {
+                unimplemented.push(name.clone());
+                ("kernel", Some("unimplemented"))
+            } else if emulator::KNOWN.contains(&name.as_str()) {
+                ("kernel", Some("implemented"))
+            } else {
+                unknown.push(name.clone());
+                ("kernel", Some("unknown"))
+            };
+            object! { "name" => name, "calls" => count, "kind" => kind, "emulator" => status }
+        })
+        .collect::<Vec<_>>();
+
+    object! {
+        "called" => called,
+        "emulator_unimplemented" => if emulator::AVAILABLE { Some(unimplemented) } else { None },
+        "emulator_unknown" => if emulator::AVAILABLE { Some(unknown) } else { None },
+        "parse_errors" => errors,
+    }
+}
+
+/// Adds up every function call in an expression, including ones nested inside it.
+fn count_calls(expr: &Expr, calls: &mut BTreeMap<String, usize>) {
+    let mut visit = |expr: &Expr| count_calls(expr, calls);
+    match expr {
+        Expr::LiteralIdentifier(_) | Expr::LiteralReal(_) | Expr::LiteralString(_) => (),
+        Expr::Unary(unary) => visit(&unary.child),
+        Expr::Binary(binary) => {
+            visit(&binary.left);
+            visit(&binary.right);
+        },
+        Expr::DoUntil(do_until) => {
+            visit(&do_until.body);
+            visit(&do_until.cond);
+        },
+        Expr::For(for_expr) => {
+            visit(&for_expr.start);
+            visit(&for_expr.cond);
+            visit(&for_expr.step);
+            visit(&for_expr.body);
+        },
+        Expr::Function(call) => {
+            *calls.entry(String::from_utf8_lossy(call.name).into_owned()).or_insert(0) += 1;
+            call.params.iter().for_each(|x| count_calls(x, calls));
+        },
+        Expr::Group(group) => group.iter().for_each(visit),
+        Expr::If(if_expr) => {
+            visit(&if_expr.cond);
+            visit(&if_expr.body);
+            if let Some(else_body) = &if_expr.else_body {
+                visit(else_body);
+            }
+        },
+        Expr::Repeat(repeat) => {
+            visit(&repeat.count);
+            visit(&repeat.body);
+        },
+        Expr::Switch(switch) => {
+            visit(&switch.input);
+            visit(&switch.body);
+        },
+        Expr::Var(_) | Expr::GlobalVar(_) => (),
+        Expr::With(with) => {
+            visit(&with.target);
+            visit(&with.body);
+        },
+        Expr::While(while_expr) => {
+            visit(&while_expr.cond);
+            visit(&while_expr.body);
+        },
+        Expr::Case(case) => visit(case),
+        Expr::Default | Expr::Continue | Expr::Break | Expr::Exit => (),
+        Expr::Return(value) => visit(value),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn calls(source: &str) -> Vec<(String, usize)> {
+        let mut calls = BTreeMap::new();
+        ast::AST::new(source.as_bytes()).unwrap().iter().for_each(|expr| count_calls(expr, &mut calls));
+        calls.into_iter().collect()
+    }
+
+    #[test]
+    fn escapes_strings() {
+        let json = Json::from("quote \" backslash \\ newline \n return \r tab \t bell \x07 nul \0 ü");
+        assert_eq!(
+            json.to_string(),
+            r#""quote \" backslash \\ newline \n return \r tab \t bell \u0007 nul \u0000 ü""#,
+        );
+        assert_eq!(object! { "a\"b" => 1 }.to_string(), "{\n  \"a\\\"b\": 1\n}");
+    }
+
+    #[test]
+    fn writes_numbers() {
+        assert_eq!(Json::from(3).to_string(), "3");
+        assert_eq!(Json::from(-0.5).to_string(), "-0.5");
+        assert_eq!(Json::from(f64::NAN).to_string(), "null");
+        assert_eq!(Json::from(f64::INFINITY).to_string(), "null");
+        assert_eq!(Json::from(f64::NEG_INFINITY).to_string(), "null");
+    }
+
+    #[test]
+    fn writes_nested_values() {
+        let json = object! {
+            "empty_array" => Vec::<Json>::new(),
+            "empty_object" => Json::Object(vec![]),
+            "nested" => vec![Json::Array(vec![]), Json::Object(vec![]), Json::from(vec![true])],
+            "missing" => None::<i32>,
+        };
+        let expected = r#"{
+  "empty_array": [],
+  "empty_object": {},
+  "nested": [
+    [],
+    {},
+    [
+      true
+    ]
+  ],
+  "missing": null
+}"#;
+        assert_eq!(json.to_string(), expected);
+        assert_eq!(Json::Array(vec![]).to_string(), "[]");
+        assert_eq!(Json::Object(vec![]).to_string(), "{}");
+    }
+
+    #[test]
+    fn counts_calls() {
+        assert_eq!(calls("a = f(g(1), g(2)); h();"), [("f".into(), 1), ("g".into(), 2), ("h".into(), 1)]);
+        assert_eq!(calls("x = 1; exit"), []);
+    }
+
+    #[test]
+    fn counts_nested_calls() {
+        let source = "
+            with (instance_find(obj, 0)) {
+                switch (choose(1, 2)) {
+                    case f(): show_message(\"\"); break;
+                    default: for (i = g(); i < g(); i += g()) repeat (h()) while (h()) { do h() until (h()) }
+                }
+            }
+            if (a()) b() else { return c(-a()); }
+        ";
+        assert_eq!(calls(source), [
+            ("a".into(), 2),
+            ("b".into(), 1),
+            ("c".into(), 1),
+            ("choose".into(), 1),
+            ("f".into(), 1),
+            ("g".into(), 3),
+            ("h".into(), 4),
+            ("instance_find".into(), 1),
+            ("show_message".into(), 1),
+        ]);
+    }
+}
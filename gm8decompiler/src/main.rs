@@ -7,8 +7,11 @@ use std::{
 
 pub mod collision;
 pub mod deobfuscate;
+pub mod extract;
 pub mod gmk;
+pub mod inspect;
 pub mod mappings;
+pub mod unpacked;
 pub mod zlib;
 
 static INFO_STRING: &str = concat!(
@@ -49,13 +52,26 @@ fn is_cmd(_argv_0: &str) -> bool {
 fn pause(_tip: bool) {}
 
 fn main() {
-    println!("{}", INFO_STRING);
-
     let args: Vec<String> = env::args().collect();
     assert!(!args.is_empty());
     let process_path = args[0].as_str();
     let should_pause = is_cmd(process_path);
 
+    // subcommands have their own options, so hand the rest of the arguments over to them
+    // inspect prints JSON, so it's the only one that doesn't print the info string first
+    match args.get(1).map(String::as_str) {
+        Some("inspect") => process::exit(inspect_main(process_path, &args[2..])),
+        Some("diff") => {
+            println!("{}", INFO_STRING);
+            process::exit(diff_main(process_path, &args[2..]))
+        },
+        Some("extract") => {
+            println!("{}", INFO_STRING);
+            process::exit(extract_main(process_path, &args[2..]))
+        },
+        _ => println!("{}", INFO_STRING),
+    }
+
     // set up getopts to parse our command line args
     let mut opts = getopts::Options::new();
     opts.optflag("h", "help", "print this help message")
@@ -64,6 +80,7 @@ fn main() {
         .optopt("d", "deobfuscate", "set deobfuscation mode auto/on/off (default=auto)", "")
         .optflag("p", "preserve", "preserve broken events (instead of trying to fix them)")
         .optflag("s", "singlethread", "decompile gamedata synchronously (lower RAM usage)")
+        .optflag("u", "unpacked", "write an unpacked project directory instead of a gmk")
         .optopt("o", "output", "specify output filename", "FILE");
 
     // parse command line arguments
@@ -91,7 +108,10 @@ fn main() {
         // I wouldn't have to resort to this.
         // TODO: Get a better argument parser in general.
         println!(
-            "Usage: {} FILENAME [options]
+            "Usage: {0} FILENAME [options]
+       {0} diff OLD NEW [-l] [-s]   list the differences between two games
+       {0} extract FILENAME [options]   save individual assets from a game
+       {0} inspect FILENAME [-l] [-s]   print a JSON report about a game
 
 Options:
     -h, --help                print this help message
@@ -100,6 +120,7 @@ Options:
     -d, --deobfuscate <mode>  set deobfuscation mode auto/on/off (defaults to auto)
     -p, --preserve            preserve broken events (instead of trying to fix them)
     -s, --singlethread        decompile gamedata synchronously (lower RAM usage)
+    -u, --unpacked            write an unpacked project directory instead of a gmk
     -o, --output <file>       specify output filename",
             process_path
         );
@@ -137,6 +158,7 @@ Options:
     };
     let out_path = matches.opt_str("o");
     let preserve = matches.opt_present("p");
+    let unpacked = matches.opt_present("u");
     // no_pause extracted before help
 
     // print flags for confirmation
@@ -155,6 +177,9 @@ Options:
     if singlethread {
         println!("Single-threaded mode ON: process will not start new threads (slow)");
     }
+    if unpacked {
+        println!("Unpacked mode ON: will write a project directory with one file per asset");
+    }
     if let Some(path) = &out_path {
         println!("Specified output path: {}", path);
     }
@@ -170,7 +195,7 @@ Options:
     }
 
     // allow decompile to handle the rest of main
-    if let Err(e) = decompile(input_path, out_path, !lazy, !singlethread, verbose, deobfuscate, !preserve) {
+    if let Err(e) = decompile(input_path, out_path, !lazy, !singlethread, verbose, deobfuscate, !preserve, unpacked) {
         eprintln!("Error parsing gamedata:\n{}", e);
         process::exit(1);
     }
@@ -180,6 +205,173 @@ Options:
     }
 }
 
+/// Reads a game for one of the subcommands, without any logging.
+fn read_game(path: &str, strict: bool, multithread: bool) -> Result<gm8exe::GameAssets, String> {
+    let file = fs::read(path).map_err(|e| format!("Failed to read '{}': {}", path, e))?;
+    parse_game(path, file, strict, multithread)
+}
+
+/// Reads the assets out of a game which has already been loaded from `path`.
+fn parse_game(path: &str, file: Vec<u8>, strict: bool, multithread: bool) -> Result<gm8exe::GameAssets, String> {
+    let logger: Option<fn(&str)> = None;
+    gm8exe::reader::from_exe(file, logger, strict, multithread)
+        .map_err(|e| format!("Reader error in '{}': {}", path, e))
+}
+
+/// Entry point for `diff OLD NEW`, which compares two games asset by asset. Returns the exit code.
+fn diff_main(process_path: &str, args: &[String]) -> i32 {
+    let mut opts = getopts::Options::new();
+    opts.optflag("h", "help", "print this help message")
+        .optflag("l", "lazy", "disable various data integrity checks")
+        .optflag("s", "singlethread", "decompile gamedata synchronously (lower RAM usage)");
+    let matches = match opts.parse(args) {
+        Ok(matches) => matches,
+        Err(err) => {
+            eprintln!("{}", err);
+            return 1
+        },
+    };
+    if matches.opt_present("h") || matches.free.len() != 2 {
+        println!(
+            "Usage: {} diff OLD NEW [options]
+
+Options:
+    -h, --help                print this help message
+    -l, --lazy                disable various data integrity checks
+    -s, --singlethread        decompile gamedata synchronously (lower RAM usage)",
+            process_path
+        );
+        return if matches.opt_present("h") { 0 } else { 1 }
+    }
+
+    let strict = !matches.opt_present("l");
+    let multithread = !matches.opt_present("s");
+    let read = |path: &str| read_game(path, strict, multithread);
+    match read(&matches.free[0]).and_then(|old| Ok((old, read(&matches.free[1])?))) {
+        Ok((old, new)) => {
+            print!("{}", gm8exe::diff::diff(&old, &new));
+            0
+        },
+        Err(e) => {
+            eprintln!("{}", e);
+            1
+        },
+    }
+}
+
+/// Entry point for `inspect FILENAME`, which prints a JSON report about a game. Returns the exit code.
+fn inspect_main(process_path: &str, args: &[String]) -> i32 {
+    let mut opts = getopts::Options::new();
+    opts.optflag("h", "help", "print this help message")
+        .optflag("l", "lazy", "disable various data integrity checks")
+        .optflag("s", "singlethread", "decompile gamedata synchronously (lower RAM usage)");
+    let matches = match opts.parse(args) {
+        Ok(matches) => matches,
+        Err(err) => {
+            eprintln!("{}", err);
+            return 1
+        },
+    };
+    if matches.opt_present("h") || matches.free.len() != 1 {
+        println!(
+            "Usage: {} inspect FILENAME [options]
+
+Options:
+    -h, --help                print this help message
+    -l, --lazy                disable various data integrity checks
+    -s, --singlethread        decompile gamedata synchronously (lower RAM usage)",
+            process_path
+        );
+        return if matches.opt_present("h") { 0 } else { 1 }
+    }
+
+    let input = &matches.free[0];
+    let result = fs::read(input).map_err(|e| format!("Failed to read '{}': {}", input, e)).and_then(|file| {
+        let protection = gm8exe::reader::detect_protection(&file).map_err(|e| format!("Reader error: {}", e))?;
+        let assets = parse_game(input, file, !matches.opt_present("l"), !matches.opt_present("s"))?;
+        Ok(inspect::report(&assets, &protection))
+    });
+    match result {
+        Ok(report) => {
+            println!("{}", report);
+            0
+        },
+        Err(e) => {
+            eprintln!("{}", e);
+            1
+        },
+    }
+}
+
+/// Entry point for `extract FILENAME`, which saves the matching assets as standalone files. Returns the exit code.
+fn extract_main(process_path: &str, args: &[String]) -> i32 {
+    let mut opts = getopts::Options::new();
+    opts.optflag("h", "help", "print this help message")
+        .optflag("l", "lazy", "disable various data integrity checks")
+        .optflag("s", "singlethread", "decompile gamedata synchronously (lower RAM usage)")
+        .optopt("t", "type", "comma-separated asset types to extract (default=all)", "TYPES")
+        .optopt("n", "name", "only extract assets whose names match a glob pattern", "PATTERN")
+        .optopt("o", "output", "specify output directory", "DIR");
+    let matches = match opts.parse(args) {
+        Ok(matches) => matches,
+        Err(err) => {
+            eprintln!("{}", err);
+            return 1
+        },
+    };
+    if matches.opt_present("h") || matches.free.len() != 1 {
+        println!(
+            "Usage: {} extract FILENAME [options]
+
+Options:
+    -h, --help                print this help message
+    -l, --lazy                disable various data integrity checks
+    -s, --singlethread        decompile gamedata synchronously (lower RAM usage)
+    -t, --type <types>        comma-separated asset types to extract (defaults to all)
+                              valid types are {}
+    -n, --name <pattern>      only extract assets whose names match a pattern, such as 'spr_player*'
+    -o, --output <dir>        specify output directory",
+            process_path,
+            extract::KINDS.join(", "),
+        );
+        return if matches.opt_present("h") { 0 } else { 1 }
+    }
+
+    let kinds = matches
+        .opt_str("t")
+        .map(|x| x.split(',').map(|x| x.trim().to_lowercase()).filter(|x| !x.is_empty()).collect::<Vec<_>>())
+        .unwrap_or_default();
+    if let Some(kind) = kinds.iter().find(|x| !extract::KINDS.contains(&x.as_str())) {
+        eprintln!("Invalid asset type: {} (valid types are {})", kind, extract::KINDS.join(", "));
+        return 1
+    }
+    let filter = extract::Filter { kinds, pattern: matches.opt_str("n") };
+
+    let input = &matches.free[0];
+    let out_path = match matches.opt_str("o") {
+        Some(path) => PathBuf::from(path),
+        None => {
+            let stem = Path::new(input).file_stem().and_then(|x| x.to_str()).unwrap_or("game");
+            Path::new(input).with_file_name(format!("{}_assets", stem))
+        },
+    };
+    let result = read_game(input, !matches.opt_present("l"), !matches.opt_present("s")).and_then(|assets| {
+        extract::extract(&assets, &out_path, &filter)
+            .map_err(|e| format!("Failed to extract to '{}': {}", out_path.display(), e))
+    });
+    match result {
+        Ok(count) => {
+            println!("Extracted {} files to '{}'", count, out_path.display());
+            0
+        },
+        Err(e) => {
+            eprintln!("{}", e);
+            1
+        },
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
 fn decompile(
     in_path: &Path,
     out_path: Option<String>,
@@ -188,6 +380,7 @@ fn decompile(
     verbose: bool,
     deobf_mode: deobfuscate::Mode,
     fix_events: bool,
+    unpacked: bool,
 ) -> Result<(), String> {
     // slurp in file contents
     let file = fs::read(&in_path).map_err(|e| format!("Failed to read '{}': {}", in_path.display(), e))?;
@@ -246,6 +439,25 @@ fn decompile(
             .for_each(|ev| fix_event(ev));
     }
 
+    if deobfuscate {
+        deobfuscate::process(&mut assets);
+    }
+
+    if unpacked {
+        let out_path = match out_path {
+            Some(p) => PathBuf::from(p),
+            None => match in_path.with_extension("") {
+                path if path == in_path => in_path.with_extension("project"),
+                path => path,
+            },
+        };
+        println!("Writing project to '{}'...", out_path.display());
+        unpacked::write(&out_path, &assets, multithread)
+            .map_err(|e| format!("Failed to write project '{}': {}", out_path.display(), e))?;
+        println!("Successfully written project to '{}'", out_path.display());
+        return Ok(())
+    }
+
     // warn user if they specified .gmk for 8.0 or .gm81 for 8.0
     let out_expected_ext = match assets.version {
         GameVersion::GameMaker8_0 => "gmk",
@@ -284,10 +496,6 @@ fn decompile(
         },
     };
 
-    if deobfuscate {
-        deobfuscate::process(&mut assets);
-    }
-
     let mut gmk = fs::File::create(&out_path)
         .map_err(|e| format!("Failed to create output file '{}': {}", out_path.display(), e))?;
 
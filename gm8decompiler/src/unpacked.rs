@@ -0,0 +1,787 @@
+//! Writes a game out as an unpacked project directory instead of a GMK.
+//!
+//! Every asset gets its own file (or folder), named after the asset, so the result can be put under version
+//! control and diffed sensibly. Each asset folder has an `index.txt` mapping asset IDs to the file names used,
+//! since names don't have to be unique (or valid file names) in GameMaker.
+
+use byteorder::{WriteBytesExt, BE};
+use flate2::{write::ZlibEncoder, Compression};
+use gm8exe::{
+    asset::{
+        extension::{CallingConvention, FileKind, FunctionValueKind},
+        included_file::ExportSetting,
+        object::EVENT_NAMES,
+        path::ConnectionKind,
+        sound::SoundKind,
+        trigger::TriggerKind,
+        CodeAction, PascalString,
+    },
+    AssetList, Colour, GameAssets, GameVersion,
+};
+use rayon::prelude::*;
+use std::{
+    collections::HashSet,
+    fs,
+    io::{self, Write},
+    path::Path,
+};
+
+/// File names given to each asset, indexed by asset ID.
+type Stems = Vec<Option<String>>;
+
+struct Context<'a> {
+    assets: &'a GameAssets,
+    sprites: Stems,
+    sounds: Stems,
+    backgrounds: Stems,
+    paths: Stems,
+    scripts: Stems,
+    fonts: Stems,
+    timelines: Stems,
+    objects: Stems,
+    rooms: Stems,
+    triggers: Stems,
+}
+
+/// Writes the whole game into the given directory, creating it if needed.
+pub fn write(dir: &Path, assets: &GameAssets, multithread: bool) -> io::Result<()> {
+    fs::create_dir_all(dir)?;
+    let ctx = Context {
+        assets,
+        sprites: stems(assets.sprites.iter().map(|x| x.as_ref().map(|x| &x.name))),
+        sounds: stems(assets.sounds.iter().map(|x| x.as_ref().map(|x| &x.name))),
+        backgrounds: stems(assets.backgrounds.iter().map(|x| x.as_ref().map(|x| &x.name))),
+        paths: stems(assets.paths.iter().map(|x| x.as_ref().map(|x| &x.name))),
+        scripts: stems(assets.scripts.iter().map(|x| x.as_ref().map(|x| &x.name))),
+        fonts: stems(assets.fonts.iter().map(|x| x.as_ref().map(|x| &x.name))),
+        timelines: stems(assets.timelines.iter().map(|x| x.as_ref().map(|x| &x.name))),
+        objects: stems(assets.objects.iter().map(|x| x.as_ref().map(|x| &x.name))),
+        rooms: stems(assets.rooms.iter().map(|x| x.as_ref().map(|x| &x.name))),
+        triggers: stems(assets.triggers.iter().map(|x| x.as_ref().map(|x| &x.name))),
+    };
+
+    write_project(dir, &ctx)?;
+    write_settings(dir, &ctx)?;
+    write_game_information(dir, &ctx)?;
+    write_constants(dir, &ctx)?;
+
+    write_list(dir, "Sprites", &assets.sprites, &ctx.sprites, multithread, |dir, stem, sprite| {
+        let dir = dir.join(stem);
+        fs::create_dir_all(&dir)?;
+        let mut out = Vec::new();
+        writeln!(out, "origin = {}, {}", sprite.origin_x, sprite.origin_y)?;
+        writeln!(out, "frames = {}", sprite.frames.len())?;
+        writeln!(out, "per_frame_colliders = {}", sprite.per_frame_colliders)?;
+        for (i, frame) in sprite.frames.iter().enumerate() {
+            writeln!(out, "frame {} = {}x{}", i, frame.width, frame.height)?;
+            write_png(&dir.join(format!("frame_{}.png", i)), frame.width, frame.height, &bgra_to_rgba(&frame.data))?;
+        }
+        for (i, mask) in sprite.colliders.iter().enumerate() {
+            writeln!(
+                out,
+                "mask {} = {}x{}, bbox {} {} {} {}",
+                i, mask.width, mask.height, mask.bbox_left, mask.bbox_top, mask.bbox_right, mask.bbox_bottom,
+            )?;
+            let rgba = mask.data.iter().flat_map(|&x| if x { [255; 4] } else { [0; 4] }).collect::<Vec<_>>();
+            write_png(&dir.join(format!("mask_{}.png", i)), mask.width, mask.height, &rgba)?;
+        }
+        fs::write(dir.join("sprite.txt"), out)
+    })?;
+
+    write_list(dir, "Sounds", &assets.sounds, &ctx.sounds, multithread, |dir, stem, sound| {
+        let mut out = Vec::new();
+        let kind = match sound.kind {
+            SoundKind::Normal => "normal",
+            SoundKind::BackgroundMusic => "music",
+            SoundKind::ThreeDimensional => "3d",
+            SoundKind::Multimedia => "multimedia",
+        };
+        writeln!(out, "kind = {}", kind)?;
+        writeln!(out, "extension = {}", quote(&sound.extension))?;
+        writeln!(out, "source = {}", quote(&sound.source))?;
+        writeln!(out, "volume = {}", sound.volume)?;
+        writeln!(out, "pan = {}", sound.pan)?;
+        writeln!(out, "preload = {}", sound.preload)?;
+        let fx = &sound.fx;
+        let fx = [
+            ("chorus", fx.chorus),
+            ("echo", fx.echo),
+            ("flanger", fx.flanger),
+            ("gargle", fx.gargle),
+            ("reverb", fx.reverb),
+        ];
+        for (name, _) in fx.iter().filter(|(_, on)| *on) {
+            writeln!(out, "fx = {}", name)?;
+        }
+        if let Some(data) = &sound.data {
+            let extension = sanitize(&sound.extension.to_string());
+            let extension = extension.trim_start_matches('.');
+            let extension =
+                if extension.is_empty() || extension.eq_ignore_ascii_case("txt") { "bin" } else { extension };
+            fs::write(dir.join(format!("{}.{}", stem, extension)), data)?;
+        }
+        fs::write(dir.join(format!("{}.txt", stem)), out)
+    })?;
+
+    write_list(dir, "Backgrounds", &assets.backgrounds, &ctx.backgrounds, multithread, |dir, stem, background| {
+        if let Some(data) = &background.data {
+            write_png(&dir.join(format!("{}.png", stem)), background.width, background.height, &bgra_to_rgba(data))?;
+        }
+        fs::write(dir.join(format!("{}.txt", stem)), format!("size = {}x{}\n", background.width, background.height))
+    })?;
+
+    write_list(dir, "Paths", &assets.paths, &ctx.paths, multithread, |dir, stem, path| {
+        let mut out = Vec::new();
+        let connection = match path.connection {
+            ConnectionKind::StraightLine => "straight",
+            ConnectionKind::SmoothCurve => "smooth",
+        };
+        writeln!(out, "connection = {}", connection)?;
+        writeln!(out, "precision = {}", path.precision)?;
+        writeln!(out, "closed = {}", path.closed)?;
+        for point in &path.points {
+            writeln!(out, "point = {}, {}, {}", point.x, point.y, point.speed)?;
+        }
+        fs::write(dir.join(format!("{}.txt", stem)), out)
+    })?;
+
+    write_list(dir, "Scripts", &assets.scripts, &ctx.scripts, multithread, |dir, stem, script| {
+        fs::write(dir.join(format!("{}.gml", stem)), &script.source.0)
+    })?;
+
+    write_list(dir, "Fonts", &assets.fonts, &ctx.fonts, multithread, |dir, stem, font| {
+        let mut out = Vec::new();
+        writeln!(out, "font = {}", quote(&font.sys_name))?;
+        writeln!(out, "size = {}", font.size)?;
+        writeln!(out, "bold = {}", font.bold)?;
+        writeln!(out, "italic = {}", font.italic)?;
+        writeln!(out, "range = {}, {}", font.range_start, font.range_end)?;
+        writeln!(out, "charset = {}", font.charset)?;
+        writeln!(out, "aa_level = {}", font.aa_level)?;
+        writeln!(out, "map_size = {}x{}", font.map_width, font.map_height)?;
+        for c in font.range_start.min(255)..=font.range_end.min(255) {
+            let glyph = &font.dmap[c as usize * 6..][..6];
+            writeln!(
+                out,
+                "glyph {} = {} {} {} {} {} {}",
+                c, glyph[0], glyph[1], glyph[2], glyph[3], glyph[4], glyph[5]
+            )?;
+        }
+        let rgba = font.pixel_map.iter().flat_map(|&a| [255, 255, 255, a]).collect::<Vec<_>>();
+        write_png(&dir.join(format!("{}.png", stem)), font.map_width, font.map_height, &rgba)?;
+        fs::write(dir.join(format!("{}.txt", stem)), out)
+    })?;
+
+    write_list(dir, "Time Lines", &assets.timelines, &ctx.timelines, multithread, |dir, stem, timeline| {
+        let dir = dir.join(stem);
+        fs::create_dir_all(&dir)?;
+        for (moment, actions) in &timeline.moments {
+            write_actions(&dir.join(format!("moment_{}.gml", moment)), actions, &ctx)?;
+        }
+        Ok(())
+    })?;
+
+    write_list(dir, "Objects", &assets.objects, &ctx.objects, multithread, |dir, stem, object| {
+        let dir = dir.join(stem);
+        fs::create_dir_all(&dir)?;
+        let mut out = Vec::new();
+        writeln!(out, "sprite = {}", reference(&ctx.sprites, object.sprite_index))?;
+        writeln!(out, "solid = {}", object.solid)?;
+        writeln!(out, "visible = {}", object.visible)?;
+        writeln!(out, "depth = {}", object.depth)?;
+        writeln!(out, "persistent = {}", object.persistent)?;
+        writeln!(out, "parent = {}", reference(&ctx.objects, object.parent_index))?;
+        writeln!(out, "mask = {}", reference(&ctx.sprites, object.mask_index))?;
+        for (ev_type, events) in object.events.iter().enumerate() {
+            for (sub, actions) in events {
+                let name = event_name(ev_type, *sub, &ctx);
+                writeln!(out, "event = {}", name)?;
+                write_actions(&dir.join(format!("{}.gml", name)), actions, &ctx)?;
+            }
+        }
+        fs::write(dir.join("object.txt"), out)
+    })?;
+
+    write_list(dir, "Rooms", &assets.rooms, &ctx.rooms, multithread, |dir, stem, room| {
+        let dir = dir.join(stem);
+        fs::create_dir_all(&dir)?;
+        let mut out = Vec::new();
+        writeln!(out, "caption = {}", quote(&room.caption))?;
+        writeln!(out, "size = {}x{}", room.width, room.height)?;
+        writeln!(out, "speed = {}", room.speed)?;
+        writeln!(out, "persistent = {}", room.persistent)?;
+        writeln!(out, "colour = {}", colour(room.bg_colour))?;
+        writeln!(out, "clear_screen = {}", room.clear_screen)?;
+        writeln!(out, "clear_region = {}", room.clear_region)?;
+        writeln!(out, "views_enabled = {}", room.views_enabled)?;
+        for (i, bg) in room.backgrounds.iter().enumerate() {
+            writeln!(out, "\n[background {}]", i)?;
+            writeln!(out, "visible = {}", bg.visible_on_start)?;
+            writeln!(out, "foreground = {}", bg.is_foreground)?;
+            writeln!(out, "background = {}", reference(&ctx.backgrounds, bg.source_bg))?;
+            writeln!(out, "offset = {}, {}", bg.xoffset, bg.yoffset)?;
+            writeln!(out, "tile = {}, {}", bg.tile_horz, bg.tile_vert)?;
+            writeln!(out, "speed = {}, {}", bg.hspeed, bg.vspeed)?;
+            writeln!(out, "stretch = {}", bg.stretch)?;
+        }
+        for (i, view) in room.views.iter().enumerate() {
+            writeln!(out, "\n[view {}]", i)?;
+            writeln!(out, "visible = {}", view.visible)?;
+            writeln!(out, "source = {}, {}, {}x{}", view.source_x, view.source_y, view.source_w, view.source_h)?;
+            writeln!(out, "port = {}, {}, {}x{}", view.port_x, view.port_y, view.port_w, view.port_h)?;
+            let follow = &view.following;
+            writeln!(out, "follow = {}", reference(&ctx.objects, follow.target))?;
+            writeln!(out, "border = {}, {}", follow.hborder, follow.vborder)?;
+            writeln!(out, "speed = {}, {}", follow.hspeed, follow.vspeed)?;
+        }
+        writeln!(out, "\n[instances]")?;
+        for instance in &room.instances {
+            writeln!(
+                out,
+                "{} = {} {}, {} scale {}, {} angle {} blend {:#010X}",
+                instance.id,
+                reference(&ctx.objects, instance.object),
+                instance.x,
+                instance.y,
+                instance.xscale,
+                instance.yscale,
+                instance.angle,
+                instance.blend,
+            )?;
+            if !instance.creation_code.0.is_empty() {
+                fs::write(dir.join(format!("instance_{}.gml", instance.id)), &instance.creation_code.0)?;
+            }
+        }
+        writeln!(out, "\n[tiles]")?;
+        for tile in &room.tiles {
+            writeln!(
+                out,
+                "{} = {} {}, {} from {}, {} {}x{} depth {} scale {}, {} blend {:#010X}",
+                tile.id,
+                reference(&ctx.backgrounds, tile.source_bg),
+                tile.x,
+                tile.y,
+                tile.tile_x,
+                tile.tile_y,
+                tile.width,
+                tile.height,
+                tile.depth,
+                tile.xscale,
+                tile.yscale,
+                tile.blend,
+            )?;
+        }
+        if !room.creation_code.0.is_empty() {
+            fs::write(dir.join("creation_code.gml"), &room.creation_code.0)?;
+        }
+        fs::write(dir.join("room.txt"), out)
+    })?;
+
+    write_list(dir, "Triggers", &assets.triggers, &ctx.triggers, multithread, |dir, stem, trigger| {
+        let moment = match trigger.moment {
+            TriggerKind::Step => "step",
+            TriggerKind::BeginStep => "step_begin",
+            TriggerKind::EndStep => "step_end",
+        };
+        let text = format!("moment = {}\nconstant = {}\n", moment, quote(&trigger.constant_name));
+        fs::write(dir.join(format!("{}.txt", stem)), text)?;
+        fs::write(dir.join(format!("{}.gml", stem)), &trigger.condition.0)
+    })?;
+
+    write_included_files(dir, &ctx)?;
+    write_extensions(dir, &ctx)?;
+
+    let init_dir = dir.join("Library Init");
+    fs::create_dir_all(&init_dir)?;
+    for (i, code) in assets.library_init_strings.iter().enumerate() {
+        fs::write(init_dir.join(format!("{}.gml", i)), &code.0)?;
+    }
+    Ok(())
+}
+
+/// Picks a unique file name for every asset, based on its name.
+/// Names that clash with each other (case-insensitively) or with `index` get the asset ID appended,
+/// plus a counter if that's taken too.
+pub(crate) fn stems<'a>(names: impl Iterator<Item = Option<&'a PascalString>>) -> Stems {
+    let names = names.map(|x| x.map(|x| sanitize(&x.to_string()))).collect::<Vec<_>>();
+    let mut taken = HashSet::new();
+    taken.insert("index".to_string());
+    let mut seen = HashSet::new();
+    let duplicates =
+        names.iter().flatten().map(|x| x.to_lowercase()).filter(|x| !seen.insert(x.clone())).collect::<HashSet<_>>();
+    names
+        .into_iter()
+        .enumerate()
+        .map(|(i, name)| {
+            name.map(|name| {
+                let lower = name.to_lowercase();
+                if name.is_empty() || duplicates.contains(&lower) || !taken.insert(lower) {
+                    let base = if name.is_empty() { format!("{}", i) } else { format!("{}_{}", name, i) };
+                    // Another asset could already be called that, so keep counting up until it's free
+                    let mut stem = base.clone();
+                    let mut suffix = 1;
+                    while !taken.insert(stem.to_lowercase()) {
+                        suffix += 1;
+                        stem = format!("{}_{}", base, suffix);
+                    }
+                    stem
+                } else {
+                    name
+                }
+            })
+        })
+        .collect()
+}
+
+/// Replaces anything which can't go in a file name on common filesystems.
+pub(crate) fn sanitize(name: &str) -> String {
+    const RESERVED: [&str; 22] = [
+        "con", "prn", "aux", "nul", "com1", "com2", "com3", "com4", "com5", "com6", "com7", "com8", "com9", "lpt1",
+        "lpt2", "lpt3", "lpt4", "lpt5", "lpt6", "lpt7", "lpt8", "lpt9",
+    ];
+    let mut name = name
+        .chars()
+        .map(|c| if c.is_control() || "<>:\"/\\|?*".contains(c) { '_' } else { c })
+        .collect::<String>()
+        .trim_end_matches(['.', ' '])
+        .to_string();
+    if RESERVED.contains(&name.to_lowercase().as_str()) {
+        name.push('_');
+    }
+    name
+}
+
+/// Looks up the file name of an asset by ID, falling back to the number itself if there's no such asset.
+fn reference(stems: &[Option<String>], id: i32) -> String {
+    usize::try_from(id).ok().and_then(|i| stems.get(i)).and_then(|x| x.clone()).unwrap_or_else(|| id.to_string())
+}
+
+fn quote(s: &PascalString) -> String {
+    format!("{:?}", s.to_string())
+}
+
+fn colour(c: Colour) -> String {
+    format!("#{:02X}{:02X}{:02X}", c.r, c.g, c.b)
+}
+
+fn event_name(ev_type: usize, sub: u32, ctx: &Context) -> String {
+    match (ev_type, sub) {
+        (0 | 1 | 8, 0) => EVENT_NAMES[ev_type].into(),
+        (3, 0) => "step".into(),
+        (3, 1) => "step_begin".into(),
+        (3, 2) => "step_end".into(),
+        (4, _) => format!("collision_{}", reference(&ctx.objects, sub as i32)),
+        _ => format!("{}_{}", EVENT_NAMES.get(ev_type).unwrap_or(&"event"), sub),
+    }
+}
+
+/// Writes an action list as GML. Code actions are written as-is, and every action is preceded by a
+/// `/// action` comment describing it.
+fn write_actions(path: &Path, actions: &[CodeAction], ctx: &Context) -> io::Result<()> {
+    let mut out = Vec::new();
+    for action in actions {
+        write!(out, "/// action {}:{}", action.lib_id, action.id)?;
+        if action.applies_to_something {
+            let target = match action.applies_to {
+                -1 => "self".into(),
+                -2 => "other".into(),
+                id => reference(&ctx.objects, id),
+            };
+            write!(out, " applies_to={}", target)?;
+        }
+        if action.is_relative {
+            write!(out, " relative")?;
+        }
+        if action.invert_condition {
+            write!(out, " not")?;
+        }
+        writeln!(out)?;
+        if action.action_kind == 7 && action.execution_type == 2 {
+            out.extend_from_slice(&action.param_strings[0].0);
+            if !out.ends_with(b"\n") {
+                out.push(b'\n');
+            }
+        } else {
+            writeln!(out, "/// kind={} execution={}", action.action_kind, action.execution_type)?;
+            if !action.fn_name.0.is_empty() {
+                writeln!(out, "/// function {}", action.fn_name)?;
+            }
+            if !action.fn_code.0.is_empty() {
+                writeln!(out, "/// code {}", quote(&action.fn_code))?;
+            }
+            for (i, arg) in action.param_strings.iter().take(action.param_count).enumerate() {
+                writeln!(out, "/// arg{} = {}", i, quote(arg))?;
+            }
+        }
+    }
+    fs::write(path, out)
+}
+
+/// Creates a folder for an asset type, with its `index.txt`, and calls `write_fn` for each existing asset.
+fn write_list<T, F>(
+    dir: &Path,
+    heading: &str,
+    list: &AssetList<T>,
+    stems: &[Option<String>],
+    multithread: bool,
+    write_fn: F,
+) -> io::Result<()>
+where
+    T: Sync,
+    F: Fn(&Path, &str, &T) -> io::Result<()> + Sync,
+{
+    let dir = dir.join(heading);
+    fs::create_dir_all(&dir)?;
+    let mut index = Vec::new();
+    for (i, (asset, stem)) in list.iter().zip(stems).enumerate() {
+        match (asset, stem) {
+            (Some(_), Some(stem)) => writeln!(index, "{} {}", i, stem)?,
+            _ => writeln!(index, "{} -", i)?,
+        }
+    }
+    fs::write(dir.join("index.txt"), index)?;
+
+    let jobs = list.iter().zip(stems).filter_map(|(asset, stem)| Some((asset.as_deref()?, stem.as_deref()?)));
+    if multithread {
+        jobs.collect::<Vec<_>>().into_par_iter().try_for_each(|(asset, stem)| write_fn(&dir, stem, asset))
+    } else {
+        jobs.into_iter().try_for_each(|(asset, stem)| write_fn(&dir, stem, asset))
+    }
+}
+
+fn write_project(dir: &Path, ctx: &Context) -> io::Result<()> {
+    let assets = ctx.assets;
+    let mut out = Vec::new();
+    let version = match assets.version {
+        GameVersion::GameMaker8_0 => 800,
+        GameVersion::GameMaker8_1 => 810,
+    };
+    writeln!(out, "version = {}", version)?;
+    writeln!(out, "game_id = {}", assets.game_id)?;
+    let [a, b, c, d] = assets.guid;
+    writeln!(out, "guid = {:08X}{:08X}{:08X}{:08X}", a, b, c, d)?;
+    writeln!(out, "last_instance_id = {}", assets.last_instance_id)?;
+    writeln!(out, "last_tile_id = {}", assets.last_tile_id)?;
+    for &room in &assets.room_order {
+        writeln!(out, "room = {}", reference(&ctx.rooms, room))?;
+    }
+    fs::write(dir.join("project.txt"), out)
+}
+
+fn write_settings(dir: &Path, ctx: &Context) -> io::Result<()> {
+    let settings = &ctx.assets.settings;
+    let mut out = Vec::new();
+    macro_rules! fields {
+        ($($field: ident),* $(,)?) => {
+            $(writeln!(out, "{} = {}", stringify!($field), settings.$field)?;)*
+        };
+    }
+    fields!(
+        fullscreen,
+        scaling,
+        interpolate_pixels,
+        allow_resize,
+        window_on_top,
+        dont_draw_border,
+        dont_show_buttons,
+        display_cursor,
+        freeze_on_lose_focus,
+        disable_screensaver,
+        force_cpu_render,
+        set_resolution,
+        colour_depth,
+        resolution,
+        frequency,
+        vsync,
+        esc_close_game,
+        treat_close_as_esc,
+        f1_help_menu,
+        f4_fullscreen_toggle,
+        f5_save_f6_load,
+        f9_screenshot,
+        priority,
+        transparent,
+        translucency,
+        loading_bar,
+        scale_progress_bar,
+        show_error_messages,
+        log_errors,
+        always_abort,
+        zero_uninitialized_vars,
+        error_on_uninitialized_args,
+        swap_creation_events,
+    );
+    writeln!(out, "clear_colour = {:#08X}", settings.clear_colour)?;
+    fs::write(dir.join("settings.txt"), out)?;
+
+    // The images are stored as they were in the exe, which is BMP format
+    let images = [
+        ("loading_image.bmp", &settings.custom_load_image),
+        ("loading_bar_back.bmp", &settings.backdata),
+        ("loading_bar_front.bmp", &settings.frontdata),
+    ];
+    for (name, data) in images {
+        if let Some(data) = data {
+            fs::write(dir.join(name), data)?;
+        }
+    }
+    if let Some(ico) = &ctx.assets.ico_file_raw {
+        fs::write(dir.join("icon.ico"), ico)?;
+    }
+    Ok(())
+}
+
+fn write_game_information(dir: &Path, ctx: &Context) -> io::Result<()> {
+    let info = &ctx.assets.help_dialog;
+    let mut out = Vec::new();
+    writeln!(out, "caption = {}", quote(&info.caption))?;
+    writeln!(out, "colour = {}", colour(info.bg_colour))?;
+    writeln!(out, "new_window = {}", info.new_window)?;
+    writeln!(out, "position = {}, {}, {}x{}", info.left, info.top, info.width, info.height)?;
+    writeln!(out, "border = {}", info.border)?;
+    writeln!(out, "resizable = {}", info.resizable)?;
+    writeln!(out, "window_on_top = {}", info.window_on_top)?;
+    writeln!(out, "freeze_game = {}", info.freeze_game)?;
+    fs::write(dir.join("game_information.txt"), out)?;
+    fs::write(dir.join("game_information.rtf"), &info.info.0)
+}
+
+fn write_constants(dir: &Path, ctx: &Context) -> io::Result<()> {
+    let mut out = Vec::new();
+    for constant in &ctx.assets.constants {
+        out.extend_from_slice(&constant.name.0);
+        out.extend_from_slice(b" = ");
+        out.extend_from_slice(&constant.expression.0);
+        out.push(b'\n');
+    }
+    fs::write(dir.join("constants.txt"), out)
+}
+
+fn write_included_files(dir: &Path, ctx: &Context) -> io::Result<()> {
+    let dir = dir.join("Included Files");
+    fs::create_dir_all(&dir)?;
+    let files = &ctx.assets.included_files;
+    let stems = stems(files.iter().map(|x| Some(&x.file_name)));
+    let mut index = Vec::new();
+    for (i, (file, stem)) in files.iter().zip(stems.iter().flatten()).enumerate() {
+        writeln!(index, "{} {}", i, stem)?;
+        let mut out = Vec::new();
+        writeln!(out, "file_name = {}", quote(&file.file_name))?;
+        writeln!(out, "source_path = {}", quote(&file.source_path))?;
+        writeln!(out, "source_length = {}", file.source_length)?;
+        writeln!(out, "stored_in_gmk = {}", file.stored_in_gmk)?;
+        match &file.export_settings {
+            ExportSetting::NoExport => writeln!(out, "export = none")?,
+            ExportSetting::TempFolder => writeln!(out, "export = temp")?,
+            ExportSetting::GameFolder => writeln!(out, "export = game")?,
+            ExportSetting::CustomFolder(folder) => writeln!(out, "export = {}", quote(folder))?,
+        }
+        writeln!(out, "overwrite_file = {}", file.overwrite_file)?;
+        writeln!(out, "free_memory = {}", file.free_memory)?;
+        writeln!(out, "remove_at_end = {}", file.remove_at_end)?;
+        if let Some(data) = &file.embedded_data {
+            fs::write(dir.join(format!("{}.data", stem)), data)?;
+        }
+        fs::write(dir.join(format!("{}.txt", stem)), out)?;
+    }
+    fs::write(dir.join("index.txt"), index)
+}
+
+fn write_extensions(dir: &Path, ctx: &Context) -> io::Result<()> {
+    let dir = dir.join("Extension Packages");
+    fs::create_dir_all(&dir)?;
+    let extensions = &ctx.assets.extensions;
+    let stems = stems(extensions.iter().map(|x| Some(&x.name)));
+    let mut index = Vec::new();
+    for (i, (extension, stem)) in extensions.iter().zip(stems.iter().flatten()).enumerate() {
+        writeln!(index, "{} {}", i, stem)?;
+        let dir = dir.join(stem);
+        fs::create_dir_all(&dir)?;
+        let file_stems = stems_for_files(extension);
+        let mut out = Vec::new();
+        writeln!(out, "folder_name = {}", quote(&extension.folder_name))?;
+        for (file, file_stem) in extension.files.iter().zip(file_stems.iter().flatten()) {
+            let kind = match file.kind {
+                FileKind::DynamicLibrary => "dll",
+                FileKind::GmlScript => "gml",
+                FileKind::ActionLibrary => "lib",
+                FileKind::Other => "other",
+            };
+            writeln!(out, "\n[file {}]", quote(&file.name))?;
+            writeln!(out, "contents = {}", file_stem)?;
+            writeln!(out, "kind = {}", kind)?;
+            writeln!(out, "initializer = {}", quote(&file.initializer))?;
+            writeln!(out, "finalizer = {}", quote(&file.finalizer))?;
+            for function in &file.functions {
+                let convention = match function.convention {
+                    CallingConvention::Gml => "gml",
+                    CallingConvention::Stdcall => "stdcall",
+                    CallingConvention::Cdecl => "cdecl",
+                    CallingConvention::Unknown => "unknown",
+                };
+                let value_kind = |kind: FunctionValueKind| match kind {
+                    FunctionValueKind::GMString => "string",
+                    FunctionValueKind::GMReal => "real",
+                };
+                let args = match usize::try_from(function.arg_count) {
+                    Ok(count) => function.arg_types.iter().take(count).map(|&x| value_kind(x)).collect::<Vec<_>>(),
+                    Err(_) => vec!["..."],
+                };
+                writeln!(
+                    out,
+                    "function {} = {} {} {}({}) -> {}",
+                    function.id,
+                    function.name,
+                    convention,
+                    function.external_name,
+                    args.join(", "),
+                    value_kind(function.return_type),
+                )?;
+            }
+            for constant in &file.consts {
+                writeln!(out, "constant {} = {}", constant.name, constant.value)?;
+            }
+            fs::write(dir.join(file_stem), &file.contents)?;
+        }
+        fs::write(dir.join("extension.txt"), out)?;
+    }
+    fs::write(dir.join("index.txt"), index)
+}
+
+fn stems_for_files(extension: &gm8exe::asset::Extension) -> Stems {
+    let mut stems = stems(extension.files.iter().map(|x| Some(&x.name)));
+    // the extension's own metadata lives alongside its files
+    for stem in stems.iter_mut().flatten() {
+        if stem.eq_ignore_ascii_case("extension.txt") {
+            stem.push('_');
+        }
+    }
+    stems
+}
+
+pub(crate) fn bgra_to_rgba(data: &[u8]) -> Vec<u8> {
+    data.chunks_exact(4).flat_map(|x| [x[2], x[1], x[0], x[3]]).collect()
+}
+
+/// Writes an 8-bit RGBA image as an unfiltered PNG. Empty images are skipped, since PNG can't represent them.
+pub(crate) fn write_png(path: &Path, width: u32, height: u32, rgba: &[u8]) -> io::Result<()> {
+    if width == 0 || height == 0 {
+        return Ok(())
+    }
+    fs::write(path, encode_png(width, height, rgba)?)
+}
+
+/// Encodes an 8-bit RGBA image as an unfiltered PNG, failing if there isn't enough pixel data for its size.
+fn encode_png(width: u32, height: u32, rgba: &[u8]) -> io::Result<Vec<u8>> {
+    fn write_chunk(out: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) -> io::Result<()> {
+        out.write_u32::<BE>(data.len() as u32)?;
+        out.extend_from_slice(kind);
+        out.extend_from_slice(data);
+        let mut hasher = crc32fast::Hasher::new();
+        hasher.update(kind);
+        hasher.update(data);
+        out.write_u32::<BE>(hasher.finalize())
+    }
+
+    let row_len = width as usize * 4;
+    let needed = row_len * height as usize;
+    if rgba.len() < needed {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("{}x{} image needs {} bytes of pixel data, got {}", width, height, needed, rgba.len()),
+        ))
+    }
+    let mut header = Vec::with_capacity(13);
+    header.write_u32::<BE>(width)?;
+    header.write_u32::<BE>(height)?;
+    header.extend_from_slice(&[8, 6, 0, 0, 0]); // 8-bit depth, RGBA, deflate, no filtering, no interlacing
+
+    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+    for row in rgba.chunks_exact(row_len).take(height as usize) {
+        encoder.write_all(&[0])?;
+        encoder.write_all(row)?;
+    }
+    let data = encoder.finish()?;
+
+    let mut out = b"\x89PNG\r\n\x1a\n".to_vec();
+    write_chunk(&mut out, b"IHDR", &header)?;
+    write_chunk(&mut out, b"IDAT", &data)?;
+    write_chunk(&mut out, b"IEND", &[])?;
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::read::ZlibDecoder;
+    use std::io::Read;
+
+    fn names(names: &[Option<&str>]) -> Vec<Option<PascalString>> {
+        names.iter().map(|x| x.map(PascalString::from)).collect()
+    }
+
+    #[test]
+    fn sanitize_names() {
+        assert_eq!(sanitize("obj_player"), "obj_player");
+        assert_eq!(sanitize("a/b\\c:d*e?f\"g<h>i|j"), "a_b_c_d_e_f_g_h_i_j");
+        assert_eq!(sanitize("tab\there"), "tab_here");
+        assert_eq!(sanitize("trailing. . "), "trailing");
+        assert_eq!(sanitize("CON"), "CON_");
+        assert_eq!(sanitize("lpt1"), "lpt1_");
+        assert_eq!(sanitize("console"), "console");
+        assert_eq!(sanitize("spr_ü"), "spr_ü");
+    }
+
+    #[test]
+    fn stems_are_unique() {
+        let list = names(&[Some("spr_a"), None, Some("SPR_A"), Some("spr_b"), Some(""), Some("index"), Some("a?")]);
+        let stems = stems(list.iter().map(|x| x.as_ref()));
+        assert_eq!(stems, [
+            Some("spr_a_0".to_string()),
+            None,
+            Some("SPR_A_2".to_string()),
+            Some("spr_b".to_string()),
+            Some("4".to_string()),
+            Some("index_5".to_string()),
+            Some("a_".to_string()),
+        ]);
+    }
+
+    #[test]
+    fn stems_count_up_past_taken_names() {
+        // "x_2" is a real asset name, so the second "x" can't use it
+        let list = names(&[Some("x_2"), Some("x"), Some("x")]);
+        let stems = stems(list.iter().map(|x| x.as_ref()));
+        assert_eq!(stems, [Some("x_2".to_string()), Some("x_1".to_string()), Some("x_2_2".to_string())]);
+    }
+
+    #[test]
+    fn encode_png_layout() {
+        let rgba = [1, 2, 3, 4, 5, 6, 7, 8];
+        let png = encode_png(2, 1, &rgba).unwrap();
+        assert_eq!(&png[..8], b"\x89PNG\r\n\x1a\n");
+        assert_eq!(&png[8..16], b"\0\0\0\x0dIHDR");
+        assert_eq!(&png[16..29], [0, 0, 0, 2, 0, 0, 0, 1, 8, 6, 0, 0, 0]);
+        assert_eq!(&png[29..33], crc32fast::hash(&png[12..29]).to_be_bytes());
+
+        let idat_len = u32::from_be_bytes(png[33..37].try_into().unwrap()) as usize;
+        assert_eq!(&png[37..41], b"IDAT");
+        let mut pixels = Vec::new();
+        ZlibDecoder::new(&png[41..41 + idat_len]).read_to_end(&mut pixels).unwrap();
+        assert_eq!(pixels, [0, 1, 2, 3, 4, 5, 6, 7, 8]);
+        assert_eq!(&png[41 + idat_len + 4..], b"\0\0\0\0IEND\xaeB`\x82");
+    }
+
+    #[test]
+    fn encode_png_rejects_short_data() {
+        assert_eq!(encode_png(2, 2, &[0; 15]).unwrap_err().kind(), io::ErrorKind::InvalidData);
+        assert!(encode_png(2, 2, &[0; 16]).is_ok());
+    }
+
+    #[test]
+    fn write_png_skips_empty_images() {
+        let path = std::env::temp_dir().join(format!("gm8decompiler-empty-{}.png", std::process::id()));
+        write_png(&path, 0, 4, &[]).unwrap();
+        assert!(!path.exists());
+    }
+}
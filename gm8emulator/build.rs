@@ -6,6 +6,8 @@ use std::{
     path::Path,
 };
 
+mod kernel_functions;
+
 static OPENGL_EXTENSIONS: &[&str] = &[];
 
 fn main() -> Result<(), Box<dyn Error>> {
@@ -40,6 +42,13 @@ fn main() -> Result<(), Box<dyn Error>> {
         fs::write(aa_macro_path, &aa_macro)?;
     }
 
+    // GML names of kernel functions which are still unimplemented!(), for the compatibility scanner
+    let (_, unimplemented) = kernel_functions::scan(Path::new("src"))?;
+    fs::write(
+        Path::new(&out).join("unimplemented_functions.rs"),
+        format!("pub static UNIMPLEMENTED_FUNCTIONS: &[&str] = &{:?};\n", unimplemented),
+    )?;
+
     // opengl bindings
     let mut bindings = File::create(&Path::new(&out).join("gl_bindings.rs"))?;
     Registry::new(Api::Gl, (3, 3), Profile::Core, Fallbacks::All, &OPENGL_EXTENSIONS)
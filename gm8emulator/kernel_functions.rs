@@ -0,0 +1,33 @@
+//! Scans the emulator's source for the GML functions it has mappings for, and which of those are still
+//! `unimplemented!`. This is used by the build scripts of both gm8emulator and gm8decompiler.
+
+use std::{fs, io, path::Path};
+
+/// Returns the GML names of every function in the mappings, then of the ones which do nothing but panic,
+/// both sorted. `src` is the emulator's `src` directory.
+pub fn scan(src: &Path) -> io::Result<(Vec<String>, Vec<String>)> {
+    // Kernel functions which do nothing but panic, by their method names
+    let kernel = fs::read_to_string(src.join("gml/kernel.rs"))?;
+    let unimplemented_methods = kernel
+        .lines()
+        .filter_map(|line| line.trim().strip_prefix("unimplemented!(\"Called unimplemented kernel function "))
+        .filter_map(|rest| rest.split('"').next())
+        .collect::<Vec<_>>();
+
+    let (mut known, mut unimplemented) = (Vec::new(), Vec::new());
+    for line in fs::read_to_string(src.join("gml/mappings.rs"))?.lines() {
+        // Lines in the mappings look like: "name" => Function::Engine(Game::method),
+        let line = line.trim();
+        let name = line.strip_prefix('"').and_then(|x| x.split_once("\" => Function::")).map(|(name, _)| name);
+        let method = line.split_once("(Game::").and_then(|(_, x)| x.split_once(')')).map(|(method, _)| method);
+        if let (Some(name), Some(method)) = (name, method) {
+            known.push(name.to_string());
+            if unimplemented_methods.contains(&method) {
+                unimplemented.push(name.to_string());
+            }
+        }
+    }
+    known.sort();
+    unimplemented.sort();
+    Ok((known, unimplemented))
+}
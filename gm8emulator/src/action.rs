@@ -1,12 +1,14 @@
 use crate::{
-    game::Game,
+    game::{profiler::Key, Game, GetAsset},
     gml::{
         self,
         compiler::Compiler,
+        debugger::Unit,
         mappings,
-        runtime::{Instruction, Node},
+        runtime::{Frame, Instruction, Node},
         Context, Value,
     },
+    types::ID,
 };
 use gm8exe::asset::CodeAction;
 use serde::{Deserialize, Serialize};
@@ -87,6 +89,16 @@ pub enum GmlBody {
     Code(Rc<[Instruction]>),
 }
 
+/// What a tree of actions is being executed for, which is needed to say where runtime errors happen.
+#[derive(Clone, Copy)]
+pub enum Owner {
+    /// An object event. Its type, number and object are passed to execute_tree alongside this, so aren't repeated here
+    Event,
+
+    /// A time line moment, given by the time line's ID and the moment
+    Moment(ID, i32),
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub enum ReturnType {
     Continue,
@@ -190,7 +202,9 @@ impl Tree {
                                         &action.param_types,
                                         action.param_count,
                                     )?,
-                                    body: GmlBody::Code(compiler.compile(&action.fn_code.0).map_err(|e| e.message)?),
+                                    body: GmlBody::Code(
+                                        compiler.compile(&action.fn_code.0).map_err(|e| e.to_string())?,
+                                    ),
                                     is_condition: action.is_condition,
                                 },
                             });
@@ -241,7 +255,7 @@ impl Tree {
                         relative: action.is_relative,
                         invert_condition: action.invert_condition,
                         body: Body::Repeat {
-                            count: compiler.compile_expression(&action.param_strings[0].0).map_err(|e| e.message)?,
+                            count: compiler.compile_expression(&action.param_strings[0].0).map_err(|e| e.to_string())?,
                         },
                     });
                 },
@@ -257,7 +271,7 @@ impl Tree {
                         invert_condition: action.invert_condition,
                         body: Body::Normal {
                             args: Box::new([]),
-                            body: GmlBody::Code(compiler.compile(&code).map_err(|e| e.message)?),
+                            body: GmlBody::Code(compiler.compile(&code).map_err(|e| e.to_string())?),
                             is_condition: false,
                         },
                     });
@@ -271,7 +285,9 @@ impl Tree {
                         invert_condition: action.invert_condition,
                         body: Body::Normal {
                             args: Box::new([]),
-                            body: GmlBody::Code(compiler.compile(&action.param_strings[0].0).map_err(|e| e.message)?),
+                            body: GmlBody::Code(
+                                compiler.compile(&action.param_strings[0].0).map_err(|e| e.to_string())?,
+                            ),
                             is_condition: false,
                         },
                     });
@@ -299,10 +315,15 @@ impl Tree {
                 _ => compiler.compile_expression(&param.0),
             })
             .collect::<Result<Vec<_>, _>>()
-            .map_err(|e| e.message)?
+            .map_err(|e| e.to_string())?
             .into_boxed_slice())
     }
 
+    /// The actions in this tree, in order.
+    pub fn actions(&self) -> &[Action] {
+        &self.0
+    }
+
     pub fn new_from_code(code: Rc<[Instruction]>) -> Rc<RefCell<Self>> {
         let mut tree = Self(Vec::new());
         tree.push_code(code);
@@ -330,11 +351,126 @@ impl Game {
         event_type: usize,
         event_number: usize,
         as_object: i32,
+        owner: Owner,
     ) -> gml::Result<()> {
-        self.exec_slice(&tree.borrow().0, this, other, event_type, event_number, as_object, false)?;
+        self.exec_slice(&tree.borrow().0, this, other, event_type, event_number, as_object, owner, false)?;
         Ok(())
     }
 
+    /// Runs a single action's function or code, once for each instance it applies to. Returns the value it returned.
+    fn exec_action(
+        &mut self,
+        action: &Action,
+        args: &[Node],
+        gml_body: &GmlBody,
+        this: usize,
+        other: usize,
+        event_type: usize,
+        event_number: usize,
+        as_object: i32,
+    ) -> gml::Result<Value> {
+        let mut context = Context {
+            this,
+            other,
+            event_action: action.index,
+            relative: action.relative,
+            event_type,
+            event_number,
+            event_object: as_object,
+            ..Default::default()
+        };
+        let unit = Unit::Action { object: as_object, event_type, event_number, action: action.index };
+
+        /*
+        let mut arg_values: [Value; 16] = Default::default();
+        for (dest, src) in arg_values.iter_mut().zip(args.iter()) {
+            *dest = self.eval(src, &mut context)?;
+        }
+        */
+
+        let mut returned_value = Default::default();
+        match action.target {
+            None | Some(gml::SELF) | Some(gml::OTHER) => {
+                if action.target == Some(gml::OTHER) {
+                    context.this = other;
+                    context.other = this;
+                }
+
+                let mut arg_values: [Value; 16] = Default::default();
+                for (dest, src) in arg_values.iter_mut().zip(args.iter()) {
+                    *dest = self.eval(src, &mut context)?;
+                }
+
+                let values = &arg_values[..args.len()];
+                returned_value = match gml_body {
+                    GmlBody::ContextFunction(f) => {
+                        self.profile(Key::Function(f.0 as usize), |game| f.0(game, &mut context, values))?
+                    },
+                    GmlBody::StateFunction(f) => self.profile(Key::Function(f.0 as usize), |game| f.0(game, values))?,
+                    GmlBody::RoutineFunction(f) => self.profile(Key::Function(f.0 as usize), |game| f.0(game, values))?,
+                    GmlBody::ValueFunction(f) => self.profile(Key::Function(f.0 as usize), |_| f.0(values))?,
+                    GmlBody::Code(code) => {
+                        context.arguments = arg_values;
+                        context.argument_count = args.len();
+                        self.execute_unit(unit, code, &mut context)?;
+                        context.return_value
+                    },
+                };
+            },
+            Some(i) if i < 0 => (),
+            Some(i) => {
+                context.other = this;
+                let mut iter = self.room.instance_list.iter_by_identity(i);
+                while let Some(instance) = iter.next(&self.room.instance_list) {
+                    context.this = instance;
+
+                    let mut arg_values: [Value; 16] = Default::default();
+                    for (dest, src) in arg_values.iter_mut().zip(args.iter()) {
+                        *dest = self.eval(src, &mut context)?;
+                    }
+
+                    let values = &arg_values[..args.len()];
+                    returned_value = match gml_body {
+                        GmlBody::ContextFunction(f) => {
+                            self.profile(Key::Function(f.0 as usize), |game| f.0(game, &mut context, values))?
+                        },
+                        GmlBody::StateFunction(f) => {
+                            self.profile(Key::Function(f.0 as usize), |game| f.0(game, values))?
+                        },
+                        GmlBody::RoutineFunction(f) => {
+                            self.profile(Key::Function(f.0 as usize), |game| f.0(game, values))?
+                        },
+                        GmlBody::ValueFunction(f) => self.profile(Key::Function(f.0 as usize), |_| f.0(values))?,
+                        GmlBody::Code(code) => {
+                            context.arguments = arg_values;
+                            context.argument_count = args.len();
+                            self.execute_unit(unit, code, &mut context)?;
+                            context.return_value.clone()
+                        },
+                    };
+                }
+            },
+        }
+
+        Ok(returned_value)
+    }
+
+    /// Describes an action like GM8's error messages do, such as "action number 1 of Step Event for object obj_player".
+    fn action_frame(&self, index: usize, owner: Owner, event_type: usize, event_number: usize, as_object: ID) -> Frame {
+        let place = match owner {
+            Owner::Event => {
+                let object = self.assets.objects.get_asset(as_object).map(|x| x.name.to_string());
+                let object = object.unwrap_or_else(|| as_object.to_string());
+                format!("{} for object {}", self.event_name(event_type, event_number), object)
+            },
+            Owner::Moment(timeline_id, moment) => {
+                let timeline = self.assets.timelines.get_asset(timeline_id).map(|x| x.name.to_string());
+                format!("time line {} at moment {}", timeline.unwrap_or_else(|| timeline_id.to_string()), moment)
+            },
+        };
+        Frame::Code(format!("action number {} of {}", index + 1, place))
+    }
+
     fn skip_actions(slice: &[Action]) -> usize {
         let mut block_depth: u32 = 0;
         for (i, action) in slice.iter().enumerate() {
@@ -363,6 +499,7 @@ impl Game {
         event_type: usize,
         event_number: usize,
         as_object: i32,
+        owner: Owner,
         one_block: bool,
     ) -> gml::Result<(ReturnType, usize)> {
         let mut block_depth = 0usize;
@@ -379,83 +516,26 @@ impl Game {
 
             match &action.body {
                 Body::Normal { args, body: gml_body, is_condition } => {
-                    let mut context = Context {
-                        this,
-                        other,
-                        event_action: action.index,
-                        relative: action.relative,
-                        event_type,
-                        event_number,
-                        event_object: as_object,
-                        ..Default::default()
-                    };
-
-                    /*
-                    let mut arg_values: [Value; 16] = Default::default();
-                    for (dest, src) in arg_values.iter_mut().zip(args.iter()) {
-                        *dest = self.eval(src, &mut context)?;
-                    }
-                    */
-
-                    let mut returned_value = Default::default();
-                    match action.target {
-                        None | Some(gml::SELF) | Some(gml::OTHER) => {
-                            if action.target == Some(gml::OTHER) {
-                                context.this = other;
-                                context.other = this;
-                            }
-
-                            let mut arg_values: [Value; 16] = Default::default();
-                            for (dest, src) in arg_values.iter_mut().zip(args.iter()) {
-                                *dest = self.eval(src, &mut context)?;
-                            }
-
-                            returned_value = match gml_body {
-                                GmlBody::ContextFunction(f) => f.0(self, &mut context, &arg_values[..args.len()])?,
-                                GmlBody::StateFunction(f) => f.0(self, &arg_values[..args.len()])?,
-                                GmlBody::RoutineFunction(f) => f.0(self, &arg_values[..args.len()])?,
-                                GmlBody::ValueFunction(f) => f.0(&arg_values[..args.len()])?,
-                                GmlBody::Code(code) => {
-                                    context.arguments = arg_values;
-                                    context.argument_count = args.len();
-                                    self.execute(code, &mut context)?;
-                                    context.return_value
-                                },
-                            };
-                        },
-                        Some(i) if i < 0 => (),
-                        Some(i) => {
-                            context.other = this;
-                            let mut iter = self.room.instance_list.iter_by_identity(i);
-                            while let Some(instance) = iter.next(&self.room.instance_list) {
-                                context.this = instance;
-
-                                let mut arg_values: [Value; 16] = Default::default();
-                                for (dest, src) in arg_values.iter_mut().zip(args.iter()) {
-                                    *dest = self.eval(src, &mut context)?;
-                                }
-
-                                returned_value = match gml_body {
-                                    GmlBody::ContextFunction(f) => f.0(self, &mut context, &arg_values[..args.len()])?,
-                                    GmlBody::StateFunction(f) => f.0(self, &arg_values[..args.len()])?,
-                                    GmlBody::RoutineFunction(f) => f.0(self, &arg_values[..args.len()])?,
-                                    GmlBody::ValueFunction(f) => f.0(&arg_values[..args.len()])?,
-                                    GmlBody::Code(code) => {
-                                        context.arguments = arg_values;
-                                        context.argument_count = args.len();
-                                        self.execute(code, &mut context)?;
-                                        context.return_value.clone()
-                                    },
-                                };
-                            }
-                        },
-                    }
+                    let returned_value = self
+                        .exec_action(action, args, gml_body, this, other, event_type, event_number, as_object)
+                        .map_err(|e| {
+                            e.in_frame(self.action_frame(action.index, owner, event_type, event_number, as_object))
+                        })?;
 
                     if *is_condition {
                         let do_if = returned_value.is_truthy() != action.invert_condition;
                         if do_if {
                             if let Some(target) = slice.get(i + 1..) {
-                                match self.exec_slice(target, this, other, event_type, event_number, as_object, true)? {
+                                match self.exec_slice(
+                                    target,
+                                    this,
+                                    other,
+                                    event_type,
+                                    event_number,
+                                    as_object,
+                                    owner,
+                                    true,
+                                )? {
                                     (ReturnType::Continue, len) => {
                                         skip_count = len;
                                         if let Some(Body::Else) = slice.get(i + len + 1).map(|a| &a.body) {
@@ -481,6 +561,7 @@ impl Game {
                                         event_type,
                                         event_number,
                                         as_object,
+                                        owner,
                                         true,
                                     )? {
                                         (ReturnType::Continue, len) => skip_count += 1 + len,
@@ -508,9 +589,21 @@ impl Game {
                             event_object: as_object,
                             ..Default::default()
                         };
-                        let mut count = i32::from(self.eval(count, &mut context)?);
+                        let count = self.eval(count, &mut context).map_err(|e| {
+                            e.in_frame(self.action_frame(action.index, owner, event_type, event_number, as_object))
+                        })?;
+                        let mut count = i32::from(count);
                         while count > 0 {
-                            match self.exec_slice(body, this, other, event_type, event_number, as_object, true)? {
+                            match self.exec_slice(
+                                body,
+                                this,
+                                other,
+                                event_type,
+                                event_number,
+                                as_object,
+                                owner,
+                                true,
+                            )? {
                                 (ReturnType::Continue, _) => (),
                                 (ReturnType::Exit, len) => return Ok((ReturnType::Exit, len)),
                             }
@@ -0,0 +1,502 @@
+//! Checks a game for things the emulator can't handle yet, so they're known before starting a TAS.
+
+use crate::{
+    action::{Body, GmlBody, Tree},
+    game::{
+        audio::AudioManager,
+        external::{dll, ExternalManager},
+        Game,
+    },
+    gml::{
+        mappings,
+        runtime::{ArrayAccessor, FieldAccessor, InstanceIdentifier, Instruction, Node, VariableAccessor},
+        Compiler, Value,
+    },
+};
+use gm8exe::{
+    asset::{
+        extension::{CallingConvention, FileKind},
+        CodeAction,
+    },
+    GameAssets,
+};
+use std::{fmt, path::Path};
+
+// GML names of kernel functions which only panic with unimplemented!(), generated by build.rs
+include!(concat!(env!("OUT_DIR"), "/unimplemented_functions.rs"));
+
+/// Something which is likely to stop a game from running properly in the emulator.
+#[derive(Clone, PartialEq)]
+pub enum Problem {
+    /// A call to a kernel function which hasn't been implemented, and will panic if it's reached.
+    Unimplemented(String),
+
+    /// An external which isn't dummied out, so the real DLL has to be loaded.
+    External { dll: String, symbol: String },
+
+    /// Code which failed to compile, or which compiled into an error that will be raised if it runs.
+    CompileError(String),
+
+    /// A sound which can't be loaded, so it will never play.
+    UnsupportedSound(String),
+}
+
+/// A problem and where in the game it was found.
+pub struct Finding {
+    pub location: String,
+    pub problem: Problem,
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Unimplemented(name) => write!(f, "calls unimplemented function {}", name),
+            Self::External { dll, symbol } => write!(f, "defines external {} from {} which isn't dummied", symbol, dll),
+            Self::CompileError(e) => write!(f, "compiler error: {}", e),
+            Self::UnsupportedSound(reason) => write!(f, "sound can't be played: {}", reason),
+        }
+    }
+}
+
+impl fmt::Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}", self.location, self.problem)
+    }
+}
+
+struct Scanner {
+    compiler: Compiler,
+    externals: ExternalManager,
+    location: String,
+    findings: Vec<Finding>,
+}
+
+/// Compiles all the code in a game the same way the emulator would and reports anything which would go wrong.
+pub fn scan(assets: &GameAssets) -> Vec<Finding> {
+    let mut scanner = Scanner {
+        compiler: Game::make_compiler(assets),
+        // This is for checking a game before recording, and recording always dummies out audio DLLs
+        externals: ExternalManager::new(true),
+        location: String::new(),
+        findings: Vec::new(),
+    };
+
+    for extension in &assets.extensions {
+        scanner.location = format!("extension {}", extension.name);
+        for file in extension.files.iter().filter(|x| x.kind == FileKind::DynamicLibrary) {
+            for function in &file.functions {
+                let symbol = if function.external_name.0.is_empty() { &function.name } else { &function.external_name };
+                let call_conv = match function.convention {
+                    CallingConvention::Cdecl => dll::CallConv::Cdecl,
+                    _ => dll::CallConv::Stdcall,
+                };
+                scanner.check_external(file.name.to_string(), symbol.to_string(), call_conv);
+            }
+        }
+    }
+
+    for sound in assets.sounds.iter().flatten() {
+        scanner.location = format!("sound {}", sound.name);
+        // This mirrors Game::launch, which leaves the sound without a handle if any of these happen
+        match (&sound.data, sound.extension.0.as_ref()) {
+            (None, _) => scanner.report(Problem::UnsupportedSound("it has no data".into())),
+            (Some(data), extension @ (b".mp3" | b".wav")) => {
+                if !AudioManager::can_load(extension, data) {
+                    scanner.report(Problem::UnsupportedSound(format!("invalid {} data", sound.extension)));
+                }
+            },
+            (Some(_), _) => {
+                scanner.report(Problem::UnsupportedSound(format!("{} files aren't supported", sound.extension)))
+            },
+        }
+    }
+
+    for script in assets.scripts.iter().flatten() {
+        scanner.location = format!("script {}", script.name);
+        scanner.compile(&script.source.0);
+    }
+
+    for trigger in assets.triggers.iter().flatten() {
+        scanner.location = format!("trigger {}", trigger.name);
+        scanner.compile(&trigger.condition.0);
+    }
+
+    for timeline in assets.timelines.iter().flatten() {
+        for (moment, actions) in &timeline.moments {
+            scanner.actions(&format!("timeline {} moment {}", timeline.name, moment), actions);
+        }
+    }
+
+    for object in assets.objects.iter().flatten() {
+        for (i, events) in object.events.iter().enumerate() {
+            for (sub, actions) in events {
+                scanner.actions(&format!("object {} event {},{}", object.name, i, sub), actions);
+            }
+        }
+    }
+
+    for room in assets.rooms.iter().flatten() {
+        scanner.location = format!("room {} creation code", room.name);
+        scanner.compile(&room.creation_code.0);
+        for instance in &room.instances {
+            scanner.location = format!("creation code of instance {} in room {}", instance.id, room.name);
+            scanner.compile(&instance.creation_code.0);
+        }
+    }
+
+    scanner.findings
+}
+
+impl Scanner {
+    fn report(&mut self, problem: Problem) {
+        // Only report each problem once per location
+        let mut here = self.findings.iter().rev().take_while(|x| x.location == self.location);
+        if !here.any(|x| x.problem == problem) {
+            self.findings.push(Finding { location: self.location.clone(), problem });
+        }
+    }
+
+    fn compile(&mut self, source: &[u8]) {
+        match self.compiler.compile(source) {
+            Ok(instructions) => self.instructions(&instructions),
+            Err(e) => self.report(Problem::CompileError(e.to_string())),
+        }
+    }
+
+    fn actions(&mut self, location: &str, actions: &[CodeAction]) {
+        // Compile actions one at a time so one broken action doesn't hide problems in the rest of them
+        for (i, action) in actions.iter().enumerate() {
+            self.location = format!("{} action {}", location, i);
+            match Tree::from_list(std::slice::from_ref(action), &mut self.compiler) {
+                Ok(tree) => self.tree(&tree),
+                Err(e) => self.report(Problem::CompileError(e)),
+            }
+        }
+    }
+
+    fn tree(&mut self, tree: &Tree) {
+        for action in tree.actions() {
+            match &action.body {
+                Body::Normal { args, body, .. } => {
+                    let name = match body {
+                        GmlBody::ContextFunction(f) => mappings::find_function_by_address(f).map(|(_, x)| x),
+                        GmlBody::StateFunction(f) => mappings::find_function_by_address(f).map(|(_, x)| x),
+                        GmlBody::RoutineFunction(f) => mappings::find_function_by_address(f).map(|(_, x)| x),
+                        GmlBody::ValueFunction(f) => mappings::find_function_by_address(f).map(|(_, x)| x),
+                        GmlBody::Code(code) => {
+                            self.nodes(args);
+                            self.instructions(code);
+                            continue
+                        },
+                    };
+                    self.function(name, args);
+                },
+                Body::Repeat { count } => self.node(count),
+                _ => (),
+            }
+        }
+    }
+
+    fn instructions(&mut self, instructions: &[Instruction]) {
+        for instruction in instructions {
+            match instruction {
+                Instruction::SetField { accessor, value } => {
+                    self.field(accessor);
+                    self.node(value);
+                },
+                Instruction::SetVariable { accessor, value } => {
+                    self.variable(accessor);
+                    self.node(value);
+                },
+                Instruction::EvalExpression { node } => self.node(node),
+                Instruction::IfElse { cond, if_body, else_body } => {
+                    self.node(cond);
+                    self.instructions(if_body);
+                    self.instructions(else_body);
+                },
+                Instruction::LoopUntil { cond, body } | Instruction::LoopWhile { cond, body } => {
+                    self.node(cond);
+                    self.instructions(body);
+                },
+                Instruction::LoopFor { cond, body, step } => {
+                    self.node(cond);
+                    self.instructions(body);
+                    self.instructions(step);
+                },
+                Instruction::Repeat { count, body } => {
+                    self.node(count);
+                    self.instructions(body);
+                },
+                Instruction::SetReturnValue { value } => self.node(value),
+                Instruction::Switch { input, cases, body, .. } => {
+                    self.node(input);
+                    for (case, _) in cases.iter() {
+                        self.node(case);
+                    }
+                    self.instructions(body);
+                },
+                Instruction::With { target, body } => {
+                    self.node(target);
+                    self.instructions(body);
+                },
+                Instruction::RuntimeError { error } => self.report(Problem::CompileError(error.to_string())),
+                Instruction::Return { .. } | Instruction::GlobalVar { .. } | Instruction::Line { .. } => (),
+            }
+        }
+    }
+
+    fn nodes(&mut self, nodes: &[Node]) {
+        for node in nodes {
+            self.node(node);
+        }
+    }
+
+    fn node(&mut self, node: &Node) {
+        match node {
+            Node::Variable { accessor } => self.variable(accessor),
+            Node::Field { accessor } => self.field(accessor),
+            Node::ContextFunction { args, function } => {
+                self.function(mappings::find_function_by_address(function).map(|(_, x)| x), args)
+            },
+            Node::StateFunction { args, function } => {
+                self.function(mappings::find_function_by_address(function).map(|(_, x)| x), args)
+            },
+            Node::RoutineFunction { args, function } => {
+                self.function(mappings::find_function_by_address(function).map(|(_, x)| x), args)
+            },
+            Node::ValueFunction { args, function } => {
+                self.function(mappings::find_function_by_address(function).map(|(_, x)| x), args)
+            },
+            Node::Unary { child, .. } => self.node(child),
+            Node::Binary { left, right, .. } => {
+                self.node(left);
+                self.node(right);
+            },
+            Node::Script { args, .. } | Node::ExtensionFunction { args, .. } => self.nodes(args),
+            Node::RuntimeError { error } => self.report(Problem::CompileError(error.to_string())),
+            Node::Constant { .. } | Node::Literal { .. } => (),
+        }
+    }
+
+    fn field(&mut self, accessor: &FieldAccessor) {
+        self.array(&accessor.array);
+        self.owner(&accessor.owner);
+    }
+
+    fn variable(&mut self, accessor: &VariableAccessor) {
+        self.array(&accessor.array);
+        self.owner(&accessor.owner);
+    }
+
+    fn array(&mut self, accessor: &ArrayAccessor) {
+        match accessor {
+            ArrayAccessor::None => (),
+            ArrayAccessor::Single(index) => self.node(index),
+            ArrayAccessor::Double(index1, index2) => {
+                self.node(index1);
+                self.node(index2);
+            },
+        }
+    }
+
+    fn owner(&mut self, owner: &InstanceIdentifier) {
+        if let InstanceIdentifier::Expression(node) = owner {
+            self.node(node);
+        }
+    }
+
+    fn function(&mut self, name: Option<&str>, args: &[Node]) {
+        self.nodes(args);
+        let name = match name {
+            Some(name) => name,
+            None => return,
+        };
+        if UNIMPLEMENTED_FUNCTIONS.iter().any(|&x| x == name) {
+            self.report(Problem::Unimplemented(name.into()));
+        }
+
+        // Externals can only be checked if the DLL and function names are written out as literals
+        if name.starts_with("external_define") {
+            let literal = |i: usize| match args.get(i) {
+                Some(Node::Literal { value: Value::Str(s) }) => Some(String::from_utf8_lossy(s.as_ref()).into_owned()),
+                _ => None,
+            };
+            let call_conv = match args.get(2) {
+                Some(Node::Literal { value }) if name == "external_define" && value.round() == 0 => {
+                    dll::CallConv::Cdecl
+                },
+                _ => dll::CallConv::Stdcall,
+            };
+            if let (Some(dll), Some(symbol)) = (literal(0), literal(1)) {
+                self.check_external(dll, symbol, call_conv);
+            }
+        }
+    }
+
+    fn check_external(&mut self, dll: String, symbol: String, call_conv: dll::CallConv) {
+        // Only the DLL and symbol matter for dummying, so the types can be anything
+        let signature =
+            dll::ExternalSignature { dll, symbol, call_conv, type_args: Vec::new(), type_return: dll::ValueType::Real };
+        if self.externals.should_dummy(&signature).is_none() {
+            let dll = Path::new(&signature.dll).file_name().map(|x| x.to_string_lossy().into_owned());
+            let dll = dll.unwrap_or(signature.dll);
+            self.report(Problem::External { dll, symbol: signature.symbol });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use gm8exe::{
+        asset::{
+            sound::{SoundFX, SoundKind},
+            Script, Sound,
+        },
+        settings::{GameHelpDialog, Settings},
+        GameVersion,
+    };
+
+    fn assets() -> GameAssets {
+        GameAssets {
+            triggers: Vec::new(),
+            constants: Vec::new(),
+            extensions: Vec::new(),
+            sprites: Vec::new(),
+            sounds: Vec::new(),
+            backgrounds: Vec::new(),
+            paths: Vec::new(),
+            scripts: Vec::new(),
+            fonts: Vec::new(),
+            timelines: Vec::new(),
+            objects: Vec::new(),
+            rooms: Vec::new(),
+            included_files: Vec::new(),
+            version: GameVersion::GameMaker8_0,
+            dx_dll: Vec::new(),
+            ico_file_raw: None,
+            help_dialog: GameHelpDialog {
+                bg_colour: 0xFFFFFF.into(),
+                new_window: false,
+                caption: "".into(),
+                left: -1,
+                top: -1,
+                width: 600,
+                height: 400,
+                border: true,
+                resizable: true,
+                window_on_top: false,
+                freeze_game: true,
+                info: "".into(),
+            },
+            last_instance_id: 100000,
+            last_tile_id: 10000000,
+            library_init_strings: Vec::new(),
+            room_order: Vec::new(),
+            settings: Settings {
+                fullscreen: false,
+                scaling: -1,
+                interpolate_pixels: false,
+                clear_colour: 0,
+                allow_resize: false,
+                window_on_top: false,
+                dont_draw_border: false,
+                dont_show_buttons: false,
+                display_cursor: true,
+                freeze_on_lose_focus: false,
+                disable_screensaver: true,
+                force_cpu_render: false,
+                set_resolution: false,
+                colour_depth: 0,
+                resolution: 0,
+                frequency: 0,
+                vsync: false,
+                esc_close_game: true,
+                treat_close_as_esc: true,
+                f1_help_menu: true,
+                f4_fullscreen_toggle: true,
+                f5_save_f6_load: true,
+                f9_screenshot: true,
+                priority: 0,
+                custom_load_image: None,
+                transparent: false,
+                translucency: 255,
+                loading_bar: 1,
+                backdata: None,
+                frontdata: None,
+                scale_progress_bar: true,
+                show_error_messages: true,
+                log_errors: false,
+                always_abort: false,
+                zero_uninitialized_vars: false,
+                error_on_uninitialized_args: true,
+                swap_creation_events: false,
+            },
+            game_id: 0,
+            guid: [0; 4],
+        }
+    }
+
+    fn script(name: &str, source: &str) -> Option<Box<Script>> {
+        Some(Box::new(Script { name: name.into(), source: source.into() }))
+    }
+
+    fn sound(name: &str, extension: &str, data: Option<&[u8]>) -> Option<Box<Sound>> {
+        Some(Box::new(Sound {
+            name: name.into(),
+            source: "".into(),
+            extension: extension.into(),
+            data: data.map(Box::from),
+            kind: SoundKind::Normal,
+            volume: 1.0,
+            pan: 0.0,
+            preload: true,
+            fx: SoundFX { chorus: false, echo: false, flanger: false, gargle: false, reverb: false },
+        }))
+    }
+
+    fn findings(assets: &GameAssets) -> Vec<String> {
+        scan(assets).iter().map(ToString::to_string).collect()
+    }
+
+    #[test]
+    fn clean_game() {
+        let mut assets = assets();
+        assets.scripts = vec![script("scr_ok", "return argument0 + 1"), None];
+        assert!(findings(&assets).is_empty());
+    }
+
+    #[test]
+    fn code_problems() {
+        let mut assets = assets();
+        assets.scripts = vec![
+            script("scr_tiles", "tile_find(0, 0, false); tile_find(1, 1, true)"),
+            script("scr_broken", "x = ;"),
+            script("scr_dll", "external_define(\"lib/mylib.dll\", \"do_thing\", dll_cdecl, ty_real, 0)"),
+            script("scr_fmod", "external_define(\"GMFMODSimple.dll\", \"FMODSoundAdd\", dll_cdecl, ty_real, 0)"),
+        ];
+        let findings = findings(&assets);
+        assert_eq!(findings.len(), 3);
+        // Each problem is only reported once per location
+        assert_eq!(findings[0], "script scr_tiles: calls unimplemented function tile_find");
+        assert!(findings[1].starts_with("script scr_broken: compiler error: "));
+        assert_eq!(findings[2], "script scr_dll: defines external do_thing from mylib.dll which isn't dummied");
+    }
+
+    #[test]
+    fn sound_problems() {
+        let mut assets = assets();
+        assets.sounds = vec![
+            sound("snd_empty", ".wav", None),
+            sound("snd_midi", ".mid", Some(b"MThd")),
+            sound("snd_bad_mp3", ".mp3", Some(b"not an mp3")),
+            sound("snd_bad_wav", ".wav", Some(b"RIFF")),
+        ];
+        assert_eq!(findings(&assets), [
+            "sound snd_empty: sound can't be played: it has no data",
+            "sound snd_midi: sound can't be played: .mid files aren't supported",
+            "sound snd_bad_mp3: sound can't be played: invalid .mp3 data",
+            "sound snd_bad_wav: sound can't be played: invalid .wav data",
+        ]);
+    }
+}
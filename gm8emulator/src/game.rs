@@ -1,8 +1,11 @@
 pub mod audio;
 pub mod background;
+pub mod capture_overlay;
+pub mod display;
 pub mod draw;
 pub mod events;
 pub mod external;
+pub mod ghost;
 pub mod gm_save;
 pub mod includedfile;
 pub mod model;
@@ -10,10 +13,12 @@ pub mod movement;
 pub mod particle;
 pub mod pathfinding;
 pub mod platform;
+pub mod profiler;
 pub mod recording;
 pub mod replay;
 pub mod savestate;
 pub mod surface;
+pub mod trace;
 pub mod transition;
 pub mod view;
 
@@ -23,7 +28,7 @@ pub use savestate::SaveState;
 pub use view::View;
 
 use crate::{
-    action::Tree,
+    action::{Owner, Tree},
     asset::{
         self,
         font::{Character, Font},
@@ -35,7 +40,21 @@ use crate::{
     },
     game::gm_save::GMSave,
     game::replay::FrameRng,
-    gml::{self, ds, ev, file, rand::Random, runtime::Instruction, Compiler, Context},
+    game::{
+        capture_overlay::CaptureOverlay,
+        ghost::{Ghost, GhostRecorder},
+        profiler::Profiler,
+        trace::{Entry, Trace},
+    },
+    gml::{
+        self,
+        debugger::Debugger,
+        ds, ev, file,
+        network::Multiplayer,
+        rand::Random,
+        runtime::{Frame, Instruction},
+        Compiler, Context,
+    },
     handleman::{HandleArray, HandleList, HandleManager},
     input::{self, Input},
     instance::{DummyFieldHolder, Instance, InstanceState},
@@ -220,8 +239,41 @@ pub struct Game {
     pub frame_limiter: bool, // whether to limit FPS of gameplay by room_speed
     pub frame_limit_at: usize, // on which frame to start limiting FPS
     pub ffmpeg_recorder: Option<Child>,
+    pub capture_overlay: Option<CaptureOverlay>, // drawn over each captured frame
 
     pub audio: audio::AudioManager,
+    pub cd: audio::cd::CdDrive,
+    pub mplay: Multiplayer,
+
+    pub display: display::Display,
+    pub virtual_clipboard: gml::String, // stands in for the host clipboard when recording or replaying
+
+    // script the TAS UI is waiting to be called, and whether it has been since it was last checked
+    pub script_breakpoint: Option<ID>,
+    pub script_breakpoint_hit: bool,
+
+    // whether the TAS UI is waiting for an instance to be created, and the first one that was since it last checked
+    pub create_breakpoint: bool,
+    pub created_instance: Option<ID>,
+
+    // whether the TAS UI is waiting for an instance to be destroyed, and the first one that was since it last checked
+    pub destroy_breakpoint: bool,
+    pub destroyed_instance: Option<ID>,
+
+    // step debugger for the TAS UI
+    pub debugger: Debugger,
+
+    // log of what runs on each frame, if one's being written
+    pub trace: Option<Trace>,
+
+    // time spent in each event, script and kernel function, if it's being measured
+    pub profiler: Option<Profiler>,
+
+    // instances to print out on each frame when running as a ghost for the TAS UI
+    pub ghost_recorder: Option<GhostRecorder>,
+
+    // another replay being run alongside this one in the TAS UI, to compare against
+    pub ghost: Option<Ghost>,
 
     // winit windowing
     pub window: Window,
@@ -233,6 +285,7 @@ pub struct Game {
     pub window_offset_spoof: (i32, i32),
     pub window_is_logical_dpi: bool,
     pub window_sizeable: bool,
+    pub window_stay_on_top: bool,
     pub window_visible: bool,
     pub close_requested: bool,
     // Scaling type
@@ -328,6 +381,97 @@ macro_rules! handle_scene_change {
 }
 
 impl Game {
+    /// Sets up a GML compiler with the names of a game's assets, scripts, extension functions and constants.
+    pub fn make_compiler(assets: &gm8exe::GameAssets) -> Compiler {
+        let gm8exe::GameAssets {
+            backgrounds,
+            constants,
+            extensions,
+            fonts,
+            objects,
+            paths,
+            rooms,
+            scripts,
+            sounds,
+            sprites,
+            timelines,
+            triggers,
+            ..
+        } = assets;
+
+        let mut compiler = Compiler::new();
+        compiler.reserve_scripts(scripts.iter().flatten().count());
+        compiler.reserve_constants(
+            backgrounds.iter().flatten().count()
+                + fonts.iter().flatten().count()
+                + objects.iter().flatten().count()
+                + paths.iter().flatten().count()
+                + rooms.iter().flatten().count()
+                + scripts.iter().flatten().count()
+                + sounds.iter().flatten().count()
+                + sprites.iter().flatten().count()
+                + timelines.iter().flatten().count()
+                + triggers.iter().flatten().count(),
+        );
+        compiler.reserve_user_constants(constants.len());
+
+        // Helper fn for registering asset names as constants
+        fn register_all<T>(compiler: &mut Compiler, assets: &[Option<T>], get_name: fn(&T) -> &PascalString) {
+            assets
+                .iter()
+                .enumerate()
+                .filter_map(|(i, x)| x.as_ref().map(|x| (i, x)))
+                .for_each(|(i, x)| compiler.register_constant(get_name(x).0.clone(), i as f64))
+        }
+
+        // Register all asset names
+        // These are in order of asset precedence, please don't change the order
+        register_all(&mut compiler, objects, |x| &x.name);
+        register_all(&mut compiler, sprites, |x| &x.name);
+        register_all(&mut compiler, sounds, |x| &x.name);
+        register_all(&mut compiler, backgrounds, |x| &x.name);
+        register_all(&mut compiler, paths, |x| &x.name);
+        register_all(&mut compiler, fonts, |x| &x.name);
+        register_all(&mut compiler, timelines, |x| &x.name);
+        register_all(&mut compiler, scripts, |x| &x.name);
+        register_all(&mut compiler, rooms, |x| &x.name);
+        register_all(&mut compiler, triggers, |x| &x.constant_name);
+
+        // Register scripts
+        scripts
+            .iter()
+            .enumerate()
+            .filter_map(|(i, x)| x.as_ref().map(|x| (i, x)))
+            .for_each(|(i, x)| compiler.register_script(x.name.0.clone(), i));
+
+        // Register extension function names and constants
+        compiler.reserve_extension_functions(
+            extensions.iter().map(|x| x.files.iter().map(|x| x.functions.len()).sum::<usize>()).sum::<usize>(),
+        );
+        let mut fn_index = 0;
+        let mut const_index = 0;
+        for extension in extensions.iter() {
+            for file in extension.files.iter() {
+                for function in file.functions.iter() {
+                    compiler.register_extension_function(function.name.0.as_ref().into(), fn_index);
+                    fn_index += 1;
+                }
+                for constant in file.consts.iter() {
+                    compiler.register_user_constant(constant.name.0.clone(), const_index);
+                    const_index += 1;
+                }
+            }
+        }
+
+        // Register user constants
+        constants
+            .iter()
+            .enumerate()
+            .for_each(|(i, x)| compiler.register_user_constant(x.name.0.clone(), i + const_index));
+
+        compiler
+    }
+
     pub fn launch(
         assets: gm8exe::GameAssets,
         file_path: PathBuf,
@@ -369,6 +513,9 @@ impl Game {
             }
         }
 
+        // Set up a GML compiler
+        let mut compiler = Self::make_compiler(&assets);
+
         // Destructure assets
         let gm8exe::GameAssets {
             game_id,
@@ -504,85 +651,22 @@ impl Game {
             .collect::<Result<Vec<_>, std::io::Error>>()
             .expect("failed to extract included files");
 
-        // Set up a GML compiler
-        let mut compiler = Compiler::new();
-        compiler.reserve_scripts(scripts.iter().flatten().count());
-        compiler.reserve_constants(
-            backgrounds.iter().flatten().count()
-                + fonts.iter().flatten().count()
-                + objects.iter().flatten().count()
-                + paths.iter().flatten().count()
-                + rooms.iter().flatten().count()
-                + scripts.iter().flatten().count()
-                + sounds.iter().flatten().count()
-                + sprites.iter().flatten().count()
-                + timelines.iter().flatten().count()
-                + triggers.iter().flatten().count(),
-        );
-        compiler.reserve_user_constants(constants.len());
-
-        // Helper fn for registering asset names as constants
-        fn register_all<T>(compiler: &mut Compiler, assets: &[Option<T>], get_name: fn(&T) -> &PascalString) {
-            assets
-                .iter()
-                .enumerate()
-                .filter_map(|(i, x)| x.as_ref().map(|x| (i, x)))
-                .for_each(|(i, x)| compiler.register_constant(get_name(x).0.clone(), i as f64))
-        }
-
-        // Register all asset names
-        // These are in order of asset precedence, please don't change the order
-        register_all(&mut compiler, &objects, |x| &x.name);
-        register_all(&mut compiler, &sprites, |x| &x.name);
-        register_all(&mut compiler, &sounds, |x| &x.name);
-        register_all(&mut compiler, &backgrounds, |x| &x.name);
-        register_all(&mut compiler, &paths, |x| &x.name);
-        register_all(&mut compiler, &fonts, |x| &x.name);
-        register_all(&mut compiler, &timelines, |x| &x.name);
-        register_all(&mut compiler, &scripts, |x| &x.name);
-        register_all(&mut compiler, &rooms, |x| &x.name);
-        register_all(&mut compiler, &triggers, |x| &x.constant_name);
-
-        // Register scripts
-        scripts
-            .iter()
-            .enumerate()
-            .filter_map(|(i, x)| x.as_ref().map(|x| (i, x)))
-            .for_each(|(i, x)| compiler.register_script(x.name.0.clone(), i));
-
-        // Register extension function names and constants
-        compiler.reserve_extension_functions(
-            extensions.iter().map(|x| x.files.iter().map(|x| x.functions.len()).sum::<usize>()).sum::<usize>(),
-        );
+        // Find extension initializers and finalizers
         let mut fn_index = 0;
-        let mut const_index = 0;
         let mut extension_initializers = Vec::new();
         let mut extension_finalizers = Vec::new();
-        for extension in extensions.iter() {
-            for file in extension.files.iter() {
-                for function in file.functions.iter() {
-                    compiler.register_extension_function(function.name.0.as_ref().into(), fn_index);
-                    if function.name.0 == file.initializer.0 {
-                        extension_initializers.push(fn_index);
-                    }
-                    if function.name.0 == file.finalizer.0 {
-                        extension_finalizers.push(fn_index);
-                    }
-                    fn_index += 1;
+        for file in extensions.iter().flat_map(|x| x.files.iter()) {
+            for function in file.functions.iter() {
+                if function.name.0 == file.initializer.0 {
+                    extension_initializers.push(fn_index);
                 }
-                for constant in file.consts.iter() {
-                    compiler.register_user_constant(constant.name.0.clone(), const_index);
-                    const_index += 1;
+                if function.name.0 == file.finalizer.0 {
+                    extension_finalizers.push(fn_index);
                 }
+                fn_index += 1;
             }
         }
 
-        // Register user constants
-        constants
-            .iter()
-            .enumerate()
-            .for_each(|(i, x)| compiler.register_user_constant(x.name.0.clone(), i + const_index));
-
         // Set up a Renderer
         let options = RendererOptions {
             size: (room1_width, room1_height),
@@ -1359,6 +1443,7 @@ impl Game {
             frame_limiter,
             frame_limit_at,
             ffmpeg_recorder,
+            capture_overlay: None,
             fps: 0,
             frame_counter: 0,
             parameters: game_arguments,
@@ -1371,6 +1456,24 @@ impl Game {
             error_occurred: false,
             error_last: "".to_string().into(),
             audio,
+            cd: audio::cd::CdDrive::new(),
+            mplay: Multiplayer::new(),
+            display: display::Display::new(match play_type {
+                PlayType::Normal => display::Mode::host().unwrap_or_default(),
+                PlayType::Record | PlayType::Replay => Default::default(),
+            }),
+            virtual_clipboard: "".into(),
+            script_breakpoint: None,
+            script_breakpoint_hit: false,
+            create_breakpoint: false,
+            created_instance: None,
+            destroy_breakpoint: false,
+            destroyed_instance: None,
+            debugger: Debugger::new(),
+            trace: None,
+            profiler: None,
+            ghost_recorder: None,
+            ghost: None,
             window,
             window_border,
             window_icons,
@@ -1390,6 +1493,7 @@ impl Game {
             window_is_logical_dpi: false,
             window_offset_spoof: (0, 0),
             window_sizeable: settings.allow_resize,
+            window_stay_on_top: false,
             window_visible: true,
         };
 
@@ -1645,6 +1749,7 @@ impl Game {
         // Update some stored vars
         let mut room_state = room_state;
         std::mem::swap(&mut self.room, &mut room_state);
+        self.trace(Entry::Room(room_id));
         if self.room.persistent && !self.game_start {
             self.stored_rooms.push(room_state);
         }
@@ -1674,7 +1779,7 @@ impl Game {
 
                     // Add instance to list
                     new_handles.push((
-                        self.room.instance_list.insert(Instance::new_ext(
+                        self.create_instance(Instance::new_ext(
                             instance.id as _,
                             Real::from(instance.x),
                             Real::from(instance.y),
@@ -1710,7 +1815,8 @@ impl Game {
                 // Run this instance's room creation code
                 let mut new_context = Context::with_single_instance(*handle);
                 new_context.event_object = instance.object;
-                self.execute(&instance.creation.clone()?, &mut new_context)?;
+                self.execute(&instance.creation.clone()?, &mut new_context)
+                    .map_err(|e| e.in_frame(Frame::Code(format!("creation code of instance {}", instance.id))))?;
 
                 if !self.swap_creation_events {
                     // Run create event for this instance
@@ -1732,7 +1838,8 @@ impl Game {
                 .instance_list
                 .insert_dummy(Instance::new_dummy(self.assets.objects.get_asset(0).map(|x| x.as_ref())));
             let mut new_context = Context::with_single_instance(dummy_instance);
-            self.execute(&room.creation_code?, &mut new_context)?;
+            self.execute(&room.creation_code?, &mut new_context)
+                .map_err(|e| e.in_frame(Frame::Code(format!("creation code of room {}", room.name))))?;
             self.room.instance_list.remove_dummy(dummy_instance);
         }
 
@@ -1872,10 +1979,15 @@ impl Game {
 
     /// Runs a frame loop and draws the screen. Exits immediately, without waiting for any FPS limitation.
     pub fn frame(&mut self) -> gml::Result<()> {
+        if let Some(trace) = &mut self.trace {
+            trace.begin_frame(self.rand.seed());
+        }
+
         if self.esc_close_game && self.input.keyboard_lastkey() == input::Button::Escape as u8 {
             self.scene_change = Some(SceneChange::End);
             return Ok(());
         }
+        self.input.clear_polled();
 
         // Update xprevious and yprevious for all instances
         let mut iter = self.room.instance_list.iter_by_drawing();
@@ -1902,8 +2014,9 @@ impl Game {
         while let Some(handle) = iter.next(&self.room.instance_list) {
             let instance = self.room.instance_list.get(handle);
             let object_index = instance.object_index.get();
+            let timeline_index = instance.timeline_index.get();
             if instance.timeline_running.get() {
-                if let Some(timeline) = self.assets.timelines.get_asset(instance.timeline_index.get()) {
+                if let Some(timeline) = self.assets.timelines.get_asset(timeline_index) {
                     let moments = timeline.moments.clone();
                     let timeline_len = Real::from(*moments.borrow().keys().max().unwrap_or(&0));
                     let old_position = instance.timeline_position.get();
@@ -1922,21 +2035,23 @@ impl Game {
 
                     if timeline_len >= Real::from(0) {
                         if speed > Real::from(0) {
-                            for (_, tree) in moments
+                            for (&moment, tree) in moments
                                 .borrow()
                                 .iter()
                                 .filter(|(&x, _)| Real::from(x) >= old_position && Real::from(x) < new_position)
                             {
-                                self.execute_tree(tree.clone(), handle, handle, 0, 0, object_index)?;
+                                let owner = Owner::Moment(timeline_index, moment);
+                                self.execute_tree(tree.clone(), handle, handle, 0, 0, object_index, owner)?;
                             }
                         } else if speed < Real::from(0) {
-                            for (_, tree) in moments
+                            for (&moment, tree) in moments
                                 .borrow()
                                 .iter()
                                 .filter(|(&x, _)| Real::from(x) <= old_position && Real::from(x) > new_position)
                                 .rev()
                             {
-                                self.execute_tree(tree.clone(), handle, handle, 0, 0, object_index)?;
+                                let owner = Owner::Moment(timeline_index, moment);
+                                self.execute_tree(tree.clone(), handle, handle, 0, 0, object_index, owner)?;
                             }
                         }
                     }
@@ -2222,8 +2337,13 @@ impl Game {
         let mut current_frame_time: u32 = 0;
         loop {
             self.process_window_events();
+            self.mplay.poll();
 
             self.frame()?;
+            // Only real frames count, not ones captured while a room transition is drawn
+            if let Some(overlay) = &mut self.capture_overlay {
+                overlay.end_frame(!self.input.polled());
+            }
 
             self.capture_recording_frame(&mut current_frame_time, self.room.speed);
             if let Some(SceneChange::End) = self.scene_change {
@@ -2270,7 +2390,10 @@ impl Game {
                 if self.scene_change.is_none() {
                     let w: i32 = self.window_inner_size.0.try_into().unwrap();
                     let h: i32 = self.window_inner_size.1.try_into().unwrap();
-                    let pixels = self.renderer.get_pixels(0, 0, w, h);
+                    let mut pixels = self.renderer.get_pixels(0, 0, w, h);
+                    if let Some(overlay) = &self.capture_overlay {
+                        overlay.draw(&mut pixels, w as usize, h as usize);
+                    }
                     let stdin = ffmpeg_recorder.stdin.as_mut().expect("Failed to open stdin");
                     stdin.write_all(&pixels).unwrap();
                 }
@@ -2283,6 +2406,10 @@ impl Game {
     }
 
     pub fn set_input_from_frame(&mut self, frame: &crate::game::replay::Frame) {
+        if let Some(overlay) = &mut self.capture_overlay {
+            overlay.add_inputs(frame);
+        }
+
         for ev in frame.events.iter() {
             self.stored_events.push_back(ev.clone());
         }
@@ -2312,11 +2439,13 @@ impl Game {
     }
 
     // Replays some recorded inputs to the game
+    // If `validate` is set, this stops at the end of the replay and fails if it desynced before getting there
     pub fn replay(
         mut self,
         replay: Replay,
         output_bin: Option<PathBuf>,
         start_save_path: Option<&PathBuf>,
+        validate: bool,
     ) -> Result<(), Box<dyn std::error::Error>> {
         let mut frame_count: usize = 0;
         self.rand.set_seed(replay.start_seed);
@@ -2340,6 +2469,16 @@ impl Game {
                     }
 
                     frame_count = rep.frame_count();
+                    if let Some(trace) = &mut self.trace {
+                        trace.set_next_frame(frame_count);
+                    }
+                    if let Some(overlay) = &mut self.capture_overlay {
+                        // Keys can be held since before the savestate, so go through everything pressed up to it
+                        for frame in (0..frame_count).filter_map(|i| replay.get_frame(i)) {
+                            overlay.add_inputs(frame);
+                        }
+                        overlay.set_frame(frame_count);
+                    }
                     clean_state = state.clean_state;
                     self.renderer.set_state(&ren);
                 },
@@ -2354,6 +2493,7 @@ impl Game {
             self.init()?;
             handle_scene_change!(self);
         }
+        self.write_ghost_frame(frame_count);
 
         let mut time_now = Instant::now();
         return loop {
@@ -2383,6 +2523,23 @@ impl Game {
                 }
             }
 
+            if validate && frame_count == replay.frame_count() {
+                if !self.stored_events.is_empty() {
+                    break Err(format!(
+                        "Desync: {} stored events were never used by the end of the replay on frame {}",
+                        self.stored_events.len(),
+                        frame_count,
+                    )
+                    .into());
+                }
+                println!("Replay ran to the end on frame {} without desyncing", frame_count);
+                break Ok(());
+            }
+
+            if !self.wait_for_ghost_target(frame_count) {
+                break Ok(());
+            }
+
             if let Some(frame) = replay.get_frame(frame_count) {
                 if !self.stored_events.is_empty() {
                     break Err(format!(
@@ -2397,6 +2554,10 @@ impl Game {
             }
 
             self.frame()?;
+            // Only real frames count, not ones captured while a room transition is drawn
+            if let Some(overlay) = &mut self.capture_overlay {
+                overlay.end_frame(!self.input.polled());
+            }
             self.capture_recording_frame(&mut current_frame_time, self.room.speed);
 
             match self.scene_change {
@@ -2409,10 +2570,15 @@ impl Game {
                 },
                 None => (),
             }
+            self.write_ghost_frame(frame_count + 1);
 
             // exit if X pressed or game_end() invoked
             if self.close_requested {
-                break Ok(self.run_game_end_events()?);
+                self.run_game_end_events()?;
+                if validate && frame_count + 1 < replay.frame_count() {
+                    break Err(format!("The game ended on frame {}, before the end of the replay", frame_count).into());
+                }
+                break Ok(());
             }
 
             // frame limiter
@@ -1,5 +1,6 @@
 mod mixer;
 mod mp3;
+pub mod cd;
 
 use serde::{Deserialize, Serialize};
 use std::{
@@ -162,6 +163,11 @@ impl AudioManager {
         }
     }
 
+    /// Stops any more sounds from being heard, while still keeping track of which ones are playing.
+    pub fn mute(&mut self) {
+        self.do_output = false;
+    }
+
     pub fn capture_audio(&mut self) {
         if let Some(mixer) = &mut self.mixer {
             // samplerate / framerate * channels
@@ -194,6 +200,16 @@ impl AudioManager {
         Mp3Player::new(file).map(|player| Mp3Handle { player, id: sound_id }).ok()
     }
 
+    /// Checks whether sound data with the given file extension would load with `add_mp3` or `add_wav`.
+    /// This doesn't need an audio device, so it can be used before the game is started.
+    pub fn can_load(extension: &[u8], file: &[u8]) -> bool {
+        match extension {
+            b".mp3" => Mp3Player::new(Box::<[u8]>::from(file)).is_ok(),
+            b".wav" => WavPlayer::new(Box::<[u8]>::from(file)).is_ok(),
+            _ => false,
+        }
+    }
+
     pub fn add_wav(
         &mut self,
         file: Box<[u8]>,
@@ -0,0 +1,311 @@
+use super::{length_to_ns, mp3::Mp3Player, AudioManager, SoundParams};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs, io,
+    path::Path,
+    sync::{atomic::AtomicU32, Arc},
+};
+use udon::{
+    rechanneler::Rechanneler,
+    resampler::Resampler,
+    source::{ChannelCount, Sample, SampleRate, Source},
+    wav::WavPlayer,
+};
+
+/// Mixer ID used for CD audio. Sound assets never have negative IDs, so this can't clash with them.
+const CD_SOUND_ID: i32 = -1;
+
+#[derive(Clone)]
+enum TrackAudio {
+    Wav(WavPlayer),
+    Mp3(Mp3Player),
+}
+
+#[derive(Clone)]
+struct Track {
+    audio: TrackAudio,
+    length: u128, // in nanoseconds
+}
+
+/// A virtual CD drive for the `cd_*` functions. Its disc is a directory of audio files, one track per file,
+/// ordered by filename. Positions are derived from the GameClock so that they replay deterministically.
+#[derive(Default)]
+pub struct CdDrive {
+    tracks: Option<Vec<Track>>,
+    state: CdState,
+}
+
+/// The part of the CD drive's state that goes into savestates.
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct CdState {
+    door_open: bool,
+    position: u128,              // disc position when playback was last started or stopped
+    end: u128,                   // disc position at which playback stops
+    running_since: Option<u128>, // clock time at which playback was last started
+    paused: bool,
+}
+
+impl CdDrive {
+    /// Creates a drive with no disc in it.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Inserts a disc made from the .wav and .mp3 files in the given directory.
+    pub fn insert_disc(&mut self, dir: &Path) -> io::Result<()> {
+        let mut paths = fs::read_dir(dir)?.map(|entry| entry.map(|e| e.path())).collect::<io::Result<Vec<_>>>()?;
+        paths.sort();
+        let mut tracks = Vec::new();
+        for path in paths {
+            let extension = path.extension().and_then(|x| x.to_str()).map(|x| x.to_ascii_lowercase());
+            let audio = match extension.as_deref() {
+                Some("wav") => WavPlayer::new(fs::read(&path)?.into_boxed_slice()).ok().map(TrackAudio::Wav),
+                Some("mp3") => Mp3Player::new(fs::read(&path)?).ok().map(TrackAudio::Mp3),
+                _ => continue,
+            };
+            let audio = audio.ok_or_else(|| {
+                io::Error::new(io::ErrorKind::InvalidData, format!("couldn't decode CD track {:?}", path))
+            })?;
+            let length = match &audio {
+                TrackAudio::Wav(p) => length_to_ns(p.length(), p.sample_rate().into(), p.channel_count().into()),
+                TrackAudio::Mp3(p) => length_to_ns(p.length(), p.sample_rate().into(), 1),
+            };
+            tracks.push(Track { audio, length });
+        }
+        self.tracks = Some(tracks);
+        Ok(())
+    }
+
+    pub fn state(&self) -> CdState {
+        self.state.clone()
+    }
+
+    /// Loads a saved state, carrying on playing from wherever the game clock says the CD has got to.
+    pub fn set_state(&mut self, state: CdState, now: u128, audio: &mut AudioManager) {
+        self.state = state;
+        if self.playing(now) {
+            self.queue_audio(self.position(now), audio);
+        } else {
+            audio.stop_sound(CD_SOUND_ID);
+        }
+    }
+
+    /// Re-reads the disc, which stops playback like GM8 does.
+    pub fn init(&mut self, audio: &mut AudioManager) {
+        self.stop(0, audio);
+        self.state.position = 0;
+    }
+
+    /// The tracks on the disc, if there's one in the drive and the door is closed.
+    fn disc(&self) -> &[Track] {
+        match &self.tracks {
+            Some(tracks) if !self.state.door_open => tracks,
+            _ => &[],
+        }
+    }
+
+    pub fn present(&self) -> bool {
+        self.tracks.is_some() && !self.state.door_open
+    }
+
+    pub fn track_count(&self) -> usize {
+        self.disc().len()
+    }
+
+    fn track_start(&self, track: usize) -> u128 {
+        self.disc().iter().take(track).map(|t| t.length).sum()
+    }
+
+    pub fn length(&self) -> u128 {
+        self.track_start(self.track_count())
+    }
+
+    /// Length of a track in nanoseconds, where the first track is 1.
+    pub fn track_length(&self, track: usize) -> u128 {
+        track.checked_sub(1).and_then(|i| self.disc().get(i)).map(|t| t.length).unwrap_or(0)
+    }
+
+    /// Current position on the disc in nanoseconds.
+    pub fn position(&self, now: u128) -> u128 {
+        match self.state.running_since {
+            Some(since) => (self.state.position + now.saturating_sub(since)).min(self.state.end),
+            None => self.state.position,
+        }
+    }
+
+    /// The track the position is in, where the first track is 1, or 0 if there aren't any tracks.
+    pub fn track(&self, now: u128) -> usize {
+        let position = self.position(now);
+        let mut start = 0;
+        for (i, track) in self.disc().iter().enumerate() {
+            if position < start + track.length {
+                return i + 1
+            }
+            start += track.length;
+        }
+        self.track_count()
+    }
+
+    pub fn track_position(&self, now: u128) -> u128 {
+        self.position(now) - self.track_start(self.track(now).saturating_sub(1))
+    }
+
+    pub fn playing(&self, now: u128) -> bool {
+        self.state.running_since.is_some() && self.position(now) < self.state.end
+    }
+
+    pub fn paused(&self) -> bool {
+        self.state.paused
+    }
+
+    /// Plays tracks `first` to `last` inclusive, where the first track is 1.
+    pub fn play(&mut self, first: usize, last: usize, now: u128, audio: &mut AudioManager) {
+        let first = first.max(1);
+        let last = last.min(self.track_count());
+        if first > last {
+            return
+        }
+        self.state.position = self.track_start(first - 1);
+        self.state.end = self.track_start(last);
+        self.state.paused = false;
+        self.start(now, audio);
+    }
+
+    pub fn stop(&mut self, now: u128, audio: &mut AudioManager) {
+        self.state.position = self.position(now);
+        self.state.running_since = None;
+        self.state.paused = false;
+        audio.stop_sound(CD_SOUND_ID);
+    }
+
+    pub fn pause(&mut self, now: u128, audio: &mut AudioManager) {
+        if self.playing(now) {
+            self.stop(now, audio);
+            self.state.paused = true;
+        }
+    }
+
+    pub fn resume(&mut self, now: u128, audio: &mut AudioManager) {
+        if self.state.paused {
+            self.state.paused = false;
+            self.start(now, audio);
+        }
+    }
+
+    /// Moves to the given disc position in nanoseconds, carrying on playing if the CD was playing.
+    pub fn set_position(&mut self, position: u128, now: u128, audio: &mut AudioManager) {
+        let was_playing = self.playing(now);
+        self.state.position = position.min(self.length());
+        if was_playing {
+            self.start(now, audio);
+        } else {
+            self.state.running_since = None;
+        }
+    }
+
+    pub fn set_track_position(&mut self, position: u128, now: u128, audio: &mut AudioManager) {
+        let track = self.track(now);
+        let start = self.track_start(track.saturating_sub(1));
+        self.set_position(start + position.min(self.track_length(track)), now, audio);
+    }
+
+    pub fn open_door(&mut self, now: u128, audio: &mut AudioManager) {
+        self.stop(now, audio);
+        self.state.position = 0;
+        self.state.door_open = true;
+    }
+
+    pub fn close_door(&mut self) {
+        self.state.door_open = false;
+    }
+
+    /// Starts playing from the current position up to the end position.
+    fn start(&mut self, now: u128, audio: &mut AudioManager) {
+        self.state.running_since = Some(now);
+        self.queue_audio(self.state.position, audio);
+    }
+
+    /// Replaces whatever the CD was outputting with the audio from the given disc position up to the end position.
+    fn queue_audio(&self, position: u128, audio: &mut AudioManager) {
+        audio.stop_sound(CD_SOUND_ID);
+        if !audio.do_output {
+            return
+        }
+
+        // Queue up every track which is at least partially inside the range that's going to be played
+        let (sample_rate, channels) = (audio.mixer_sample_rate, audio.mixer_channel_count);
+        let mut queue: Vec<Box<dyn Source + Send>> = Vec::new();
+        let mut skip = 0;
+        let mut start = 0;
+        for track in self.disc() {
+            let end = start + track.length;
+            if end > position && start < self.state.end {
+                if queue.is_empty() {
+                    let offset = position - start;
+                    let frames = offset * u128::from(u32::from(sample_rate)) / 1_000_000_000;
+                    skip = frames as usize * usize::from(u16::from(channels));
+                }
+                let source: Box<dyn Source + Send> = match track.audio.clone() {
+                    TrackAudio::Wav(p) => Box::new(Rechanneler::new(Resampler::new(p, sample_rate), channels)),
+                    TrackAudio::Mp3(p) => Box::new(Rechanneler::new(Resampler::new(p, sample_rate), channels)),
+                };
+                queue.push(source);
+            }
+            start = end;
+        }
+        queue.reverse();
+
+        let params = Arc::new(SoundParams { volume: AtomicU32::new(1.0f32.to_bits()) });
+        let _ = audio.mixer_handle.add(CdSource { queue, skip, sample_rate, channels }, params, CD_SOUND_ID);
+    }
+}
+
+/// Plays a list of tracks back-to-back, optionally starting partway through the first one.
+/// Tracks are stored in reverse order so that finished ones can be popped off the end.
+struct CdSource {
+    queue: Vec<Box<dyn Source + Send>>,
+    skip: usize,
+    sample_rate: SampleRate,
+    channels: ChannelCount,
+}
+
+impl Source for CdSource {
+    fn channel_count(&self) -> ChannelCount {
+        self.channels
+    }
+
+    fn sample_rate(&self) -> SampleRate {
+        self.sample_rate
+    }
+
+    fn write_samples(&mut self, buffer: &mut [Sample]) -> usize {
+        if buffer.is_empty() {
+            return 0
+        }
+        while self.skip > 0 {
+            let len = self.skip.min(buffer.len());
+            match self.queue.last_mut() {
+                Some(track) => {
+                    let count = track.write_samples(&mut buffer[..len]);
+                    self.skip -= count;
+                    if count < len {
+                        self.queue.pop();
+                        self.skip = 0;
+                    }
+                },
+                None => self.skip = 0,
+            }
+        }
+        let mut written = 0;
+        while let Some(track) = self.queue.last_mut() {
+            written += track.write_samples(&mut buffer[written..]);
+            if written == buffer.len() {
+                break
+            }
+            self.queue.pop();
+        }
+        written
+    }
+
+    fn reset(&mut self) {}
+}
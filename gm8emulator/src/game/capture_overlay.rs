@@ -0,0 +1,318 @@
+//! Information drawn over the frames captured with -c, such as the inputs being held and a frame counter.
+//!
+//! It's drawn straight into the captured pixels after they've been read back from the renderer,
+//! so nothing the game can see is touched.
+
+use crate::{
+    game::replay::{Frame, Input},
+    input::{Button, MouseButton},
+};
+use std::{collections::BTreeSet, convert::TryFrom};
+
+const TEXT_COLOUR: [u8; 3] = [255, 255, 255];
+const LAG_COLOUR: [u8; 3] = [255, 80, 80];
+
+const GLYPH_WIDTH: usize = 5;
+const GLYPH_HEIGHT: usize = 7;
+
+pub struct CaptureOverlay {
+    show_input: bool,
+    show_frame: bool,
+    show_lag: bool,
+    rerecords: Option<u64>,
+
+    held_keys: BTreeSet<u8>,
+    held_buttons: BTreeSet<i8>,
+    frame: usize,
+    lag_frames: usize,
+    lagged: bool,
+}
+
+impl CaptureOverlay {
+    /// Reads which overlays to draw from a list such as "input,frame,lag,rerecords=120".
+    /// A plain "rerecords" takes the count from `project_rerecords`, which comes from the replay's project.
+    pub fn from_list(list: &str, project_rerecords: Option<u64>) -> Result<Self, String> {
+        let mut overlay = Self {
+            show_input: false,
+            show_frame: false,
+            show_lag: false,
+            rerecords: None,
+            held_keys: BTreeSet::new(),
+            held_buttons: BTreeSet::new(),
+            frame: 0,
+            lag_frames: 0,
+            lagged: false,
+        };
+        for item in list.split(',').map(str::trim).filter(|x| !x.is_empty()) {
+            match item.split_once('=') {
+                None if item == "input" => overlay.show_input = true,
+                None if item == "frame" => overlay.show_frame = true,
+                None if item == "lag" => overlay.show_lag = true,
+                None if item == "rerecords" => {
+                    overlay.rerecords = Some(project_rerecords.ok_or("no project.cfg found to read rerecords from")?)
+                },
+                Some(("rerecords", count)) => {
+                    overlay.rerecords = Some(count.parse().map_err(|_| format!("invalid rerecord count {}", count))?)
+                },
+                _ => return Err(format!("unknown overlay {}", item)),
+            }
+        }
+        Ok(overlay)
+    }
+
+    /// Sets the number of the next frame, such as after starting from a savestate.
+    pub fn set_frame(&mut self, frame: usize) {
+        self.frame = frame;
+    }
+
+    /// Keeps track of which keys and mouse buttons are held, going by the inputs on a replay frame.
+    pub fn add_inputs(&mut self, frame: &Frame) {
+        for input in frame.inputs.iter() {
+            match *input {
+                Input::KeyPress(key) => {
+                    self.held_keys.insert(key);
+                },
+                Input::KeyRelease(key) => {
+                    self.held_keys.remove(&key);
+                },
+                Input::MousePress(button) => {
+                    self.held_buttons.insert(button);
+                },
+                Input::MouseRelease(button) => {
+                    self.held_buttons.remove(&button);
+                },
+                Input::MouseWheelUp | Input::MouseWheelDown => (),
+            }
+        }
+    }
+
+    /// Moves on to the next frame, noting whether the one that just ran was a lag frame.
+    pub fn end_frame(&mut self, lagged: bool) {
+        self.frame += 1;
+        self.lagged = lagged;
+        if lagged {
+            self.lag_frames += 1;
+        }
+    }
+
+    /// Draws the overlays onto a frame of RGBA pixels.
+    pub fn draw(&self, pixels: &mut [u8], width: usize, height: usize) {
+        // Small games get small text, otherwise it'd cover most of the screen
+        let scale = if width >= 640 && height >= 480 { 2 } else { 1 };
+        let line_height = (GLYPH_HEIGHT + 3) * scale;
+        let mut canvas = Canvas { pixels, width, height, scale };
+
+        let mut y = scale;
+        if self.show_frame {
+            canvas.text(&format!("FRAME {}", self.frame), scale, y, TEXT_COLOUR);
+            y += line_height;
+        }
+        if let Some(rerecords) = self.rerecords {
+            canvas.text(&format!("RERECORDS {}", rerecords), scale, y, TEXT_COLOUR);
+            y += line_height;
+        }
+        if self.show_lag {
+            let colour = if self.lagged { LAG_COLOUR } else { TEXT_COLOUR };
+            canvas.text(&format!("LAG {}", self.lag_frames), scale, y, colour);
+        }
+
+        if self.show_input {
+            let keys = self
+                .held_keys
+                .iter()
+                .map(|key| Button::try_from(*key).map(|b| b.to_string()).unwrap_or_else(|_| format!("KEY {}", key)));
+            let buttons = self.held_buttons.iter().map(|button| match *button {
+                x if x == MouseButton::Left as i8 => "LMB".to_string(),
+                x if x == MouseButton::Right as i8 => "RMB".to_string(),
+                x if x == MouseButton::Middle as i8 => "MMB".to_string(),
+                x => format!("MOUSE {}", x),
+            });
+            let text = keys.chain(buttons).collect::<Vec<_>>().join("  ");
+            if !text.is_empty() {
+                canvas.text(&text, scale, height.saturating_sub(line_height), TEXT_COLOUR);
+            }
+        }
+    }
+}
+
+struct Canvas<'a> {
+    pixels: &'a mut [u8],
+    width: usize,
+    height: usize,
+    scale: usize,
+}
+
+impl Canvas<'_> {
+    /// Draws a line of text over a darkened box so it can be read against anything.
+    fn text(&mut self, text: &str, x: usize, y: usize, colour: [u8; 3]) {
+        let text = text.to_uppercase();
+        let advance = (GLYPH_WIDTH + 1) * self.scale;
+        let box_width = text.chars().count() * advance + self.scale;
+        let box_height = (GLYPH_HEIGHT + 2) * self.scale;
+        for py in y..(y + box_height).min(self.height) {
+            for px in x..(x + box_width).min(self.width) {
+                let i = (py * self.width + px) * 4;
+                for channel in &mut self.pixels[i..i + 3] {
+                    *channel /= 3;
+                }
+            }
+        }
+
+        for (n, c) in text.chars().enumerate() {
+            let left = x + self.scale + n * advance;
+            for (row, bits) in glyph(c).iter().enumerate() {
+                for column in 0..GLYPH_WIDTH {
+                    if bits & (1 << (GLYPH_WIDTH - 1 - column)) != 0 {
+                        self.dot(left + column * self.scale, y + self.scale + row * self.scale, colour);
+                    }
+                }
+            }
+        }
+    }
+
+    fn dot(&mut self, x: usize, y: usize, colour: [u8; 3]) {
+        for py in y..(y + self.scale).min(self.height) {
+            for px in x..(x + self.scale).min(self.width) {
+                let i = (py * self.width + px) * 4;
+                self.pixels[i..i + 3].copy_from_slice(&colour);
+            }
+        }
+    }
+}
+
+/// Rows of a 5x7 character, with the leftmost pixel in the highest bit.
+fn glyph(c: char) -> [u8; GLYPH_HEIGHT] {
+    match c {
+        '0' => [0b01110, 0b10001, 0b10011, 0b10101, 0b11001, 0b10001, 0b01110],
+        '1' => [0b00100, 0b01100, 0b00100, 0b00100, 0b00100, 0b00100, 0b01110],
+        '2' => [0b01110, 0b10001, 0b00001, 0b00010, 0b00100, 0b01000, 0b11111],
+        '3' => [0b11111, 0b00010, 0b00100, 0b00010, 0b00001, 0b10001, 0b01110],
+        '4' => [0b00010, 0b00110, 0b01010, 0b10010, 0b11111, 0b00010, 0b00010],
+        '5' => [0b11111, 0b10000, 0b11110, 0b00001, 0b00001, 0b10001, 0b01110],
+        '6' => [0b00110, 0b01000, 0b10000, 0b11110, 0b10001, 0b10001, 0b01110],
+        '7' => [0b11111, 0b00001, 0b00010, 0b00100, 0b01000, 0b01000, 0b01000],
+        '8' => [0b01110, 0b10001, 0b10001, 0b01110, 0b10001, 0b10001, 0b01110],
+        '9' => [0b01110, 0b10001, 0b10001, 0b01111, 0b00001, 0b00010, 0b01100],
+        'A' => [0b01110, 0b10001, 0b10001, 0b11111, 0b10001, 0b10001, 0b10001],
+        'B' => [0b11110, 0b10001, 0b10001, 0b11110, 0b10001, 0b10001, 0b11110],
+        'C' => [0b01110, 0b10001, 0b10000, 0b10000, 0b10000, 0b10001, 0b01110],
+        'D' => [0b11100, 0b10010, 0b10001, 0b10001, 0b10001, 0b10010, 0b11100],
+        'E' => [0b11111, 0b10000, 0b10000, 0b11110, 0b10000, 0b10000, 0b11111],
+        'F' => [0b11111, 0b10000, 0b10000, 0b11110, 0b10000, 0b10000, 0b10000],
+        'G' => [0b01110, 0b10001, 0b10000, 0b10111, 0b10001, 0b10001, 0b01111],
+        'H' => [0b10001, 0b10001, 0b10001, 0b11111, 0b10001, 0b10001, 0b10001],
+        'I' => [0b01110, 0b00100, 0b00100, 0b00100, 0b00100, 0b00100, 0b01110],
+        'J' => [0b00111, 0b00010, 0b00010, 0b00010, 0b00010, 0b10010, 0b01100],
+        'K' => [0b10001, 0b10010, 0b10100, 0b11000, 0b10100, 0b10010, 0b10001],
+        'L' => [0b10000, 0b10000, 0b10000, 0b10000, 0b10000, 0b10000, 0b11111],
+        'M' => [0b10001, 0b11011, 0b10101, 0b10101, 0b10001, 0b10001, 0b10001],
+        'N' => [0b10001, 0b10001, 0b11001, 0b10101, 0b10011, 0b10001, 0b10001],
+        'O' => [0b01110, 0b10001, 0b10001, 0b10001, 0b10001, 0b10001, 0b01110],
+        'P' => [0b11110, 0b10001, 0b10001, 0b11110, 0b10000, 0b10000, 0b10000],
+        'Q' => [0b01110, 0b10001, 0b10001, 0b10001, 0b10101, 0b10010, 0b01101],
+        'R' => [0b11110, 0b10001, 0b10001, 0b11110, 0b10100, 0b10010, 0b10001],
+        'S' => [0b01111, 0b10000, 0b10000, 0b01110, 0b00001, 0b00001, 0b11110],
+        'T' => [0b11111, 0b00100, 0b00100, 0b00100, 0b00100, 0b00100, 0b00100],
+        'U' => [0b10001, 0b10001, 0b10001, 0b10001, 0b10001, 0b10001, 0b01110],
+        'V' => [0b10001, 0b10001, 0b10001, 0b10001, 0b10001, 0b01010, 0b00100],
+        'W' => [0b10001, 0b10001, 0b10001, 0b10101, 0b10101, 0b10101, 0b01010],
+        'X' => [0b10001, 0b10001, 0b01010, 0b00100, 0b01010, 0b10001, 0b10001],
+        'Y' => [0b10001, 0b10001, 0b10001, 0b01010, 0b00100, 0b00100, 0b00100],
+        'Z' => [0b11111, 0b00001, 0b00010, 0b00100, 0b01000, 0b10000, 0b11111],
+        ' ' => [0; GLYPH_HEIGHT],
+        ':' => [0b00000, 0b01100, 0b01100, 0b00000, 0b01100, 0b01100, 0b00000],
+        '/' => [0b00000, 0b00001, 0b00010, 0b00100, 0b01000, 0b10000, 0b00000],
+        '-' => [0b00000, 0b00000, 0b00000, 0b11111, 0b00000, 0b00000, 0b00000],
+        '+' => [0b00000, 0b00100, 0b00100, 0b11111, 0b00100, 0b00100, 0b00000],
+        '.' => [0b00000, 0b00000, 0b00000, 0b00000, 0b00000, 0b01100, 0b01100],
+        '(' => [0b00010, 0b00100, 0b01000, 0b01000, 0b01000, 0b00100, 0b00010],
+        ')' => [0b01000, 0b00100, 0b00010, 0b00010, 0b00010, 0b00100, 0b01000],
+        _ => [0b01110, 0b10001, 0b00001, 0b00010, 0b00100, 0b00000, 0b00100],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CHARSET: &str = "0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZ :/-+.()";
+
+    #[test]
+    fn from_list_reads_items() {
+        let overlay = CaptureOverlay::from_list(" input,frame ,,lag,rerecords=120", None).unwrap();
+        assert!(overlay.show_input && overlay.show_frame && overlay.show_lag);
+        assert_eq!(overlay.rerecords, Some(120));
+
+        let overlay = CaptureOverlay::from_list("", Some(5)).unwrap();
+        assert!(!overlay.show_input && !overlay.show_frame && !overlay.show_lag);
+        assert_eq!(overlay.rerecords, None);
+    }
+
+    #[test]
+    fn from_list_project_rerecords() {
+        assert_eq!(CaptureOverlay::from_list("rerecords", Some(7)).unwrap().rerecords, Some(7));
+        assert_eq!(CaptureOverlay::from_list("rerecords=3", Some(7)).unwrap().rerecords, Some(3));
+        assert!(CaptureOverlay::from_list("rerecords", None).is_err());
+    }
+
+    #[test]
+    fn from_list_rejects_unknown() {
+        assert!(CaptureOverlay::from_list("fps", None).is_err());
+        assert!(CaptureOverlay::from_list("frame=2", None).is_err());
+        assert!(CaptureOverlay::from_list("rerecords=lots", None).is_err());
+        assert!(CaptureOverlay::from_list("rerecords=-1", None).is_err());
+    }
+
+    #[test]
+    fn end_frame_counts_lag() {
+        let mut overlay = CaptureOverlay::from_list("frame,lag", None).unwrap();
+        overlay.set_frame(10);
+        overlay.end_frame(true);
+        assert!(overlay.lagged);
+        overlay.end_frame(false);
+        assert_eq!(overlay.frame, 12);
+        assert_eq!(overlay.lag_frames, 1);
+        assert!(!overlay.lagged);
+    }
+
+    #[test]
+    fn glyphs_fit_and_differ() {
+        let glyphs = CHARSET.chars().map(glyph).collect::<Vec<_>>();
+        for (c, rows) in CHARSET.chars().zip(glyphs.iter()) {
+            assert!(rows.iter().all(|row| *row < 1 << GLYPH_WIDTH), "glyph for {:?} is too wide", c);
+            assert_ne!(*rows, glyph('?'), "glyph for {:?} is the unknown character", c);
+        }
+        for (i, rows) in glyphs.iter().enumerate() {
+            assert!(!glyphs[i + 1..].contains(rows), "glyph for {:?} is used twice", CHARSET.chars().nth(i));
+        }
+    }
+
+    #[test]
+    fn text_pixels() {
+        let (width, height) = (12, 10);
+        let mut pixels = vec![255u8; width * height * 4];
+        let mut canvas = Canvas { pixels: &mut pixels, width, height, scale: 1 };
+        canvas.text("1", 0, 0, [1, 2, 3]);
+
+        let pixel = |x: usize, y: usize| &pixels[(y * width + x) * 4..(y * width + x) * 4 + 4];
+        // The top of the 1, one pixel in from the box's corner
+        assert_eq!(pixel(3, 1), [1, 2, 3, 255]);
+        // Darkened box around it, which is 7x9 for one character
+        assert_eq!(pixel(1, 1), [85, 85, 85, 255]);
+        assert_eq!(pixel(6, 8), [85, 85, 85, 255]);
+        // Outside the box
+        assert_eq!(pixel(7, 0), [255, 255, 255, 255]);
+        assert_eq!(pixel(0, 9), [255, 255, 255, 255]);
+    }
+
+    #[test]
+    fn draw_clips_to_small_frames() {
+        let mut overlay = CaptureOverlay::from_list("input,frame,lag,rerecords=99999", None).unwrap();
+        overlay.held_keys.insert(Button::Z as u8);
+        overlay.held_buttons.insert(MouseButton::Left as i8);
+        for (width, height) in [(1, 1), (5, 3), (40, 12), (700, 500)] {
+            let mut pixels = vec![0u8; width * height * 4];
+            overlay.draw(&mut pixels, width, height);
+        }
+    }
+}
@@ -0,0 +1,86 @@
+#[cfg(target_os = "windows")]
+use crate::game::platform;
+use serde::{Deserialize, Serialize};
+
+/// A display mode: resolution, colour depth in bits per pixel and refresh rate in hertz.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Mode {
+    pub width: u32,
+    pub height: u32,
+    pub colour_depth: u32,
+    pub frequency: u32,
+}
+
+impl Default for Mode {
+    fn default() -> Self {
+        Self { width: 1280, height: 720, colour_depth: 32, frequency: 60 }
+    }
+}
+
+impl Mode {
+    /// Reads the host's current display mode, if that's supported on this platform.
+    pub fn host() -> Option<Self> {
+        #[cfg(target_os = "windows")]
+        return Some(Self {
+            width: platform::display_width()?,
+            height: platform::display_height()?,
+            colour_depth: platform::display_colour_depth()?,
+            frequency: platform::display_frequency()?,
+        });
+        #[cfg(not(target_os = "windows"))]
+        None
+    }
+
+    /// Whether a real display adapter could plausibly switch to this mode.
+    /// GM8 only lets games choose between 16- and 32-bit colour.
+    pub fn is_valid(&self) -> bool {
+        (1..=16384).contains(&self.width)
+            && (1..=16384).contains(&self.height)
+            && (self.colour_depth == 16 || self.colour_depth == 32)
+            && (1..=500).contains(&self.frequency)
+    }
+}
+
+/// A spoofed display device. Games may query and change its mode freely,
+/// but nothing here ever changes the resolution of the host's real display.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Display {
+    current: Mode,
+    startup: Mode,
+}
+
+impl Display {
+    pub fn new(mode: Mode) -> Self {
+        Self { current: mode, startup: mode }
+    }
+
+    pub fn mode(&self) -> Mode {
+        self.current
+    }
+
+    /// Builds the mode that would result from applying the given GML arguments, where -1 means "keep current".
+    pub fn mode_with(&self, width: i32, height: i32, frequency: i32, colour_depth: i32) -> Mode {
+        let pick = |new: i32, old: u32| if new == -1 { old } else { new.max(0) as u32 };
+        Mode {
+            width: pick(width, self.current.width),
+            height: pick(height, self.current.height),
+            colour_depth: pick(colour_depth, self.current.colour_depth),
+            frequency: pick(frequency, self.current.frequency),
+        }
+    }
+
+    /// Switches to the given mode if it's valid, returning whether it was applied.
+    pub fn set_mode(&mut self, mode: Mode) -> bool {
+        if mode.is_valid() {
+            self.current = mode;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Restores the mode the display was in when the game started.
+    pub fn reset(&mut self) {
+        self.current = self.startup;
+    }
+}
@@ -412,7 +412,7 @@ impl Game {
 
         self.renderer.set_depth(-13000.0);
         if let Some(sprite) = self.assets.sprites.get_asset(self.cursor_sprite) {
-            let (x, y) = self.get_mouse_in_room();
+            let (x, y) = self.input.peek(|_| self.get_mouse_in_room());
             if let Some(atlas_ref) =
                 sprite.get_atlas_ref((self.cursor_sprite_frame % sprite.frames.len() as u32) as i32)
             {
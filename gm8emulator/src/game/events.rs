@@ -1,7 +1,8 @@
 use crate::{
+    action::Owner,
     asset::trigger::TriggerTime,
-    game::{Game, GetAsset},
-    gml,
+    game::{profiler::Key, trace::Entry, Game, GetAsset},
+    gml::{self, runtime::Frame},
     input::MouseButton,
     instance::Instance,
     types::ID,
@@ -77,12 +78,95 @@ impl Game {
                 }
             };
 
-            self.execute_tree(event, instance, other, event_id, event_sub as _, object_id)
+            self.trace(Entry::Event { instance, event_type: event_id, event_number: event_sub as _ });
+            // The engine only peeks at the input to decide which input events to run, but running one means the
+            // game's reacting to it
+            if matches!(event_id, gml::ev::KEYBOARD | gml::ev::KEYPRESS | gml::ev::KEYRELEASE | gml::ev::MOUSE) {
+                self.input.mark_polled();
+            }
+            let key = Key::Event { object: object_id, event_type: event_id, event_number: event_sub as _ };
+            self.profile(key, |game| {
+                game.execute_tree(event, instance, other, event_id, event_sub as _, object_id, Owner::Event)
+            })
         } else {
             Ok(())
         }
     }
 
+    /// Names an event the way GM8 does in its error messages, such as "Step Event" or "Alarm Event for alarm 0".
+    pub fn event_name(&self, event_type: usize, event_number: usize) -> String {
+        let key = |code: usize| match code {
+            0 => "<no key>".into(),
+            1 => "<any key>".into(),
+            13 => "<Enter>".into(),
+            27 => "<Escape>".into(),
+            32 => "<Space>".into(),
+            37 => "<Left>".into(),
+            38 => "<Up>".into(),
+            39 => "<Right>".into(),
+            40 => "<Down>".into(),
+            48..=57 | 65..=90 => format!("{}-key", char::from(code as u8)),
+            _ => format!("key {}", code),
+        };
+        match event_type {
+            gml::ev::CREATE => "Create Event".into(),
+            gml::ev::DESTROY => "Destroy Event".into(),
+            gml::ev::ALARMS => format!("Alarm Event for alarm {}", event_number),
+            gml::ev::STEP => match event_number {
+                1 => "Begin Step Event".into(),
+                2 => "End Step Event".into(),
+                _ => "Step Event".into(),
+            },
+            gml::ev::COLLISION => {
+                let object = self.assets.objects.get_asset(event_number as ID).map(|x| x.name.to_string());
+                format!("Collision Event with object {}", object.unwrap_or_else(|| event_number.to_string()))
+            },
+            gml::ev::KEYBOARD => format!("Keyboard Event for {} Key", key(event_number)),
+            gml::ev::MOUSE => {
+                const BUTTONS: [&str; 3] = ["Left", "Right", "Middle"];
+                match event_number {
+                    0..=2 => format!("Mouse Event for {} Button", BUTTONS[event_number]),
+                    3 => "Mouse Event for No Button".into(),
+                    4..=6 => format!("Mouse Event for {} Pressed", BUTTONS[event_number - 4]),
+                    7..=9 => format!("Mouse Event for {} Released", BUTTONS[event_number - 7]),
+                    10 => "Mouse Event for Mouse Enter".into(),
+                    11 => "Mouse Event for Mouse Leave".into(),
+                    50..=52 => format!("Mouse Event for Glob {} Button", BUTTONS[event_number - 50]),
+                    53..=55 => format!("Mouse Event for Glob {} Pressed", BUTTONS[event_number - 53]),
+                    56..=58 => format!("Mouse Event for Glob {} Released", BUTTONS[event_number - 56]),
+                    60 => "Mouse Event for Mouse Wheel Up".into(),
+                    61 => "Mouse Event for Mouse Wheel Down".into(),
+                    _ => format!("Mouse Event {}", event_number),
+                }
+            },
+            gml::ev::OTHER => match event_number {
+                0 => "Other Event: Outside Room".into(),
+                1 => "Other Event: Intersect Boundary".into(),
+                2 => "Other Event: Game Start".into(),
+                3 => "Other Event: Game End".into(),
+                4 => "Other Event: Room Start".into(),
+                5 => "Other Event: Room End".into(),
+                6 => "Other Event: No More Lives".into(),
+                7 => "Other Event: Animation End".into(),
+                8 => "Other Event: End of Path".into(),
+                9 => "Other Event: No More Health".into(),
+                10..=25 => format!("Other Event: User Defined {}", event_number - 10),
+                30 => "Other Event: Close Button".into(),
+                40..=47 => format!("Other Event: Outside View {}", event_number - 40),
+                50..=57 => format!("Other Event: Boundary View {}", event_number - 50),
+                _ => format!("Other Event {}", event_number),
+            },
+            gml::ev::DRAW => "Draw Event".into(),
+            gml::ev::KEYPRESS => format!("Key Press Event for {} Key", key(event_number)),
+            gml::ev::KEYRELEASE => format!("Key Release Event for {} Key", key(event_number)),
+            gml::ev::TRIGGER => {
+                let trigger = self.assets.triggers.get_asset(event_number as ID).map(|x| x.name.to_string());
+                format!("Trigger Event for trigger {}", trigger.unwrap_or_else(|| event_number.to_string()))
+            },
+            _ => format!("Event {},{}", event_type, event_number),
+        }
+    }
+
     /// Runs room end followed by game end events for all instances. Should be called only when the game ends.
     pub fn run_game_end_events(&mut self) -> gml::Result<()> {
         // Reset this so the events will run
@@ -127,7 +211,9 @@ impl Game {
                             context.event_type = 11; // ev_trigger
                             context.event_number = trigger_id as _;
                             context.event_object = self.room.instance_list.get(handle).object_index.get();
-                            self.execute(&trigger.condition, &mut context)?;
+                            self.execute(&trigger.condition, &mut context).map_err(|e| {
+                                e.in_frame(Frame::Code(format!("condition of trigger {}", trigger.name)))
+                            })?;
                             if context.return_value.is_truthy() {
                                 self.run_instance_event(gml::ev::TRIGGER, trigger_id, handle, handle, None)?;
                             }
@@ -176,7 +262,7 @@ impl Game {
             self.event_holders[gml::ev::KEYBOARD].get_index(i).map(|(x, y)| (*x, y.clone()))
         {
             if let Ok(vk) = u8::try_from(key) {
-                if self.input.keyboard_check(vk) {
+                if self.input.peek(|input| input.keyboard_check(vk)) {
                     // Get all the objects which have this key event registered
                     for object_id in objects.borrow().iter().copied() {
                         // Iter all instances of this object
@@ -189,7 +275,7 @@ impl Game {
             }
             i += 1;
         }
-        if self.input.keyboard_check_any() {
+        if self.input.peek(|input| input.keyboard_check_any()) {
             self.run_object_event(gml::ev::KEYBOARD, 1, None)?;
         } else {
             self.run_object_event(gml::ev::KEYBOARD, 0, None)?;
@@ -204,7 +290,7 @@ impl Game {
             self.event_holders[gml::ev::KEYPRESS].get_index(i).map(|(x, y)| (*x, y.clone()))
         {
             if let Ok(vk) = u8::try_from(key) {
-                if self.input.keyboard_check_pressed(vk) {
+                if self.input.peek(|input| input.keyboard_check_pressed(vk)) {
                     // Get all the objects which have this key event registered
                     for object_id in objects.borrow().iter().copied() {
                         // Iter all instances of this object
@@ -217,7 +303,7 @@ impl Game {
             }
             i += 1;
         }
-        if self.input.keyboard_check_pressed_any() {
+        if self.input.peek(|input| input.keyboard_check_pressed_any()) {
             self.run_object_event(gml::ev::KEYPRESS, 1, None)?;
         } else {
             self.run_object_event(gml::ev::KEYPRESS, 0, None)?;
@@ -232,7 +318,7 @@ impl Game {
             self.event_holders[gml::ev::KEYRELEASE].get_index(i).map(|(x, y)| (*x, y.clone()))
         {
             if let Ok(vk) = u8::try_from(key) {
-                if self.input.keyboard_check_released(vk) {
+                if self.input.peek(|input| input.keyboard_check_released(vk)) {
                     // Get all the objects which have this key event registered
                     for object_id in objects.borrow().iter().copied() {
                         // Iter all instances of this object
@@ -245,7 +331,7 @@ impl Game {
             }
             i += 1;
         }
-        if self.input.keyboard_check_released_any() {
+        if self.input.peek(|input| input.keyboard_check_released_any()) {
             self.run_object_event(gml::ev::KEYRELEASE, 1, None)?;
         } else {
             self.run_object_event(gml::ev::KEYRELEASE, 0, None)?;
@@ -255,8 +341,9 @@ impl Game {
 
     /// Runs all mouse events, including button, button pressed, button released, mouse scroll, mouse enter/leave
     pub fn run_mouse_events(&mut self) -> gml::Result<()> {
-        let (mouse_x, mouse_y) = self.get_mouse_in_room();
-        let (mouse_x_previous, mouse_y_previous) = self.get_mouse_previous_in_room();
+        // The engine checking where the mouse is doesn't count as the game polling it
+        let ((mouse_x, mouse_y), (mouse_x_previous, mouse_y_previous)) =
+            self.input.peek(|_| (self.get_mouse_in_room(), self.get_mouse_previous_in_room()));
 
         // Macro which runs a given event for all instances which the mouse is currently over.
         // Event type is gml::ev::MOUSE, you must provide the sub-event.
@@ -279,52 +366,52 @@ impl Game {
         }
 
         // Left button
-        if self.input.mouse_check_button(MouseButton::Left as i8) {
+        if self.input.peek(|input| input.mouse_check_button(MouseButton::Left as i8)) {
             try_mouse_events!(0);
         }
 
         // Right button
-        if self.input.mouse_check_button(MouseButton::Right as i8) {
+        if self.input.peek(|input| input.mouse_check_button(MouseButton::Right as i8)) {
             try_mouse_events!(1);
         }
 
         // Middle button
-        if self.input.mouse_check_button(MouseButton::Middle as i8) {
+        if self.input.peek(|input| input.mouse_check_button(MouseButton::Middle as i8)) {
             try_mouse_events!(2);
         }
 
         // No button
-        if !self.input.mouse_check_button_any() {
+        if !self.input.peek(|input| input.mouse_check_button_any()) {
             try_mouse_events!(3);
         }
 
         // Left button pressed
-        if self.input.mouse_check_button_pressed(MouseButton::Left as i8) {
+        if self.input.peek(|input| input.mouse_check_button_pressed(MouseButton::Left as i8)) {
             try_mouse_events!(4);
         }
 
         // Right button pressed
-        if self.input.mouse_check_button_pressed(MouseButton::Right as i8) {
+        if self.input.peek(|input| input.mouse_check_button_pressed(MouseButton::Right as i8)) {
             try_mouse_events!(5);
         }
 
         // Middle button pressed
-        if self.input.mouse_check_button_pressed(MouseButton::Middle as i8) {
+        if self.input.peek(|input| input.mouse_check_button_pressed(MouseButton::Middle as i8)) {
             try_mouse_events!(6);
         }
 
         // Left button released
-        if self.input.mouse_check_button_released(MouseButton::Left as i8) {
+        if self.input.peek(|input| input.mouse_check_button_released(MouseButton::Left as i8)) {
             try_mouse_events!(7);
         }
 
         // Right button released
-        if self.input.mouse_check_button_released(MouseButton::Right as i8) {
+        if self.input.peek(|input| input.mouse_check_button_released(MouseButton::Right as i8)) {
             try_mouse_events!(8);
         }
 
         // Middle button released
-        if self.input.mouse_check_button_released(MouseButton::Middle as i8) {
+        if self.input.peek(|input| input.mouse_check_button_released(MouseButton::Middle as i8)) {
             try_mouse_events!(9);
         }
 
@@ -363,17 +450,17 @@ impl Game {
         }
 
         // Global left button
-        if self.input.mouse_check_button(MouseButton::Left as i8) {
+        if self.input.peek(|input| input.mouse_check_button(MouseButton::Left as i8)) {
             self.run_object_event(gml::ev::MOUSE, 50, None)?;
         }
 
         // Global right button
-        if self.input.mouse_check_button(MouseButton::Right as i8) {
+        if self.input.peek(|input| input.mouse_check_button(MouseButton::Right as i8)) {
             self.run_object_event(gml::ev::MOUSE, 51, None)?;
         }
 
         // Global middle button
-        if self.input.mouse_check_button(MouseButton::Middle as i8) {
+        if self.input.peek(|input| input.mouse_check_button(MouseButton::Middle as i8)) {
             self.run_object_event(gml::ev::MOUSE, 52, None)?;
         }
 
@@ -381,48 +468,48 @@ impl Game {
         // step for the same instance - that's why they're duplicated next.
 
         // Global left button pressed
-        if self.input.mouse_check_button_pressed(MouseButton::Left as i8) {
+        if self.input.peek(|input| input.mouse_check_button_pressed(MouseButton::Left as i8)) {
             self.run_object_event(gml::ev::MOUSE, 53, None)?;
             self.run_object_event(gml::ev::MOUSE, 12, None)?; // Global Pressed ("any"; GM 5.x)
         }
 
         // Global right button pressed
-        if self.input.mouse_check_button_pressed(MouseButton::Right as i8) {
+        if self.input.peek(|input| input.mouse_check_button_pressed(MouseButton::Right as i8)) {
             self.run_object_event(gml::ev::MOUSE, 54, None)?;
             self.run_object_event(gml::ev::MOUSE, 12, None)?; // Global Pressed ("any"; GM 5.x)
         }
 
         // Global middle button pressed
-        if self.input.mouse_check_button_pressed(MouseButton::Middle as i8) {
+        if self.input.peek(|input| input.mouse_check_button_pressed(MouseButton::Middle as i8)) {
             self.run_object_event(gml::ev::MOUSE, 55, None)?;
             self.run_object_event(gml::ev::MOUSE, 12, None)?; // Global Pressed ("any"; GM 5.x)
         }
 
         // Global left button released
-        if self.input.mouse_check_button_released(MouseButton::Left as i8) {
+        if self.input.peek(|input| input.mouse_check_button_released(MouseButton::Left as i8)) {
             self.run_object_event(gml::ev::MOUSE, 56, None)?;
             self.run_object_event(gml::ev::MOUSE, 13, None)?; // Global Released ("any"; GM 5.x)
         }
 
         // Global right button released
-        if self.input.mouse_check_button_released(MouseButton::Right as i8) {
+        if self.input.peek(|input| input.mouse_check_button_released(MouseButton::Right as i8)) {
             self.run_object_event(gml::ev::MOUSE, 57, None)?;
             self.run_object_event(gml::ev::MOUSE, 13, None)?; // Global Released ("any"; GM 5.x)
         }
 
         // Global middle button released
-        if self.input.mouse_check_button_released(MouseButton::Middle as i8) {
+        if self.input.peek(|input| input.mouse_check_button_released(MouseButton::Middle as i8)) {
             self.run_object_event(gml::ev::MOUSE, 58, None)?;
             self.run_object_event(gml::ev::MOUSE, 13, None)?; // Global Released ("any"; GM 5.x)
         }
 
         // Mouse wheel up
-        if self.input.mouse_wheel_up() {
+        if self.input.peek(|input| input.mouse_wheel_up()) {
             self.run_object_event(gml::ev::MOUSE, 60, None)?;
         }
 
         // Mouse wheel down
-        if self.input.mouse_wheel_down() {
+        if self.input.peek(|input| input.mouse_wheel_down()) {
             self.run_object_event(gml::ev::MOUSE, 61, None)?;
         }
 
@@ -122,7 +122,7 @@ impl ExternalManager {
         }
     }
 
-    fn should_dummy(&self, signature: &dll::ExternalSignature) -> Option<gml::Value> {
+    pub fn should_dummy(&self, signature: &dll::ExternalSignature) -> Option<gml::Value> {
         let dll = &signature.dll;
         let sym = &signature.symbol;
         let dll = Path::new(dll).file_name().and_then(|oss| oss.to_str()).unwrap_or(dll);
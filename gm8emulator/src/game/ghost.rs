@@ -0,0 +1,476 @@
+//! Ghosts: another replay played back alongside the TAS, so the two runs can be compared.
+//!
+//! Every Game has its own window and renderer and moves the working directory, so the ghost's replay gets run by a
+//! second copy of the emulator with --ghost. It's kept in lockstep with the TAS: whenever the TAS has run a frame, it
+//! writes how many frames it's on to the ghost's stdin, and the ghost runs its replay up to there. After every frame,
+//! the ghost prints where the chosen objects' instances are:
+//!
+//! ```text
+//! ghost 120 3 4 224.5 96 5 1.25 1 1 0 16777215 1
+//! ```
+//!
+//! That's how many frames it's run and the room, then each instance's object, position, sprite, image index, scale,
+//! angle, blend colour and alpha, with assets written as their IDs.
+//!
+//! Replays always play out the same way, so the TAS keeps every frame it's been sent. Going back with a savestate
+//! just uses those, and the ghost only ever gets asked to go forwards.
+
+use crate::{game::Game, types::ID};
+use std::{
+    io::{self, BufRead, BufReader, Write},
+    path::{Path, PathBuf},
+    process::{Child, ChildStdin, Command, Stdio},
+    sync::mpsc::{self, Receiver, RecvTimeoutError},
+    time::Duration,
+};
+
+/// How long to wait for the ghost to finish a frame before giving up on it.
+const GHOST_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Where one of the chosen instances was at the end of a frame, and how it was drawn.
+#[derive(Clone, Debug)]
+pub struct GhostInstance {
+    pub object: ID,
+    pub x: f64,
+    pub y: f64,
+    pub sprite: ID,
+    pub image_index: f64,
+    pub xscale: f64,
+    pub yscale: f64,
+    pub angle: f64,
+    pub blend: i32,
+    pub alpha: f64,
+}
+
+#[derive(Clone, Debug)]
+pub struct GhostFrame {
+    pub room: ID,
+    pub instances: Vec<GhostInstance>,
+}
+
+impl GhostFrame {
+    /// Finds where the first instance of an object was, if it was in the given room.
+    fn position(&self, room: ID, object: ID) -> Option<(f64, f64)> {
+        let instance = self.instances.iter().find(|i| i.object == object).filter(|_| self.room == room)?;
+        Some((instance.x, instance.y))
+    }
+}
+
+/// Writes out the chosen objects' instances after each frame, in the copy of the emulator running the ghost.
+pub struct GhostRecorder {
+    objects: Vec<ID>,
+
+    /// How many frames the TAS UI has asked to be run
+    target: usize,
+}
+
+impl GhostRecorder {
+    /// Looks up the objects to record from a list of names such as "obj_player,obj_block".
+    pub fn from_list(game: &Game, list: &str) -> Result<Self, String> {
+        let objects = list
+            .split(',')
+            .map(str::trim)
+            .filter(|x| !x.is_empty())
+            .map(|name| {
+                game.assets
+                    .objects
+                    .iter()
+                    .position(|o| o.as_ref().map(|o| o.name.as_ref() == name.as_bytes()).unwrap_or(false))
+                    .map(|id| id as ID)
+                    .ok_or_else(|| format!("no object called {}", name))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Self { objects, target: 0 })
+    }
+}
+
+/// How the current run compares with the ghost, going by the first instance of the first object being recorded.
+pub struct Comparison {
+    /// How many frames ago the ghost was where the current run is now, or if it's negative,
+    /// how many frames ago the current run was where the ghost is now
+    pub frames_ahead: isize,
+
+    /// How far the current run is from the ghost on the same frame, if they're in the same room
+    pub offset: Option<(f64, f64)>,
+}
+
+impl Comparison {
+    pub fn describe(&self) -> String {
+        let plural = |n: isize| if n == 1 { "" } else { "s" };
+        let frames = match self.frames_ahead {
+            0 => "Level with ghost".to_string(),
+            n if n > 0 => format!("{} frame{} ahead of ghost", n, plural(n)),
+            n => format!("{} frame{} behind ghost", -n, plural(-n)),
+        };
+        match self.offset {
+            Some((x, y)) => format!("{} ({:+}, {:+})", frames, x, y),
+            None => frames,
+        }
+    }
+}
+
+/// A replay being run as a ghost alongside the TAS, for the TAS UI.
+pub struct Ghost {
+    child: Child,
+    stdin: ChildStdin,
+    lines: Receiver<String>,
+    objects: Vec<ID>,
+
+    /// What the ghost was doing after each number of frames, as far as it's been run
+    frames: Vec<GhostFrame>,
+
+    /// The room and position of the game's first recorded instance after each number of frames, as far as it's known
+    positions: Vec<Option<(ID, f64, f64)>>,
+
+    finished: bool,
+    error: Option<String>,
+}
+
+impl Ghost {
+    /// Starts another copy of the emulator running `replay` on the same game, recording the listed objects,
+    /// and runs it up to the frame the game's on.
+    pub fn spawn(game: &Game, replay: &Path, list: &str, frame: usize) -> Result<Self, String> {
+        let objects = GhostRecorder::from_list(game, list)?.objects;
+        if objects.is_empty() {
+            return Err("no objects to record".into())
+        }
+        // The ghost moves its working directory when it starts up, so it needs absolute paths
+        let replay = std::env::current_dir().map_err(|e| e.to_string())?.join(replay);
+        let game_file = game.parameters.first().and_then(|x| Path::new(x).file_name()).unwrap_or_default();
+        let game_file = PathBuf::from(game.decode_str(game.program_directory.as_ref()).into_owned()).join(game_file);
+
+        let mut command = Command::new(std::env::current_exe().map_err(|e| e.to_string())?);
+        command.arg(game_file).arg("-f").arg(replay).arg("--ghost").arg(list);
+        for arg in game.parameters.iter().skip(1) {
+            command.arg("-a").arg(arg);
+        }
+        let mut child = command
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .map_err(|e| format!("couldn't start the ghost: {}", e))?;
+        let stdin = child.stdin.take().expect("ghost stdin should be piped");
+        let stdout = child.stdout.take().expect("ghost stdout should be piped");
+
+        // Lines are read on another thread so a ghost that's stopped responding can be given up on
+        let (sender, lines) = mpsc::channel();
+        std::thread::spawn(move || {
+            for line in BufReader::new(stdout).lines() {
+                match line {
+                    Ok(line) if sender.send(line).is_ok() => (),
+                    _ => break,
+                }
+            }
+        });
+
+        let mut ghost = Self {
+            child,
+            stdin,
+            lines,
+            objects,
+            frames: Vec::new(),
+            positions: Vec::new(),
+            finished: false,
+            error: None,
+        };
+        ghost.seek(game, frame);
+        Ok(ghost)
+    }
+
+    pub fn objects(&self) -> &[ID] {
+        &self.objects
+    }
+
+    /// Runs the ghost up to `frame` frames if it's not been that far yet, and notes where the game is on that frame.
+    pub fn seek(&mut self, game: &Game, frame: usize) {
+        if self.positions.len() <= frame {
+            self.positions.resize(frame + 1, None);
+        }
+        self.positions[frame] = self.objects.first().and_then(|&object| {
+            let (x, y) = first_instance(game, object)?;
+            Some((game.room.id, x, y))
+        });
+
+        if self.finished || frame < self.frames.len() {
+            return
+        }
+        if writeln!(self.stdin, "{}", frame).and_then(|()| self.stdin.flush()).is_err() {
+            self.finished = true;
+            return
+        }
+        while self.frames.len() <= frame {
+            match self.lines.recv_timeout(GHOST_TIMEOUT) {
+                Ok(line) => match parse_frame(&line) {
+                    Some((number, ghost_frame)) => {
+                        // A replay which starts from a savestate doesn't have the frames before it
+                        while self.frames.len() < number {
+                            self.frames.push(GhostFrame { room: -1, instances: Vec::new() });
+                        }
+                        self.frames.push(ghost_frame);
+                    },
+                    None if line.starts_with("Runtime error") => self.error = Some(line),
+                    None => (),
+                },
+                Err(RecvTimeoutError::Timeout) => {
+                    self.error = Some(format!("The ghost didn't finish frame {} in time", self.frames.len()));
+                    self.finished = true;
+                    let _ = self.child.kill();
+                    break
+                },
+                Err(RecvTimeoutError::Disconnected) => {
+                    // It got to the end of its replay, or the game ended
+                    self.finished = true;
+                    break
+                },
+            }
+        }
+    }
+
+    /// Gets what the ghost was doing after `frame` frames, if it got that far.
+    pub fn frame(&self, frame: usize) -> Option<&GhostFrame> {
+        self.frames.get(frame)
+    }
+
+    /// Compares the game with the ghost, once `frame` frames of both have been run.
+    pub fn compare(&self, game: &Game, frame: usize) -> Option<Comparison> {
+        let object = *self.objects.first()?;
+        let room = game.room.id;
+        let (x, y) = first_instance(game, object)?;
+        let ghost = self.frames.get(frame).and_then(|f| f.position(room, object));
+
+        let ghost_positions =
+            (0..=frame).filter_map(|i| Some((i, self.frames.get(i)?.position(room, object)?))).collect::<Vec<_>>();
+        let game_positions = (0..=frame)
+            .filter_map(|i| match self.positions.get(i) {
+                Some(&Some((r, px, py))) if r == room => Some((i, (px, py))),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        // How many frames ago the ghost was nearest to the game, and the other way round
+        let behind = closest(frame, ghost_positions, (x, y));
+        let ahead = ghost.and_then(|ghost| closest(frame, game_positions, ghost));
+        let frames_ahead = match (behind, ahead) {
+            (Some((_, behind_distance)), Some((ahead, ahead_distance))) if ahead_distance < behind_distance => {
+                ahead as isize
+            },
+            (Some((behind, _)), _) => -(behind as isize),
+            (None, Some((ahead, _))) => ahead as isize,
+            (None, None) => return None,
+        };
+        Some(Comparison { frames_ahead, offset: ghost.map(|(gx, gy)| (x - gx, y - gy)) })
+    }
+
+    /// How many frames the ghost has run, whether it's finished, and why it stopped early if it did.
+    pub fn progress(&self) -> (usize, bool, Option<String>) {
+        (self.frames.len().saturating_sub(1), self.finished, self.error.clone())
+    }
+}
+
+impl Drop for Ghost {
+    fn drop(&mut self) {
+        // It's only there for the TAS UI, so there's no point letting it carry on
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+/// Finds where the first instance of an object is in the game.
+fn first_instance(game: &Game, object: ID) -> Option<(f64, f64)> {
+    let mut iter = game.room.instance_list.iter_by_drawing();
+    loop {
+        let instance = game.room.instance_list.get(iter.next(&game.room.instance_list)?);
+        if instance.object_index.get() == object {
+            break Some((instance.x.get().into_inner(), instance.y.get().into_inner()))
+        }
+    }
+}
+
+/// Finds which of the positions recorded up to `frame` is closest to a point, giving how many frames ago it was and
+/// how far away. The latest one wins if several are just as close.
+fn closest(frame: usize, positions: Vec<(usize, (f64, f64))>, (x, y): (f64, f64)) -> Option<(usize, f64)> {
+    positions
+        .into_iter()
+        .rev()
+        .map(|(i, (px, py))| (frame - i, (px - x).hypot(py - y)))
+        .min_by(|(_, a), (_, b)| a.total_cmp(b))
+}
+
+fn parse_frame(line: &str) -> Option<(usize, GhostFrame)> {
+    let mut words = line.strip_prefix("ghost ")?.split(' ');
+    let number = words.next()?.parse().ok()?;
+    let room = words.next()?.parse().ok()?;
+    let words = words.collect::<Vec<_>>();
+    let instances = words
+        .chunks(10)
+        .map(|chunk| match *chunk {
+            [object, x, y, sprite, image_index, xscale, yscale, angle, blend, alpha] => Some(GhostInstance {
+                object: object.parse().ok()?,
+                x: x.parse().ok()?,
+                y: y.parse().ok()?,
+                sprite: sprite.parse().ok()?,
+                image_index: image_index.parse().ok()?,
+                xscale: xscale.parse().ok()?,
+                yscale: yscale.parse().ok()?,
+                angle: angle.parse().ok()?,
+                blend: blend.parse().ok()?,
+                alpha: alpha.parse().ok()?,
+            }),
+            _ => None,
+        })
+        .collect::<Option<Vec<_>>>()?;
+    Some((number, GhostFrame { room, instances }))
+}
+
+impl Game {
+    /// Brings the ghost up to `frame` frames, if there is one, after the TAS UI's moved to that frame.
+    pub fn seek_ghost(&mut self, frame: usize) {
+        if let Some(mut ghost) = self.ghost.take() {
+            ghost.seek(self, frame);
+            self.ghost = Some(ghost);
+        }
+    }
+
+    /// With --ghost, waits for the TAS UI to ask for more than `frame` frames to be run.
+    /// Returns false if it's stopped asking, so the ghost should stop.
+    pub fn wait_for_ghost_target(&mut self, frame: usize) -> bool {
+        let recorder = match &mut self.ghost_recorder {
+            Some(recorder) => recorder,
+            None => return true,
+        };
+        let stdin = io::stdin();
+        while recorder.target <= frame {
+            let mut line = String::new();
+            match stdin.read_line(&mut line) {
+                Ok(0) | Err(_) => return false,
+                Ok(_) => recorder.target = line.trim().parse().unwrap_or(recorder.target),
+            }
+        }
+        true
+    }
+
+    /// Prints where the recorded objects' instances are after `frame` frames have been run with --ghost.
+    pub fn write_ghost_frame(&mut self, frame: usize) {
+        let recorder = match &self.ghost_recorder {
+            Some(recorder) => recorder,
+            None => return,
+        };
+        let mut line = format!("ghost {} {}", frame, self.room.id);
+        let mut iter = self.room.instance_list.iter_by_drawing();
+        while let Some(handle) = iter.next(&self.room.instance_list) {
+            let instance = self.room.instance_list.get(handle);
+            let object = instance.object_index.get();
+            if recorder.objects.contains(&object) {
+                line += &format!(
+                    " {} {} {} {} {} {} {} {} {} {}",
+                    object,
+                    instance.x.get(),
+                    instance.y.get(),
+                    instance.sprite_index.get(),
+                    instance.image_index.get(),
+                    instance.image_xscale.get(),
+                    instance.image_yscale.get(),
+                    instance.image_angle.get(),
+                    instance.image_blend.get(),
+                    instance.image_alpha.get(),
+                );
+            }
+        }
+
+        let mut stdout = io::stdout().lock();
+        if writeln!(stdout, "{}", line).and_then(|()| stdout.flush()).is_err() {
+            // Nothing's reading it any more
+            self.close_requested = true;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame(room: ID, positions: &[(f64, f64)]) -> GhostFrame {
+        let instances = positions
+            .iter()
+            .map(|&(x, y)| GhostInstance {
+                object: 0,
+                x,
+                y,
+                sprite: -1,
+                image_index: 0.0,
+                xscale: 1.0,
+                yscale: 1.0,
+                angle: 0.0,
+                blend: 0xFFFFFF,
+                alpha: 1.0,
+            })
+            .collect();
+        GhostFrame { room, instances }
+    }
+
+    #[test]
+    fn parse_frames() {
+        let (number, frame) =
+            parse_frame("ghost 120 3 4 224.5 96 5 1.25 1 1 0 16777215 1 7 -10 0 -1 0 -2 0.5 90 255 0.25").unwrap();
+        assert_eq!(number, 120);
+        assert_eq!(frame.room, 3);
+        assert_eq!(frame.instances.len(), 2);
+        let instance = &frame.instances[0];
+        assert_eq!((instance.object, instance.x, instance.y), (4, 224.5, 96.0));
+        assert_eq!((instance.sprite, instance.image_index), (5, 1.25));
+        assert_eq!((instance.xscale, instance.yscale, instance.angle), (1.0, 1.0, 0.0));
+        assert_eq!((instance.blend, instance.alpha), (0xFFFFFF, 1.0));
+        let instance = &frame.instances[1];
+        assert_eq!((instance.object, instance.x, instance.y, instance.sprite), (7, -10.0, 0.0, -1));
+        assert_eq!((instance.xscale, instance.yscale, instance.angle), (-2.0, 0.5, 90.0));
+        assert_eq!((instance.blend, instance.alpha), (255, 0.25));
+
+        let (number, frame) = parse_frame("ghost 0 1").unwrap();
+        assert_eq!((number, frame.room), (0, 1));
+        assert!(frame.instances.is_empty());
+    }
+
+    #[test]
+    fn parse_bad_frames() {
+        assert!(parse_frame("").is_none());
+        assert!(parse_frame("Runtime error: oops").is_none());
+        assert!(parse_frame("ghost").is_none());
+        assert!(parse_frame("ghost 5").is_none());
+        assert!(parse_frame("ghost -1 0").is_none());
+        assert!(parse_frame("ghost 5 0 4 1 2 3").is_none());
+        assert!(parse_frame("ghost 5 0 4 1 2 3 4 5").is_none());
+        assert!(parse_frame("ghost 5 0 4 1 2 3 4 1 1 0 255 x").is_none());
+        assert!(parse_frame("ghost 5 0 4 1 2 3 4 1 1 0 0.5 1").is_none());
+        assert!(parse_frame("ghost 5 0 4 1 2 3 4 1 1 0 255 1 7").is_none());
+        assert!(parse_frame("ghost  5 0").is_none());
+    }
+
+    #[test]
+    fn frame_positions() {
+        let frame = frame(2, &[(10.0, 20.0), (30.0, 40.0)]);
+        assert_eq!(frame.position(2, 0), Some((10.0, 20.0)));
+        assert_eq!(frame.position(3, 0), None);
+        assert_eq!(frame.position(2, 1), None);
+    }
+
+    #[test]
+    fn closest_positions() {
+        let positions = vec![(0, (0.0, 0.0)), (1, (5.0, 0.0)), (2, (10.0, 0.0)), (3, (5.0, 0.0))];
+        assert_eq!(closest(3, positions.clone(), (9.0, 0.0)), Some((1, 1.0)));
+        // Frames 1 and 3 are both right on it, so the latest one counts
+        assert_eq!(closest(3, positions, (5.0, 0.0)), Some((0, 0.0)));
+        assert_eq!(closest(3, Vec::new(), (5.0, 0.0)), None);
+    }
+
+    #[test]
+    fn describe_comparisons() {
+        let describe = |frames_ahead, offset| Comparison { frames_ahead, offset }.describe();
+        assert_eq!(describe(0, None), "Level with ghost");
+        assert_eq!(describe(3, None), "3 frames ahead of ghost");
+        assert_eq!(describe(-2, None), "2 frames behind ghost");
+        assert_eq!(describe(0, Some((0.0, 0.0))), "Level with ghost (+0, +0)");
+        assert_eq!(describe(1, Some((1.5, -16.0))), "1 frame ahead of ghost (+1.5, -16)");
+        assert_eq!(describe(-1, None), "1 frame behind ghost");
+        assert_eq!(describe(-12, Some((-0.25, 2.0))), "12 frames behind ghost (-0.25, +2)");
+    }
+}
@@ -0,0 +1,188 @@
+//! An opt-in profiler which measures how long is spent in each object event, script and kernel function.
+//!
+//! Time is counted separately for every call stack it's spent under, and gets written out in the "folded stacks"
+//! format when the profiler is dropped, so it can be turned straight into a flamegraph:
+//!
+//! ```text
+//! obj_player Step Event;scr_move;place_meeting 5120
+//! ```
+//!
+//! Each number is how many microseconds were spent in the last function of the stack and not in anything it called.
+
+use crate::{
+    game::{Game, GetAsset},
+    gml::mappings,
+    types::ID,
+};
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+/// Something that can show up on the profiler's call stack.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Key {
+    Event { object: ID, event_type: usize, event_number: usize },
+    Script(ID),
+
+    /// A kernel function, by its address
+    Function(usize),
+}
+
+struct Call {
+    start: Instant,
+    children: Duration,
+}
+
+struct Sample {
+    time: Duration,
+    calls: usize,
+}
+
+pub struct Profiler {
+    path: PathBuf,
+    names: HashMap<Key, String>,
+    stack: Vec<Key>,
+    calls: Vec<Call>,
+    samples: HashMap<Vec<Key>, Sample>,
+}
+
+impl Profiler {
+    /// Makes a profiler which writes to the given file once it's done.
+    pub fn new(path: &Path) -> Self {
+        Self {
+            path: path.to_path_buf(),
+            names: HashMap::new(),
+            stack: Vec::new(),
+            calls: Vec::new(),
+            samples: HashMap::new(),
+        }
+    }
+
+    fn enter(&mut self, key: Key) {
+        self.stack.push(key);
+        self.calls.push(Call { start: Instant::now(), children: Duration::ZERO });
+    }
+
+    fn exit(&mut self) {
+        if let Some(call) = self.calls.pop() {
+            let elapsed = call.start.elapsed();
+            let time = elapsed.saturating_sub(call.children);
+            match self.samples.get_mut(self.stack.as_slice()) {
+                Some(sample) => {
+                    sample.time += time;
+                    sample.calls += 1;
+                },
+                None => {
+                    self.samples.insert(self.stack.clone(), Sample { time, calls: 1 });
+                },
+            }
+            self.stack.pop();
+            if let Some(parent) = self.calls.last_mut() {
+                parent.children += elapsed;
+            }
+        }
+    }
+
+    fn name(&self, key: &Key) -> &str {
+        self.names.get(key).map(String::as_str).unwrap_or("?unknown?")
+    }
+
+    fn write(&self) -> io::Result<()> {
+        let mut writer = BufWriter::new(File::create(&self.path)?);
+        for (stack, sample) in self.samples.iter() {
+            let names = stack.iter().map(|key| self.name(key)).collect::<Vec<_>>();
+            writeln!(writer, "{} {}", names.join(";"), sample.time.as_micros())?;
+        }
+        writer.flush()
+    }
+
+    /// Prints the most expensive events, scripts and functions, with the time spent in each including and not
+    /// including what they called.
+    fn print_summary(&self) {
+        let mut totals: HashMap<Key, (usize, Duration, Duration)> = HashMap::new();
+        for (stack, sample) in self.samples.iter() {
+            if let Some(key) = stack.last() {
+                let total = totals.entry(*key).or_default();
+                total.0 += sample.calls;
+                total.2 += sample.time;
+            }
+            // Recursive calls shouldn't count the same time twice
+            for (i, key) in stack.iter().enumerate() {
+                if !stack[..i].contains(key) {
+                    totals.entry(*key).or_default().1 += sample.time;
+                }
+            }
+        }
+        let mut totals = totals.into_iter().collect::<Vec<_>>();
+        totals.sort_by(|(_, a), (_, b)| b.2.cmp(&a.2));
+
+        println!("{:>10} {:>12} {:>12}  name", "calls", "total ms", "self ms");
+        for (key, (calls, total, own)) in totals.iter().take(30) {
+            let ms = |time: &Duration| time.as_secs_f64() * 1000.0;
+            println!("{:>10} {:>12.3} {:>12.3}  {}", calls, ms(total), ms(own), self.name(key));
+        }
+    }
+}
+
+impl Drop for Profiler {
+    fn drop(&mut self) {
+        match self.write() {
+            Ok(()) => {
+                println!("Wrote profile to {}", self.path.display());
+                self.print_summary();
+            },
+            Err(e) => eprintln!("Failed to write profile to {}: {}", self.path.display(), e),
+        }
+    }
+}
+
+impl Game {
+    /// Runs something as a call on the profiler's stack, if there's a profiler.
+    #[inline(always)]
+    pub fn profile<T>(&mut self, key: Key, f: impl FnOnce(&mut Self) -> T) -> T {
+        if self.profiler.is_some() { self.profile_call(key, f) } else { f(self) }
+    }
+
+    fn profile_call<T>(&mut self, key: Key, f: impl FnOnce(&mut Self) -> T) -> T {
+        // Names are worked out here since the assets might not be around by the time the profile gets written
+        if !self.profiler.as_ref().map(|p| p.names.contains_key(&key)).unwrap_or(true) {
+            let name = self.profile_name(key);
+            if let Some(profiler) = &mut self.profiler {
+                profiler.names.insert(key, name);
+            }
+        }
+
+        if let Some(profiler) = &mut self.profiler {
+            profiler.enter(key);
+        }
+        let result = f(self);
+        if let Some(profiler) = &mut self.profiler {
+            profiler.exit();
+        }
+        result
+    }
+
+    fn profile_name(&self, key: Key) -> String {
+        // Semicolons separate the calls in a folded stack, so they can't go in names
+        let name = match key {
+            Key::Event { object, event_type, event_number } => {
+                let name = self.assets.objects.get_asset(object).map(|x| x.name.to_string());
+                format!("{} {}", name.unwrap_or_else(|| object.to_string()), self.event_name(event_type, event_number))
+            },
+            Key::Script(id) => {
+                let name = self.assets.scripts.get_asset(id).map(|x| x.name.to_string());
+                name.unwrap_or_else(|| id.to_string())
+            },
+            Key::Function(address) => mappings::FUNCTIONS
+                .entries()
+                .find(|(_, function)| function.addr() as usize == address)
+                .map(|(name, _)| name.to_string())
+                .unwrap_or_else(|| format!("{:#x}", address)),
+        };
+        name.replace(';', ",")
+    }
+}
@@ -1,6 +1,9 @@
 mod console;
 mod control_window;
+mod debugger_window;
 mod game_window;
+mod ghost_window;
+mod hitbox_overlay;
 mod input_edit;
 mod input_window;
 mod instance_report;
@@ -8,14 +11,21 @@ mod keybinds;
 mod macro_window;
 mod menu_bar;
 mod popup_dialog;
+mod replay_edit;
+mod run_until;
 mod savestate_window;
+mod search_window;
 mod set_mouse_dialog;
+mod trace_window;
+mod watch_window;
 mod window;
 
 use crate::{
     game::{
         recording::{
+            hitbox_overlay::OverlayConfig,
             instance_report::InstanceReport,
+            run_until::RunUntil,
             window::{EmulatorContext, Openable, Window},
         },
         replay::{self, Replay},
@@ -30,7 +40,11 @@ use crate::{
 use imgui::{self, internal::RawWrapper, DrawCmd};
 use ramen::{event::Event, input::Key};
 use serde::{Deserialize, Serialize};
-use std::{fs::File, path::PathBuf, time::Instant};
+use std::{
+    fs::File,
+    path::{Path, PathBuf},
+    time::Instant,
+};
 
 use super::replay::FrameRng;
 const GRID_COLOUR_GOOD: Colour = Colour::new(0.25, 0.625, 0.38671875);
@@ -124,6 +138,9 @@ struct UIState<'g> {
     /// Until which frame the game should advance
     run_until_frame: Option<usize>,
 
+    /// What the game should advance until, if it's not a particular frame
+    run_until: RunUntil,
+
     /// Whether or not the current state of the game is clean or has potentially been modified
     clean_state: bool,
 
@@ -432,6 +449,13 @@ pub enum WindowKind {
     Keybindings,
     Macro(usize),
     Console(usize),
+    Watch,
+    RunUntil,
+    Debugger,
+    Trace,
+    Search,
+    ReplayEditor,
+    Ghost,
 }
 
 #[derive(Deserialize, Serialize)]
@@ -441,6 +465,7 @@ pub struct ProjectConfig {
     ui_maximised: bool,
     rerecords: u64,
     watched_ids: Vec<i32>,
+    watched_expressions: Vec<String>,
     open_windows: Vec<WindowKind>,
     full_keyboard: bool,
     input_mode: InputMode,
@@ -449,6 +474,7 @@ pub struct ProjectConfig {
     is_read_only: bool,
     current_frame: usize,
     set_mouse_using_textbox: bool,
+    overlay: OverlayConfig,
 }
 
 impl ProjectConfig {
@@ -459,6 +485,7 @@ impl ProjectConfig {
             ui_maximised: false,
             rerecords: 0,
             watched_ids: Vec::new(),
+            watched_expressions: Vec::new(),
             open_windows: Vec::new(),
             full_keyboard: false,
             input_mode: InputMode::Mouse,
@@ -467,6 +494,7 @@ impl ProjectConfig {
             is_read_only: false,
             current_frame: 0,
             set_mouse_using_textbox: false,
+            overlay: OverlayConfig::default(),
         };
 
         let mut config = if config_path.exists() {
@@ -496,6 +524,12 @@ impl ProjectConfig {
     }
 }
 
+/// Reads how many rerecords a TAS project has, if there's a project.cfg in the given directory.
+pub fn project_rerecords(project_path: &Path) -> Option<u64> {
+    let file = File::open(project_path.join("project.cfg")).ok()?;
+    bincode::deserialize_from::<_, ProjectConfig>(file).ok().map(|config| config.rerecords)
+}
+
 impl Game {
     pub fn record(&mut self, project_path: PathBuf, pause: bool, start_save_path: Option<&PathBuf>) {
         let mut save_buffer = savestate::Buffer::new();
@@ -792,6 +826,13 @@ impl Game {
                 WindowKind::Keybindings => windows.push((Box::new(keybinds::KeybindWindow::open(0)), false)),
                 WindowKind::Macro(id) => windows.push((Box::new(macro_window::MacroWindow::open(*id)), false)),
                 WindowKind::Console(id) => windows.push((Box::new(console::ConsoleWindow::open(*id)), false)),
+                WindowKind::Watch => windows.push((Box::new(watch_window::WatchWindow::open(0)), false)),
+                WindowKind::RunUntil => windows.push((Box::new(run_until::RunUntilWindow::open(0)), false)),
+                WindowKind::Debugger => windows.push((Box::new(debugger_window::DebuggerWindow::open(0)), false)),
+                WindowKind::Trace => windows.push((Box::new(trace_window::TraceWindow::open(0)), false)),
+                WindowKind::Search => windows.push((Box::new(search_window::SearchWindow::open(0)), false)),
+                WindowKind::ReplayEditor => windows.push((Box::new(replay_edit::ReplayEditWindow::open(0)), false)),
+                WindowKind::Ghost => windows.push((Box::new(ghost_window::GhostWindow::open(0)), false)),
                 WindowKind::Control
                 | WindowKind::Game
                 | WindowKind::InstanceReports
@@ -825,6 +866,7 @@ impl Game {
             context_menu_window: None,
             context_menu_pos: Vec2(0.0, 0.0),
             run_until_frame: None,
+            run_until: RunUntil::new(),
             clean_state,
             clean_state_previous: clean_state,
             clean_state_instant: None,
@@ -1010,6 +1052,7 @@ impl UIState<'_> {
                             }
                             // Cancel running until a frame when Escape was pressed
                             self.run_until_frame = None;
+                            self.run_until.stop(self.game, "Stopped: cancelled with Escape".into());
                         }
 
                         if !self.config.set_mouse_using_textbox {
@@ -1093,6 +1136,7 @@ impl UIState<'_> {
 
             clean_state: &mut self.clean_state,
             run_until_frame: &mut self.run_until_frame,
+            run_until: &mut self.run_until,
 
             startup_successful: &self.startup_successful,
             ui_renderer_state: &self.ui_renderer_state,
@@ -5,7 +5,8 @@ use crate::{
             window::{EmulatorContext, Window},
             InputMode, KeyState,
         },
-        replay::{self, Frame, FrameRng},
+        replay::{self, Frame, FrameRng, Replay},
+        savestate::SaveState,
         Game, GameClock, SceneChange,
     },
     imgui_utils::{UiCustomFunction, Vec2},
@@ -45,10 +46,25 @@ impl Window for ControlWindow {
             if (info.frame.button_with_size("Advance", [content_width, 20.0])
                 || info.keybind_pressed(Binding::Advance)
                 || run_until_frame
+                || info.run_until.running
+                || info.game.debugger.resume_requested()
               ) && *info.game_running
                 && info.err_string.is_none()
             {
+                if info.run_until.running {
+                    info.run_until.before_frame(info.game);
+                }
                 self.advance_frame(info);
+                if info.game.debugger.is_paused() {
+                    *info.run_until_frame = None;
+                    let status = format!("Stopped on frame {}: the debugger stopped", info.config.current_frame);
+                    info.run_until.stop(info.game, status);
+                } else if *info.game_running {
+                    let frame = info.config.current_frame;
+                    info.run_until.after_frame(info.game, info.renderer_state, *info.clean_state, frame);
+                } else {
+                    info.run_until.stop(info.game, "Stopped: the game crashed".into());
+                }
             }
 
             if (info.frame.button_with_size("Quick Save", [content_width, 20.0])
@@ -235,12 +251,22 @@ impl ControlWindow {
     }
 
     fn advance_frame(&mut self, info: &mut EmulatorContext) {
+        // If the debugger stops partway through the frame, the game gets put back to how it was before it.
+        // Only the game's state matters here, so the replay in the savestate is left empty.
+        let rewind_state = if info.game.debugger.is_enabled() {
+            let replay = Replay::new(0, 0);
+            Some(SaveState::from(info.game, replay, info.renderer_state.clone(), *info.clean_state))
+        } else {
+            None
+        };
+
         info.game.input.mouse_step();
 
         let frame: &mut Frame;
         let mut current_frame: Frame;
 
-        if info.config.is_read_only && matches!(info.replay.get_frame(info.config.current_frame), Some(_)) {
+        let playing_back = info.config.is_read_only && info.replay.get_frame(info.config.current_frame).is_some();
+        if playing_back {
             current_frame = info.replay.get_frame(info.config.current_frame).unwrap().clone();
             frame = &mut current_frame;
         } else {
@@ -283,13 +309,31 @@ impl ControlWindow {
         }
 
         info.game.set_input_from_frame(frame);
+        if let Some(trace) = &mut info.game.trace {
+            trace.set_next_frame(info.config.current_frame);
+        }
 
-        if let Some(error) = self.run_frame(info.game, info.renderer_state) {
+        let error = self.run_frame(info.game, info.renderer_state);
+        if let Some(state) = rewind_state.filter(|_| info.game.debugger.is_paused()) {
+            self.rewind_frame(info, state);
+            return
+        }
+        if let Some(error) = error {
             *info.err_string = Some(error);
             *info.game_running = false;
         }
 
+        // Any stored events the game didn't ask for mean it's gone differently to when the replay was made
+        if playing_back && !info.game.stored_events.is_empty() && info.err_string.is_none() {
+            *info.err_string = Some(format!(
+                "Desync: {} stored events weren't used on frame {}",
+                info.game.stored_events.len(),
+                info.config.current_frame,
+            ));
+        }
+
         info.config.current_frame += 1;
+        info.game.seek_ghost(info.config.current_frame);
 
         if !info.config.is_read_only {
             for ev in info.game.stored_events.iter() {
@@ -314,6 +358,29 @@ impl ControlWindow {
         }
         info.game.frame_counter += 1;
 
+        self.restore_ui_renderer(info);
+        info.clear_context_menu();
+        *info.new_rand = None;
+        *info.new_mouse_pos = None;
+
+        info.update_instance_reports();
+    }
+
+    /// Puts the game back how it was before a frame which the debugger stopped partway through,
+    /// so that it can be run again from the start when the debugger carries on.
+    fn rewind_frame(&mut self, info: &mut EmulatorContext, state: SaveState) {
+        info.game.stored_events.clear();
+        if !info.config.is_read_only {
+            info.replay.truncate_frames(info.config.current_frame);
+        }
+        self.restore_ui_renderer(info);
+        let (_, renderer_state) = state.load_into(info.game);
+        *info.renderer_state = renderer_state;
+        info.clear_context_menu();
+    }
+
+    /// Stores the game's renderer state after running a frame, and switches back to the UI's.
+    fn restore_ui_renderer(&self, info: &mut EmulatorContext) {
         info.game.renderer.resize_framebuffer(info.config.ui_width.into(), info.config.ui_height.into(), true);
         info.game.renderer.set_view(
             0, 0,
@@ -334,11 +401,6 @@ impl ControlWindow {
         );
         *info.renderer_state = info.game.renderer.state();
         info.game.renderer.set_state(info.ui_renderer_state);
-        info.clear_context_menu();
-        *info.new_rand = None;
-        *info.new_mouse_pos = None;
-
-        info.update_instance_reports();
     }
 
     fn update_keyboard_state(&self, keyboard_state: &mut [KeyState; 256], frame: &mut Frame) {
@@ -400,7 +462,8 @@ impl ControlWindow {
             game.unscaled_height as _,
         );
         game.renderer.draw_stored(0, 0, w, h);
-        if let Err(e) = match game.frame() {
+        game.debugger.begin_frame();
+        let result = match game.frame() {
             Ok(()) => match game.scene_change {
                 Some(SceneChange::Room(id)) => game.load_room(id),
                 Some(SceneChange::Restart) => game.restart(),
@@ -412,7 +475,9 @@ impl ControlWindow {
                 None => Ok(()),
             },
             Err(e) => Err(e.into()),
-        } {
+        };
+        game.debugger.end_frame();
+        if let Err(e) = result {
             Some(format!("Game crashed: {}\n\nPlease load a savestate.", e))
         } else {
             None
@@ -0,0 +1,247 @@
+use crate::{
+    game::{
+        recording::window::{EmulatorContext, Openable, Window},
+        Game, GetAsset,
+    },
+    gml::{
+        debugger::{Breakpoint, Step},
+        Value,
+    },
+    imgui_utils::UiCustomFunction,
+    types::{Colour, ID},
+};
+
+const STOPPED_COLOUR: Colour = Colour::new(1.0, 0.85, 0.3);
+const ERROR_COLOUR: Colour = Colour::new(1.0, 0.5, 0.5);
+
+/// Event types in the order of their numbers, as used by gml::ev
+const EVENT_TYPES: [&str; 12] = [
+    "Create",
+    "Destroy",
+    "Alarm",
+    "Step",
+    "Collision",
+    "Keyboard",
+    "Mouse",
+    "Other",
+    "Draw",
+    "Key Press",
+    "Key Release",
+    "Trigger",
+];
+
+pub struct DebuggerWindow {
+    script: String,
+    script_line: i32,
+    object: String,
+    event_type: usize,
+    event_number: i32,
+    action: i32,
+    error: Option<String>,
+    is_open: bool,
+}
+
+impl Openable<Self> for DebuggerWindow {
+    fn window_name() -> &'static str {
+        "Debugger"
+    }
+
+    fn open(_id: usize) -> Self {
+        Self::new()
+    }
+}
+
+impl Window for DebuggerWindow {
+    fn stored_kind(&self) -> Option<super::WindowKind> {
+        Some(super::WindowKind::Debugger)
+    }
+
+    fn name(&self) -> String {
+        "Debugger".into()
+    }
+
+    fn show_window(&mut self, info: &mut EmulatorContext) {
+        let EmulatorContext { frame, game, keybindings, .. } = info;
+
+        // Adding a breakpoint borrows the whole window, so the open flag can't be borrowed alongside it
+        let mut is_open = self.is_open;
+        frame
+            .window(self.name())
+            .opened(&mut is_open)
+            .position([420.0, 100.0], imgui::Condition::FirstUseEver)
+            .size([400.0, 500.0], imgui::Condition::FirstUseEver)
+            .build(|| {
+                let mut step = None;
+                match game.debugger.pause() {
+                    Some(pause) => {
+                        for (i, place) in pause.stack.iter().enumerate() {
+                            if i == 0 {
+                                frame.coloured_text(&format!("Stopped in {}", place), STOPPED_COLOUR);
+                            } else {
+                                frame.text(format!("    called from {}", place));
+                            }
+                        }
+                        if frame.button("Step Into") {
+                            step = Some(Step::Into);
+                        }
+                        frame.same_line();
+                        if frame.button("Step Over") {
+                            step = Some(Step::Over);
+                        }
+                        frame.same_line();
+                        if frame.button("Step Out") {
+                            step = Some(Step::Out);
+                        }
+                        frame.same_line();
+                        if frame.button("Continue") {
+                            step = Some(Step::Continue);
+                        }
+
+                        frame.separator();
+                        frame.text(format!("self: {}", pause.this));
+                        frame.text(format!("other: {}", pause.other));
+                        for (i, value) in pause.arguments.iter().enumerate() {
+                            frame.text(format!("argument{} = {}", i, value_text(value)));
+                        }
+                        if pause.locals.is_empty() {
+                            frame.text("No local variables");
+                        }
+                        for (name, value) in pause.locals.iter() {
+                            frame.text(format!("{} = {}", name, value_text(value)));
+                        }
+                    },
+                    None => {
+                        frame.text_wrapped("Not stopped. Add a breakpoint and advance to stop when it's reached.");
+                    },
+                }
+                if let Some(step) = step {
+                    game.debugger.resume(step);
+                }
+
+                frame.separator();
+                frame.text("Breakpoints");
+                let mut removed = None;
+                for (i, breakpoint) in game.debugger.breakpoints().iter().enumerate() {
+                    if frame.small_button(format!("X##breakpointremove{}", i)) {
+                        removed = Some(i);
+                    }
+                    frame.same_line();
+                    frame.text(describe_breakpoint(game, breakpoint));
+                }
+                if let Some(i) = removed {
+                    game.debugger.remove_breakpoint(i);
+                }
+
+                frame.separator();
+                frame.input_text("Script", &mut self.script).build();
+                if frame.is_item_focused() {
+                    keybindings.disable_bindings();
+                }
+                frame.input_int("Line (0 for any)", &mut self.script_line).build();
+                if frame.is_item_focused() {
+                    keybindings.disable_bindings();
+                }
+                self.script_line = self.script_line.max(0);
+                if frame.button("Add script breakpoint") {
+                    self.error = self.add_script_breakpoint(game).err();
+                }
+
+                frame.separator();
+                frame.input_text("Object", &mut self.object).build();
+                if frame.is_item_focused() {
+                    keybindings.disable_bindings();
+                }
+                frame.combo_simple_string("Event", &mut self.event_type, &EVENT_TYPES);
+                frame.input_int("Event number", &mut self.event_number).build();
+                if frame.is_item_focused() {
+                    keybindings.disable_bindings();
+                }
+                frame.input_int("Action (0 for any)", &mut self.action).build();
+                if frame.is_item_focused() {
+                    keybindings.disable_bindings();
+                }
+                self.event_number = self.event_number.max(0);
+                self.action = self.action.max(0);
+                if frame.button("Add event breakpoint") {
+                    self.error = self.add_event_breakpoint(game).err();
+                }
+
+                if let Some(error) = &self.error {
+                    frame.coloured_text(error, ERROR_COLOUR);
+                }
+            });
+        self.is_open = is_open;
+    }
+
+    fn is_open(&self) -> bool {
+        self.is_open
+    }
+}
+
+impl DebuggerWindow {
+    pub fn new() -> Self {
+        Self {
+            script: String::new(),
+            script_line: 0,
+            object: String::new(),
+            event_type: 0,
+            event_number: 0,
+            action: 0,
+            error: None,
+            is_open: true,
+        }
+    }
+
+    fn add_script_breakpoint(&self, game: &mut Game) -> Result<(), String> {
+        let name = self.script.trim();
+        let id = game.compiler.get_script_id(name.as_bytes()).ok_or_else(|| format!("No script named {}", name))?;
+        let line = if self.script_line > 0 { Some(self.script_line as usize) } else { None };
+        game.debugger.add_breakpoint(Breakpoint::Script { id: id as ID, line });
+        Ok(())
+    }
+
+    fn add_event_breakpoint(&self, game: &mut Game) -> Result<(), String> {
+        let name = self.object.trim();
+        let object = game
+            .assets
+            .objects
+            .iter()
+            .position(|o| o.as_ref().map(|o| o.name.as_ref() == name.as_bytes()).unwrap_or(false))
+            .ok_or_else(|| format!("No object named {}", name))?;
+        let action = if self.action > 0 { Some(self.action as usize - 1) } else { None };
+        game.debugger.add_breakpoint(Breakpoint::Event {
+            object: object as ID,
+            event_type: self.event_type,
+            event_number: self.event_number as usize,
+            action,
+        });
+        Ok(())
+    }
+}
+
+fn value_text(value: &Value) -> String {
+    match value {
+        Value::Real(real) => real.to_string(),
+        Value::Str(string) => format!("\"{}\"", string),
+    }
+}
+
+fn describe_breakpoint(game: &Game, breakpoint: &Breakpoint) -> String {
+    match *breakpoint {
+        Breakpoint::Script { id, line } => {
+            let name = game.assets.scripts.get_asset(id).map(|s| s.name.to_string()).unwrap_or_default();
+            match line {
+                Some(line) => format!("script {}, line {}", name, line),
+                None => format!("script {}", name),
+            }
+        },
+        Breakpoint::Event { object, event_type, event_number, action } => {
+            let name = game.assets.objects.get_asset(object).map(|o| o.name.to_string()).unwrap_or_default();
+            let event = game.event_name(event_type, event_number);
+            match action {
+                Some(action) => format!("action number {} of {} for object {}", action + 1, event, name),
+                None => format!("{} for object {}", event, name),
+            }
+        },
+    }
+}
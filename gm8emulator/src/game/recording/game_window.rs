@@ -1,6 +1,7 @@
 use crate::{
     game::{
         recording::{
+            hitbox_overlay,
             instance_report::InstanceReport,
             set_mouse_dialog::{MouseDialogResult, SetMouseDialog},
             window::{EmulatorContext, Window},
@@ -145,6 +146,16 @@ impl GameWindow {
 
                 info.frame.callback(callback, &mut self.callback_data);
 
+                if info.config.overlay.enabled {
+                    let origin = [x + info.win_border_size, y + info.win_frame_height];
+                    hitbox_overlay::draw(info.frame, info.game, &info.config.overlay, origin, (w, h));
+                }
+                if let Some(ghost) = &info.game.ghost {
+                    let origin = [x + info.win_border_size, y + info.win_frame_height];
+                    let frames = info.config.current_frame;
+                    hitbox_overlay::draw_ghost(info.frame, info.game, ghost, frames, origin, (w, h));
+                }
+
                 if *info.setting_mouse_pos && !info.config.set_mouse_using_textbox {
                     let Vec2(mouse_x, mouse_y) = info.frame.mouse_pos();
                     let position =
@@ -0,0 +1,111 @@
+use crate::{
+    game::{
+        ghost::Ghost,
+        recording::window::{EmulatorContext, Openable, Window},
+    },
+    imgui_utils::UiCustomFunction,
+    types::Colour,
+};
+use std::path::PathBuf;
+
+const ERROR_COLOUR: Colour = Colour::new(1.0, 0.5, 0.5);
+
+pub struct GhostWindow {
+    replay_path: String,
+    objects: String,
+    error: Option<String>,
+    is_open: bool,
+}
+
+impl Openable<Self> for GhostWindow {
+    fn window_name() -> &'static str {
+        "Ghost"
+    }
+
+    fn open(_id: usize) -> Self {
+        Self::new()
+    }
+}
+
+impl Window for GhostWindow {
+    fn stored_kind(&self) -> Option<super::WindowKind> {
+        Some(super::WindowKind::Ghost)
+    }
+
+    fn name(&self) -> String {
+        "Ghost".into()
+    }
+
+    fn show_window(&mut self, info: &mut EmulatorContext) {
+        let EmulatorContext { config, frame, game, keybindings, project_path, .. } = info;
+
+        if self.replay_path.is_empty() {
+            self.replay_path = project_path.join("save.gmtas").to_string_lossy().into_owned();
+        }
+
+        frame
+            .window(self.name())
+            .opened(&mut self.is_open)
+            .position([150.0, 150.0], imgui::Condition::FirstUseEver)
+            .size([400.0, 220.0], imgui::Condition::FirstUseEver)
+            .build(|| {
+                let mut text_input = |label: &str, text: &mut String| {
+                    frame.input_text(label, text).build();
+                    if frame.is_item_focused() {
+                        keybindings.disable_bindings();
+                    }
+                };
+
+                match &game.ghost {
+                    Some(ghost) => {
+                        let (frames, finished, error) = ghost.progress();
+                        match (finished, error) {
+                            (_, Some(error)) => frame.coloured_text(&error, ERROR_COLOUR),
+                            (true, None) => frame.text(format!("Ghost finished after {} frames", frames)),
+                            (false, None) => frame.text(format!("Ghost is on frame {}", frames)),
+                        }
+                        match ghost.compare(game, config.current_frame) {
+                            Some(comparison) => frame.text(comparison.describe()),
+                            None => frame.text_disabled("Nothing to compare on this frame"),
+                        }
+                        if frame.button("Stop") {
+                            // Dropping the ghost stops the emulator running it
+                            game.ghost = None;
+                        }
+                    },
+                    None => {
+                        text_input("Replay", &mut self.replay_path);
+                        text_input("Objects", &mut self.objects);
+                        frame.text_disabled("Object names separated by commas. The first one gets compared.");
+                        frame.text_wrapped(
+                            "The ghost is run a frame at a time alongside the TAS. \
+                            It uses the same folder as the TAS, so any files the game writes are shared.",
+                        );
+                        if frame.button("Start") {
+                            let replay = PathBuf::from(&self.replay_path);
+                            match Ghost::spawn(game, &replay, &self.objects, config.current_frame) {
+                                Ok(ghost) => {
+                                    game.ghost = Some(ghost);
+                                    self.error = None;
+                                },
+                                Err(e) => self.error = Some(e),
+                            }
+                        }
+                    },
+                }
+                if let Some(error) = &self.error {
+                    frame.coloured_text(error, ERROR_COLOUR);
+                }
+            });
+    }
+
+    fn is_open(&self) -> bool {
+        self.is_open
+    }
+}
+
+impl GhostWindow {
+    pub fn new() -> Self {
+        Self { replay_path: String::new(), objects: String::new(), error: None, is_open: true }
+    }
+}
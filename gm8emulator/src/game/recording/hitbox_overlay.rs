@@ -0,0 +1,318 @@
+use crate::{
+    game::{ghost::Ghost, view::View, Game, GetAsset},
+    types::Colour,
+    util,
+};
+use imgui::{DrawListMut, ImColor32, TextureId};
+use serde::{Deserialize, Serialize};
+
+const BBOX_COLOUR: Colour = Colour::new(1.0, 0.2, 0.2);
+const MASK_COLOUR: Colour = Colour::new(0.2, 0.6, 1.0);
+const PATH_COLOUR: Colour = Colour::new(1.0, 0.85, 0.2);
+const VIEW_COLOUR: Colour = Colour::new(0.3, 1.0, 0.4);
+const GHOST_COLOUR: Colour = Colour::new(0.8, 0.5, 1.0);
+
+/// How opaque the ghost's sprites are, on top of their own alpha.
+const GHOST_ALPHA: f64 = 0.5;
+
+/// Masks covering more room pixels than this are skipped, since each pixel has to be checked every frame.
+const MASK_PIXEL_LIMIT: i64 = 512 * 512;
+
+/// Which parts of the hitbox overlay get drawn on top of the game view.
+#[derive(Deserialize, Serialize)]
+pub struct OverlayConfig {
+    pub enabled: bool,
+    pub bboxes: bool,
+    pub masks: bool,
+    pub paths: bool,
+    pub views: bool,
+
+    /// Object indices whose instances are left out of the overlay
+    pub hidden_objects: Vec<i32>,
+}
+
+impl Default for OverlayConfig {
+    fn default() -> Self {
+        Self { enabled: false, bboxes: true, masks: true, paths: true, views: true, hidden_objects: Vec::new() }
+    }
+}
+
+/// Somewhere the room gets drawn to in the game view: either a view's port, or the whole thing.
+struct Port<'a> {
+    view: Option<&'a View>,
+    origin: [f32; 2],
+}
+
+impl Port<'_> {
+    /// Transforms a point in room-space to the point on screen where it's drawn.
+    /// This is View::untransform_point without the rounding, so shapes line up with the pixels they cover.
+    fn to_screen(&self, x: f64, y: f64) -> [f32; 2] {
+        let (x, y) = match self.view {
+            Some(view) => {
+                let src_x = f64::from(view.source_x);
+                let src_y = f64::from(view.source_y);
+                let src_w = f64::from(view.source_w);
+                let src_h = f64::from(view.source_h);
+                let (mut x, mut y) = (x, y);
+                let angle = -view.angle.to_radians();
+                util::rotate_around(
+                    &mut x,
+                    &mut y,
+                    src_x + (src_w / 2.0),
+                    src_y + (src_h / 2.0),
+                    angle.sin().into(),
+                    angle.cos().into(),
+                );
+                (
+                    f64::from(view.port_x) + (f64::from(view.port_w) * (x - src_x) / src_w),
+                    f64::from(view.port_y) + (f64::from(view.port_h) * (y - src_y) / src_h),
+                )
+            },
+            None => (x, y),
+        };
+        [self.origin[0] + x as f32, self.origin[1] + y as f32]
+    }
+
+    /// Gets the corners of a rectangle in room-space, which might not be a rectangle on screen if the view is rotated.
+    fn quad(&self, left: f64, top: f64, right: f64, bottom: f64) -> Vec<[f32; 2]> {
+        vec![
+            self.to_screen(left, top),
+            self.to_screen(right, top),
+            self.to_screen(right, bottom),
+            self.to_screen(left, bottom),
+        ]
+    }
+}
+
+fn colour(colour: Colour, alpha: u8) -> ImColor32 {
+    let mut colour: ImColor32 = colour.into();
+    colour.a = alpha;
+    colour
+}
+
+/// Runs something for each place the room gets drawn in the game view, clipped to that place.
+fn for_each_port(frame: &imgui::Ui, game: &Game, origin: [f32; 2], size: (u32, u32), f: impl Fn(&DrawListMut, &Port)) {
+    let mut ports = Vec::new();
+    if game.room.views_enabled {
+        for view in game.room.views.iter().filter(|v| v.visible) {
+            ports.push(Port { view: Some(view), origin });
+        }
+    } else {
+        ports.push(Port { view: None, origin });
+    }
+
+    let draw_list = frame.get_window_draw_list();
+    for port in &ports {
+        let (clip_min, clip_max) = match port.view {
+            Some(view) => (
+                [origin[0] + view.port_x as f32, origin[1] + view.port_y as f32],
+                [
+                    origin[0] + (view.port_x + view.port_w as i32) as f32,
+                    origin[1] + (view.port_y + view.port_h as i32) as f32,
+                ],
+            ),
+            None => (origin, [origin[0] + size.0 as f32, origin[1] + size.1 as f32]),
+        };
+        draw_list.with_clip_rect_intersect(clip_min, clip_max, || f(&draw_list, port));
+    }
+}
+
+/// Draws the overlay on top of the game view, whose top-left corner is at `origin` on screen.
+/// This only reads from the game and draws with imgui, so the game's own renderer state is left alone.
+pub fn draw(frame: &imgui::Ui, game: &Game, config: &OverlayConfig, origin: [f32; 2], size: (u32, u32)) {
+    for_each_port(frame, game, origin, size, |draw_list, port| {
+        let outline = |mut points: Vec<[f32; 2]>, col: ImColor32| {
+            points.push(points[0]);
+            draw_list.add_polyline(points, col).build();
+        };
+
+        if config.views && game.room.views_enabled {
+            for view in game.room.views.iter().filter(|v| v.visible) {
+                let left = f64::from(view.source_x);
+                let top = f64::from(view.source_y);
+                let right = left + f64::from(view.source_w);
+                let bottom = top + f64::from(view.source_h);
+                outline(port.quad(left, top, right, bottom), colour(VIEW_COLOUR, 255));
+                if view.follow_target >= 0 {
+                    let hborder = f64::from(view.follow_hborder);
+                    let vborder = f64::from(view.follow_vborder);
+                    outline(
+                        port.quad(left + hborder, top + vborder, right - hborder, bottom - vborder),
+                        colour(VIEW_COLOUR, 120),
+                    );
+                }
+            }
+        }
+
+        let mut iter = game.room.instance_list.iter_by_drawing();
+        while let Some(handle) = iter.next(&game.room.instance_list) {
+            let instance = game.room.instance_list.get(handle);
+            if config.hidden_objects.contains(&instance.object_index.get()) {
+                continue
+            }
+
+            if config.paths {
+                if let Some(path) = game.assets.paths.get_asset(instance.path_index.get()) {
+                    // Same transformation as Game::apply_speeds uses to move the instance along its path
+                    let angle = instance.path_orientation.get().to_radians();
+                    let (sin, cos) = (angle.sin().into_inner(), angle.cos().into_inner());
+                    let scale = instance.path_scale.get();
+                    let points = path
+                        .control_nodes
+                        .iter()
+                        .map(|node| {
+                            let mut x = ((node.point.x - path.start.x) * scale).into_inner();
+                            let mut y = ((node.point.y - path.start.y) * scale).into_inner();
+                            util::rotate_around_center(&mut x, &mut y, sin, cos);
+                            port.to_screen(
+                                x + instance.path_xstart.get().into_inner(),
+                                y + instance.path_ystart.get().into_inner(),
+                            )
+                        })
+                        .collect::<Vec<_>>();
+                    if points.len() > 1 {
+                        draw_list.add_polyline(points, colour(PATH_COLOUR, 200)).build();
+                    }
+                }
+            }
+
+            // Instances without a mask don't collide with anything, so there's nothing to show
+            let sprite = match game.get_instance_mask_sprite(handle) {
+                Some(sprite) => sprite,
+                None => continue,
+            };
+            // Work out a stale bbox separately, as updating it here would change what gets saved in savestates
+            let (left, top, right, bottom) = if instance.bbox_is_stale.get() {
+                instance.compute_bbox(sprite)
+            } else {
+                (
+                    instance.bbox_left.get(),
+                    instance.bbox_top.get(),
+                    instance.bbox_right.get(),
+                    instance.bbox_bottom.get(),
+                )
+            };
+
+            if config.masks
+                && i64::from(right - left + 1) * i64::from(bottom - top + 1) <= MASK_PIXEL_LIMIT
+            {
+                // Pick the collider and check each pixel the same way Game::check_collision does
+                let collider = if sprite.per_frame_colliders {
+                    let count = sprite.colliders.len() as i32;
+                    instance
+                        .image_index
+                        .get()
+                        .floor()
+                        .to_i32()
+                        .checked_rem_euclid(count)
+                        .and_then(|i| sprite.colliders.get(i as usize))
+                } else {
+                    sprite.colliders.first()
+                };
+                if let Some(collider) = collider {
+                    let x = instance.x.get().round().to_i32();
+                    let y = instance.y.get().round().to_i32();
+                    let angle = instance.image_angle.get().to_radians();
+                    let (sin, cos) = (angle.sin().into_inner(), angle.cos().into_inner());
+                    let xscale = instance.image_xscale.get();
+                    let yscale = instance.image_yscale.get();
+                    let col = colour(MASK_COLOUR, 110);
+                    for py in top..=bottom {
+                        // Fill in runs of solid pixels rather than every pixel on its own
+                        let mut run_start = None;
+                        for px in left..=right + 1 {
+                            let solid = px <= right
+                                && collider.check_collision_point_precise(
+                                    px,
+                                    py,
+                                    x,
+                                    y,
+                                    sprite.origin_x,
+                                    sprite.origin_y,
+                                    xscale,
+                                    yscale,
+                                    sin,
+                                    cos,
+                                );
+                            match (solid, run_start) {
+                                (true, None) => run_start = Some(px),
+                                (false, Some(start)) => {
+                                    let quad = port.quad(
+                                        f64::from(start),
+                                        f64::from(py),
+                                        f64::from(px),
+                                        f64::from(py + 1),
+                                    );
+                                    draw_list.add_polyline(quad, col).filled(true).build();
+                                    run_start = None;
+                                },
+                                _ => (),
+                            }
+                        }
+                    }
+                }
+            }
+
+            if config.bboxes {
+                let quad = port.quad(
+                    f64::from(left),
+                    f64::from(top),
+                    f64::from(right + 1),
+                    f64::from(bottom + 1),
+                );
+                outline(quad, colour(BBOX_COLOUR, 255));
+            }
+        }
+    });
+}
+
+/// Draws the ghost's instances as they were on the same frame as the game, and how far ahead it is.
+/// Their sprites are drawn see-through on top of the game view, with each instance's scale, angle and blend colour.
+pub fn draw_ghost(frame: &imgui::Ui, game: &Game, ghost: &Ghost, frames: usize, origin: [f32; 2], size: (u32, u32)) {
+    if let Some(ghost_frame) = ghost.frame(frames).filter(|f| f.room == game.room.id) {
+        for_each_port(frame, game, origin, size, |draw_list, port| {
+            for instance in &ghost_frame.instances {
+                let sprite = match game.assets.sprites.get_asset(instance.sprite) {
+                    Some(sprite) => sprite,
+                    None => continue,
+                };
+                let atlas_ref = match sprite.get_atlas_ref(instance.image_index.floor() as i32) {
+                    Some(atlas_ref) => atlas_ref,
+                    None => continue,
+                };
+                let left = -f64::from(sprite.origin_x);
+                let top = -f64::from(sprite.origin_y);
+                let right = left + f64::from(sprite.width);
+                let bottom = top + f64::from(sprite.height);
+                let angle = -instance.angle.to_radians();
+                let corner = |x: f64, y: f64| {
+                    let (mut x, mut y) = (x * instance.xscale, y * instance.yscale);
+                    util::rotate_around_center(&mut x, &mut y, angle.sin(), angle.cos());
+                    port.to_screen(instance.x + x, instance.y + y)
+                };
+                let alpha = (instance.alpha * GHOST_ALPHA * 255.0).clamp(0.0, 255.0) as u32;
+                draw_list
+                    .add_image_quad(
+                        TextureId::new(atlas_ref.0 as usize),
+                        corner(left, top),
+                        corner(right, top),
+                        corner(right, bottom),
+                        corner(left, bottom),
+                    )
+                    .col((instance.blend as u32 & 0xFFFFFF) | (alpha << 24))
+                    .build();
+            }
+        });
+    }
+
+    if let Some(comparison) = ghost.compare(game, frames) {
+        let text = comparison.describe();
+        let [width, height] = frame.calc_text_size(&text);
+        let min = [origin[0] + 4.0, origin[1] + 4.0];
+        let max = [min[0] + width + 4.0, min[1] + height + 2.0];
+        let draw_list = frame.get_window_draw_list();
+        draw_list.add_rect(min, max, colour(Colour::new(0.0, 0.0, 0.0), 160)).filled(true).build();
+        draw_list.add_text([min[0] + 2.0, min[1] + 1.0], colour(GHOST_COLOUR, 255), &text);
+    }
+}
+
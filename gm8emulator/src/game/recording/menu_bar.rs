@@ -1,6 +1,7 @@
 use crate::game::recording::{
-    console::ConsoleWindow, input_edit::InputEditWindow, keybinds::KeybindWindow, macro_window::MacroWindow,
-    window::Openable, UIState,
+    console::ConsoleWindow, debugger_window::DebuggerWindow, ghost_window::GhostWindow, input_edit::InputEditWindow,
+    keybinds::KeybindWindow, macro_window::MacroWindow, replay_edit::ReplayEditWindow, run_until::RunUntilWindow,
+    search_window::SearchWindow, trace_window::TraceWindow, watch_window::WatchWindow, window::Openable, UIState,
 };
 
 impl UIState<'_> {
@@ -70,6 +71,13 @@ impl UIState<'_> {
                     openable! {
                         single KeybindWindow,
                         single InputEditWindow,
+                        single WatchWindow,
+                        single RunUntilWindow,
+                        single DebuggerWindow,
+                        single TraceWindow,
+                        single SearchWindow,
+                        single ReplayEditWindow,
+                        single GhostWindow,
                         multi ConsoleWindow,
                         multi MacroWindow,
                     }
@@ -80,6 +88,48 @@ impl UIState<'_> {
                 window_menu_token.end();
             }
 
+            if let Some(overlay_menu_token) = frame.begin_menu("Overlay") {
+                let overlay = &mut self.config.overlay;
+                let mut changed = frame.menu_item_config("Show Hitbox Overlay").build_with_ref(&mut overlay.enabled);
+                frame.separator();
+                changed |= frame.menu_item_config("Bounding Boxes").build_with_ref(&mut overlay.bboxes);
+                changed |= frame.menu_item_config("Collision Masks").build_with_ref(&mut overlay.masks);
+                changed |= frame.menu_item_config("Paths").build_with_ref(&mut overlay.paths);
+                changed |= frame.menu_item_config("Views").build_with_ref(&mut overlay.views);
+
+                if let Some(objects_menu_token) = frame.begin_menu("Objects") {
+                    let objects = &self.game.assets.objects;
+                    if frame.menu_item("Show All") {
+                        overlay.hidden_objects.clear();
+                        changed = true;
+                    }
+                    if frame.menu_item("Hide All") {
+                        overlay.hidden_objects = (0..objects.len() as i32).collect();
+                        changed = true;
+                    }
+                    frame.separator();
+                    let objects = objects.iter().enumerate().filter_map(|(i, o)| o.as_ref().map(|o| (i as i32, o)));
+                    for (i, object) in objects {
+                        let label = format!("{}##overlayobject{}", object.name, i);
+                        let mut shown = !overlay.hidden_objects.contains(&i);
+                        if frame.menu_item_config(label).build_with_ref(&mut shown) {
+                            if shown {
+                                overlay.hidden_objects.retain(|x| *x != i);
+                            } else {
+                                overlay.hidden_objects.push(i);
+                            }
+                            changed = true;
+                        }
+                    }
+                    objects_menu_token.end();
+                }
+
+                if changed {
+                    self.config.save();
+                }
+                overlay_menu_token.end();
+            }
+
             main_menu_bar_token.end();
         }
         true
@@ -0,0 +1,231 @@
+use crate::{
+    game::{
+        recording::window::{EmulatorContext, Openable, Window},
+        replay::Replay,
+    },
+    imgui_utils::UiCustomFunction,
+    types::Colour,
+};
+use std::{ops::Range, path::PathBuf};
+
+const ERROR_COLOUR: Colour = Colour::new(1.0, 0.5, 0.5);
+const SUCCESS_COLOUR: Colour = Colour::new(0.5, 1.0, 0.5);
+
+const OPERATIONS: [&str; 3] = ["Append replay", "Splice from replay", "Shift inputs"];
+const APPEND: usize = 0;
+const SPLICE: usize = 1;
+
+/// How an edit's going after it's been made.
+enum Status {
+    None,
+    Error(String),
+    /// Playing the edited replay back up to the given frame to check that it doesn't desync
+    Checking(usize),
+    Desynced(String),
+    Valid(usize),
+}
+
+pub struct ReplayEditWindow {
+    operation: usize,
+    other_path: String,
+    other: Option<Replay>,
+
+    // Frames are shown from 0 like everywhere else in the UI, and ranges include the end frame
+    at: i32,
+    source_start: i32,
+    source_end: i32,
+    dest_start: i32,
+    dest_end: i32,
+    offset: i32,
+
+    status: Status,
+    is_open: bool,
+}
+
+impl Openable<Self> for ReplayEditWindow {
+    fn window_name() -> &'static str {
+        "Replay Editor"
+    }
+
+    fn open(_id: usize) -> Self {
+        Self::new()
+    }
+}
+
+impl Window for ReplayEditWindow {
+    fn stored_kind(&self) -> Option<super::WindowKind> {
+        Some(super::WindowKind::ReplayEditor)
+    }
+
+    fn name(&self) -> String {
+        "Replay Editor".into()
+    }
+
+    fn show_window(&mut self, info: &mut EmulatorContext) {
+        self.update_status(info);
+
+        let mut is_open = self.is_open;
+        info.frame
+            .window(self.name())
+            .opened(&mut is_open)
+            .position([150.0, 150.0], imgui::Condition::FirstUseEver)
+            .size([400.0, 300.0], imgui::Condition::FirstUseEver)
+            .build(|| {
+                let frame = info.frame;
+                frame.combo_simple_string("Operation", &mut self.operation, &OPERATIONS);
+                if self.operation == APPEND || self.operation == SPLICE {
+                    frame.input_text("Other replay", &mut self.other_path).build();
+                    if frame.is_item_focused() {
+                        info.keybindings.disable_bindings();
+                    }
+                    frame.same_line();
+                    if frame.button("Load") {
+                        self.load_other();
+                    }
+                    match &self.other {
+                        Some(other) => frame.text(format!("Loaded {} frames", other.frame_count())),
+                        None => frame.text_disabled("No replay loaded"),
+                    }
+                }
+
+                let mut int_input = |label: &str, value: &mut i32| {
+                    frame.input_int(label, value).build();
+                    if frame.is_item_focused() {
+                        info.keybindings.disable_bindings();
+                    }
+                };
+                match self.operation {
+                    APPEND => int_input("At frame", &mut self.at),
+                    SPLICE => {
+                        int_input("First frame to copy", &mut self.source_start);
+                        int_input("Last frame to copy", &mut self.source_end);
+                        int_input("First frame to replace", &mut self.dest_start);
+                        int_input("Last frame to replace", &mut self.dest_end);
+                    },
+                    _ => {
+                        int_input("First frame", &mut self.source_start);
+                        int_input("Last frame", &mut self.source_end);
+                        int_input("Frames to shift by", &mut self.offset);
+                    },
+                }
+
+                if frame.button("Apply and check") {
+                    self.apply(info);
+                }
+                frame.text_wrapped(
+                    "Only frames from the current one onwards can be changed. The edited replay gets played back in \
+                     read-only mode to check it doesn't desync.",
+                );
+
+                match &self.status {
+                    Status::None => (),
+                    Status::Error(error) => frame.coloured_text(error, ERROR_COLOUR),
+                    Status::Checking(end) => {
+                        frame.text(format!("Checking frame {} of {}...", info.config.current_frame, end))
+                    },
+                    Status::Desynced(error) => frame.coloured_text(error, ERROR_COLOUR),
+                    Status::Valid(end) => frame.coloured_text(
+                        &format!("Played to the end on frame {} without desyncing", end),
+                        SUCCESS_COLOUR,
+                    ),
+                }
+            });
+        self.is_open = is_open;
+    }
+
+    fn is_open(&self) -> bool {
+        self.is_open
+    }
+}
+
+impl ReplayEditWindow {
+    pub fn new() -> Self {
+        Self {
+            operation: APPEND,
+            other_path: String::new(),
+            other: None,
+            at: 0,
+            source_start: 0,
+            source_end: 0,
+            dest_start: 0,
+            dest_end: 0,
+            offset: 0,
+            status: Status::None,
+            is_open: true,
+        }
+    }
+
+    fn load_other(&mut self) {
+        match Replay::from_file(&PathBuf::from(&self.other_path)) {
+            Ok(replay) => {
+                self.other = Some(replay);
+                self.status = Status::None;
+            },
+            Err(e) => {
+                self.other = None;
+                self.status = Status::Error(format!("Couldn't load {}: {:?}", self.other_path, e));
+            },
+        }
+    }
+
+    /// Makes the edit on the replay, then starts playing it back from the current frame.
+    fn apply(&mut self, info: &mut EmulatorContext) {
+        let frame_number = |x: i32| usize::try_from(x).map_err(|_| format!("Invalid frame number {}", x));
+        // The UI's ranges include their last frame, but the replay's don't
+        let range =
+            |start: i32, end: i32| -> Result<Range<usize>, String> { Ok(frame_number(start)?..frame_number(end)? + 1) };
+
+        let mut replay = info.replay.clone();
+        let result = match self.operation {
+            APPEND | SPLICE if self.other.is_none() => Err("Load the other replay first".into()),
+            APPEND => frame_number(self.at)
+                .and_then(|at| replay.append(self.other.as_ref().unwrap(), at).map(|()| at).map_err(|e| e.to_string())),
+            SPLICE => range(self.source_start, self.source_end).and_then(|source| {
+                let dest = range(self.dest_start, self.dest_end)?;
+                let first = dest.start;
+                replay.splice(self.other.as_ref().unwrap(), source, dest).map(|()| first).map_err(|e| e.to_string())
+            }),
+            _ => range(self.source_start, self.source_end).and_then(|frames| {
+                let first = (frames.start as isize + self.offset as isize).max(0) as usize;
+                let first = first.min(frames.start);
+                replay.shift_inputs(frames, self.offset as isize).map(|()| first).map_err(|e| e.to_string())
+            }),
+        };
+
+        // Frames before the current one have already been run, so they couldn't be checked
+        match result {
+            Ok(first) if first < info.config.current_frame => {
+                self.status = Status::Error(format!(
+                    "This changes frame {}, which is before the current frame. Load an earlier savestate first.",
+                    first,
+                ));
+            },
+            Ok(_) => {
+                *info.replay = replay;
+                info.config.is_read_only = true;
+                if let Some(e) = info.config.save() {
+                    *info.err_string = Some(e);
+                }
+                *info.run_until_frame = Some(info.replay.frame_count());
+                self.status = Status::Checking(info.replay.frame_count());
+            },
+            Err(e) => self.status = Status::Error(e),
+        }
+    }
+
+    /// Keeps track of the edited replay while it's being played back.
+    fn update_status(&mut self, info: &mut EmulatorContext) {
+        if let Status::Checking(end) = self.status {
+            let current_frame = info.config.current_frame;
+            if let Some(error) = info.err_string.as_ref() {
+                self.status = Status::Desynced(format!("Stopped on frame {}: {}", current_frame, error));
+            } else if !*info.game_running {
+                self.status = Status::Desynced(format!("The game crashed on frame {}", current_frame));
+            } else if current_frame >= end {
+                self.status = Status::Valid(end);
+            } else if info.run_until_frame.is_none() {
+                self.status = Status::Error(format!("Stopped checking on frame {}", current_frame));
+            }
+        }
+    }
+}
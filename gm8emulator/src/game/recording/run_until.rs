@@ -0,0 +1,227 @@
+use crate::{
+    game::{
+        recording::window::{EmulatorContext, Openable, Window},
+        replay::Replay,
+        savestate::SaveState,
+        Game, GetAsset,
+    },
+    gml::{runtime::Node, Context},
+    render::RendererState,
+    types::ID,
+};
+
+/// What "advance until" is waiting for. While it's running, the control window keeps advancing
+/// with the current input, and checks after every frame whether it's time to stop.
+pub struct RunUntil {
+    /// Whether frames are currently being advanced
+    pub running: bool,
+
+    /// Why it stopped last time, or why it couldn't start
+    pub status: String,
+
+    condition: Option<Node>,
+    last_frame: usize,
+    room_change: bool,
+
+    // State from before the frame that's being advanced, to compare against
+    gml_context: Context,
+    room: i32,
+}
+
+impl RunUntil {
+    pub fn new() -> Self {
+        Self {
+            running: false,
+            status: String::new(),
+            condition: None,
+            last_frame: 0,
+            room_change: false,
+            gml_context: Context::with_single_instance(0),
+            room: 0,
+        }
+    }
+
+    /// Stops advancing, if it was running, with a reason to show the user.
+    pub fn stop(&mut self, game: &mut Game, status: String) {
+        if self.running {
+            self.running = false;
+            self.status = status;
+            game.script_breakpoint = None;
+            game.create_breakpoint = false;
+            game.destroy_breakpoint = false;
+        }
+    }
+
+    /// Remembers what the next frame will be compared to. Call this right before advancing.
+    pub fn before_frame(&mut self, game: &mut Game) {
+        self.room = game.room.id;
+        game.script_breakpoint_hit = false;
+        game.created_instance = None;
+        game.destroyed_instance = None;
+    }
+
+    /// Checks whether anything that's being waited for happened on the frame that was just advanced,
+    /// and stops if so. `frame` is the frame that's now current.
+    pub fn after_frame(
+        &mut self,
+        game: &mut Game,
+        renderer_state: &mut RendererState,
+        clean_state: bool,
+        frame: usize,
+    ) {
+        if !self.running {
+            return
+        }
+        let reason = if game.script_breakpoint_hit {
+            let script = game.script_breakpoint.and_then(|id| game.assets.scripts.get_asset(id));
+            Some(format!("{} was called", script.map(|s| s.name.to_string()).unwrap_or_default()))
+        } else if self.room_change && game.room.id != self.room {
+            let room = game.assets.rooms.get_asset(game.room.id);
+            Some(format!("the room changed to {}", room.map(|r| r.name.to_string()).unwrap_or_default()))
+        } else if let Some(id) = game.created_instance {
+            Some(format!("instance {} was created", id))
+        } else if let Some(id) = game.destroyed_instance {
+            Some(format!("instance {} was destroyed", id))
+        } else if let Some(node) = &self.condition {
+            // Evaluating the condition mustn't change the game, so put everything back how it was afterwards
+            let state = SaveState::from(game, Replay::new(0, 0), renderer_state.clone(), clean_state);
+            let result = game.eval(node, &mut self.gml_context);
+            let (_, restored_renderer_state) = state.load_into(game);
+            *renderer_state = restored_renderer_state;
+            match result {
+                Ok(value) if value.is_truthy() => Some("the condition became true".into()),
+                Ok(_) => None,
+                Err(e) => Some(format!("the condition couldn't be evaluated: {}", e)),
+            }
+        } else {
+            None
+        };
+
+        if let Some(reason) = reason {
+            self.stop(game, format!("Stopped on frame {}: {}", frame, reason));
+        } else if frame >= self.last_frame {
+            self.stop(game, format!("Stopped on frame {}: the frame limit was reached", frame));
+        }
+    }
+}
+
+pub struct RunUntilWindow {
+    condition: String,
+    max_frames: i32,
+    room_change: bool,
+    instance_created: bool,
+    instance_destroyed: bool,
+    script: String,
+    is_open: bool,
+}
+
+impl Openable<Self> for RunUntilWindow {
+    fn window_name() -> &'static str {
+        "Advance Until"
+    }
+
+    fn open(_id: usize) -> Self {
+        Self::new()
+    }
+}
+
+impl Window for RunUntilWindow {
+    fn stored_kind(&self) -> Option<super::WindowKind> {
+        Some(super::WindowKind::RunUntil)
+    }
+
+    fn name(&self) -> String {
+        "Advance Until".into()
+    }
+
+    fn show_window(&mut self, info: &mut EmulatorContext) {
+        let EmulatorContext { config, frame, game, keybindings, run_until, .. } = info;
+
+        // Starting borrows the whole window, so the open flag can't be borrowed alongside it
+        let mut is_open = self.is_open;
+        frame
+            .window(self.name())
+            .opened(&mut is_open)
+            .position([100.0, 100.0], imgui::Condition::FirstUseEver)
+            .size([300.0, 240.0], imgui::Condition::FirstUseEver)
+            .build(|| {
+                frame.text("Advance until...");
+                frame.input_text("GML condition", &mut self.condition).build();
+                if frame.is_item_focused() {
+                    keybindings.disable_bindings();
+                }
+                frame.input_text("Script called", &mut self.script).build();
+                if frame.is_item_focused() {
+                    keybindings.disable_bindings();
+                }
+                frame.checkbox("Room changes", &mut self.room_change);
+                frame.checkbox("Instance created", &mut self.instance_created);
+                frame.checkbox("Instance destroyed", &mut self.instance_destroyed);
+                frame.input_int("Max frames", &mut self.max_frames).build();
+                if frame.is_item_focused() {
+                    keybindings.disable_bindings();
+                }
+                self.max_frames = self.max_frames.max(1);
+
+                let content_width = frame.content_region_avail()[0];
+                if run_until.running {
+                    if frame.button_with_size("Stop", [content_width, 20.0]) {
+                        let status = format!("Stopped on frame {}", config.current_frame);
+                        run_until.stop(game, status);
+                    }
+                } else if frame.button_with_size("Start", [content_width, 20.0]) {
+                    match self.start(run_until, game, config.current_frame) {
+                        Ok(()) => run_until.status = "Running...".into(),
+                        Err(e) => run_until.status = e,
+                    }
+                }
+                frame.text_wrapped(&run_until.status);
+            });
+        self.is_open = is_open;
+    }
+
+    fn is_open(&self) -> bool {
+        self.is_open
+    }
+}
+
+impl RunUntilWindow {
+    pub fn new() -> Self {
+        Self {
+            condition: String::new(),
+            max_frames: 1000,
+            room_change: false,
+            instance_created: false,
+            instance_destroyed: false,
+            script: String::new(),
+            is_open: true,
+        }
+    }
+
+    fn start(&self, run_until: &mut RunUntil, game: &mut Game, current_frame: usize) -> Result<(), String> {
+        let condition = match self.condition.trim() {
+            "" => None,
+            condition => Some(
+                game.compiler
+                    .compile_expression(condition.as_bytes())
+                    .map_err(|e| format!("Invalid condition: {}", e))?,
+            ),
+        };
+        let script = match self.script.trim() {
+            "" => None,
+            name => {
+                let id = game.compiler.get_script_id(name.as_bytes());
+                Some(id.ok_or_else(|| format!("No script named {}", name))?)
+            },
+        };
+
+        run_until.condition = condition;
+        run_until.last_frame = current_frame + self.max_frames as usize;
+        run_until.room_change = self.room_change;
+        run_until.running = true;
+        game.script_breakpoint = script.map(|id| id as ID);
+        game.create_breakpoint = self.instance_created;
+        game.destroy_breakpoint = self.instance_destroyed;
+        Ok(())
+    }
+}
@@ -0,0 +1,446 @@
+use crate::{
+    game::{
+        recording::window::{EmulatorContext, Openable, Window},
+        Game, GetAsset,
+    },
+    gml::{mappings, InstanceVariable, Value},
+    imgui_utils::UiCustomFunction,
+    instance::Field,
+    types::{Colour, ID},
+};
+use imgui::{TableColumnSetup, TableFlags};
+use std::collections::HashMap;
+
+const CHANGED_COLOUR: Colour = Colour::new(1.0, 0.85, 0.3);
+const ERROR_COLOUR: Colour = Colour::new(1.0, 0.5, 0.5);
+
+/// How many searches can be undone.
+const UNDO_LENGTH: usize = 16;
+
+/// Somewhere a variable can be kept. Instances are found by their ID, since it stays the same across savestates.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+enum Location {
+    Global { field: usize, index: u32 },
+    GlobalVar { var: InstanceVariable, index: u32 },
+    Field { instance: ID, field: usize, index: u32 },
+    Alarm { instance: ID, alarm: u32 },
+}
+
+impl Location {
+    /// Puts globals first, then each instance's variables together.
+    fn sort_key(&self) -> (u8, ID, usize, u32) {
+        match *self {
+            Self::Global { field, index } => (0, 0, field, index),
+            Self::GlobalVar { var, index } => (1, 0, var as usize, index),
+            Self::Field { instance, field, index } => (2, instance, field, index),
+            Self::Alarm { instance, alarm } => (2, instance, usize::MAX, alarm),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Condition {
+    Changed,
+    Unchanged,
+    Increased,
+    Decreased,
+    ChangedBy,
+    EqualTo,
+    NotEqualTo,
+    GreaterThan,
+    LessThan,
+}
+
+impl Condition {
+    const ALL: [Self; 9] = [
+        Self::Changed,
+        Self::Unchanged,
+        Self::Increased,
+        Self::Decreased,
+        Self::ChangedBy,
+        Self::EqualTo,
+        Self::NotEqualTo,
+        Self::GreaterThan,
+        Self::LessThan,
+    ];
+    const NAMES: [&'static str; 9] = [
+        "changed",
+        "unchanged",
+        "increased",
+        "decreased",
+        "changed by",
+        "equal to",
+        "not equal to",
+        "greater than",
+        "less than",
+    ];
+
+    /// Whether the condition needs a value to compare against.
+    fn has_operand(self) -> bool {
+        !matches!(self, Self::Changed | Self::Unchanged | Self::Increased | Self::Decreased)
+    }
+
+    fn test(self, old: &Value, new: &Value, operand: &Value) -> bool {
+        let reals = |a: &Value, b: &Value| a.as_real().zip(b.as_real()).map(|(a, b)| (a.into_inner(), b.into_inner()));
+        match self {
+            Self::Changed => !old.almost_equals(new),
+            Self::Unchanged => old.almost_equals(new),
+            Self::Increased => reals(old, new).map(|(a, b)| b > a).unwrap_or(false),
+            Self::Decreased => reals(old, new).map(|(a, b)| b < a).unwrap_or(false),
+            Self::ChangedBy => match (reals(old, new), operand.as_real()) {
+                (Some((a, b)), Some(by)) => Value::from(b - a).almost_equals(&by.into()),
+                _ => false,
+            },
+            Self::EqualTo => new.almost_equals(operand),
+            Self::NotEqualTo => !new.almost_equals(operand),
+            Self::GreaterThan => reals(new, operand).map(|(a, b)| a > b).unwrap_or(false),
+            Self::LessThan => reals(new, operand).map(|(a, b)| a < b).unwrap_or(false),
+        }
+    }
+}
+
+pub struct SearchWindow {
+    /// Every variable that's still in the running, with its value as of the last search
+    candidates: Option<Vec<(Location, Value)>>,
+    undo: Vec<Vec<(Location, Value)>>,
+    searched_frame: usize,
+
+    condition: usize,
+    operand: String,
+    error: Option<String>,
+
+    is_open: bool,
+}
+
+impl Openable<Self> for SearchWindow {
+    fn window_name() -> &'static str {
+        "Variable Search"
+    }
+
+    fn open(_id: usize) -> Self {
+        Self::new()
+    }
+}
+
+impl Window for SearchWindow {
+    fn stored_kind(&self) -> Option<super::WindowKind> {
+        Some(super::WindowKind::Search)
+    }
+
+    fn name(&self) -> String {
+        "Variable Search".into()
+    }
+
+    fn show_window(&mut self, info: &mut EmulatorContext) {
+        let EmulatorContext { config, frame, game, keybindings, .. } = info;
+
+        // Searching borrows the whole window, so the open flag can't be borrowed alongside it
+        let mut is_open = self.is_open;
+        frame
+            .window(self.name())
+            .opened(&mut is_open)
+            .position([520.0, 100.0], imgui::Condition::FirstUseEver)
+            .size([450.0, 500.0], imgui::Condition::FirstUseEver)
+            .build(|| {
+                frame.text_wrapped(
+                    "Take a snapshot of every variable, then narrow them down as the game goes on, \
+                    such as by advancing a frame or loading a savestate and keeping the ones which decreased.",
+                );
+                if frame.button("New search") {
+                    let mut candidates = snapshot(game).into_iter().collect::<Vec<_>>();
+                    candidates.sort_by_key(|(location, _)| location.sort_key());
+                    self.candidates = Some(candidates);
+                    self.undo.clear();
+                    self.searched_frame = config.current_frame;
+                    self.error = None;
+                }
+
+                if self.candidates.is_some() {
+                    frame.same_line();
+                    if frame.button("Clear") {
+                        self.candidates = None;
+                        self.undo.clear();
+                    }
+                    if !self.undo.is_empty() {
+                        frame.same_line();
+                        if frame.button("Undo") {
+                            self.candidates = self.undo.pop();
+                        }
+                    }
+                }
+
+                let candidates = match &self.candidates {
+                    Some(candidates) => candidates,
+                    None => return,
+                };
+
+                frame.separator();
+                frame.text("Keep the variables which have");
+                frame.combo_simple_string("##searchcondition", &mut self.condition, &Condition::NAMES);
+                let condition = Condition::ALL[self.condition];
+                if condition.has_operand() {
+                    frame.input_text("Value", &mut self.operand).build();
+                    if frame.is_item_focused() {
+                        keybindings.disable_bindings();
+                    }
+                }
+                frame.text(format!("since frame {}", self.searched_frame));
+                let search = frame.button("Search");
+
+                frame.text(format!("{} variables", candidates.len()));
+                if let Some(error) = &self.error {
+                    frame.coloured_text(error, ERROR_COLOUR);
+                }
+
+                frame.child_window("##searchresults").build(|| {
+                    let table = frame.begin_table_header_with_flags(
+                        "##searchtable",
+                        [
+                            TableColumnSetup::new("Variable"),
+                            TableColumnSetup::new("Object"),
+                            TableColumnSetup::new("Previous"),
+                            TableColumnSetup::new("Current"),
+                        ],
+                        TableFlags::ROW_BG | TableFlags::BORDERS,
+                    );
+                    if let Some(table) = table {
+                        let mut clipper = imgui::ListClipper::new(candidates.len() as i32).begin(frame);
+                        while clipper.step() {
+                            let shown = &candidates[clipper.display_start() as usize..clipper.display_end() as usize];
+                            for (location, old) in shown {
+                                let new = read(game, *location);
+                                frame.table_next_column();
+                                frame.text(describe(game, *location));
+                                frame.table_next_column();
+                                frame.text(object_name(game, *location));
+                                frame.table_next_column();
+                                frame.text(old.to_string());
+                                frame.table_next_column();
+                                match new {
+                                    Some(new) if new.almost_equals(old) => frame.text(new.to_string()),
+                                    Some(new) => frame.coloured_text(&new.to_string(), CHANGED_COLOUR),
+                                    None => frame.text_disabled("gone"),
+                                }
+                            }
+                        }
+                        table.end();
+                    }
+                });
+
+                if search {
+                    self.search(game, condition, config.current_frame);
+                }
+            });
+        self.is_open = is_open;
+    }
+
+    fn is_open(&self) -> bool {
+        self.is_open
+    }
+}
+
+impl SearchWindow {
+    pub fn new() -> Self {
+        Self {
+            candidates: None,
+            undo: Vec::new(),
+            searched_frame: 0,
+            condition: 0,
+            operand: String::new(),
+            error: None,
+            is_open: true,
+        }
+    }
+
+    /// Drops every candidate that doesn't meet the condition, and remembers what the rest are now.
+    fn search(&mut self, game: &Game, condition: Condition, frame: usize) {
+        let operand = if condition.has_operand() {
+            let text = self.operand.trim();
+            match text.parse::<f64>() {
+                Ok(real) => Value::from(real),
+                Err(_) if condition == Condition::EqualTo || condition == Condition::NotEqualTo => {
+                    Value::from(text.trim_matches('"'))
+                },
+                Err(_) => {
+                    self.error = Some(format!("{} isn't a number", text));
+                    return
+                },
+            }
+        } else {
+            Value::default()
+        };
+        self.error = None;
+
+        if let Some(candidates) = self.candidates.take() {
+            let current = snapshot(game);
+            let kept = candidates
+                .iter()
+                .filter_map(|(location, old)| {
+                    let new = current.get(location)?;
+                    condition.test(old, new, &operand).then(|| (*location, new.clone()))
+                })
+                .collect();
+            if self.undo.len() >= UNDO_LENGTH {
+                self.undo.remove(0);
+            }
+            self.undo.push(candidates);
+            self.candidates = Some(kept);
+            self.searched_frame = frame;
+        }
+    }
+}
+
+/// Adds every value held by a field, treating each entry in an array as its own variable.
+fn push_field(values: &mut HashMap<Location, Value>, field: &Field, location: impl Fn(u32) -> Location) {
+    match field {
+        Field::Single(value) => {
+            values.insert(location(0), value.clone());
+        },
+        Field::Array(array) => {
+            for (index, value) in array.iter() {
+                values.insert(location(*index), value.clone());
+            }
+        },
+    }
+}
+
+/// Takes a copy of every global and every active instance's fields and alarms.
+fn snapshot(game: &Game) -> HashMap<Location, Value> {
+    let mut values = HashMap::new();
+    for (field, value) in game.globals.fields.iter() {
+        push_field(&mut values, value, |index| Location::Global { field: *field, index });
+    }
+    for (var, value) in game.globals.vars.iter() {
+        push_field(&mut values, value, |index| Location::GlobalVar { var: *var, index });
+    }
+
+    let mut iter = game.room.instance_list.iter_by_drawing();
+    while let Some(handle) = iter.next(&game.room.instance_list) {
+        let instance = game.room.instance_list.get(handle);
+        let id = instance.id.get();
+        for (field, value) in instance.fields.borrow().iter() {
+            push_field(&mut values, value, |index| Location::Field { instance: id, field: *field, index });
+        }
+        for (alarm, value) in instance.alarms.borrow().iter() {
+            values.insert(Location::Alarm { instance: id, alarm: *alarm }, Value::from(*value));
+        }
+    }
+    values
+}
+
+/// Gets what's in a variable now, if it's still there.
+fn read(game: &Game, location: Location) -> Option<Value> {
+    let instance = |id: ID| game.room.instance_list.get_by_instid(id).map(|h| game.room.instance_list.get(h));
+    match location {
+        Location::Global { field, index } => game.globals.fields.get(&field)?.get(index),
+        Location::GlobalVar { var, index } => game.globals.vars.get(&var)?.get(index),
+        Location::Field { instance: id, field, index } => instance(id)?.fields.borrow().get(&field)?.get(index),
+        Location::Alarm { instance: id, alarm } => instance(id)?.alarms.borrow().get(&alarm).map(|x| Value::from(*x)),
+    }
+}
+
+/// Writes a variable's location as GML, so it can be pasted into the watch window.
+fn describe(game: &Game, location: Location) -> String {
+    let field_name = |field: usize| game.compiler.get_field_name(field).unwrap_or_else(|| field.to_string());
+    let array = |index: u32| match index {
+        0 => String::new(),
+        i if i < 32000 => format!("[{}]", i),
+        i => format!("[{}, {}]", i / 32000, i % 32000),
+    };
+    match location {
+        Location::Global { field, index } => format!("global.{}{}", field_name(field), array(index)),
+        Location::GlobalVar { var, index } => {
+            let name = mappings::INSTANCE_VARIABLES.iter().find(|(_, v)| *v == var).map(|(name, _)| *name);
+            format!("{}{}", name.unwrap_or("?unknown?"), array(index))
+        },
+        Location::Field { instance, field, index } => format!("({}).{}{}", instance, field_name(field), array(index)),
+        Location::Alarm { instance, alarm } => format!("({}).alarm[{}]", instance, alarm),
+    }
+}
+
+fn object_name(game: &Game, location: Location) -> String {
+    match location {
+        Location::Global { .. } | Location::GlobalVar { .. } => String::new(),
+        Location::Field { instance, .. } | Location::Alarm { instance, .. } => game
+            .room
+            .instance_list
+            .get_by_instid(instance)
+            .map(|handle| game.room.instance_list.get(handle).object_index.get())
+            .and_then(|object| game.assets.objects.get_asset(object))
+            .map(|object| object.name.to_string())
+            .unwrap_or_default(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test(condition: Condition, old: impl Into<Value>, new: impl Into<Value>, operand: impl Into<Value>) -> bool {
+        condition.test(&old.into(), &new.into(), &operand.into())
+    }
+
+    #[test]
+    fn changed_and_unchanged() {
+        assert!(test(Condition::Changed, 1.0, 2.0, 0.0));
+        assert!(!test(Condition::Changed, 1.0, 1.0, 0.0));
+        assert!(test(Condition::Changed, "a", "b", 0.0));
+        assert!(!test(Condition::Changed, "a", "a", 0.0));
+        // A value changing type counts as a change, even if it looks the same
+        assert!(test(Condition::Changed, 1.0, "1", 0.0));
+        assert!(test(Condition::Unchanged, 1.5, 1.5, 0.0));
+        assert!(!test(Condition::Unchanged, 1.5, 2.5, 0.0));
+        assert!(!test(Condition::Unchanged, 1.0, "1", 0.0));
+    }
+
+    #[test]
+    fn increased_and_decreased() {
+        assert!(test(Condition::Increased, 1.0, 2.0, 0.0));
+        assert!(!test(Condition::Increased, 2.0, 1.0, 0.0));
+        assert!(!test(Condition::Increased, 1.0, 1.0, 0.0));
+        assert!(test(Condition::Decreased, 2.0, -1.0, 0.0));
+        assert!(!test(Condition::Decreased, 1.0, 1.0, 0.0));
+        // Strings can't go up or down
+        assert!(!test(Condition::Increased, "a", "b", 0.0));
+        assert!(!test(Condition::Decreased, 1.0, "0", 0.0));
+    }
+
+    #[test]
+    fn changed_by() {
+        assert!(test(Condition::ChangedBy, 3.0, 5.0, 2.0));
+        assert!(test(Condition::ChangedBy, 5.0, 3.0, -2.0));
+        assert!(!test(Condition::ChangedBy, 3.0, 5.0, -2.0));
+        assert!(test(Condition::ChangedBy, 4.0, 4.0, 0.0));
+        assert!(!test(Condition::ChangedBy, "a", "b", 1.0));
+        assert!(!test(Condition::ChangedBy, 3.0, 5.0, "2"));
+    }
+
+    #[test]
+    fn equal_to() {
+        // The old value doesn't matter when comparing against the operand
+        assert!(test(Condition::EqualTo, 0.0, 7.0, 7.0));
+        assert!(!test(Condition::EqualTo, 7.0, 6.0, 7.0));
+        assert!(test(Condition::EqualTo, 0.0, "hi", "hi"));
+        assert!(!test(Condition::EqualTo, 0.0, "7", 7.0));
+        assert!(test(Condition::NotEqualTo, 7.0, 6.0, 7.0));
+        assert!(!test(Condition::NotEqualTo, 0.0, "hi", "hi"));
+        assert!(test(Condition::NotEqualTo, 0.0, "7", 7.0));
+    }
+
+    #[test]
+    fn greater_and_less_than() {
+        assert!(test(Condition::GreaterThan, 0.0, 8.0, 7.0));
+        assert!(!test(Condition::GreaterThan, 0.0, 7.0, 7.0));
+        assert!(test(Condition::LessThan, 0.0, -1.0, 0.0));
+        assert!(!test(Condition::LessThan, 0.0, 0.0, 0.0));
+        assert!(!test(Condition::GreaterThan, 0.0, "b", "a"));
+        assert!(!test(Condition::LessThan, 0.0, 1.0, "2"));
+    }
+
+    #[test]
+    fn operands() {
+        let with_operand = Condition::ALL.iter().filter(|x| x.has_operand()).count();
+        assert_eq!(with_operand, 5);
+        assert_eq!(Condition::ALL.len(), Condition::NAMES.len());
+    }
+}
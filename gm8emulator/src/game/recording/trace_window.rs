@@ -0,0 +1,246 @@
+use crate::{
+    game::{
+        recording::window::{EmulatorContext, Openable, Window},
+        trace::Trace,
+    },
+    imgui_utils::UiCustomFunction,
+    types::Colour,
+};
+use std::path::PathBuf;
+
+const DIFFERENCE_COLOUR: Colour = Colour::new(1.0, 0.5, 0.5);
+
+/// Kinds of entry in a trace, which can each be shown or hidden
+const KINDS: [&str; 7] = ["frame", "event", "script", "random", "create", "destroy", "room"];
+
+/// A trace loaded from a file, split into lines.
+struct TraceFile {
+    path: String,
+    lines: Vec<String>,
+}
+
+pub struct TraceWindow {
+    write_path: String,
+    write_error: Option<String>,
+
+    file: Option<TraceFile>,
+    other: Option<TraceFile>,
+    load_path: String,
+    compare_path: String,
+    load_error: Option<String>,
+
+    filter: String,
+    shown_kinds: [bool; KINDS.len()],
+
+    // Lines which pass the filter, and the first line that's different in the other trace
+    shown: Vec<usize>,
+    difference: Option<usize>,
+    dirty: bool,
+
+    is_open: bool,
+}
+
+impl Openable<Self> for TraceWindow {
+    fn window_name() -> &'static str {
+        "Trace"
+    }
+
+    fn open(_id: usize) -> Self {
+        Self::new()
+    }
+}
+
+impl Window for TraceWindow {
+    fn stored_kind(&self) -> Option<super::WindowKind> {
+        Some(super::WindowKind::Trace)
+    }
+
+    fn name(&self) -> String {
+        "Trace".into()
+    }
+
+    fn show_window(&mut self, info: &mut EmulatorContext) {
+        let EmulatorContext { frame, game, keybindings, project_path, .. } = info;
+
+        if self.write_path.is_empty() {
+            self.write_path = project_path.join("trace.txt").to_string_lossy().into_owned();
+            self.load_path = self.write_path.clone();
+        }
+
+        // Loading a trace borrows the whole window, so the open flag can't be borrowed alongside it
+        let mut is_open = self.is_open;
+        frame
+            .window(self.name())
+            .opened(&mut is_open)
+            .position([100.0, 100.0], imgui::Condition::FirstUseEver)
+            .size([500.0, 500.0], imgui::Condition::FirstUseEver)
+            .build(|| {
+                let mut text_input = |label: &str, text: &mut String| {
+                    frame.input_text(label, text).build();
+                    if frame.is_item_focused() {
+                        keybindings.disable_bindings();
+                    }
+                };
+
+                if game.trace.is_some() {
+                    frame.text_wrapped(format!("Writing a trace of every frame that's run to {}", self.write_path));
+                    if frame.button("Stop writing") {
+                        // Dropping the trace flushes whatever hasn't been written yet
+                        game.trace = None;
+                    }
+                } else {
+                    text_input("Output file", &mut self.write_path);
+                    if frame.button("Start writing") {
+                        match Trace::create(&PathBuf::from(&self.write_path)) {
+                            Ok(trace) => {
+                                game.trace = Some(trace);
+                                self.write_error = None;
+                            },
+                            Err(e) => self.write_error = Some(format!("Couldn't create {}: {}", self.write_path, e)),
+                        }
+                    }
+                }
+                if let Some(error) = &self.write_error {
+                    frame.coloured_text(error, DIFFERENCE_COLOUR);
+                }
+
+                frame.separator();
+                text_input("Trace", &mut self.load_path);
+                frame.same_line();
+                if frame.button("Load##loadtrace") {
+                    self.file = self.load(&self.load_path.clone());
+                    self.dirty = true;
+                }
+                text_input("Compare with", &mut self.compare_path);
+                frame.same_line();
+                if frame.button("Load##loadcompare") {
+                    self.other = self.load(&self.compare_path.clone());
+                    self.dirty = true;
+                }
+                if let Some(error) = &self.load_error {
+                    frame.coloured_text(error, DIFFERENCE_COLOUR);
+                }
+
+                if frame.input_text("Filter", &mut self.filter).build() {
+                    self.dirty = true;
+                }
+                if frame.is_item_focused() {
+                    keybindings.disable_bindings();
+                }
+                for (i, kind) in KINDS.iter().enumerate() {
+                    if i > 0 {
+                        frame.same_line();
+                    }
+                    if frame.checkbox(kind, &mut self.shown_kinds[i]) {
+                        self.dirty = true;
+                    }
+                }
+
+                if self.dirty {
+                    self.update();
+                    self.dirty = false;
+                }
+
+                let file = match &self.file {
+                    Some(file) => file,
+                    None => return,
+                };
+                if let Some(other) = &self.other {
+                    match self.difference {
+                        Some(line) => {
+                            let text = |trace: &TraceFile| {
+                                let line = trace.lines.get(line).map(String::as_str).unwrap_or("(end of trace)");
+                                format!("{}: {}", trace.path, line)
+                            };
+                            frame.coloured_text(&format!("First difference on line {}:", line + 1), DIFFERENCE_COLOUR);
+                            frame.text(text(file));
+                            frame.text(text(other));
+                        },
+                        None => frame.text("The traces are the same"),
+                    }
+                }
+                frame.text(format!("Showing {} of {} lines", self.shown.len(), file.lines.len()));
+
+                let shown = &self.shown;
+                let difference = self.difference;
+                frame.child_window("##traceentries").build(|| {
+                    let mut clipper = imgui::ListClipper::new(shown.len() as i32).begin(frame);
+                    while clipper.step() {
+                        for &line in &shown[clipper.display_start() as usize..clipper.display_end() as usize] {
+                            if Some(line) == difference {
+                                frame.coloured_text(&file.lines[line], DIFFERENCE_COLOUR);
+                            } else {
+                                frame.text(&file.lines[line]);
+                            }
+                        }
+                    }
+                });
+            });
+        self.is_open = is_open;
+    }
+
+    fn is_open(&self) -> bool {
+        self.is_open
+    }
+}
+
+impl TraceWindow {
+    pub fn new() -> Self {
+        Self {
+            write_path: String::new(),
+            write_error: None,
+            file: None,
+            other: None,
+            load_path: String::new(),
+            compare_path: String::new(),
+            load_error: None,
+            filter: String::new(),
+            shown_kinds: [true; KINDS.len()],
+            shown: Vec::new(),
+            difference: None,
+            dirty: false,
+            is_open: true,
+        }
+    }
+
+    fn load(&mut self, path: &str) -> Option<TraceFile> {
+        match std::fs::read_to_string(path) {
+            Ok(text) => {
+                self.load_error = None;
+                Some(TraceFile { path: path.into(), lines: text.lines().map(String::from).collect() })
+            },
+            Err(e) => {
+                self.load_error = Some(format!("Couldn't read {}: {}", path, e));
+                None
+            },
+        }
+    }
+
+    /// Works out which lines pass the filter, and where the two traces stop being the same.
+    fn update(&mut self) {
+        self.shown.clear();
+        self.difference = None;
+        if let Some(file) = &self.file {
+            let filter = self.filter.to_lowercase();
+            for (i, line) in file.lines.iter().enumerate() {
+                // Lines are "<frame> <kind> <details>"
+                let kind = line.split(' ').nth(1).unwrap_or("");
+                let shown_kind = KINDS.iter().position(|k| *k == kind).map(|k| self.shown_kinds[k]).unwrap_or(true);
+                if shown_kind && (filter.is_empty() || line.to_lowercase().contains(&filter)) {
+                    self.shown.push(i);
+                }
+            }
+
+            if let Some(other) = &self.other {
+                // If one trace is just longer than the other, it's different from where the shorter one ends
+                let shorter = file.lines.len().min(other.lines.len());
+                self.difference = file
+                    .lines
+                    .iter()
+                    .zip(other.lines.iter())
+                    .position(|(a, b)| a != b)
+                    .or_else(|| (file.lines.len() != other.lines.len()).then(|| shorter));
+            }
+        }
+    }
+}
@@ -0,0 +1,205 @@
+use crate::{
+    game::{
+        recording::window::{EmulatorContext, Openable, Window},
+        replay::Replay,
+        savestate::SaveState,
+        Game,
+    },
+    gml::{runtime::Node, Context, Value},
+    imgui_utils::UiCustomFunction,
+    types::Colour,
+};
+
+const CHANGED_COLOUR: Colour = Colour::new(1.0, 0.85, 0.3);
+const ERROR_COLOUR: Colour = Colour::new(1.0, 0.5, 0.5);
+
+/// A GML expression being watched, with the values it's had on recent frames.
+struct Watch {
+    expression: String,
+    node: Result<Node, String>,
+    text: String,
+    is_error: bool,
+    changed: bool,
+
+    /// (frame, value) for every frame it's been evaluated on, as long as it evaluated to a real
+    history: Vec<(usize, f32)>,
+}
+
+pub struct WatchWindow {
+    input_string: String,
+    gml_context: Context,
+    watches: Vec<Watch>,
+
+    loaded: bool,
+    last_frame: usize,
+    last_rerecords: u64,
+
+    is_open: bool,
+}
+
+impl Openable<Self> for WatchWindow {
+    fn window_name() -> &'static str {
+        "Watch"
+    }
+
+    fn open(_id: usize) -> Self {
+        Self::new()
+    }
+}
+
+impl Window for WatchWindow {
+    fn stored_kind(&self) -> Option<super::WindowKind> {
+        Some(super::WindowKind::Watch)
+    }
+
+    fn name(&self) -> String {
+        "Watch".into()
+    }
+
+    fn show_window(&mut self, info: &mut EmulatorContext) {
+        let EmulatorContext { config, frame, game, keybindings, renderer_state, clean_state, .. } = info;
+
+        // The window can't see the config until it's shown, so that's when the watches get compiled
+        if !self.loaded {
+            self.watches = config.watched_expressions.iter().map(|e| Watch::new(game, e.clone())).collect();
+            self.loaded = true;
+        }
+
+        // Re-evaluate everything once per frame advance, or when a savestate gets loaded
+        let mut evaluate = self.last_frame != config.current_frame || self.last_rerecords != config.rerecords;
+        self.last_frame = config.current_frame;
+        self.last_rerecords = config.rerecords;
+
+        frame
+            .window(self.name())
+            .opened(&mut self.is_open)
+            .position([100.0, 380.0], imgui::Condition::FirstUseEver)
+            .size([400.0, 300.0], imgui::Condition::FirstUseEver)
+            .build(|| {
+                let content_width = frame.window_size()[0] - frame.window_content_region_min()[0] * 2.0;
+
+                frame.set_next_item_width(content_width - Self::ADD_BUTTON_WIDTH - frame.item_spacing().0);
+                let pressed_enter =
+                    frame.input_text("##watchinput", &mut self.input_string).enter_returns_true(true).build();
+                if pressed_enter {
+                    frame.set_keyboard_focus_here();
+                }
+                if frame.is_item_focused() {
+                    keybindings.disable_bindings();
+                }
+                frame.same_line();
+                let add = frame.button_with_size("Add", [Self::ADD_BUTTON_WIDTH, 20.0]) || pressed_enter;
+                if add && !self.input_string.trim().is_empty() {
+                    let expression = std::mem::take(&mut self.input_string);
+                    self.watches.push(Watch::new(game, expression.clone()));
+                    config.watched_expressions.push(expression);
+                    config.save();
+                    evaluate = true;
+                }
+
+                if evaluate {
+                    // Watches are meant to be read-only, but nothing stops anyone watching random(1) or a script
+                    // with side effects, so put the whole game back how it was afterwards to avoid any desyncs.
+                    let state = SaveState::from(game, Replay::new(0, 0), renderer_state.clone(), **clean_state);
+                    for watch in self.watches.iter_mut() {
+                        watch.update(game, &mut self.gml_context, config.current_frame);
+                    }
+                    let (_, restored_renderer_state) = state.load_into(game);
+                    **renderer_state = restored_renderer_state;
+                }
+
+                frame.separator();
+
+                let mut removed = None;
+                for (i, watch) in self.watches.iter().enumerate() {
+                    if frame.small_button(format!("X##watchremove{}", i)) {
+                        removed = Some(i);
+                    }
+                    frame.same_line();
+                    frame.text(&watch.expression);
+                    frame.same_line();
+                    if watch.is_error {
+                        frame.coloured_text(&watch.text, ERROR_COLOUR);
+                    } else if watch.changed {
+                        frame.coloured_text(&watch.text, CHANGED_COLOUR);
+                    } else {
+                        frame.text(&watch.text);
+                    }
+
+                    if watch.history.len() > 1 {
+                        let values = watch.history.iter().map(|(_, v)| *v).collect::<Vec<_>>();
+                        frame
+                            .plot_lines(format!("##watchgraph{}", i), &values)
+                            .graph_size([content_width, Self::GRAPH_HEIGHT])
+                            .build();
+                    }
+                }
+
+                if let Some(i) = removed {
+                    self.watches.remove(i);
+                    config.watched_expressions.remove(i);
+                    config.save();
+                }
+            });
+    }
+
+    fn is_open(&self) -> bool {
+        self.is_open
+    }
+}
+
+impl WatchWindow {
+    const ADD_BUTTON_WIDTH: f32 = 50.0;
+    const GRAPH_HEIGHT: f32 = 40.0;
+
+    /// How many frames of history to keep for each graph.
+    const HISTORY_LENGTH: usize = 600;
+
+    pub fn new() -> Self {
+        Self {
+            input_string: String::with_capacity(256),
+            gml_context: Context::with_single_instance(0),
+            watches: Vec::new(),
+
+            loaded: false,
+            last_frame: usize::MAX,
+            last_rerecords: 0,
+
+            is_open: true,
+        }
+    }
+}
+
+impl Watch {
+    fn new(game: &mut Game, expression: String) -> Self {
+        let node = game.compiler.compile_expression(expression.as_bytes()).map_err(|e| e.to_string());
+        Self { expression, node, text: String::new(), is_error: false, changed: false, history: Vec::new() }
+    }
+
+    fn update(&mut self, game: &mut Game, context: &mut Context, frame: usize) {
+        let result = match &self.node {
+            Ok(node) => game.eval(node, context).map_err(|e| e.to_string()),
+            Err(e) => Err(e.clone()),
+        };
+
+        // Loading a savestate can go back in time, so forget anything that's now in the future
+        if let Some(pos) = self.history.iter().position(|(f, _)| *f >= frame) {
+            self.history.truncate(pos);
+        }
+
+        self.is_error = result.is_err();
+        let text = match result {
+            Ok(Value::Real(real)) => {
+                self.history.push((frame, real.into_inner() as f32));
+                if self.history.len() > WatchWindow::HISTORY_LENGTH {
+                    self.history.remove(0);
+                }
+                real.to_string()
+            },
+            Ok(Value::Str(string)) => format!("\"{}\"", string),
+            Err(e) => format!("Error: {}", e),
+        };
+        self.changed = !self.text.is_empty() && text != self.text;
+        self.text = text;
+    }
+}
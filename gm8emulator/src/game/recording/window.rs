@@ -4,6 +4,7 @@ use crate::{
             instance_report::InstanceReport,
             keybinds::{Binding, Keybindings},
             popup_dialog::Dialog,
+            run_until::RunUntil,
             KeyState, ProjectConfig, WindowKind,
         },
         replay::{FrameRng, Replay},
@@ -35,6 +36,7 @@ pub struct EmulatorContext<'a> {
 
     pub clean_state: &'a mut bool,
     pub run_until_frame: &'a mut Option<usize>,
+    pub run_until: &'a mut RunUntil,
 
     pub save_paths: &'a Vec<PathBuf>,
     pub fps_text: &'a String,
@@ -145,6 +147,8 @@ impl EmulatorContext<'_> {
 
     pub fn savestate_load(&mut self, slot: usize) -> bool {
         *self.run_until_frame = None;
+        self.run_until.stop(self.game, "Stopped: a savestate was loaded".into());
+        self.game.debugger.cancel();
         if slot == self.config.quicksave_slot {
             self.savestate_load_from_state(self.savestate.clone());
             true
@@ -239,6 +243,8 @@ impl EmulatorContext<'_> {
         *self.game_running = true;
 
         self.config.current_frame = new_replay.frame_count();
+        // Going back uses the frames the ghost already sent, so it only ever gets run forwards
+        self.game.seek_ghost(self.config.current_frame);
 
         if self.config.is_read_only {
             if !self.replay.contains_part(&new_replay) {
@@ -3,8 +3,10 @@ use byteorder::{ReadBytesExt, WriteBytesExt, LE};
 use lzzzz::lz4;
 use serde::{Deserialize, Serialize};
 use std::{
+    fmt,
     fs::{File, OpenOptions},
     io::{self, Read, Write},
+    ops::Range,
     path::PathBuf,
 };
 
@@ -99,6 +101,26 @@ pub enum WriteError {
     SerializeErr(Box<bincode::ErrorKind>),
 }
 
+// Reasons an edit such as a splice can't be made
+#[derive(Debug)]
+pub enum EditError {
+    // A range of frames going past the end of a replay with this many frames
+    OutOfRange(Range<usize>, usize),
+    // Inputs being shifted to before the first frame
+    BeforeStart,
+}
+
+impl fmt::Display for EditError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::OutOfRange(range, count) => {
+                write!(f, "frames {}-{} aren't all in a replay of {} frames", range.start, range.end, count)
+            },
+            Self::BeforeStart => write!(f, "inputs can't be shifted to before frame 0"),
+        }
+    }
+}
+
 impl Replay {
     pub fn new(start_time: u128, start_seed: i32) -> Self {
         Self { start_time, start_seed, startup_events: Vec::new(), frames: Vec::new() }
@@ -222,6 +244,67 @@ impl Replay {
         self.frames.truncate(len)
     }
 
+    // Fails if the range is backwards or goes past the end of the replay
+    fn check_range(&self, range: &Range<usize>) -> Result<(), EditError> {
+        if range.start > range.end || range.end > self.frames.len() {
+            Err(EditError::OutOfRange(range.clone(), self.frames.len()))
+        } else {
+            Ok(())
+        }
+    }
+
+    // Replaces everything from the given frame onwards with all of the other replay's frames
+    pub fn append(&mut self, other: &Replay, at: usize) -> Result<(), EditError> {
+        self.check_range(&(at..at))?;
+        self.frames.truncate(at);
+        self.frames.extend_from_slice(&other.frames);
+        Ok(())
+    }
+
+    // Replaces the frames in `dest` with the frames in `source` from the other replay.
+    // The ranges don't have to be the same length, so this can also insert or remove frames.
+    pub fn splice(&mut self, other: &Replay, source: Range<usize>, dest: Range<usize>) -> Result<(), EditError> {
+        other.check_range(&source)?;
+        self.check_range(&dest)?;
+        self.frames.splice(dest, other.frames[source].iter().cloned());
+        Ok(())
+    }
+
+    // Moves the inputs and mouse positions on a range of frames earlier or later by some number of frames,
+    // overwriting the inputs on the frames they get moved to. Stored events and RNG changes stay where they are.
+    // Frames left behind have no inputs and keep the mouse where it was before the range.
+    // New frames are added to the end if the inputs get moved past it.
+    pub fn shift_inputs(&mut self, range: Range<usize>, offset: isize) -> Result<(), EditError> {
+        self.check_range(&range)?;
+        let new_start = range.start as isize + offset;
+        if new_start < 0 {
+            return Err(EditError::BeforeStart)
+        }
+
+        let (mouse_x, mouse_y) = match range.start.checked_sub(1).and_then(|i| self.frames.get(i)) {
+            Some(frame) => (frame.mouse_x, frame.mouse_y),
+            None => (0, 0),
+        };
+        let mut moved = Vec::with_capacity(range.len());
+        for frame in &mut self.frames[range] {
+            moved.push((std::mem::take(&mut frame.inputs), frame.mouse_x, frame.mouse_y));
+            frame.mouse_x = mouse_x;
+            frame.mouse_y = mouse_y;
+        }
+
+        for (i, (inputs, mouse_x, mouse_y)) in moved.into_iter().enumerate() {
+            let index = new_start as usize + i;
+            while self.frames.len() <= index {
+                self.new_frame();
+            }
+            let frame = &mut self.frames[index];
+            frame.inputs = inputs;
+            frame.mouse_x = mouse_x;
+            frame.mouse_y = mouse_y;
+        }
+        Ok(())
+    }
+
     // Returns whether this replay begins the same way as the other one.
     pub fn contains_part(&self, other: &Replay) -> bool {
         if self.frame_count() > other.frame_count() {
@@ -235,3 +318,140 @@ impl Replay {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Frames numbered from `first`, each with that number as its key press, event and mouse position
+    fn replay(first: usize, count: usize) -> Replay {
+        let mut replay = Replay::new(0, 0);
+        for n in first..first + count {
+            let frame = replay.new_frame();
+            frame.mouse_x = n as i32;
+            frame.mouse_y = -(n as i32);
+            frame.inputs.push(Input::KeyPress(n as u8));
+            frame.events.push(Event::Randomize(n as i32));
+        }
+        replay
+    }
+
+    // Which numbered frame each frame's inputs came from, if any
+    fn inputs(replay: &Replay) -> Vec<Option<u8>> {
+        let key = |frame: &Frame| match frame.inputs.as_slice() {
+            [Input::KeyPress(key)] => Some(*key),
+            [] => None,
+            inputs => panic!("unexpected inputs {:?}", inputs),
+        };
+        replay.frames.iter().map(key).collect()
+    }
+
+    fn mouse(replay: &Replay, index: usize) -> (i32, i32) {
+        let frame = replay.get_frame(index).unwrap();
+        (frame.mouse_x, frame.mouse_y)
+    }
+
+    fn events(replay: &Replay) -> Vec<Vec<Event>> {
+        replay.frames.iter().map(|frame| frame.events.clone()).collect()
+    }
+
+    #[test]
+    fn append() {
+        let mut a = replay(0, 4);
+        a.append(&replay(10, 2), 3).unwrap();
+        assert_eq!(inputs(&a), [Some(0), Some(1), Some(2), Some(10), Some(11)]);
+
+        a.append(&replay(20, 1), 5).unwrap();
+        assert_eq!(a.frame_count(), 6);
+        a.append(&replay(30, 0), 2).unwrap();
+        assert_eq!(inputs(&a), [Some(0), Some(1)]);
+    }
+
+    #[test]
+    fn append_out_of_range() {
+        let mut a = replay(0, 3);
+        assert!(matches!(a.append(&replay(10, 2), 4), Err(EditError::OutOfRange(_, 3))));
+        assert_eq!(a, replay(0, 3));
+    }
+
+    #[test]
+    fn splice() {
+        let mut a = replay(0, 5);
+        a.splice(&replay(10, 3), 1..3, 2..3).unwrap();
+        assert_eq!(inputs(&a), [Some(0), Some(1), Some(11), Some(12), Some(3), Some(4)]);
+
+        // Removing frames, and inserting them without replacing any
+        a.splice(&replay(10, 3), 0..0, 1..4).unwrap();
+        assert_eq!(inputs(&a), [Some(0), Some(3), Some(4)]);
+        a.splice(&replay(10, 3), 2..3, 3..3).unwrap();
+        assert_eq!(inputs(&a), [Some(0), Some(3), Some(4), Some(12)]);
+    }
+
+    #[test]
+    #[allow(clippy::reversed_empty_ranges)]
+    fn splice_out_of_range() {
+        let mut a = replay(0, 5);
+        let b = replay(10, 3);
+        assert!(matches!(a.splice(&b, 2..4, 0..1), Err(EditError::OutOfRange(_, 3))));
+        assert!(matches!(a.splice(&b, 2..1, 0..1), Err(EditError::OutOfRange(_, 3))));
+        assert!(matches!(a.splice(&b, 0..1, 4..6), Err(EditError::OutOfRange(_, 5))));
+        assert!(matches!(a.splice(&b, 0..1, 3..2), Err(EditError::OutOfRange(_, 5))));
+        assert_eq!(a, replay(0, 5));
+    }
+
+    #[test]
+    fn shift_inputs_later() {
+        let mut a = replay(0, 5);
+        a.shift_inputs(1..3, 1).unwrap();
+        assert_eq!(inputs(&a), [Some(0), None, Some(1), Some(2), Some(4)]);
+        assert_eq!(mouse(&a, 1), (0, 0));
+        assert_eq!(mouse(&a, 2), (1, -1));
+        assert_eq!(mouse(&a, 3), (2, -2));
+        assert_eq!(events(&a), events(&replay(0, 5)));
+    }
+
+    #[test]
+    fn shift_inputs_earlier() {
+        let mut a = replay(0, 5);
+        a.shift_inputs(2..4, -1).unwrap();
+        assert_eq!(inputs(&a), [Some(0), Some(2), Some(3), None, Some(4)]);
+        // The vacated frame keeps the mouse where it was before the range, not where it was moved to
+        assert_eq!(mouse(&a, 1), (2, -2));
+        assert_eq!(mouse(&a, 3), (1, -1));
+        assert_eq!(events(&a), events(&replay(0, 5)));
+    }
+
+    #[test]
+    fn shift_inputs_from_start() {
+        let mut a = replay(1, 3);
+        a.shift_inputs(0..2, 2).unwrap();
+        assert_eq!(inputs(&a), [None, None, Some(1), Some(2)]);
+        assert_eq!(mouse(&a, 0), (0, 0));
+        assert_eq!(mouse(&a, 1), (0, 0));
+    }
+
+    #[test]
+    fn shift_inputs_past_end() {
+        let mut a = replay(0, 3);
+        a.shift_inputs(1..3, 3).unwrap();
+        assert_eq!(inputs(&a), [Some(0), None, None, None, Some(1), Some(2)]);
+        assert_eq!(mouse(&a, 2), (0, 0));
+        assert_eq!(mouse(&a, 3), (0, 0));
+        assert_eq!(mouse(&a, 5), (2, -2));
+        assert!(a.frames[3..].iter().all(|frame| frame.events.is_empty() && frame.new_seed.is_none()));
+    }
+
+    #[test]
+    #[allow(clippy::reversed_empty_ranges)]
+    fn shift_inputs_out_of_range() {
+        let mut a = replay(0, 3);
+        assert!(matches!(a.shift_inputs(1..2, -2), Err(EditError::BeforeStart)));
+        assert!(matches!(a.shift_inputs(2..4, 1), Err(EditError::OutOfRange(_, 3))));
+        assert!(matches!(a.shift_inputs(2..1, 1), Err(EditError::OutOfRange(_, 3))));
+        assert_eq!(a, replay(0, 3));
+
+        // An empty range doesn't move anything, even if it's at the end
+        a.shift_inputs(3..3, 5).unwrap();
+        assert_eq!(a, replay(0, 3));
+    }
+}
@@ -1,10 +1,17 @@
 use crate::{
     game::{
-        audio::AudioState, draw, external, includedfile::IncludedFile, model::Model, particle,
-        pathfinding::PotentialStepSettings, surface::Surface, transition::UserTransition, Assets,
-        Game, GameClock, Replay, RoomState, Version,
+        audio::{cd::CdState, AudioState},
+        display::Display,
+        draw, external,
+        includedfile::IncludedFile,
+        model::Model,
+        particle,
+        pathfinding::PotentialStepSettings,
+        surface::Surface,
+        transition::UserTransition,
+        Assets, Game, GameClock, Replay, RoomState, Version,
     },
-    gml::{self, ds, rand::Random, Compiler},
+    gml::{self, ds, network::MultiplayerState, rand::Random, Compiler},
     handleman::HandleList,
     input::Input,
     instance::DummyFieldHolder,
@@ -106,6 +113,9 @@ pub struct SaveState {
     pub included_files: Vec<IncludedFile>,
     pub gm_version: Version,
     pub clock: GameClock,
+    pub display: Display,
+    pub virtual_clipboard: gml::String,
+    pub window_stay_on_top: bool,
 
     pub clean_state: bool,
 
@@ -116,6 +126,8 @@ pub struct SaveState {
     window_height: u32,
 
     audio_state: AudioState,
+    mplay_state: MultiplayerState,
+    cd_state: CdState,
 
     replay: Replay,
     screenshot: Box<[u8]>,
@@ -195,12 +207,17 @@ impl SaveState {
             included_files: game.included_files.clone(),
             gm_version: game.gm_version.clone(),
             clock: game.clock.clone(),
+            display: game.display.clone(),
+            virtual_clipboard: game.virtual_clipboard.clone(),
+            window_stay_on_top: game.window_stay_on_top,
             scaling: game.scaling,
             unscaled_width: game.unscaled_width,
             unscaled_height: game.unscaled_height,
             window_width,
             window_height,
             audio_state: game.audio.state(),
+            mplay_state: game.mplay.state(),
+            cd_state: game.cd.state(),
             replay,
             screenshot,
             zbuffer,
@@ -285,7 +302,12 @@ impl SaveState {
         game.included_files = self.included_files;
         game.gm_version = self.gm_version;
         game.clock = self.clock;
+        game.display = self.display;
+        game.virtual_clipboard = self.virtual_clipboard;
+        game.window_stay_on_top = self.window_stay_on_top;
         game.audio.set_state(self.audio_state);
+        game.mplay.set_state(self.mplay_state);
+        game.cd.set_state(self.cd_state, game.clock.as_nanos(), &mut game.audio);
         game.scaling = self.scaling;
         game.unscaled_width = self.unscaled_width;
         game.unscaled_height = self.unscaled_height;
@@ -0,0 +1,150 @@
+//! An opt-in log of what the game ran on each frame, for finding out where two runs went different ways.
+//!
+//! It's written as plain text with one short line per entry, each starting with the frame number,
+//! so two traces can be compared with any diff tool as well as with the TAS UI's trace viewer:
+//!
+//! ```text
+//! 12 frame 4079823
+//! 12 event 100003 obj_player Step Event
+//! 12 script scr_move
+//! 12 random 2 -974639043
+//! 12 random irandom 3
+//! 12 create 100021 obj_bullet
+//! 12 destroy 100021 obj_bullet
+//! 12 room rm_stage2
+//! ```
+//!
+//! Lots of things use the RNG besides GML's random functions, such as particles and some actions,
+//! so rather than each of them writing to the trace, how many times it was cycled since the last line
+//! gets written down before the next one, along with the new seed. If the seed was set instead, that's
+//! written as "random set" followed by the seed. GML's random functions also write down which function
+//! was called and what it returned, right after the count that includes their own cycle.
+
+use crate::{
+    game::{Game, GetAsset},
+    gml::{rand::Random, Value},
+    types::ID,
+};
+use std::{
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::Path,
+};
+
+/// Something that happened which is worth writing to the trace.
+pub enum Entry {
+    /// An instance's event ran, by its InstanceList handle
+    Event { instance: usize, event_type: usize, event_number: usize },
+    Script(ID),
+    Random { function: &'static str, result: Value },
+    Create(usize),
+    Destroy(usize),
+    Room(ID),
+}
+
+pub struct Trace {
+    writer: Option<BufWriter<File>>,
+
+    /// Number of the frame being run, and of the next one
+    frame: usize,
+    next_frame: usize,
+
+    /// What the RNG seed was when the last line was written, or None if there's nothing to compare it to yet
+    seed: Option<i32>,
+}
+
+/// How far ahead to look for the RNG's new seed before deciding it must have been set.
+const MAX_RAND_CYCLES: usize = 100_000;
+
+impl Trace {
+    pub fn create(path: &Path) -> io::Result<Self> {
+        Ok(Self { writer: Some(BufWriter::new(File::create(path)?)), frame: 0, next_frame: 0, seed: None })
+    }
+
+    /// Sets the number that the next frame gets written with, such as after a savestate's been loaded.
+    pub fn set_next_frame(&mut self, frame: usize) {
+        self.next_frame = frame;
+        // The seed will have been loaded too, which isn't something the last frame did
+        self.seed = None;
+    }
+
+    /// Starts a new frame, writing down what the RNG seed was at the start of it.
+    pub fn begin_frame(&mut self, seed: i32) {
+        // Anything that used the RNG after the last frame's last line still belongs to that frame
+        self.write_rand(seed);
+        self.frame = self.next_frame;
+        self.next_frame += 1;
+        self.write(format_args!("frame {}", seed));
+    }
+
+    /// Writes down how far the RNG has moved on since the last line, if it has.
+    fn write_rand(&mut self, seed: i32) {
+        let last_seed = match self.seed.replace(seed) {
+            Some(last_seed) => last_seed,
+            None => return,
+        };
+        if seed != last_seed {
+            let mut stepped = Random::with_seed(last_seed);
+            let cycles = (1..=MAX_RAND_CYCLES).find(|_| {
+                stepped.cycle();
+                stepped.seed() == seed
+            });
+            match cycles {
+                Some(cycles) => self.write(format_args!("random {} {}", cycles, seed)),
+                None => self.write(format_args!("random set {}", seed)),
+            }
+        }
+    }
+
+    fn write(&mut self, entry: std::fmt::Arguments) {
+        if let Some(writer) = &mut self.writer {
+            if let Err(e) = writeln!(writer, "{} {}", self.frame, entry) {
+                // Keep running the game, since the trace is only there to help
+                eprintln!("Stopped writing trace: {}", e);
+                self.writer = None;
+            }
+        }
+    }
+}
+
+impl Game {
+    /// Writes an entry to the trace, if there is one.
+    #[inline(always)]
+    pub fn trace(&mut self, entry: Entry) {
+        if self.trace.is_some() {
+            self.write_trace(entry);
+        }
+    }
+
+    fn write_trace(&mut self, entry: Entry) {
+        let object_name = |game: &Game, object: ID| {
+            let name = game.assets.objects.get_asset(object).map(|x| x.name.to_string());
+            name.unwrap_or_else(|| object.to_string())
+        };
+        let instance_text = |game: &Game, handle: usize| {
+            let instance = game.room.instance_list.get(handle);
+            format!("{} {}", instance.id.get(), object_name(game, instance.object_index.get()))
+        };
+
+        let line = match entry {
+            Entry::Event { instance, event_type, event_number } => {
+                format!("event {} {}", instance_text(self, instance), self.event_name(event_type, event_number))
+            },
+            Entry::Script(id) => {
+                let name = self.assets.scripts.get_asset(id).map(|x| x.name.to_string());
+                format!("script {}", name.unwrap_or_else(|| id.to_string()))
+            },
+            Entry::Random { function, result } => format!("random {} {}", function, result),
+            Entry::Create(instance) => format!("create {}", instance_text(self, instance)),
+            Entry::Destroy(instance) => format!("destroy {}", instance_text(self, instance)),
+            Entry::Room(id) => {
+                let name = self.assets.rooms.get_asset(id).map(|x| x.name.to_string());
+                format!("room {}", name.unwrap_or_else(|| id.to_string()))
+            },
+        };
+        if let Some(trace) = &mut self.trace {
+            trace.write_rand(self.rand.seed());
+            trace.write(format_args!("{}", line));
+        }
+    }
+}
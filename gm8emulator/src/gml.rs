@@ -1,6 +1,7 @@
 pub mod compiler;
 pub mod context;
 pub mod datetime;
+pub mod debugger;
 pub mod ds;
 pub mod file;
 pub mod kernel;
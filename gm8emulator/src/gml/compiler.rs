@@ -103,6 +103,14 @@ impl Compiler {
 
     /// Compile a single line of code from an AST expression.
     fn compile_ast_line<'a>(&mut self, line: &'a ast::Expr, output: &mut Vec<Instruction>, locals: &mut Vec<&'a [u8]>) {
+        // Mark where each line starts for the debugger, leaving out "var" since it doesn't do anything at runtime
+        if let Some(span) = line.span() {
+            let marked = matches!(output.last(), Some(Instruction::Line { line }) if *line == span.line);
+            if !marked && !matches!(line, ast::Expr::Var(_)) {
+                output.push(Instruction::Line { line: span.line });
+            }
+        }
+
         match line {
             // Line of code identified by an assignment operator
             ast::Expr::Binary(binary_expr) => {
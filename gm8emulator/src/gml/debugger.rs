@@ -0,0 +1,348 @@
+//! A debugger for stepping through GML in record mode.
+//!
+//! GML runs to completion inside Game::frame, so there's no way to hold it open while the UI gets drawn.
+//! Instead, when the debugger decides to stop, it takes a snapshot of the call stack and locals and aborts the
+//! frame with Error::DebuggerStopped. The TAS UI then puts the game back how it was before the frame, and shows
+//! the snapshot. Stepping or continuing runs the same frame again with the same inputs - since the game is
+//! deterministic, it takes the same path back to where it stopped, and the debugger carries on from there.
+
+use crate::{
+    game::{profiler::Key, Game, GetAsset},
+    gml::{
+        self,
+        runtime::{Error, Frame, Instruction, ReturnType},
+        Context, Value,
+    },
+    instance::Field,
+    types::ID,
+};
+
+/// Somewhere for the debugger to stop.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Breakpoint {
+    /// A script, on its first line or on the given line
+    Script { id: ID, line: Option<usize> },
+
+    /// An object's event, on the first line of each code action in it or only of the given action
+    Event { object: ID, event_type: usize, event_number: usize, action: Option<usize> },
+}
+
+/// Some code with its own place on the call stack.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Unit {
+    Script(ID),
+    Action { object: ID, event_type: usize, event_number: usize, action: usize },
+}
+
+/// How far to go before stopping again, on top of any breakpoints.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Step {
+    /// Only stop at breakpoints
+    Continue,
+
+    /// Stop on the next line
+    Into,
+
+    /// Stop on the next line which isn't deeper in the call stack
+    Over,
+
+    /// Stop on the next line which is further up the call stack
+    Out,
+}
+
+struct StackFrame {
+    unit: Unit,
+    line: Option<usize>,
+    lines_run: usize,
+}
+
+/// Where the debugger stopped, and what everything looked like at the time.
+pub struct Pause {
+    /// Every frame on the call stack, from the innermost outwards
+    pub stack: Vec<String>,
+
+    pub this: String,
+    pub other: String,
+    pub arguments: Vec<Value>,
+    pub locals: Vec<(String, Value)>,
+
+    // How many lines had been run when it stopped, and how deep the call stack was
+    position: usize,
+    depth: usize,
+}
+
+pub struct Debugger {
+    breakpoints: Vec<Breakpoint>,
+    step: Step,
+    step_depth: usize,
+    pause: Option<Pause>,
+    resume_requested: bool,
+
+    // Only kept up to date while a frame's being run under the debugger
+    tracking: bool,
+    stack: Vec<StackFrame>,
+    outer_line: Option<usize>,
+    position: usize,
+    resume_from: usize,
+}
+
+impl Debugger {
+    pub fn new() -> Self {
+        Self {
+            breakpoints: Vec::new(),
+            step: Step::Continue,
+            step_depth: 0,
+            pause: None,
+            resume_requested: false,
+            tracking: false,
+            stack: Vec::new(),
+            outer_line: None,
+            position: 0,
+            resume_from: 0,
+        }
+    }
+
+    pub fn breakpoints(&self) -> &[Breakpoint] {
+        &self.breakpoints
+    }
+
+    pub fn add_breakpoint(&mut self, breakpoint: Breakpoint) {
+        if !self.breakpoints.contains(&breakpoint) {
+            self.breakpoints.push(breakpoint);
+        }
+    }
+
+    pub fn remove_breakpoint(&mut self, index: usize) {
+        if index < self.breakpoints.len() {
+            self.breakpoints.remove(index);
+        }
+    }
+
+    /// Whether the next frame should be run under the debugger, meaning it might stop partway through.
+    pub fn is_enabled(&self) -> bool {
+        !self.breakpoints.is_empty() || self.pause.is_some()
+    }
+
+    /// Where the last frame stopped, if it did.
+    pub fn pause(&self) -> Option<&Pause> {
+        self.pause.as_ref()
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.pause.is_some()
+    }
+
+    /// Asks for the frame it stopped in to be run again, stopping at the next place `step` says to.
+    pub fn resume(&mut self, step: Step) {
+        if let Some(pause) = &self.pause {
+            self.step = step;
+            self.step_depth = pause.depth;
+            self.resume_requested = true;
+        }
+    }
+
+    /// Whether the UI should run the frame again, because resume() was called since the frame last ran.
+    pub fn resume_requested(&self) -> bool {
+        self.resume_requested
+    }
+
+    /// Forgets where it stopped, such as when the game gets put into a different state.
+    pub fn cancel(&mut self) {
+        self.pause = None;
+        self.step = Step::Continue;
+        self.resume_requested = false;
+    }
+
+    /// Gets ready to run a frame. If it stopped partway through the frame last time, it carries on from there.
+    pub fn begin_frame(&mut self) {
+        match self.pause.take() {
+            Some(pause) => self.resume_from = pause.position,
+            None => {
+                self.resume_from = 0;
+                self.step = Step::Continue;
+            },
+        }
+        self.resume_requested = false;
+        self.tracking = !self.breakpoints.is_empty() || self.step != Step::Continue;
+        self.stack.clear();
+        self.outer_line = None;
+        self.position = 0;
+    }
+
+    /// Stops tracking once the frame is done, so that GML run by the UI between frames can't stop.
+    pub fn end_frame(&mut self) {
+        self.tracking = false;
+    }
+
+    fn should_stop(&self, line: usize) -> bool {
+        let depth = self.stack.len();
+        let stepped = match self.step {
+            Step::Continue => false,
+            Step::Into => true,
+            Step::Over => depth <= self.step_depth,
+            Step::Out => depth < self.step_depth,
+        };
+        stepped
+            || match self.stack.last() {
+                Some(frame) => self.breakpoints.iter().any(|breakpoint| match (*breakpoint, frame.unit) {
+                    (Breakpoint::Script { id, line: None }, Unit::Script(script)) => {
+                        id == script && frame.lines_run == 1
+                    },
+                    (Breakpoint::Script { id, line: Some(bp_line) }, Unit::Script(script)) => {
+                        id == script && bp_line == line
+                    },
+                    (
+                        Breakpoint::Event { object, event_type, event_number, action },
+                        Unit::Action { object: a_object, event_type: a_type, event_number: a_number, action: a_action },
+                    ) => {
+                        object == a_object
+                            && event_type == a_type
+                            && event_number == a_number
+                            && action.map(|a| a == a_action).unwrap_or(true)
+                            && frame.lines_run == 1
+                    },
+                    _ => false,
+                }),
+                None => false,
+            }
+    }
+}
+
+impl Game {
+    /// Runs some code as its own frame on the debugger's call stack, and on the profiler's if it's a script.
+    pub fn execute_unit(
+        &mut self,
+        unit: Unit,
+        instructions: &[Instruction],
+        context: &mut Context,
+    ) -> gml::Result<ReturnType> {
+        match unit {
+            Unit::Script(id) => self.profile(Key::Script(id), |game| game.execute_tracked(unit, instructions, context)),
+            Unit::Action { .. } => self.execute_tracked(unit, instructions, context),
+        }
+    }
+
+    fn execute_tracked(
+        &mut self,
+        unit: Unit,
+        instructions: &[Instruction],
+        context: &mut Context,
+    ) -> gml::Result<ReturnType> {
+        if self.debugger.tracking {
+            // If an error gets passed up through here, it'll still be taken off the stack
+            let depth = self.debugger.stack.len();
+            self.debugger.stack.push(StackFrame { unit, line: None, lines_run: 0 });
+            let result = self.execute(instructions, context);
+            self.debugger.stack.truncate(depth);
+            result
+        } else {
+            self.execute(instructions, context)
+        }
+    }
+
+    /// Notes that a new line is about to be run, and stops there if the debugger says so.
+    #[inline(always)]
+    pub fn debug_line(&mut self, line: usize, context: &Context) -> gml::Result<()> {
+        if self.debugger.tracking { self.debug_step(line, context) } else { Ok(()) }
+    }
+
+    fn debug_step(&mut self, line: usize, context: &Context) -> gml::Result<()> {
+        // Something might catch the error and carry on, so keep stopping until the frame's over
+        if self.debugger.pause.is_some() {
+            return Err(Error::DebuggerStopped)
+        }
+
+        match self.debugger.stack.last_mut() {
+            Some(frame) => {
+                frame.line = Some(line);
+                frame.lines_run += 1;
+            },
+            None => self.debugger.outer_line = Some(line),
+        }
+        self.debugger.position += 1;
+
+        // Everything up to where it last stopped already got looked at the first time the frame ran
+        if self.debugger.position > self.debugger.resume_from && self.debugger.should_stop(line) {
+            let pause = self.debug_pause(context);
+            self.debugger.pause = Some(pause);
+            self.debugger.step = Step::Continue;
+            Err(Error::DebuggerStopped)
+        } else {
+            Ok(())
+        }
+    }
+
+    fn debug_pause(&self, context: &Context) -> Pause {
+        let mut stack = self
+            .debugger
+            .stack
+            .iter()
+            .rev()
+            .map(|frame| {
+                let place = self.debug_frame(frame.unit);
+                match frame.line {
+                    Some(line) => format!("{}, line {}", place, line),
+                    None => place.to_string(),
+                }
+            })
+            .collect::<Vec<_>>();
+        if let Some(line) = self.debugger.outer_line {
+            stack.push(format!("code outside any script or action, line {}", line));
+        }
+
+        let mut locals = Vec::new();
+        for (id, field) in context.locals.fields.iter() {
+            let name = self.compiler.get_field_name(*id).unwrap_or_else(|| id.to_string());
+            match field {
+                Field::Single(value) => locals.push((name, value.clone())),
+                Field::Array(array) => {
+                    for (index, value) in array.iter() {
+                        let name = match index / 32000 {
+                            0 => format!("{}[{}]", name, index),
+                            i => format!("{}[{}, {}]", name, i, index % 32000),
+                        };
+                        locals.push((name, value.clone()));
+                    }
+                },
+            }
+        }
+        locals.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+        let argument_count = context.argument_count.min(context.arguments.len());
+        Pause {
+            stack,
+            this: self.debug_instance(context.this),
+            other: self.debug_instance(context.other),
+            arguments: context.arguments[..argument_count].to_vec(),
+            locals,
+            position: self.debugger.position,
+            depth: self.debugger.stack.len(),
+        }
+    }
+
+    /// Describes some code the same way as runtime errors do in their call stacks.
+    fn debug_frame(&self, unit: Unit) -> Frame {
+        match unit {
+            Unit::Script(id) => {
+                let name = self.assets.scripts.get_asset(id).map(|x| x.name.to_string());
+                Frame::Script(name.unwrap_or_else(|| id.to_string()))
+            },
+            Unit::Action { object, event_type, event_number, action } => {
+                let name = self.assets.objects.get_asset(object).map(|x| x.name.to_string());
+                Frame::Code(format!(
+                    "action number {} of {} for object {}",
+                    action + 1,
+                    self.event_name(event_type, event_number),
+                    name.unwrap_or_else(|| object.to_string()),
+                ))
+            },
+        }
+    }
+
+    fn debug_instance(&self, handle: usize) -> String {
+        let instance = self.room.instance_list.get(handle);
+        let object = instance.object_index.get();
+        let name = self.assets.objects.get_asset(object).map(|x| x.name.to_string());
+        format!("{} ({})", instance.id.get(), name.unwrap_or_else(|| object.to_string()))
+    }
+}
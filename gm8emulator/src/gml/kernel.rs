@@ -6,11 +6,13 @@ use crate::{
     action, asset,
     game::{
         draw, external, gm_save::GMSave, model, particle, pathfinding, platform, replay, surface::Surface,
-        transition::UserTransition, view::View, Game, GameClock, GetAsset, PlayType, SceneChange, Version,
+        trace::Entry, transition::UserTransition, view::View, Game, GameClock, GetAsset, PlayType, SceneChange,
+        Version,
     },
     gml::{
         self,
         datetime::{self, DateTime},
+        debugger::Unit,
         ds, file,
         mappings::{self, constants as gml_consts},
         network, Context, Value,
@@ -22,6 +24,7 @@ use crate::{
     render::{BlendType, Fog, Light, Renderer, Scaling},
     tile::Tile,
 };
+use clipboard::{ClipboardContext, ClipboardProvider};
 use image::RgbaImage;
 use ramen::window::Cursor;
 use std::{
@@ -94,93 +97,88 @@ fn rgb_to_hsv(colour: i32) -> (i32, i32, i32) {
     (h, s, v)
 }
 
+fn host_clipboard_get() -> Option<String> {
+    let mut ctx: ClipboardContext = ClipboardProvider::new().ok()?;
+    ctx.get_contents().ok()
+}
+
+fn host_clipboard_set(text: String) {
+    if let Ok(mut ctx) = <ClipboardContext as ClipboardProvider>::new() {
+        let _ = ctx.set_contents(text);
+    }
+}
+
 impl Game {
     pub fn display_get_width(&self, args: &[Value]) -> gml::Result<Value> {
         expect_args!(args, [])?;
-        #[cfg(target_os = "windows")]
-        if self.play_type == PlayType::Normal {
-            return platform::display_width().map(Value::from).ok_or_else(|| {
-                gml::Error::FunctionError("display_get_width".into(), "getting display width failed".into())
-            });
-        }
-        Ok(1280.into())
+        Ok(self.display.mode().width.into())
     }
 
     pub fn display_get_height(&self, args: &[Value]) -> gml::Result<Value> {
         expect_args!(args, [])?;
-        #[cfg(target_os = "windows")]
-        if self.play_type == PlayType::Normal {
-            return platform::display_height().map(Value::from).ok_or_else(|| {
-                gml::Error::FunctionError("display_get_height".into(), "getting display height failed".into())
-            });
-        }
-        Ok(720.into())
+        Ok(self.display.mode().height.into())
     }
 
     pub fn display_get_colordepth(&self, args: &[Value]) -> gml::Result<Value> {
         expect_args!(args, [])?;
-        #[cfg(target_os = "windows")]
-        if self.play_type == PlayType::Normal {
-            return platform::display_colour_depth().map(Value::from).ok_or_else(|| {
-                gml::Error::FunctionError("display_get_colordepth".into(), "getting display colour depth failed".into())
-            });
-        }
-        Ok(32.into())
+        Ok(self.display.mode().colour_depth.into())
     }
 
     pub fn display_get_frequency(&self, args: &[Value]) -> gml::Result<Value> {
         expect_args!(args, [])?;
-        #[cfg(target_os = "windows")]
-        if self.play_type == PlayType::Normal {
-            return platform::display_frequency().map(Value::from).ok_or_else(|| {
-                gml::Error::FunctionError("display_get_frequency".into(), "getting display frequency failed".into())
-            });
-        }
-        Ok(60.into())
+        Ok(self.display.mode().frequency.into())
     }
 
-    pub fn display_set_size(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 2
-        unimplemented!("Called unimplemented kernel function display_set_size")
+    pub fn display_set_size(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let (width, height) = expect_args!(args, [int, int])?;
+        let mode = self.display.mode_with(width, height, -1, -1);
+        Ok(self.display.set_mode(mode).into())
     }
 
-    pub fn display_set_colordepth(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 1
-        unimplemented!("Called unimplemented kernel function display_set_colordepth")
+    pub fn display_set_colordepth(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let colour_depth = expect_args!(args, [int])?;
+        let mode = self.display.mode_with(-1, -1, -1, colour_depth);
+        Ok(self.display.set_mode(mode).into())
     }
 
-    pub fn display_set_frequency(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 1
-        unimplemented!("Called unimplemented kernel function display_set_frequency")
+    pub fn display_set_frequency(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let frequency = expect_args!(args, [int])?;
+        let mode = self.display.mode_with(-1, -1, frequency, -1);
+        Ok(self.display.set_mode(mode).into())
     }
 
-    pub fn display_set_all(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 4
-        unimplemented!("Called unimplemented kernel function display_set_all")
+    pub fn display_set_all(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let (width, height, frequency, colour_depth) = expect_args!(args, [int, int, int, int])?;
+        let mode = self.display.mode_with(width, height, frequency, colour_depth);
+        Ok(self.display.set_mode(mode).into())
     }
 
-    pub fn display_test_all(&self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 4
-        unimplemented!("Called unimplemented kernel function display_test_all")
+    pub fn display_test_all(&self, args: &[Value]) -> gml::Result<Value> {
+        let (width, height, frequency, colour_depth) = expect_args!(args, [int, int, int, int])?;
+        Ok(self.display.mode_with(width, height, frequency, colour_depth).is_valid().into())
     }
 
-    pub fn display_reset(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        unimplemented!("Called unimplemented kernel function display_reset")
+    pub fn display_reset(&mut self, args: &[Value]) -> gml::Result<Value> {
+        expect_args!(args, [])?;
+        self.display.reset();
+        Ok(Default::default())
     }
 
-    pub fn display_mouse_get_x(&self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 0
-        unimplemented!("Called unimplemented kernel function display_mouse_get_x")
+    pub fn display_mouse_get_x(&self, args: &[Value]) -> gml::Result<Value> {
+        expect_args!(args, [])?;
+        Ok((self.window_offset_spoof.0 + self.input.mouse_x()).into())
     }
 
-    pub fn display_mouse_get_y(&self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 0
-        unimplemented!("Called unimplemented kernel function display_mouse_get_y")
+    pub fn display_mouse_get_y(&self, args: &[Value]) -> gml::Result<Value> {
+        expect_args!(args, [])?;
+        Ok((self.window_offset_spoof.1 + self.input.mouse_y()).into())
     }
 
-    pub fn display_mouse_set(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 2
-        unimplemented!("Called unimplemented kernel function display_mouse_set")
+    // NB: The mouse is only moved in the emulated input state, the host's cursor is never warped.
+    pub fn display_mouse_set(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let (x, y) = expect_args!(args, [int, int])?;
+        self.input.mouse_move_to((x - self.window_offset_spoof.0, y - self.window_offset_spoof.1));
+        Ok(Default::default())
     }
 
     pub fn window_set_visible(&mut self, args: &[Value]) -> gml::Result<Value> {
@@ -241,14 +239,16 @@ impl Game {
         Ok(self.window_icons.into())
     }
 
-    pub fn window_set_stayontop(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 1
-        unimplemented!("Called unimplemented kernel function window_set_stayontop")
+    pub fn window_set_stayontop(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let stay_on_top = expect_args!(args, [bool])?;
+        // TODO: pass this on to ramen in normal play once it supports topmost windows
+        self.window_stay_on_top = stay_on_top;
+        Ok(Default::default())
     }
 
-    pub fn window_get_stayontop(&self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 0
-        unimplemented!("Called unimplemented kernel function window_get_stayontop")
+    pub fn window_get_stayontop(&self, args: &[Value]) -> gml::Result<Value> {
+        expect_args!(args, [])?;
+        Ok(self.window_stay_on_top.into())
     }
 
     pub fn window_set_sizeable(&mut self, args: &[Value]) -> gml::Result<Value> {
@@ -350,9 +350,14 @@ impl Game {
         Ok(Default::default())
     }
 
-    pub fn window_set_rectangle(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 4
-        unimplemented!("Called unimplemented kernel function window_set_rectangle")
+    pub fn window_set_rectangle(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let (x, y, width, height) = expect_args!(args, [int, int, int, int])?;
+        self.window_offset_spoof = (x, y);
+        if width > 0 && height > 0 && self.play_type != PlayType::Record {
+            self.window_inner_size = (width as u32, height as u32);
+            self.window.set_size((width as _, height as _));
+        }
+        Ok(Default::default())
     }
 
     pub fn window_center(&mut self, _args: &[Value]) -> gml::Result<Value> {
@@ -360,9 +365,22 @@ impl Game {
         Ok(Default::default())
     }
 
-    pub fn window_default(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 0
-        unimplemented!("Called unimplemented kernel function window_default")
+    pub fn window_default(&mut self, args: &[Value]) -> gml::Result<Value> {
+        expect_args!(args, [])?;
+        let (width, height) = match self.scaling {
+            Scaling::Fixed(scale) => {
+                ((f64::from(self.unscaled_width) * scale) as u32, (f64::from(self.unscaled_height) * scale) as u32)
+            },
+            _ => (self.unscaled_width, self.unscaled_height),
+        };
+        let display = self.display.mode();
+        self.window_offset_spoof =
+            ((display.width as i32 - width as i32) / 2, (display.height as i32 - height as i32) / 2);
+        if self.play_type != PlayType::Record {
+            self.window_inner_size = (width, height);
+            self.window.set_size((width as _, height as _));
+        }
+        Ok(Default::default())
     }
 
     pub fn window_get_x(&self, args: &[Value]) -> gml::Result<Value> {
@@ -385,12 +403,26 @@ impl Game {
         Ok(self.window_inner_size.1.into())
     }
 
-    pub fn window_set_region_size(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 3
-        // unscaled_width and unscaled_height will need to be separated into framebuffer size
-        // and window region size for this to work
-        // probably keep the framebuffer size on the renderer and make a getter?
-        unimplemented!("Called unimplemented kernel function window_set_region_size")
+    pub fn window_set_region_size(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let (width, height, adapt_window) = expect_args!(args, [int, int, bool])?;
+        if width > 0 && height > 0 {
+            let (width, height) = (width as u32, height as u32);
+            self.unscaled_width = width;
+            self.unscaled_height = height;
+            self.renderer.resize_framebuffer(width, height, false);
+            if adapt_window && self.play_type != PlayType::Record {
+                let (region_w, region_h) = match self.scaling {
+                    Scaling::Fixed(scale) => ((f64::from(width) * scale) as u32, (f64::from(height) * scale) as u32),
+                    _ => (width, height),
+                };
+                let (window_w, window_h) = self.window_inner_size;
+                if region_w > window_w || region_h > window_h {
+                    self.window_inner_size = (region_w.max(window_w), region_h.max(window_h));
+                    self.window.set_size((self.window_inner_size.0 as _, self.window_inner_size.1 as _));
+                }
+            }
+        }
+        Ok(Default::default())
     }
 
     pub fn window_get_region_width(&self, args: &[Value]) -> gml::Result<Value> {
@@ -446,39 +478,58 @@ impl Game {
         Ok(self.input.mouse_y().into())
     }
 
-    pub fn window_mouse_set(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 2
-        unimplemented!("Called unimplemented kernel function window_mouse_set")
+    pub fn window_mouse_set(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let (x, y) = expect_args!(args, [int, int])?;
+        self.input.mouse_move_to((x, y));
+        Ok(Default::default())
     }
 
-    pub fn window_view_mouse_get_x(&self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 1
-        unimplemented!("Called unimplemented kernel function window_view_mouse_get_x")
+    pub fn window_view_mouse_get_x(&self, args: &[Value]) -> gml::Result<Value> {
+        let view_id = expect_args!(args, [int])?;
+        let (x, y) = (self.input.mouse_x(), self.input.mouse_y());
+        match self.room.views.get(view_id as usize) {
+            Some(view) if view_id >= 0 => Ok(view.transform_point(x, y).0.into()),
+            _ => Ok(x.into()),
+        }
     }
 
-    pub fn window_view_mouse_get_y(&self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 1
-        unimplemented!("Called unimplemented kernel function window_view_mouse_get_y")
+    pub fn window_view_mouse_get_y(&self, args: &[Value]) -> gml::Result<Value> {
+        let view_id = expect_args!(args, [int])?;
+        let (x, y) = (self.input.mouse_x(), self.input.mouse_y());
+        match self.room.views.get(view_id as usize) {
+            Some(view) if view_id >= 0 => Ok(view.transform_point(x, y).1.into()),
+            _ => Ok(y.into()),
+        }
     }
 
-    pub fn window_view_mouse_set(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 3
-        unimplemented!("Called unimplemented kernel function window_view_mouse_set")
+    pub fn window_view_mouse_set(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let (view_id, x, y) = expect_args!(args, [int, int, int])?;
+        let pos = match self.room.views.get(view_id as usize) {
+            Some(view) if view_id >= 0 => view.untransform_point(x, y),
+            _ => (x, y),
+        };
+        self.input.mouse_move_to(pos);
+        Ok(Default::default())
     }
 
-    pub fn window_views_mouse_get_x(&self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 0
-        unimplemented!("Called unimplemented kernel function window_views_mouse_get_x")
+    pub fn window_views_mouse_get_x(&self, args: &[Value]) -> gml::Result<Value> {
+        expect_args!(args, [])?;
+        Ok(self.get_mouse_in_room().0.into())
     }
 
-    pub fn window_views_mouse_get_y(&self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 0
-        unimplemented!("Called unimplemented kernel function window_views_mouse_get_y")
+    pub fn window_views_mouse_get_y(&self, args: &[Value]) -> gml::Result<Value> {
+        expect_args!(args, [])?;
+        Ok(self.get_mouse_in_room().1.into())
     }
 
-    pub fn window_views_mouse_set(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 2
-        unimplemented!("Called unimplemented kernel function window_views_mouse_set")
+    pub fn window_views_mouse_set(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let (x, y) = expect_args!(args, [int, int])?;
+        let pos = match self.room.views.iter().find(|view| view.visible) {
+            Some(view) if self.room.views_enabled => view.untransform_point(x, y),
+            _ => (x, y),
+        };
+        self.input.mouse_move_to(pos);
+        Ok(Default::default())
     }
 
     pub fn set_synchronization(&mut self, args: &[Value]) -> gml::Result<Value> {
@@ -2832,7 +2883,7 @@ impl Game {
                 (Real::from(0.0), Real::from(0.0))
             };
             self.last_instance_id += 1;
-            let instance = self.room.instance_list.insert(Instance::new(
+            let instance = self.create_instance(Instance::new(
                 self.last_instance_id,
                 x + relative_x,
                 y + relative_y,
@@ -2856,7 +2907,7 @@ impl Game {
                 (Real::from(0.0), Real::from(0.0))
             };
             self.last_instance_id += 1;
-            let instance = self.room.instance_list.insert(Instance::new(
+            let instance = self.create_instance(Instance::new(
                 self.last_instance_id,
                 x + relative_x,
                 y + relative_y,
@@ -2892,7 +2943,7 @@ impl Game {
             };
             self.last_instance_id += 1;
             let id = self.last_instance_id;
-            let instance = self.room.instance_list.insert(Instance::new(id, x, y, object_id, object));
+            let instance = self.create_instance(Instance::new(id, x, y, object_id, object));
             self.run_instance_event(gml::ev::CREATE, 0, instance, instance, None)?;
         }
         Ok(Default::default())
@@ -3229,7 +3280,9 @@ impl Game {
                 ],
                 5,
             );
-            self.execute(&instructions, &mut new_context)?;
+            self.script_called(script_id);
+            self.execute_unit(Unit::Script(script_id), &instructions, &mut new_context)
+                .map_err(|e| self.in_script(e, script_id))?;
             Ok(new_context.return_value)
         } else {
             Err(gml::Error::NonexistentAsset(asset::Type::Script, script_id))
@@ -3836,22 +3889,30 @@ impl Game {
 
     pub fn random(&mut self, args: &[Value]) -> gml::Result<Value> {
         let bound = expect_args!(args, [real])?;
-        Ok(self.rand.next(bound.into()).into())
+        let result: Value = self.rand.next(bound.into()).into();
+        self.trace(Entry::Random { function: "random", result: result.clone() });
+        Ok(result)
     }
 
     pub fn random_range(&mut self, args: &[Value]) -> gml::Result<Value> {
         let (lower, upper) = expect_args!(args, [real, real])?;
-        Ok((lower.min(upper) + Real::from(self.rand.next((upper - lower).abs().into()))).into())
+        let result: Value = (lower.min(upper) + Real::from(self.rand.next((upper - lower).abs().into()))).into();
+        self.trace(Entry::Random { function: "random_range", result: result.clone() });
+        Ok(result)
     }
 
     pub fn irandom(&mut self, args: &[Value]) -> gml::Result<Value> {
         let bound = expect_args!(args, [int])?;
-        Ok(self.rand.next_int(bound as _).into())
+        let result: Value = self.rand.next_int(bound as _).into();
+        self.trace(Entry::Random { function: "irandom", result: result.clone() });
+        Ok(result)
     }
 
     pub fn irandom_range(&mut self, args: &[Value]) -> gml::Result<Value> {
         let (lower, upper) = expect_args!(args, [int, int])?;
-        Ok((lower.min(upper) + self.rand.next_int((upper - lower).abs() as _)).into())
+        let result: Value = (lower.min(upper) + self.rand.next_int((upper - lower).abs() as _)).into();
+        self.trace(Entry::Random { function: "irandom_range", result: result.clone() });
+        Ok(result)
     }
 
     pub fn random_set_seed(&mut self, args: &[Value]) -> gml::Result<Value> {
@@ -4058,7 +4119,11 @@ impl Game {
 
     pub fn choose(&mut self, args: &[Value]) -> gml::Result<Value> {
         match args.len().checked_sub(1) {
-            Some(i) => Ok(args[self.rand.next_int(i as _) as usize].clone()),
+            Some(i) => {
+                let result = args[self.rand.next_int(i as _) as usize].clone();
+                self.trace(Entry::Random { function: "choose", result: result.clone() });
+                Ok(result)
+            },
             None => Ok(Default::default()),
         }
     }
@@ -5414,7 +5479,7 @@ impl Game {
             .ok_or(gml::Error::NonexistentAsset(asset::Type::Object, object_id))?;
         self.last_instance_id += 1;
         let id = self.last_instance_id;
-        let instance = self.room.instance_list.insert(Instance::new(id, x, y, object_id, object));
+        let instance = self.create_instance(Instance::new(id, x, y, object_id, object));
         self.run_instance_event(gml::ev::CREATE, 0, instance, instance, None)?;
         Ok(id.into())
     }
@@ -5425,7 +5490,7 @@ impl Game {
         self.last_instance_id += 1;
         let id = self.last_instance_id;
         new_instance.id.set(id);
-        let handle = self.room.instance_list.insert(new_instance);
+        let handle = self.create_instance(new_instance);
         if run_event {
             self.run_instance_event(gml::ev::CREATE, 0, handle, handle, None)?;
         }
@@ -5478,6 +5543,7 @@ impl Game {
     pub fn instance_destroy(&mut self, context: &mut Context, args: &[Value]) -> gml::Result<Value> {
         expect_args!(args, [])?;
         self.run_instance_event(gml::ev::DESTROY, 0, context.this, context.this, None)?;
+        self.instance_destroyed(context.this);
         self.room.instance_list.mark_deleted(context.this);
         Ok(Default::default())
     }
@@ -5514,6 +5580,7 @@ impl Game {
         while let Some(handle) = iter.next(&self.room.instance_list) {
             if self.check_collision_point(handle, x, y, true) {
                 self.run_instance_event(gml::ev::DESTROY, 0, handle, handle, None)?;
+                self.instance_destroyed(handle);
                 self.room.instance_list.mark_deleted(handle);
             }
         }
@@ -7343,144 +7410,175 @@ impl Game {
         unimplemented!("Called unimplemented kernel function mouse_wait")
     }
 
-    pub fn mplay_init_ipx(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 0
-        unimplemented!("Called unimplemented kernel function mplay_init_ipx")
+    pub fn mplay_init_ipx(&mut self, args: &[Value]) -> gml::Result<Value> {
+        expect_args!(args, [])?;
+        // Only TCP/IP is supported
+        Ok(false.into())
     }
 
-    pub fn mplay_init_tcpip(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 1
-        unimplemented!("Called unimplemented kernel function mplay_init_tcpip")
+    pub fn mplay_init_tcpip(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let address = expect_args!(args, [string])?;
+        // Network traffic can't be recorded or replayed, so act as if there's no network in a TAS
+        if self.play_type != PlayType::Normal {
+            return Ok(false.into())
+        }
+        Ok(self.mplay.init_tcpip(address.as_ref()).into())
     }
 
-    pub fn mplay_init_modem(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 2
-        unimplemented!("Called unimplemented kernel function mplay_init_modem")
+    pub fn mplay_init_modem(&mut self, args: &[Value]) -> gml::Result<Value> {
+        expect_args!(args, [any, any])?;
+        Ok(false.into())
     }
 
-    pub fn mplay_init_serial(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 5
-        unimplemented!("Called unimplemented kernel function mplay_init_serial")
+    pub fn mplay_init_serial(&mut self, args: &[Value]) -> gml::Result<Value> {
+        expect_args!(args, [any, any, any, any, any])?;
+        Ok(false.into())
     }
 
-    pub fn mplay_connect_status(&self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 0
-        unimplemented!("Called unimplemented kernel function mplay_connect_status")
+    pub fn mplay_connect_status(&self, args: &[Value]) -> gml::Result<Value> {
+        expect_args!(args, [])?;
+        Ok(self.mplay.connect_status().into())
     }
 
-    pub fn mplay_end(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 0
-        unimplemented!("Called unimplemented kernel function mplay_end")
+    pub fn mplay_end(&mut self, args: &[Value]) -> gml::Result<Value> {
+        expect_args!(args, [])?;
+        self.mplay.end();
+        Ok(Default::default())
     }
 
-    pub fn mplay_session_mode(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 1
-        unimplemented!("Called unimplemented kernel function mplay_session_mode")
+    pub fn mplay_session_mode(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let _move_host = expect_args!(args, [bool])?;
+        // TODO: host migration - the session currently ends when the host leaves
+        Ok(Default::default())
     }
 
-    pub fn mplay_session_create(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 3
-        unimplemented!("Called unimplemented kernel function mplay_session_create")
+    pub fn mplay_session_create(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let (name, max_players, player_name) = expect_args!(args, [bytes, int, bytes])?;
+        Ok(self.mplay.session_create(name, max_players.max(0) as u32, player_name).into())
     }
 
-    pub fn mplay_session_find(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 0
-        unimplemented!("Called unimplemented kernel function mplay_session_find")
+    pub fn mplay_session_find(&mut self, args: &[Value]) -> gml::Result<Value> {
+        expect_args!(args, [])?;
+        Ok(self.mplay.session_find().into())
     }
 
-    pub fn mplay_session_name(&self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 1
-        unimplemented!("Called unimplemented kernel function mplay_session_name")
+    pub fn mplay_session_name(&self, args: &[Value]) -> gml::Result<Value> {
+        let index = expect_args!(args, [int])?;
+        Ok(usize::try_from(index).ok().and_then(|i| self.mplay.session_name(i)).unwrap_or_else(|| "".into()).into())
     }
 
-    pub fn mplay_session_join(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 2
-        unimplemented!("Called unimplemented kernel function mplay_session_join")
+    pub fn mplay_session_join(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let (index, player_name) = expect_args!(args, [int, bytes])?;
+        match usize::try_from(index) {
+            Ok(index) => Ok(self.mplay.session_join(index, player_name).into()),
+            Err(_) => Ok(false.into()),
+        }
     }
 
-    pub fn mplay_session_status(&self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 0
-        unimplemented!("Called unimplemented kernel function mplay_session_status")
+    pub fn mplay_session_status(&self, args: &[Value]) -> gml::Result<Value> {
+        expect_args!(args, [])?;
+        Ok(self.mplay.session_status().into())
     }
 
-    pub fn mplay_session_end(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 0
-        unimplemented!("Called unimplemented kernel function mplay_session_end")
+    pub fn mplay_session_end(&mut self, args: &[Value]) -> gml::Result<Value> {
+        expect_args!(args, [])?;
+        self.mplay.session_end();
+        Ok(Default::default())
     }
 
-    pub fn mplay_player_find(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 0
-        unimplemented!("Called unimplemented kernel function mplay_player_find")
+    pub fn mplay_player_find(&mut self, args: &[Value]) -> gml::Result<Value> {
+        expect_args!(args, [])?;
+        self.mplay.poll();
+        Ok(self.mplay.player_count().into())
     }
 
-    pub fn mplay_player_name(&self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 1
-        unimplemented!("Called unimplemented kernel function mplay_player_name")
+    pub fn mplay_player_name(&self, args: &[Value]) -> gml::Result<Value> {
+        let index = expect_args!(args, [int])?;
+        Ok(usize::try_from(index).ok().and_then(|i| self.mplay.player_name(i)).unwrap_or_else(|| "".into()).into())
     }
 
-    pub fn mplay_player_id(&self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 1
-        unimplemented!("Called unimplemented kernel function mplay_player_id")
+    pub fn mplay_player_id(&self, args: &[Value]) -> gml::Result<Value> {
+        let index = expect_args!(args, [int])?;
+        Ok(usize::try_from(index).ok().and_then(|i| self.mplay.player_id(i)).unwrap_or(0).into())
     }
 
-    pub fn mplay_data_write(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 2
-        unimplemented!("Called unimplemented kernel function mplay_data_write")
+    pub fn mplay_data_write(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let (index, value) = expect_args!(args, [int, any])?;
+        self.mplay.data_write(index, value);
+        Ok(Default::default())
     }
 
-    pub fn mplay_data_read(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 1
-        unimplemented!("Called unimplemented kernel function mplay_data_read")
+    pub fn mplay_data_read(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let index = expect_args!(args, [int])?;
+        self.mplay.poll();
+        Ok(self.mplay.data_read(index))
     }
 
-    pub fn mplay_data_mode(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 1
-        unimplemented!("Called unimplemented kernel function mplay_data_mode")
+    pub fn mplay_data_mode(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let guaranteed = expect_args!(args, [bool])?;
+        self.mplay.set_data_mode(guaranteed);
+        Ok(Default::default())
     }
 
-    pub fn mplay_message_send(&self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 3
-        unimplemented!("Called unimplemented kernel function mplay_message_send")
+    pub fn mplay_message_send(&self, args: &[Value]) -> gml::Result<Value> {
+        let (player, id, value) = expect_args!(args, [any, int, any])?;
+        Ok(match self.mplay.resolve_player(&player) {
+            Some(to) => self.mplay.message_send(to, id, value, false),
+            None => false,
+        }
+        .into())
     }
 
-    pub fn mplay_message_send_guaranteed(&self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 3
-        unimplemented!("Called unimplemented kernel function mplay_message_send_guaranteed")
+    pub fn mplay_message_send_guaranteed(&self, args: &[Value]) -> gml::Result<Value> {
+        let (player, id, value) = expect_args!(args, [any, int, any])?;
+        Ok(match self.mplay.resolve_player(&player) {
+            Some(to) => self.mplay.message_send(to, id, value, true),
+            None => false,
+        }
+        .into())
     }
 
-    pub fn mplay_message_receive(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 1
-        unimplemented!("Called unimplemented kernel function mplay_message_receive")
+    pub fn mplay_message_receive(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let player = expect_args!(args, [any])?;
+        self.mplay.poll();
+        Ok(match self.mplay.resolve_player(&player) {
+            Some(from) => self.mplay.message_receive(from),
+            None => false,
+        }
+        .into())
     }
 
-    pub fn mplay_message_id(&self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 0
-        unimplemented!("Called unimplemented kernel function mplay_message_id")
+    pub fn mplay_message_id(&self, args: &[Value]) -> gml::Result<Value> {
+        expect_args!(args, [])?;
+        Ok(self.mplay.last_message().map(|m| m.id).unwrap_or(0).into())
     }
 
-    pub fn mplay_message_value(&self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 0
-        unimplemented!("Called unimplemented kernel function mplay_message_value")
+    pub fn mplay_message_value(&self, args: &[Value]) -> gml::Result<Value> {
+        expect_args!(args, [])?;
+        Ok(self.mplay.last_message().map(|m| m.value.clone()).unwrap_or_default())
     }
 
-    pub fn mplay_message_player(&self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 0
-        unimplemented!("Called unimplemented kernel function mplay_message_player")
+    pub fn mplay_message_player(&self, args: &[Value]) -> gml::Result<Value> {
+        expect_args!(args, [])?;
+        Ok(self.mplay.last_message().map(|m| m.player_id).unwrap_or(0).into())
     }
 
-    pub fn mplay_message_name(&self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 0
-        unimplemented!("Called unimplemented kernel function mplay_message_name")
+    pub fn mplay_message_name(&self, args: &[Value]) -> gml::Result<Value> {
+        expect_args!(args, [])?;
+        Ok(self.mplay.last_message().map(|m| m.player_name.clone()).unwrap_or_else(|| "".into()).into())
     }
 
-    pub fn mplay_message_count(&self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 1
-        unimplemented!("Called unimplemented kernel function mplay_message_count")
+    pub fn mplay_message_count(&self, args: &[Value]) -> gml::Result<Value> {
+        let player = expect_args!(args, [any])?;
+        Ok(self.mplay.resolve_player(&player).map(|from| self.mplay.message_count(from)).unwrap_or(0).into())
     }
 
-    pub fn mplay_message_clear(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 1
-        unimplemented!("Called unimplemented kernel function mplay_message_clear")
+    pub fn mplay_message_clear(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let player = expect_args!(args, [any])?;
+        if let Some(from) = self.mplay.resolve_player(&player) {
+            self.mplay.message_clear(from);
+        }
+        Ok(Default::default())
     }
 
     pub fn mplay_ipaddress(&mut self, args: &[Value]) -> gml::Result<Value> {
@@ -7532,51 +7630,85 @@ impl Game {
         if let (Some(dll_name), Some(fn_name), Some(call_conv), Some(res_type), Some(argnumb)) =
             (args.get(0), args.get(1), args.get(2), args.get(3), args.get(4))
         {
-            let encoding = match self.gm_version {
-                Version::GameMaker8_0 => self.encoding,
-                Version::GameMaker8_1 => encoding_rs::UTF_8,
-            };
-            let gm_dll = gml::String::from(dll_name.clone());
-            let dll = gm_dll.decode(encoding);
-            let gm_function = gml::String::from(fn_name.clone());
-            let function = gm_function.decode(encoding);
-
             let call_conv = match call_conv.round() {
                 0 => external::dll::CallConv::Cdecl,
                 _ => external::dll::CallConv::Stdcall,
             };
-            let res_type = match res_type.round() {
-                0 => external::dll::ValueType::Real,
-                _ => external::dll::ValueType::Str,
-            };
             let argnumb = argnumb.round();
             if args.len() as i32 != 5 + argnumb {
                 return Err(gml::Error::WrongArgumentCount(5 + argnumb.max(5) as usize, args.len()))
             }
-
-            let arg_types = args[5..]
-                .iter()
-                .map(|v| match v.round() {
-                    0 => external::dll::ValueType::Real,
-                    _ => external::dll::ValueType::Str,
-                })
-                .collect::<Vec<_>>();
-
-            self.externals
-                .define(external::dll::ExternalSignature {
-                    dll: dll.into_owned(),
-                    symbol: function.into_owned(),
-                    call_conv,
-                    type_args: arg_types,
-                    type_return: res_type,
-                })
-                .map(Value::from)
-                .map_err(|e| gml::Error::FunctionError("external_define".into(), e))
+            self.define_external("external_define", dll_name, fn_name, call_conv, &args[5..], res_type)
         } else {
             Err(gml::Error::WrongArgumentCount(5, args.len()))
         }
     }
 
+    /// Registers an external with the ExternalManager. Argument and return types are GML's ty_real or ty_string.
+    fn define_external(
+        &mut self,
+        function_name: &str,
+        dll_name: &Value,
+        fn_name: &Value,
+        call_conv: external::dll::CallConv,
+        arg_types: &[Value],
+        res_type: &Value,
+    ) -> gml::Result<Value> {
+        let encoding = match self.gm_version {
+            Version::GameMaker8_0 => self.encoding,
+            Version::GameMaker8_1 => encoding_rs::UTF_8,
+        };
+        let gm_dll = gml::String::from(dll_name.clone());
+        let dll = gm_dll.decode(encoding);
+        let gm_function = gml::String::from(fn_name.clone());
+        let function = gm_function.decode(encoding);
+
+        let value_type = |v: &Value| match v.round() {
+            0 => external::dll::ValueType::Real,
+            _ => external::dll::ValueType::Str,
+        };
+
+        self.externals
+            .define(external::dll::ExternalSignature {
+                dll: dll.into_owned(),
+                symbol: function.into_owned(),
+                call_conv,
+                type_args: arg_types.iter().map(value_type).collect(),
+                type_return: value_type(res_type),
+            })
+            .map(Value::from)
+            .map_err(|e| gml::Error::FunctionError(function_name.into(), e))
+    }
+
+    /// Shared implementation of the fixed-arity `external_define0` to `external_define8` from GM5 and GM6.
+    /// Those take the argument types explicitly for up to four arguments, and above that all arguments are reals.
+    /// The calling convention wasn't configurable back then, it was always stdcall.
+    fn external_define_legacy(&mut self, args: &[Value], argc: usize) -> gml::Result<Value> {
+        let explicit_types = if argc <= 4 { argc } else { 0 };
+        if args.len() != 3 + explicit_types {
+            return Err(gml::Error::WrongArgumentCount(3 + explicit_types, args.len()))
+        }
+        let reals = vec![Value::from(gml_consts::TY_REAL); argc];
+        let arg_types = if argc <= 4 { &args[2..2 + argc] } else { reals.as_slice() };
+        self.define_external(
+            &format!("external_define{}", argc),
+            &args[0],
+            &args[1],
+            external::dll::CallConv::Stdcall,
+            arg_types,
+            &args[2 + explicit_types],
+        )
+    }
+
+    /// Shared implementation of the fixed-arity `external_call0` to `external_call8` from GM5 and GM6.
+    fn external_call_legacy(&mut self, context: &mut Context, args: &[Value], argc: usize) -> gml::Result<Value> {
+        if args.len() != argc + 1 {
+            return Err(gml::Error::WrongArgumentCount(argc + 1, args.len()))
+        }
+        self.call_external(args[0].round(), context, &args[1..])
+            .map_err(|e| gml::Error::FunctionError(format!("external_call{}", argc), e.to_string()))
+    }
+
     pub fn external_call(&mut self, context: &mut Context, args: &[Value]) -> gml::Result<Value> {
         if let Some(id) = args.get(0) {
             let id = id.round();
@@ -7606,94 +7738,76 @@ impl Game {
         Ok((-1).into())
     }
 
-    pub fn external_define0(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 3
-        unimplemented!("Called unimplemented kernel function external_define0")
+    pub fn external_define0(&mut self, args: &[Value]) -> gml::Result<Value> {
+        self.external_define_legacy(args, 0)
     }
 
-    pub fn external_call0(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 1
-        unimplemented!("Called unimplemented kernel function external_call0")
+    pub fn external_call0(&mut self, context: &mut Context, args: &[Value]) -> gml::Result<Value> {
+        self.external_call_legacy(context, args, 0)
     }
 
-    pub fn external_define1(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 4
-        unimplemented!("Called unimplemented kernel function external_define1")
+    pub fn external_define1(&mut self, args: &[Value]) -> gml::Result<Value> {
+        self.external_define_legacy(args, 1)
     }
 
-    pub fn external_call1(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 2
-        unimplemented!("Called unimplemented kernel function external_call1")
+    pub fn external_call1(&mut self, context: &mut Context, args: &[Value]) -> gml::Result<Value> {
+        self.external_call_legacy(context, args, 1)
     }
 
-    pub fn external_define2(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 5
-        unimplemented!("Called unimplemented kernel function external_define2")
+    pub fn external_define2(&mut self, args: &[Value]) -> gml::Result<Value> {
+        self.external_define_legacy(args, 2)
     }
 
-    pub fn external_call2(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 3
-        unimplemented!("Called unimplemented kernel function external_call2")
+    pub fn external_call2(&mut self, context: &mut Context, args: &[Value]) -> gml::Result<Value> {
+        self.external_call_legacy(context, args, 2)
     }
 
-    pub fn external_define3(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 6
-        unimplemented!("Called unimplemented kernel function external_define3")
+    pub fn external_define3(&mut self, args: &[Value]) -> gml::Result<Value> {
+        self.external_define_legacy(args, 3)
     }
 
-    pub fn external_call3(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 4
-        unimplemented!("Called unimplemented kernel function external_call3")
+    pub fn external_call3(&mut self, context: &mut Context, args: &[Value]) -> gml::Result<Value> {
+        self.external_call_legacy(context, args, 3)
     }
 
-    pub fn external_define4(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 7
-        unimplemented!("Called unimplemented kernel function external_define4")
+    pub fn external_define4(&mut self, args: &[Value]) -> gml::Result<Value> {
+        self.external_define_legacy(args, 4)
     }
 
-    pub fn external_call4(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 5
-        unimplemented!("Called unimplemented kernel function external_call4")
+    pub fn external_call4(&mut self, context: &mut Context, args: &[Value]) -> gml::Result<Value> {
+        self.external_call_legacy(context, args, 4)
     }
 
-    pub fn external_define5(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 3
-        unimplemented!("Called unimplemented kernel function external_define5")
+    pub fn external_define5(&mut self, args: &[Value]) -> gml::Result<Value> {
+        self.external_define_legacy(args, 5)
     }
 
-    pub fn external_call5(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 6
-        unimplemented!("Called unimplemented kernel function external_call5")
+    pub fn external_call5(&mut self, context: &mut Context, args: &[Value]) -> gml::Result<Value> {
+        self.external_call_legacy(context, args, 5)
     }
 
-    pub fn external_define6(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 3
-        unimplemented!("Called unimplemented kernel function external_define6")
+    pub fn external_define6(&mut self, args: &[Value]) -> gml::Result<Value> {
+        self.external_define_legacy(args, 6)
     }
 
-    pub fn external_call6(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 7
-        unimplemented!("Called unimplemented kernel function external_call6")
+    pub fn external_call6(&mut self, context: &mut Context, args: &[Value]) -> gml::Result<Value> {
+        self.external_call_legacy(context, args, 6)
     }
 
-    pub fn external_define7(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 3
-        unimplemented!("Called unimplemented kernel function external_define7")
+    pub fn external_define7(&mut self, args: &[Value]) -> gml::Result<Value> {
+        self.external_define_legacy(args, 7)
     }
 
-    pub fn external_call7(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 8
-        unimplemented!("Called unimplemented kernel function external_call7")
+    pub fn external_call7(&mut self, context: &mut Context, args: &[Value]) -> gml::Result<Value> {
+        self.external_call_legacy(context, args, 7)
     }
 
-    pub fn external_define8(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 3
-        unimplemented!("Called unimplemented kernel function external_define8")
+    pub fn external_define8(&mut self, args: &[Value]) -> gml::Result<Value> {
+        self.external_define_legacy(args, 8)
     }
 
-    pub fn external_call8(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 9
-        unimplemented!("Called unimplemented kernel function external_call8")
+    pub fn external_call8(&mut self, context: &mut Context, args: &[Value]) -> gml::Result<Value> {
+        self.external_call_legacy(context, args, 8)
     }
 
     pub fn execute_string(&mut self, context: &mut Context, args: &[Value]) -> gml::Result<Value> {
@@ -7709,7 +7823,7 @@ impl Game {
                     self.execute(&instrs, &mut new_context)?;
                     Ok(new_context.return_value)
                 },
-                Err(e) => Err(gml::Error::FunctionError("execute_string".into(), e.message)),
+                Err(e) => Err(gml::Error::FunctionError("execute_string".into(), e.to_string())),
             }
         } else {
             // eg execute_string(42) - does nothing, returns 0
@@ -7906,19 +8020,37 @@ impl Game {
         self.variable_local_array_set(context, &[identifier, ((index1 * 32000) + index2).into(), value])
     }
 
-    pub fn clipboard_has_text(&self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 0
-        unimplemented!("Called unimplemented kernel function clipboard_has_text")
+    pub fn clipboard_has_text(&self, args: &[Value]) -> gml::Result<Value> {
+        expect_args!(args, [])?;
+        Ok(match self.play_type {
+            PlayType::Normal => host_clipboard_get().map(|s| !s.is_empty()).unwrap_or(false),
+            PlayType::Record | PlayType::Replay => !self.virtual_clipboard.as_ref().is_empty(),
+        }
+        .into())
     }
 
-    pub fn clipboard_set_text(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 1
-        unimplemented!("Called unimplemented kernel function clipboard_set_text")
+    pub fn clipboard_set_text(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let text = expect_args!(args, [bytes])?;
+        match self.play_type {
+            PlayType::Normal => host_clipboard_set(self.decode_str(text.as_ref()).into_owned()),
+            PlayType::Record | PlayType::Replay => self.virtual_clipboard = text,
+        }
+        Ok(Default::default())
     }
 
-    pub fn clipboard_get_text(&self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 0
-        unimplemented!("Called unimplemented kernel function clipboard_get_text")
+    pub fn clipboard_get_text(&self, args: &[Value]) -> gml::Result<Value> {
+        expect_args!(args, [])?;
+        match self.play_type {
+            PlayType::Normal => {
+                let text = host_clipboard_get().unwrap_or_default();
+                let encoded = match self.encode_str_maybe(&text) {
+                    Some(encoded) => encoded.into_owned(),
+                    None => self.encoding.encode(&text).0.into_owned(),
+                };
+                Ok(encoded.into())
+            },
+            PlayType::Record | PlayType::Replay => Ok(self.virtual_clipboard.clone().into()),
+        }
     }
 
     pub fn date_current_datetime(&self, args: &[Value]) -> gml::Result<Value> {
@@ -9357,7 +9489,9 @@ impl Game {
                     *dest = src.clone();
                 }
                 let mut new_context = Context::copy_with_args(context, new_args, args.len() - 1);
-                self.execute(&instructions, &mut new_context)?;
+                self.script_called(script_id);
+                self.execute_unit(Unit::Script(script_id), &instructions, &mut new_context)
+                    .map_err(|e| self.in_script(e, script_id))?;
                 Ok(new_context.return_value)
             } else {
                 Err(gml::Error::NonexistentAsset(asset::Type::Script, script_id))
@@ -9750,7 +9884,7 @@ impl Game {
             let instrs = self
                 .compiler
                 .compile(code.as_ref())
-                .map_err(|e| gml::Error::FunctionError("timeline_moment_add".into(), e.message))?;
+                .map_err(|e| gml::Error::FunctionError("timeline_moment_add".into(), e.to_string()))?;
 
             timeline.moments.borrow_mut().entry(moment).or_insert(Default::default()).borrow_mut().push_code(instrs);
         }
@@ -9971,7 +10105,7 @@ impl Game {
         if let Some(object) = self.assets.objects.get_asset_mut(object_index) {
             let instrs = match self.compiler.compile(code.as_ref()) {
                 Ok(instrs) => instrs,
-                Err(e) => return Err(gml::Error::FunctionError("object_event_add".into(), e.message)),
+                Err(e) => return Err(gml::Error::FunctionError("object_event_add".into(), e.to_string())),
             };
             let object_event_map = &mut object.events[ev_type as usize];
             match object_event_map.get_mut(&(ev_number as u32)) {
@@ -12594,94 +12728,105 @@ impl Game {
         unimplemented!("Called unimplemented kernel function sound_3d_set_sound_cone")
     }
 
-    pub fn cd_init(&self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 0
-        unimplemented!("Called unimplemented kernel function cd_init")
+    pub fn cd_init(&mut self, args: &[Value]) -> gml::Result<Value> {
+        expect_args!(args, [])?;
+        self.cd.init(&mut self.audio);
+        Ok(true.into())
     }
 
-    pub fn cd_present(&self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 0
-        unimplemented!("Called unimplemented kernel function cd_present")
+    pub fn cd_present(&self, args: &[Value]) -> gml::Result<Value> {
+        expect_args!(args, [])?;
+        Ok(self.cd.present().into())
     }
 
-    pub fn cd_number(&self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 0
-        unimplemented!("Called unimplemented kernel function cd_number")
+    pub fn cd_number(&self, args: &[Value]) -> gml::Result<Value> {
+        expect_args!(args, [])?;
+        Ok((self.cd.track_count() as f64).into())
     }
 
-    pub fn cd_playing(&self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 0
-        unimplemented!("Called unimplemented kernel function cd_playing")
+    pub fn cd_playing(&self, args: &[Value]) -> gml::Result<Value> {
+        expect_args!(args, [])?;
+        Ok(self.cd.playing(self.clock.as_nanos()).into())
     }
 
-    pub fn cd_paused(&self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 0
-        unimplemented!("Called unimplemented kernel function cd_paused")
+    pub fn cd_paused(&self, args: &[Value]) -> gml::Result<Value> {
+        expect_args!(args, [])?;
+        Ok(self.cd.paused().into())
     }
 
-    pub fn cd_track(&self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 0
-        unimplemented!("Called unimplemented kernel function cd_track")
+    pub fn cd_track(&self, args: &[Value]) -> gml::Result<Value> {
+        expect_args!(args, [])?;
+        Ok((self.cd.track(self.clock.as_nanos()) as f64).into())
     }
 
-    pub fn cd_length(&self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 0
-        unimplemented!("Called unimplemented kernel function cd_length")
+    pub fn cd_length(&self, args: &[Value]) -> gml::Result<Value> {
+        expect_args!(args, [])?;
+        Ok(((self.cd.length() / 1_000_000) as f64).into())
     }
 
-    pub fn cd_track_length(&self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 1
-        unimplemented!("Called unimplemented kernel function cd_track_length")
+    pub fn cd_track_length(&self, args: &[Value]) -> gml::Result<Value> {
+        let track = expect_args!(args, [int])?;
+        Ok(((self.cd.track_length(track.max(0) as usize) / 1_000_000) as f64).into())
     }
 
-    pub fn cd_position(&self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 0
-        unimplemented!("Called unimplemented kernel function cd_position")
+    pub fn cd_position(&self, args: &[Value]) -> gml::Result<Value> {
+        expect_args!(args, [])?;
+        Ok(((self.cd.position(self.clock.as_nanos()) / 1_000_000) as f64).into())
     }
 
-    pub fn cd_track_position(&self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 0
-        unimplemented!("Called unimplemented kernel function cd_track_position")
+    pub fn cd_track_position(&self, args: &[Value]) -> gml::Result<Value> {
+        expect_args!(args, [])?;
+        Ok(((self.cd.track_position(self.clock.as_nanos()) / 1_000_000) as f64).into())
     }
 
-    pub fn cd_play(&self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 2
-        unimplemented!("Called unimplemented kernel function cd_play")
+    pub fn cd_play(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let (first, last) = expect_args!(args, [int, int])?;
+        self.cd.play(first.max(0) as usize, last.max(0) as usize, self.clock.as_nanos(), &mut self.audio);
+        Ok(Default::default())
     }
 
-    pub fn cd_stop(&self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 0
-        unimplemented!("Called unimplemented kernel function cd_stop")
+    pub fn cd_stop(&mut self, args: &[Value]) -> gml::Result<Value> {
+        expect_args!(args, [])?;
+        self.cd.stop(self.clock.as_nanos(), &mut self.audio);
+        Ok(Default::default())
     }
 
-    pub fn cd_pause(&self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 0
-        unimplemented!("Called unimplemented kernel function cd_pause")
+    pub fn cd_pause(&mut self, args: &[Value]) -> gml::Result<Value> {
+        expect_args!(args, [])?;
+        self.cd.pause(self.clock.as_nanos(), &mut self.audio);
+        Ok(Default::default())
     }
 
-    pub fn cd_resume(&self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 0
-        unimplemented!("Called unimplemented kernel function cd_resume")
+    pub fn cd_resume(&mut self, args: &[Value]) -> gml::Result<Value> {
+        expect_args!(args, [])?;
+        self.cd.resume(self.clock.as_nanos(), &mut self.audio);
+        Ok(Default::default())
     }
 
-    pub fn cd_set_position(&self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 1
-        unimplemented!("Called unimplemented kernel function cd_set_position")
+    pub fn cd_set_position(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let position = expect_args!(args, [int])?;
+        let position = u128::from(position.max(0) as u32) * 1_000_000;
+        self.cd.set_position(position, self.clock.as_nanos(), &mut self.audio);
+        Ok(Default::default())
     }
 
-    pub fn cd_set_track_position(&self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 1
-        unimplemented!("Called unimplemented kernel function cd_set_track_position")
+    pub fn cd_set_track_position(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let position = expect_args!(args, [int])?;
+        let position = u128::from(position.max(0) as u32) * 1_000_000;
+        self.cd.set_track_position(position, self.clock.as_nanos(), &mut self.audio);
+        Ok(Default::default())
     }
 
-    pub fn cd_open_door(&self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 0
-        unimplemented!("Called unimplemented kernel function cd_open_door")
+    pub fn cd_open_door(&mut self, args: &[Value]) -> gml::Result<Value> {
+        expect_args!(args, [])?;
+        self.cd.open_door(self.clock.as_nanos(), &mut self.audio);
+        Ok(Default::default())
     }
 
-    pub fn cd_close_door(&self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 0
-        unimplemented!("Called unimplemented kernel function cd_close_door")
+    pub fn cd_close_door(&mut self, args: &[Value]) -> gml::Result<Value> {
+        expect_args!(args, [])?;
+        self.cd.close_door();
+        Ok(Default::default())
     }
 
     pub fn mci_command(&self, _args: &[Value]) -> gml::Result<Value> {
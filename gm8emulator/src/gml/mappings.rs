@@ -1175,11 +1175,11 @@ pub const FUNCTIONS: phf::OrderedMap<&'static str, Function> = phf_ordered_map!
     "action_partemit_destroy" => Function::Engine(Game::action_partemit_destroy),
     "action_partemit_burst" => Function::Engine(Game::action_partemit_burst),
     "action_partemit_stream" => Function::Engine(Game::action_partemit_stream),
-    "action_cd_play" => Function::Volatile(Game::cd_play),
-    "action_cd_stop" => Function::Volatile(Game::cd_stop),
-    "action_cd_pause" => Function::Volatile(Game::cd_pause),
-    "action_cd_resume" => Function::Volatile(Game::cd_resume),
-    "action_cd_present" => Function::Volatile(Game::cd_present),
+    "action_cd_play" => Function::Engine(Game::cd_play),
+    "action_cd_stop" => Function::Engine(Game::cd_stop),
+    "action_cd_pause" => Function::Engine(Game::cd_pause),
+    "action_cd_resume" => Function::Engine(Game::cd_resume),
+    "action_cd_present" => Function::Constant(Game::cd_present),
     "action_cd_playing" => Function::Volatile(Game::cd_playing),
     "action_set_cursor" => Function::Engine(Game::action_set_cursor),
     "action_webpage" => Function::Engine(Game::action_webpage),
@@ -1578,23 +1578,23 @@ pub const FUNCTIONS: phf::OrderedMap<&'static str, Function> = phf_ordered_map!
     "external_free" => Function::Engine(Game::external_free),
     "get_function_address" => Function::Pure(Game::get_function_address),
     "external_define0" => Function::Engine(Game::external_define0),
-    "external_call0" => Function::Engine(Game::external_call0),
+    "external_call0" => Function::Runtime(Game::external_call0),
     "external_define1" => Function::Engine(Game::external_define1),
-    "external_call1" => Function::Engine(Game::external_call1),
+    "external_call1" => Function::Runtime(Game::external_call1),
     "external_define2" => Function::Engine(Game::external_define2),
-    "external_call2" => Function::Engine(Game::external_call2),
+    "external_call2" => Function::Runtime(Game::external_call2),
     "external_define3" => Function::Engine(Game::external_define3),
-    "external_call3" => Function::Engine(Game::external_call3),
+    "external_call3" => Function::Runtime(Game::external_call3),
     "external_define4" => Function::Engine(Game::external_define4),
-    "external_call4" => Function::Engine(Game::external_call4),
+    "external_call4" => Function::Runtime(Game::external_call4),
     "external_define5" => Function::Engine(Game::external_define5),
-    "external_call5" => Function::Engine(Game::external_call5),
+    "external_call5" => Function::Runtime(Game::external_call5),
     "external_define6" => Function::Engine(Game::external_define6),
-    "external_call6" => Function::Engine(Game::external_call6),
+    "external_call6" => Function::Runtime(Game::external_call6),
     "external_define7" => Function::Engine(Game::external_define7),
-    "external_call7" => Function::Engine(Game::external_call7),
+    "external_call7" => Function::Runtime(Game::external_call7),
     "external_define8" => Function::Engine(Game::external_define8),
-    "external_call8" => Function::Engine(Game::external_call8),
+    "external_call8" => Function::Runtime(Game::external_call8),
     "execute_string" => Function::Runtime(Game::execute_string),
     "execute_file" => Function::Runtime(Game::execute_file),
     "window_handle" => Function::Constant(Game::window_handle),
@@ -2041,24 +2041,24 @@ pub const FUNCTIONS: phf::OrderedMap<&'static str, Function> = phf_ordered_map!
     "sound_3d_set_sound_velocity" => Function::Engine(Game::sound_3d_set_sound_velocity),
     "sound_3d_set_sound_distance" => Function::Engine(Game::sound_3d_set_sound_distance),
     "sound_3d_set_sound_cone" => Function::Engine(Game::sound_3d_set_sound_cone),
-    "cd_init" => Function::Volatile(Game::cd_init),
-    "cd_present" => Function::Volatile(Game::cd_present),
-    "cd_number" => Function::Volatile(Game::cd_number),
+    "cd_init" => Function::Engine(Game::cd_init),
+    "cd_present" => Function::Constant(Game::cd_present),
+    "cd_number" => Function::Constant(Game::cd_number),
     "cd_playing" => Function::Volatile(Game::cd_playing),
-    "cd_paused" => Function::Volatile(Game::cd_paused),
+    "cd_paused" => Function::Constant(Game::cd_paused),
     "cd_track" => Function::Volatile(Game::cd_track),
-    "cd_length" => Function::Volatile(Game::cd_length),
-    "cd_track_length" => Function::Volatile(Game::cd_track_length),
+    "cd_length" => Function::Constant(Game::cd_length),
+    "cd_track_length" => Function::Constant(Game::cd_track_length),
     "cd_position" => Function::Volatile(Game::cd_position),
     "cd_track_position" => Function::Volatile(Game::cd_track_position),
-    "cd_play" => Function::Volatile(Game::cd_play),
-    "cd_stop" => Function::Volatile(Game::cd_stop),
-    "cd_pause" => Function::Volatile(Game::cd_pause),
-    "cd_resume" => Function::Volatile(Game::cd_resume),
-    "cd_set_position" => Function::Volatile(Game::cd_set_position),
-    "cd_set_track_position" => Function::Volatile(Game::cd_set_track_position),
-    "cd_open_door" => Function::Volatile(Game::cd_open_door),
-    "cd_close_door" => Function::Volatile(Game::cd_close_door),
+    "cd_play" => Function::Engine(Game::cd_play),
+    "cd_stop" => Function::Engine(Game::cd_stop),
+    "cd_pause" => Function::Engine(Game::cd_pause),
+    "cd_resume" => Function::Engine(Game::cd_resume),
+    "cd_set_position" => Function::Engine(Game::cd_set_position),
+    "cd_set_track_position" => Function::Engine(Game::cd_set_track_position),
+    "cd_open_door" => Function::Engine(Game::cd_open_door),
+    "cd_close_door" => Function::Engine(Game::cd_close_door),
     "MCI_command" => Function::Volatile(Game::mci_command),
     "d3d_start" => Function::Engine(Game::d3d_start),
     "d3d_end" => Function::Engine(Game::d3d_end),
@@ -1,4 +1,18 @@
-use std::{io, net};
+use crate::gml::{self, Value};
+use byteorder::{ReadBytesExt, WriteBytesExt, LE};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{HashMap, VecDeque},
+    io::{self, Read, Write},
+    net::{self, Ipv4Addr, Shutdown, SocketAddr, TcpListener, TcpStream, ToSocketAddrs, UdpSocket},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{self, Receiver, Sender},
+        Arc, Mutex,
+    },
+    thread,
+    time::{Duration, Instant},
+};
 
 pub fn get_local_ip() -> io::Result<net::IpAddr> {
     // For the meaning of 0.0.0.0, see 'INADDR_ANY'. Port 0 states that we don't expect any
@@ -42,3 +56,723 @@ pub fn get_local_ip() -> io::Result<net::IpAddr> {
     socket.connect(&broadcast[..])?;
     Ok(socket.local_addr()?.ip())
 }
+
+/// UDP port which hosts listen on for session enumeration by default, the same one DirectPlay used.
+pub const DEFAULT_DISCOVERY_PORT: u16 = 47624;
+
+/// Highest index usable with `mplay_data_write` and `mplay_data_read`.
+pub const DATA_MAX_INDEX: i32 = 10000;
+
+/// How long `mplay_session_find` waits for hosts to answer.
+const FIND_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// How long `mplay_session_join` waits for the host to accept or refuse us.
+const JOIN_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// Largest packet we're willing to receive, so a broken peer can't make us allocate gigabytes.
+const MAX_PACKET_SIZE: usize = 1 << 20;
+
+/// Everything that gets sent between emulator instances. Packets are bincode-encoded, and on TCP they're
+/// prefixed with their length as a little-endian u32. Non-guaranteed messages and data go over UDP.
+#[derive(Serialize, Deserialize)]
+enum Packet {
+    /// Broadcast by clients looking for sessions.
+    FindSessions,
+    /// A host's answer to `FindSessions`.
+    SessionInfo { name: gml::String, tcp_port: u16 },
+    /// First thing a client sends after connecting to a host.
+    Join { name: gml::String, udp_port: u16 },
+    /// The host's answer to `Join` if the session had room for the player.
+    Welcome { id: u32, host_udp_port: u16, players: Vec<(u32, gml::String)>, data: Vec<(i32, Value)> },
+    /// The host's answer to `Join` if the session is full.
+    Refused,
+    PlayerJoined { id: u32, name: gml::String },
+    PlayerLeft { id: u32 },
+    Data { index: i32, value: Value },
+    Message { from: u32, to: u32, id: i32, value: Value },
+    /// Sent by the host when it ends the session.
+    SessionEnded,
+}
+
+/// Things the background threads report back to the game thread.
+enum NetEvent {
+    Connected(usize, TcpStream),
+    Packet(usize, Vec<u8>),
+    Closed(usize),
+    Datagram(SocketAddr, Vec<u8>),
+}
+
+/// A session found by `mplay_session_find`.
+struct FoundSession {
+    name: gml::String,
+    address: SocketAddr,
+}
+
+/// A received message, as reported by the `mplay_message_*` functions.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Message {
+    pub id: i32,
+    pub value: Value,
+    pub player_id: u32,
+    pub player_name: gml::String,
+}
+
+/// A client connected to a session we're hosting.
+struct Peer {
+    key: usize,
+    stream: TcpStream,
+    udp: SocketAddr,
+    id: Option<u32>,
+}
+
+enum Role {
+    Host { peers: Vec<Peer>, next_id: u32, max_players: u32, info: Vec<u8>, advert: Arc<Mutex<Option<Vec<u8>>>> },
+    Client { stream: TcpStream, host_udp: SocketAddr },
+}
+
+struct Session {
+    role: Role,
+    udp: UdpSocket,
+    own_id: u32,
+    own_name: gml::String,
+    players: Vec<(u32, gml::String)>,
+    events: Receiver<NetEvent>,
+    stop: Arc<AtomicBool>,
+}
+
+impl Drop for Session {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        match &self.role {
+            Role::Host { peers, .. } => {
+                for peer in peers {
+                    let _ = peer.stream.shutdown(Shutdown::Both);
+                }
+            },
+            Role::Client { stream, .. } => {
+                let _ = stream.shutdown(Shutdown::Both);
+            },
+        }
+    }
+}
+
+/// Reimplementation of GM8's DirectPlay-based multiplayer (the `mplay_*` functions) on a small custom protocol.
+/// Only TCP/IP is supported. Sessions use a star topology, with the host relaying messages and shared data
+/// between clients. Host migration (`mplay_session_mode(true)`) isn't supported.
+pub struct Multiplayer {
+    address: Option<Option<String>>,
+    discovery_port: u16,
+    session: Option<Session>,
+    found: Vec<FoundSession>,
+    data: HashMap<i32, Value>,
+    guaranteed_data: bool,
+    messages: VecDeque<Message>,
+    last_message: Option<Message>,
+}
+
+/// The parts of `Multiplayer` which don't depend on a connection, for savestates. Sessions can't be saved, but
+/// shared data and messages can still be used without one, which is all that's possible in a TAS.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct MultiplayerState {
+    data: HashMap<i32, Value>,
+    guaranteed_data: bool,
+    messages: VecDeque<Message>,
+    last_message: Option<Message>,
+}
+
+fn encode(packet: &Packet) -> Vec<u8> {
+    bincode::serialize(packet).expect("failed to serialize mplay packet")
+}
+
+fn write_packet(mut stream: &TcpStream, packet: &Packet) -> io::Result<()> {
+    let body = encode(packet);
+    let mut buf = Vec::with_capacity(body.len() + 4);
+    buf.write_u32::<LE>(body.len() as u32)?;
+    buf.extend_from_slice(&body);
+    stream.write_all(&buf)
+}
+
+fn read_frame(mut stream: &TcpStream) -> io::Result<Vec<u8>> {
+    let len = stream.read_u32::<LE>()? as usize;
+    if len > MAX_PACKET_SIZE {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "mplay packet too large"))
+    }
+    let mut buf = vec![0; len];
+    stream.read_exact(&mut buf)?;
+    Ok(buf)
+}
+
+fn spawn_reader(key: usize, stream: TcpStream, sender: Sender<NetEvent>) {
+    thread::spawn(move || {
+        while let Ok(frame) = read_frame(&stream) {
+            if sender.send(NetEvent::Packet(key, frame)).is_err() {
+                return
+            }
+        }
+        let _ = sender.send(NetEvent::Closed(key));
+    });
+}
+
+fn spawn_datagram_reader(socket: UdpSocket, sender: Sender<NetEvent>, stop: Arc<AtomicBool>) {
+    thread::spawn(move || {
+        let mut buf = vec![0; 65536];
+        let _ = socket.set_read_timeout(Some(Duration::from_millis(100)));
+        while !stop.load(Ordering::Relaxed) {
+            if let Ok((len, src)) = socket.recv_from(&mut buf) {
+                if sender.send(NetEvent::Datagram(src, buf[..len].to_vec())).is_err() {
+                    return
+                }
+            }
+        }
+    });
+}
+
+impl Multiplayer {
+    pub fn new() -> Self {
+        Self {
+            address: None,
+            discovery_port: DEFAULT_DISCOVERY_PORT,
+            session: None,
+            found: Vec::new(),
+            data: HashMap::new(),
+            guaranteed_data: true,
+            messages: VecDeque::new(),
+            last_message: None,
+        }
+    }
+
+    /// Sets the UDP port that sessions are advertised and searched for on.
+    pub fn set_discovery_port(&mut self, port: u16) {
+        self.discovery_port = port;
+    }
+
+    pub fn state(&self) -> MultiplayerState {
+        MultiplayerState {
+            data: self.data.clone(),
+            guaranteed_data: self.guaranteed_data,
+            messages: self.messages.clone(),
+            last_message: self.last_message.clone(),
+        }
+    }
+
+    pub fn set_state(&mut self, state: MultiplayerState) {
+        self.data = state.data;
+        self.guaranteed_data = state.guaranteed_data;
+        self.messages = state.messages;
+        self.last_message = state.last_message;
+    }
+
+    /// Initializes a TCP/IP connection. The address is only needed to find sessions outside of the local network.
+    pub fn init_tcpip(&mut self, address: &str) -> bool {
+        self.end();
+        let address = address.trim();
+        self.address = Some((!address.is_empty()).then(|| address.to_string()));
+        true
+    }
+
+    /// The `mplay_connect_status` code: 0 for no connection, 2 for TCP/IP.
+    pub fn connect_status(&self) -> i32 {
+        if self.address.is_some() { 2 } else { 0 }
+    }
+
+    pub fn end(&mut self) {
+        self.session_end();
+        self.address = None;
+        self.found.clear();
+    }
+
+    pub fn set_data_mode(&mut self, guaranteed: bool) {
+        self.guaranteed_data = guaranteed;
+    }
+
+    /// The `mplay_session_status` code: 0 for no session, 1 if we created it, 2 if we joined it.
+    pub fn session_status(&self) -> i32 {
+        match self.session.as_ref().map(|s| &s.role) {
+            None => 0,
+            Some(Role::Host { .. }) => 1,
+            Some(Role::Client { .. }) => 2,
+        }
+    }
+
+    pub fn session_create(&mut self, name: gml::String, max_players: u32, player_name: gml::String) -> bool {
+        if self.address.is_none() {
+            return false
+        }
+        self.session_end();
+        let discovery_port = self.discovery_port;
+        let create = || -> io::Result<Session> {
+            let listener = TcpListener::bind((Ipv4Addr::UNSPECIFIED, 0))?;
+            let tcp_port = listener.local_addr()?.port();
+            let udp = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, discovery_port))?;
+            let (sender, events) = mpsc::channel();
+            let stop = Arc::new(AtomicBool::new(false));
+            let info = encode(&Packet::SessionInfo { name, tcp_port });
+            let advert = Arc::new(Mutex::new(Some(info.clone())));
+
+            // Accept connections in the background, the game thread assigns player IDs when it sees them join.
+            listener.set_nonblocking(true)?;
+            let (accept_sender, accept_stop) = (sender.clone(), stop.clone());
+            thread::spawn(move || {
+                let mut key = 0;
+                while !accept_stop.load(Ordering::Relaxed) {
+                    match listener.accept() {
+                        Ok((stream, _)) => {
+                            let _ = stream.set_nonblocking(false);
+                            let _ = stream.set_nodelay(true);
+                            if let Ok(reader) = stream.try_clone() {
+                                spawn_reader(key, reader, accept_sender.clone());
+                                if accept_sender.send(NetEvent::Connected(key, stream)).is_err() {
+                                    return
+                                }
+                                key += 1;
+                            }
+                        },
+                        Err(_) => thread::sleep(Duration::from_millis(50)),
+                    }
+                }
+            });
+
+            // Session enumeration is answered straight from the UDP thread so that it works even while the game
+            // isn't calling any mplay functions. Everything else gets passed on to the game thread.
+            let (udp_reader, udp_advert, udp_stop) = (udp.try_clone()?, advert.clone(), stop.clone());
+            thread::spawn(move || {
+                let mut buf = vec![0; 65536];
+                let _ = udp_reader.set_read_timeout(Some(Duration::from_millis(100)));
+                while !udp_stop.load(Ordering::Relaxed) {
+                    if let Ok((len, src)) = udp_reader.recv_from(&mut buf) {
+                        if let Ok(Packet::FindSessions) = bincode::deserialize(&buf[..len]) {
+                            if let Some(advert) = udp_advert.lock().unwrap().as_ref() {
+                                let _ = udp_reader.send_to(advert, src);
+                            }
+                        } else if sender.send(NetEvent::Datagram(src, buf[..len].to_vec())).is_err() {
+                            return
+                        }
+                    }
+                }
+            });
+
+            Ok(Session {
+                role: Role::Host { peers: Vec::new(), next_id: 2, max_players, info, advert },
+                udp,
+                own_id: 1,
+                own_name: player_name,
+                players: Vec::new(),
+                events,
+                stop,
+            })
+        };
+        match create() {
+            Ok(session) => {
+                self.session = Some(session);
+                true
+            },
+            Err(_) => false,
+        }
+    }
+
+    /// Searches for sessions accepting players, returning how many were found.
+    pub fn session_find(&mut self) -> usize {
+        self.found.clear();
+        let port = self.discovery_port;
+        let targets: Vec<SocketAddr> = match &self.address {
+            Some(Some(address)) => {
+                let address = if address.contains(':') { address.clone() } else { format!("{}:{}", address, port) };
+                address.to_socket_addrs().map(|x| x.collect()).unwrap_or_default()
+            },
+            Some(None) => vec![(Ipv4Addr::BROADCAST, port).into(), (Ipv4Addr::LOCALHOST, port).into()],
+            None => return 0,
+        };
+        let find = |found: &mut Vec<FoundSession>| -> io::Result<()> {
+            let socket = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0))?;
+            let _ = socket.set_broadcast(true);
+            let request = encode(&Packet::FindSessions);
+            for target in &targets {
+                let _ = socket.send_to(&request, target);
+            }
+            let deadline = Instant::now() + FIND_TIMEOUT;
+            let mut buf = vec![0; 65536];
+            while let Some(remaining) = deadline.checked_duration_since(Instant::now()).filter(|d| !d.is_zero()) {
+                socket.set_read_timeout(Some(remaining))?;
+                let (len, src) = match socket.recv_from(&mut buf) {
+                    Ok(x) => x,
+                    Err(_) => break,
+                };
+                if let Ok(Packet::SessionInfo { name, tcp_port }) = bincode::deserialize(&buf[..len]) {
+                    // Hosts on this machine answer both the broadcast and the loopback request
+                    if !found.iter().any(|s| s.address.port() == tcp_port && s.name == name) {
+                        found.push(FoundSession { name, address: SocketAddr::new(src.ip(), tcp_port) });
+                    }
+                }
+            }
+            Ok(())
+        };
+        let _ = find(&mut self.found);
+        self.found.len()
+    }
+
+    pub fn session_name(&self, index: usize) -> Option<gml::String> {
+        self.found.get(index).map(|s| s.name.clone())
+    }
+
+    pub fn session_join(&mut self, index: usize, player_name: gml::String) -> bool {
+        let address = match self.found.get(index) {
+            Some(session) => session.address,
+            None => return false,
+        };
+        self.session_end();
+        let join = || -> io::Result<Option<Session>> {
+            let stream = TcpStream::connect_timeout(&address, JOIN_TIMEOUT)?;
+            let _ = stream.set_nodelay(true);
+            let udp = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0))?;
+            write_packet(&stream, &Packet::Join { name: player_name.clone(), udp_port: udp.local_addr()?.port() })?;
+            stream.set_read_timeout(Some(JOIN_TIMEOUT))?;
+            let welcome = bincode::deserialize(&read_frame(&stream)?);
+            stream.set_read_timeout(None)?;
+            match welcome {
+                Ok(Packet::Welcome { id, host_udp_port, players, data }) => {
+                    let (sender, events) = mpsc::channel();
+                    let stop = Arc::new(AtomicBool::new(false));
+                    spawn_reader(0, stream.try_clone()?, sender.clone());
+                    spawn_datagram_reader(udp.try_clone()?, sender, stop.clone());
+                    self.data = data.into_iter().collect();
+                    Ok(Some(Session {
+                        role: Role::Client { stream, host_udp: SocketAddr::new(address.ip(), host_udp_port) },
+                        udp,
+                        own_id: id,
+                        own_name: player_name,
+                        players,
+                        events,
+                        stop,
+                    }))
+                },
+                _ => Ok(None),
+            }
+        };
+        self.session = join().ok().flatten();
+        self.session.is_some()
+    }
+
+    pub fn session_end(&mut self) {
+        if let Some(session) = &self.session {
+            if let Role::Host { peers, .. } = &session.role {
+                for peer in peers.iter().filter(|p| p.id.is_some()) {
+                    let _ = write_packet(&peer.stream, &Packet::SessionEnded);
+                }
+            }
+        }
+        self.session = None;
+        self.data.clear();
+        self.messages.clear();
+    }
+
+    /// All players in the session, starting with ourselves.
+    fn players(&self) -> impl Iterator<Item = (u32, &gml::String)> {
+        self.session.iter().flat_map(|s| {
+            std::iter::once((s.own_id, &s.own_name)).chain(s.players.iter().map(|(id, name)| (*id, name)))
+        })
+    }
+
+    pub fn player_count(&self) -> usize {
+        self.players().count()
+    }
+
+    pub fn player_name(&self, index: usize) -> Option<gml::String> {
+        self.players().nth(index).map(|(_, name)| name.clone())
+    }
+
+    pub fn player_id(&self, index: usize) -> Option<u32> {
+        self.players().nth(index).map(|(id, _)| id)
+    }
+
+    /// Turns a GML player argument (an ID or a name) into a player ID, with 0 meaning everybody.
+    /// Negative IDs don't belong to anybody.
+    pub fn resolve_player(&self, player: &Value) -> Option<u32> {
+        match player {
+            Value::Real(id) if id.round().to_i32() == 0 => Some(0),
+            Value::Real(id) => u32::try_from(id.round().to_i32()).ok(),
+            Value::Str(name) => self.players().find(|(_, n)| n.eq_ignore_ascii_case(name.as_ref())).map(|(id, _)| id),
+        }
+    }
+
+    pub fn data_read(&self, index: i32) -> Value {
+        self.data.get(&index).cloned().unwrap_or_default()
+    }
+
+    pub fn data_write(&mut self, index: i32, value: Value) {
+        if !(0..=DATA_MAX_INDEX).contains(&index) {
+            return
+        }
+        self.data.insert(index, value.clone());
+        if let Some(session) = &self.session {
+            send_all(&session.role, &session.udp, &Packet::Data { index, value }, self.guaranteed_data, None);
+        }
+    }
+
+    /// Sends a message to a player, or to everybody else if `to` is 0. Returns whether it could be sent.
+    pub fn message_send(&self, to: u32, id: i32, value: Value, guaranteed: bool) -> bool {
+        match &self.session {
+            Some(session) if to != session.own_id => {
+                let packet = Packet::Message { from: session.own_id, to, id, value };
+                match &session.role {
+                    Role::Host { peers, .. } if to != 0 => match peers.iter().find(|p| p.id == Some(to)) {
+                        Some(peer) => send_peer(&session.udp, peer, &packet, guaranteed),
+                        None => false,
+                    },
+                    role => send_all(role, &session.udp, &packet, guaranteed, None),
+                }
+            },
+            _ => false,
+        }
+    }
+
+    pub fn message_receive(&mut self, from: u32) -> bool {
+        match self.messages.iter().position(|m| from == 0 || m.player_id == from) {
+            Some(index) => {
+                self.last_message = self.messages.remove(index);
+                true
+            },
+            None => false,
+        }
+    }
+
+    pub fn message_count(&self, from: u32) -> usize {
+        self.messages.iter().filter(|m| from == 0 || m.player_id == from).count()
+    }
+
+    pub fn message_clear(&mut self, from: u32) {
+        self.messages.retain(|m| from != 0 && m.player_id != from);
+    }
+
+    pub fn last_message(&self) -> Option<&Message> {
+        self.last_message.as_ref()
+    }
+
+    /// Processes everything that arrived from the network since the last call.
+    pub fn poll(&mut self) {
+        let mut session = match self.session.take() {
+            Some(session) => session,
+            None => return,
+        };
+        let mut ended = false;
+        while let Ok(event) = session.events.try_recv() {
+            match event {
+                NetEvent::Connected(key, stream) => {
+                    if let Role::Host { peers, .. } = &mut session.role {
+                        // The UDP port gets filled in once the client tells us about it
+                        let ip = stream.peer_addr().map(|a| a.ip()).unwrap_or(Ipv4Addr::LOCALHOST.into());
+                        peers.push(Peer { key, stream, udp: SocketAddr::new(ip, 0), id: None });
+                    }
+                },
+                NetEvent::Packet(key, frame) => {
+                    if let Ok(packet) = bincode::deserialize(&frame) {
+                        ended |= self.handle_packet(&mut session, Some(key), packet, true);
+                    }
+                },
+                NetEvent::Datagram(src, datagram) => {
+                    let key = match &session.role {
+                        Role::Host { peers, .. } => match peers.iter().find(|p| p.id.is_some() && p.udp == src) {
+                            Some(peer) => Some(peer.key),
+                            None => continue,
+                        },
+                        Role::Client { host_udp, .. } if *host_udp == src => None,
+                        Role::Client { .. } => continue,
+                    };
+                    if let Ok(packet @ (Packet::Data { .. } | Packet::Message { .. })) = bincode::deserialize(&datagram) {
+                        ended |= self.handle_packet(&mut session, key, packet, false);
+                    }
+                },
+                NetEvent::Closed(key) => match &mut session.role {
+                    Role::Host { peers, .. } => {
+                        if let Some(index) = peers.iter().position(|p| p.key == key) {
+                            let peer = peers.remove(index);
+                            if let Some(id) = peer.id {
+                                session.players.retain(|(i, _)| *i != id);
+                                send_all(&session.role, &session.udp, &Packet::PlayerLeft { id }, true, None);
+                            }
+                        }
+                    },
+                    Role::Client { .. } => ended = true,
+                },
+            }
+        }
+
+        // Stop advertising the session once it's full
+        if let Role::Host { max_players, info, advert, .. } = &session.role {
+            let open = *max_players == 0 || (session.players.len() as u32) + 1 < *max_players;
+            *advert.lock().unwrap() = open.then(|| info.clone());
+        }
+
+        if ended {
+            self.data.clear();
+        } else {
+            self.session = Some(session);
+        }
+    }
+
+    /// Handles one packet, which came either from the peer with the given key (as host) or from the host (as client).
+    /// Returns true if the session has ended.
+    fn handle_packet(&mut self, session: &mut Session, key: Option<usize>, packet: Packet, guaranteed: bool) -> bool {
+        let Session { role, udp, own_id, own_name, players, .. } = session;
+        match (&mut *role, packet) {
+            (Role::Host { peers, next_id, max_players, .. }, Packet::Join { name, udp_port }) => {
+                let full = *max_players != 0 && players.len() as u32 + 1 >= *max_players;
+                let peer = match peers.iter_mut().find(|p| Some(p.key) == key && p.id.is_none()) {
+                    Some(peer) => peer,
+                    None => return false,
+                };
+                if full {
+                    let _ = write_packet(&peer.stream, &Packet::Refused);
+                    let _ = peer.stream.shutdown(Shutdown::Both);
+                    return false
+                }
+                let id = *next_id;
+                *next_id += 1;
+                peer.id = Some(id);
+                peer.udp.set_port(udp_port);
+                let welcome = Packet::Welcome {
+                    id,
+                    host_udp_port: udp.local_addr().map(|a| a.port()).unwrap_or(self.discovery_port),
+                    players: std::iter::once((*own_id, own_name.clone())).chain(players.iter().cloned()).collect(),
+                    data: self.data.iter().map(|(k, v)| (*k, v.clone())).collect(),
+                };
+                let _ = write_packet(&peer.stream, &welcome);
+                send_all(role, udp, &Packet::PlayerJoined { id, name: name.clone() }, true, Some(id));
+                players.push((id, name));
+            },
+            (Role::Host { peers, .. }, Packet::Data { index, value }) => {
+                let from = match peers.iter().find(|p| Some(p.key) == key).and_then(|p| p.id) {
+                    Some(from) => from,
+                    None => return false,
+                };
+                if (0..=DATA_MAX_INDEX).contains(&index) {
+                    self.data.insert(index, value.clone());
+                    send_all(role, udp, &Packet::Data { index, value }, guaranteed, Some(from));
+                }
+            },
+            (Role::Host { peers, .. }, Packet::Message { to, id, value, .. }) => {
+                let from = match peers.iter().find(|p| Some(p.key) == key).and_then(|p| p.id) {
+                    Some(from) => from,
+                    None => return false,
+                };
+                if to == 0 || to == *own_id {
+                    let player_name = players.iter().find(|(i, _)| *i == from).map(|(_, n)| n.clone());
+                    let player_name = player_name.unwrap_or_else(|| "".into());
+                    self.messages.push_back(Message { id, value: value.clone(), player_id: from, player_name });
+                }
+                let packet = Packet::Message { from, to, id, value };
+                if to == 0 {
+                    send_all(role, udp, &packet, guaranteed, Some(from));
+                } else if let Some(peer) = peers.iter().find(|p| to != *own_id && p.id == Some(to)) {
+                    send_peer(udp, peer, &packet, guaranteed);
+                }
+            },
+            (Role::Client { .. }, Packet::PlayerJoined { id, name }) => players.push((id, name)),
+            (Role::Client { .. }, Packet::PlayerLeft { id }) => players.retain(|(i, _)| *i != id),
+            (Role::Client { .. }, Packet::Data { index, value }) => {
+                self.data.insert(index, value);
+            },
+            (Role::Client { .. }, Packet::Message { from, id, value, .. }) => {
+                let player_name = players.iter().find(|(i, _)| *i == from).map(|(_, n)| n.clone());
+                let player_name = player_name.unwrap_or_else(|| "".into());
+                self.messages.push_back(Message { id, value, player_id: from, player_name });
+            },
+            (Role::Client { .. }, Packet::SessionEnded) => return true,
+            _ => (),
+        }
+        false
+    }
+}
+
+/// Sends a packet to everybody we're directly connected to, except for the given player.
+fn send_all(role: &Role, udp: &UdpSocket, packet: &Packet, guaranteed: bool, except: Option<u32>) -> bool {
+    match role {
+        Role::Host { peers, .. } => peers
+            .iter()
+            .filter(|p| p.id.is_some() && p.id != except)
+            .filter(|peer| !send_peer(udp, peer, packet, guaranteed))
+            .count()
+            == 0,
+        Role::Client { stream, host_udp } => {
+            if guaranteed {
+                write_packet(stream, packet).is_ok()
+            } else {
+                udp.send_to(&encode(packet), host_udp).is_ok()
+            }
+        },
+    }
+}
+
+fn send_peer(udp: &UdpSocket, peer: &Peer, packet: &Packet, guaranteed: bool) -> bool {
+    if guaranteed {
+        write_packet(&peer.stream, packet).is_ok()
+    } else {
+        udp.send_to(&encode(packet), peer.udp).is_ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Keeps polling until `done` returns true, or gives up after a few seconds.
+    fn poll_until(mplay: &mut Multiplayer, mut done: impl FnMut(&mut Multiplayer) -> bool) -> bool {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while Instant::now() < deadline {
+            mplay.poll();
+            if done(mplay) {
+                return true
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        false
+    }
+
+    #[test]
+    fn resolve_player_ids() {
+        let mplay = Multiplayer::new();
+        assert_eq!(mplay.resolve_player(&0.0.into()), Some(0));
+        assert_eq!(mplay.resolve_player(&3.0.into()), Some(3));
+        assert_eq!(mplay.resolve_player(&(-1.0).into()), None);
+        assert_eq!(mplay.resolve_player(&"nobody".into()), None);
+    }
+
+    #[test]
+    fn loopback_session() {
+        // Use a free port rather than the default, which something else on the machine might be using
+        let port = UdpSocket::bind((Ipv4Addr::LOCALHOST, 0)).unwrap().local_addr().unwrap().port();
+        let mut host = Multiplayer::new();
+        host.set_discovery_port(port);
+        assert!(host.init_tcpip(""));
+        assert!(host.session_create("session".into(), 0, "host".into()));
+        host.data_write(5, 12.0.into());
+
+        // Joining blocks until the host answers, so the client gets its own thread. GML strings can't be sent
+        // between threads, so it reports back with plain values.
+        let client = thread::spawn(move || {
+            let mut client = Multiplayer::new();
+            client.set_discovery_port(port);
+            assert!(client.init_tcpip("127.0.0.1"));
+            assert_eq!(client.session_find(), 1);
+            assert_eq!(client.session_name(0).unwrap().as_ref(), b"session");
+            assert!(client.session_join(0, "client".into()));
+            assert_eq!(client.session_status(), 2);
+            let data = client.data_read(5);
+            assert!(client.message_send(0, 1, "hello".into(), true));
+            assert!(poll_until(&mut client, |c| c.message_receive(0)));
+            let message = client.last_message().unwrap();
+            (client.player_count(), data == 12.0.into(), message.id, message.player_id, message.value == 3.0.into())
+        });
+
+        assert!(poll_until(&mut host, |h| h.player_count() == 2));
+        assert_eq!(host.player_name(1).unwrap().as_ref(), b"client");
+        assert!(poll_until(&mut host, |h| h.message_receive(0)));
+        let message = host.last_message().unwrap();
+        assert_eq!(message.id, 1);
+        assert_eq!(message.player_name.as_ref(), b"client");
+        let client_id = message.player_id;
+        assert!(host.message_send(client_id, 2, 3.0.into(), true));
+
+        assert_eq!(client.join().unwrap(), (2, true, 2, 1, true));
+        host.end();
+    }
+}
@@ -1,14 +1,16 @@
 use crate::{
     asset,
-    game::{Game, GameClock, GetAsset, SceneChange, Version},
+    game::{profiler::Key, trace::Entry, Game, GameClock, GetAsset, SceneChange, Version},
     gml::{
         self,
         datetime::DateTime,
+        debugger::Unit,
         mappings::{self, constants as gml_constants},
         Context, InstanceVariable, Value,
     },
-    instance::Field,
+    instance::{Field, Instance},
     math::Real,
+    types::ID,
 };
 use gml_parser::token::Operator;
 use serde::{Deserialize, Serialize};
@@ -36,6 +38,9 @@ pub enum Instruction {
     With { target: Node, body: Box<[Instruction]> },
     GlobalVar { fields: Vec<usize> },
     RuntimeError { error: Error },
+
+    /// Marks where a new line of code starts, for the debugger.
+    Line { line: usize },
 }
 
 /// Node representing one value in an expression.
@@ -181,6 +186,56 @@ pub enum Error {
     BadDirectoryError(String),
     ExternalFunction(String, String),
     InvalidExternal(i32),
+    DebuggerStopped,
+    AtLine(Box<Error>, usize),
+    Traced(Box<Error>, Vec<(Frame, Option<usize>)>),
+}
+
+/// Something which was executing GML when a runtime error happened.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum Frame {
+    /// A script, by name.
+    Script(String),
+
+    /// Anything else which runs code, described like in GM8's error messages,
+    /// such as "action number 1 of Step Event for object obj_player".
+    Code(String),
+}
+
+impl Error {
+    /// Records the line of code this error happened on, to be put in the next frame it's added to.
+    /// Code blocks can be nested, so the line from the innermost one is kept.
+    pub fn at_line(self, line: usize) -> Self {
+        match self {
+            Self::AtLine(..) => self,
+            error => Self::AtLine(Box::new(error), line),
+        }
+    }
+
+    /// Records that this error happened inside the given frame, so that it can be reported with a call stack.
+    /// Frames should be added from the innermost outwards, as the error is passed up.
+    pub fn in_frame(self, frame: Frame) -> Self {
+        let (error, line) = match self {
+            Self::AtLine(error, line) => (*error, Some(line)),
+            error => (error, None),
+        };
+        match error {
+            Self::Traced(error, mut frames) => {
+                frames.push((frame, line));
+                Self::Traced(error, frames)
+            },
+            error => Self::Traced(Box::new(error), vec![(frame, line)]),
+        }
+    }
+}
+
+impl Display for Frame {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Script(name) => write!(f, "script {}", name),
+            Self::Code(description) => write!(f, "{}", description),
+        }
+    }
 }
 
 impl std::error::Error for Error {}
@@ -228,6 +283,26 @@ impl Display for Error {
             Self::BadDirectoryError(s) => write!(f, "cannot encode working directory {} with current encoding", s),
             Self::ExternalFunction(s, e) => write!(f, "failed to call external function \"{}\": {}", s, e),
             Self::InvalidExternal(i) => write!(f, "tried to call nonexistent external function with id {}", i),
+            Self::DebuggerStopped => write!(f, "stopped by the debugger"),
+            Self::AtLine(error, line) => write!(f, "{} (at line {})", error, line),
+            Self::Traced(error, frames) => {
+                // Like GM8, name the event or piece of code the error happened in, even if it was inside a script
+                let location = frames.iter().find(|(x, _)| matches!(x, Frame::Code(_))).or(frames.first());
+                match location {
+                    Some((location, Some(line))) => write!(f, "ERROR in {} at line {}: {}", location, line, error)?,
+                    Some((location, None)) => write!(f, "ERROR in {}: {}", location, error)?,
+                    None => write!(f, "{}", error)?,
+                }
+                if frames.len() > 1 {
+                    for (frame, line) in frames {
+                        match line {
+                            Some(line) => write!(f, "\n    in {} at line {}", frame, line)?,
+                            None => write!(f, "\n    in {}", frame)?,
+                        }
+                    }
+                }
+                Ok(())
+            },
         }
     }
 }
@@ -262,6 +337,7 @@ impl fmt::Debug for Instruction {
             Instruction::With { target, body } => write!(f, "With({:?}, {:?})", target, body),
             Instruction::GlobalVar { fields } => write!(f, "GlobalVar({:?})", fields),
             Instruction::RuntimeError { error } => write!(f, "RuntimeError({:?})", error),
+            Instruction::Line { line } => write!(f, "Line({})", line),
         }
     }
 }
@@ -351,15 +427,54 @@ impl UnaryOperator {
 
 impl Game {
     pub fn execute(&mut self, instructions: &[Instruction], context: &mut Context) -> gml::Result<ReturnType> {
+        let mut line = None;
         for instruction in instructions.iter() {
-            match self.exec_instruction(instruction, context)? {
-                ReturnType::Normal => (),
-                r => return Ok(r),
+            if let Instruction::Line { line: next } = instruction {
+                line = Some(*next);
+            }
+            match self.exec_instruction(instruction, context) {
+                Ok(ReturnType::Normal) => (),
+                Ok(r) => return Ok(r),
+                Err(e) => return Err(if let Some(line) = line { e.at_line(line) } else { e }),
             }
         }
         Ok(ReturnType::Normal)
     }
 
+    /// Notes that a script is about to be run, for the TAS UI's script breakpoint and the trace.
+    #[inline(always)]
+    pub fn script_called(&mut self, script_id: ID) {
+        if self.script_breakpoint == Some(script_id) {
+            self.script_breakpoint_hit = true;
+        }
+        self.trace(Entry::Script(script_id));
+    }
+
+    /// Adds a newly created instance to the room, noting it for the TAS UI's advance-until and the trace,
+    /// and returns its handle. Its create event isn't run, since when that happens depends on what's creating it.
+    pub fn create_instance(&mut self, instance: Instance) -> usize {
+        if self.create_breakpoint && self.created_instance.is_none() {
+            self.created_instance = Some(instance.id.get());
+        }
+        let handle = self.room.instance_list.insert(instance);
+        self.trace(Entry::Create(handle));
+        handle
+    }
+
+    /// Notes that an instance's destroy event has run, for the TAS UI's advance-until and the trace.
+    pub fn instance_destroyed(&mut self, handle: usize) {
+        if self.destroy_breakpoint && self.destroyed_instance.is_none() {
+            self.destroyed_instance = Some(self.room.instance_list.get(handle).id.get());
+        }
+        self.trace(Entry::Destroy(handle));
+    }
+
+    /// Adds a script to a runtime error's call stack.
+    pub fn in_script(&self, error: Error, script_id: ID) -> Error {
+        let name = self.assets.scripts.get_asset(script_id).map(|x| x.name.to_string());
+        error.in_frame(Frame::Script(name.unwrap_or_else(|| script_id.to_string())))
+    }
+
     fn exec_instruction(&mut self, instruction: &Instruction, context: &mut Context) -> gml::Result<ReturnType> {
         match instruction {
             Instruction::SetField { accessor, value } => {
@@ -598,6 +713,7 @@ impl Game {
                 }
             },
             Instruction::RuntimeError { error } => return Err(error.clone()),
+            Instruction::Line { line } => self.debug_line(*line, context)?,
         }
 
         Ok(ReturnType::Normal)
@@ -618,28 +734,32 @@ impl Game {
                 for (src, dest) in args.iter().zip(arg_values.iter_mut()) {
                     *dest = self.eval(src, context)?;
                 }
-                function.0(self, context, &arg_values[..args.len()])
+                let key = Key::Function(function.0 as usize);
+                self.profile(key, |game| function.0(game, context, &arg_values[..args.len()]))
             },
             Node::StateFunction { args, function } => {
                 let mut arg_values: [Value; 16] = Default::default();
                 for (src, dest) in args.iter().zip(arg_values.iter_mut()) {
                     *dest = self.eval(src, context)?;
                 }
-                function.0(self, &arg_values[..args.len()])
+                let key = Key::Function(function.0 as usize);
+                self.profile(key, |game| function.0(game, &arg_values[..args.len()]))
             },
             Node::RoutineFunction { args, function } => {
                 let mut arg_values: [Value; 16] = Default::default();
                 for (src, dest) in args.iter().zip(arg_values.iter_mut()) {
                     *dest = self.eval(src, context)?;
                 }
-                function.0(self, &arg_values[..args.len()])
+                let key = Key::Function(function.0 as usize);
+                self.profile(key, |game| function.0(game, &arg_values[..args.len()]))
             },
             Node::ValueFunction { args, function } => {
                 let mut arg_values: [Value; 16] = Default::default();
                 for (src, dest) in args.iter().zip(arg_values.iter_mut()) {
                     *dest = self.eval(src, context)?;
                 }
-                function.0(&arg_values[..args.len()])
+                let key = Key::Function(function.0 as usize);
+                self.profile(key, |_| function.0(&arg_values[..args.len()]))
             },
             Node::Script { args, script_id } => {
                 if let Some(Some(script)) = self.assets.scripts.get(*script_id) {
@@ -651,7 +771,9 @@ impl Game {
                     }
 
                     let mut new_context = Context::copy_with_args(context, arg_values, args.len());
-                    self.execute(&instructions, &mut new_context)?;
+                    self.script_called(*script_id as ID);
+                    self.execute_unit(Unit::Script(*script_id as ID), &instructions, &mut new_context)
+                        .map_err(|e| self.in_script(e, *script_id as ID))?;
                     Ok(new_context.return_value)
                 } else {
                     Err(Error::NonexistentAsset(asset::Type::Script, *script_id as i32))
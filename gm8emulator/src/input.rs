@@ -1,6 +1,7 @@
 use crate::types::ArraySerde;
 use serde::{Deserialize, Serialize};
 use std::{
+    cell::Cell,
     fmt::{
         Display,
         Error,
@@ -1117,6 +1118,10 @@ pub struct Input {
     mouse_previous: i8,
     mouse_position_previous: (i32, i32),
     numlock_state: bool, // spoofed!
+
+    // whether the game's looked at any input since this was cleared, for telling which frames are lag frames
+    #[serde(skip)]
+    polled: Cell<bool>,
 }
 
 impl Input {
@@ -1134,6 +1139,7 @@ impl Input {
             mouse_previous: 0,
             mouse_position_previous: (0, 0),
             numlock_state: false,
+            polled: Cell::new(false),
         }
     }
 
@@ -1195,6 +1201,7 @@ impl Input {
     // == GameMaker Mappings ==
 
     fn keyboard_check_any_internal_indirect(&self, state: &[bool; KEY_MAX]) -> bool {
+        self.polled.set(true);
         state.iter().enumerate().any(|(vk, flag)| match vk {
             vk if vk == Button::Shift as usize => {
                 state[Button::LeftShift as usize] || state[Button::RightShift as usize]
@@ -1209,6 +1216,7 @@ impl Input {
     }
 
     fn keyboard_check_internal(&self, state: &[bool; KEY_MAX], vk: u8) -> bool {
+        self.polled.set(true);
         if vk == Button::Shift as u8 {
             state[Button::LeftShift as usize] || state[Button::RightShift as usize]
         } else if vk == Button::Control as u8 {
@@ -1316,11 +1324,13 @@ impl Input {
 
     #[inline]
     pub fn keyboard_key(&self) -> u8 {
+        self.polled.set(true);
         self.key_current
     }
 
     #[inline]
     pub fn keyboard_lastkey(&self) -> u8 {
+        self.polled.set(true);
         self.key_previous
     }
 
@@ -1335,6 +1345,7 @@ impl Input {
     }
 
     fn mouse_check_button_internal_indirect(&self, state: &[bool; KEY_MAX], mb: i8) -> bool {
+        self.polled.set(true);
         match mb {
             MB_ANY => {
                 state[Button::MouseLeft as usize]
@@ -1357,11 +1368,13 @@ impl Input {
 
     #[inline]
     pub fn mouse_button(&self) -> i8 {
+        self.polled.set(true);
         self.mouse_current
     }
 
     #[inline]
     pub fn mouse_lastbutton(&self) -> i8 {
+        self.polled.set(true);
         self.mouse_previous
     }
 
@@ -1426,31 +1439,37 @@ impl Input {
 
     #[inline]
     pub fn mouse_wheel_up(&self) -> bool {
+        self.polled.set(true);
         self.mouse_wheel.0
     }
 
     #[inline]
     pub fn mouse_wheel_down(&self) -> bool {
+        self.polled.set(true);
         self.mouse_wheel.1
     }
 
     #[inline]
     pub fn mouse_x(&self) -> i32 {
+        self.polled.set(true);
         self.mouse_position.0
     }
 
     #[inline]
     pub fn mouse_y(&self) -> i32 {
+        self.polled.set(true);
         self.mouse_position.1
     }
 
     #[inline]
     pub fn mouse_x_previous(&self) -> i32 {
+        self.polled.set(true);
         self.mouse_position_previous.0
     }
 
     #[inline]
     pub fn mouse_y_previous(&self) -> i32 {
+        self.polled.set(true);
         self.mouse_position_previous.1
     }
 
@@ -1466,6 +1485,27 @@ impl Input {
         self.mouse_wheel = (false, false);
     }
 
+    /// Whether anything's looked at the input since clear_polled() was last called.
+    pub fn polled(&self) -> bool {
+        self.polled.get()
+    }
+
+    pub fn clear_polled(&self) {
+        self.polled.set(false);
+    }
+
+    pub fn mark_polled(&self) {
+        self.polled.set(true);
+    }
+
+    /// Looks at the input without it counting as being polled, for checks the engine makes by itself.
+    pub fn peek<T>(&self, f: impl FnOnce(&Self) -> T) -> T {
+        let polled = self.polled.get();
+        let result = f(self);
+        self.polled.set(polled);
+        result
+    }
+
     /// Hard reset, clearing all state.
     pub fn reset(&mut self) {
         *self = Self::new();
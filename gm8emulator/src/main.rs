@@ -1,5 +1,6 @@
 mod action;
 mod asset;
+mod compat;
 mod game;
 mod gml;
 mod handleman;
@@ -15,11 +16,17 @@ mod types;
 mod util;
 
 use game::{
+    capture_overlay::CaptureOverlay,
+    ghost::GhostRecorder,
+    profiler::Profiler,
+    recording,
     savestate::{self, SaveState},
+    trace::Trace,
     Game, GameClock, PlayType, Replay,
 };
 use std::{
     env, fs,
+    ops::Range,
     path::{Path, PathBuf},
     process,
 };
@@ -44,6 +51,57 @@ fn main() {
     process::exit(xmain());
 }
 
+/// Loads a replay from either a .gmtas or the replay stored in a .bin savestate.
+fn load_replay(filepath: &Path) -> Result<Replay, String> {
+    match filepath.extension().and_then(|x| x.to_str()) {
+        Some("bin") => match SaveState::from_file(&filepath.to_path_buf(), &mut savestate::Buffer::new()) {
+            Ok(state) => Ok(state.into_replay()),
+            Err(e) => Err(format!("couldn't load {:?}: {:?}", filepath, e)),
+        },
+
+        Some("gmtas") => match Replay::from_file(&filepath.to_path_buf()) {
+            Ok(replay) => Ok(replay),
+            Err(e) => Err(format!("couldn't load {:?}: {:?}", filepath, e)),
+        },
+
+        _ => Err(format!("unknown filetype for {:?}, expected '.bin' or '.gmtas'", filepath)),
+    }
+}
+
+/// Parses a range of frames written as "START-END", which includes END like the TAS UI's selections do.
+fn parse_frames(text: &str) -> Result<Range<usize>, String> {
+    let (start, end) = text.split_once('-').ok_or_else(|| format!("expected START-END, got {}", text))?;
+    let frame = |x: &str| x.parse::<usize>().map_err(|_| format!("invalid frame number {}", x));
+    Ok(frame(start)?..frame(end)? + 1)
+}
+
+/// Applies the --append, --splice and --shift edits to a replay, in that order.
+fn edit_replay(replay: &mut Replay, matches: &getopts::Matches) -> Result<(), String> {
+    if let Some(arg) = matches.opt_str("append") {
+        // Split from the right, since there could be a colon in the path
+        let (file, at) = arg.rsplit_once(':').ok_or("expected FILE:FRAME for --append")?;
+        let at = at.parse::<usize>().map_err(|_| format!("invalid frame number {}", at))?;
+        replay.append(&load_replay(Path::new(file))?, at).map_err(|e| format!("can't append: {}", e))?;
+    }
+    if let Some(arg) = matches.opt_str("splice") {
+        let (rest, last) = arg.rsplit_once(':').ok_or("expected FILE:START-END[:AT-END] for --splice")?;
+        let last = parse_frames(last)?;
+        // Without AT-END, the frames go in the same place they were in the other replay
+        let (file, source, dest) = match rest.rsplit_once(':').map(|(file, source)| (file, parse_frames(source))) {
+            Some((file, Ok(source))) => (file, source, last),
+            _ => (rest, last.clone(), last),
+        };
+        let other = load_replay(Path::new(file))?;
+        replay.splice(&other, source, dest).map_err(|e| format!("can't splice: {}", e))?;
+    }
+    if let Some(arg) = matches.opt_str("shift") {
+        let (frames, offset) = arg.rsplit_once(':').ok_or("expected START-END:OFFSET for --shift")?;
+        let offset = offset.parse::<isize>().map_err(|_| format!("invalid offset {}", offset))?;
+        replay.shift_inputs(parse_frames(frames)?, offset).map_err(|e| format!("can't shift: {}", e))?;
+    }
+    Ok(())
+}
+
 fn xmain() -> i32 {
     let args: Vec<String> = env::args().collect();
     let process = args[0].clone();
@@ -55,11 +113,22 @@ fn xmain() -> i32 {
     opts.optflag("v", "verbose", "enables verbose logging");
     opts.optflag("r", "realtime", "disables clock spoofing");
     opts.optflag("c", "capture", "captures a recording");
+    opts.optflag("k", "check", "checks the game for things the emulator doesn't support, then exits");
     opts.optflagopt("l", "no-framelimit-until", "disables the frame-limiter until specified frame", "FRAME");
     opts.optopt("n", "project-name", "name of TAS project to create or load", "NAME");
     opts.optopt("f", "replay-file", "path to savestate file to replay", "FILE");
     opts.optopt("o", "output-file", "output savestate name in replay mode", "FILE.bin");
     opts.optmulti("a", "game-arg", "argument to pass to the game", "ARG");
+    opts.optopt("T", "trace", "writes a log of what the game runs on each frame to FILE", "FILE");
+    opts.optopt("O", "overlay", "draws overlays on captured video: input, frame, lag, rerecords[=N]", "LIST");
+    opts.optopt("P", "profile", "writes time spent in events, scripts and functions to FILE as folded stacks", "FILE");
+    opts.optopt("", "append", "replaces the -f replay from FRAME onwards with all of FILE", "FILE:FRAME");
+    opts.optopt("", "splice", "replaces AT-END of the -f replay with START-END of FILE", "FILE:START-END[:AT-END]");
+    opts.optopt("", "shift", "moves the inputs on frames START-END of the -f replay by OFFSET", "START-END:OFFSET");
+    opts.optopt("", "edit-output", "writes the edited replay, then plays it to check for desyncs", "FILE.gmtas");
+    opts.optopt("", "ghost", "runs the -f replay hidden for the TAS UI, printing where OBJECTS are", "OBJECTS");
+    opts.optopt("", "mplay-port", "UDP port for finding multiplayer sessions (default 47624)", "PORT");
+    opts.optopt("d", "cd-dir", "directory of audio tracks to use as the CD (must match when replaying)", "DIR");
     opts.optflagopt("p", "start-save", "Either loads the savestate specified after this parameter or starts at the first frame. If a .gmtas is specified by -f this will start the replay from this savestate instead", "savestate");
 
     let matches = match opts.parse(&args[1..]) {
@@ -95,9 +164,22 @@ fn xmain() -> i32 {
             },
         })
         .unwrap_or(0);
-    let frame_limiter = !matches.opt_present("l");
+    let editing = ["append", "splice", "shift"].iter().any(|x| matches.opt_present(x));
+    let ghost_objects = matches.opt_str("ghost");
+    let frame_limiter = !matches.opt_present("l") && !editing && ghost_objects.is_none();
     let verbose = matches.opt_present("v");
+    let check = matches.opt_present("k");
     let output_bin = matches.opt_str("o").map(PathBuf::from);
+    let cd_dir = matches.opt_str("d").map(PathBuf::from);
+    let mplay_port = match matches.opt_str("mplay-port").map(|x| x.parse::<u16>()).transpose() {
+        Ok(port) => port,
+        Err(e) => {
+            eprintln!("invalid port for --mplay-port: {}", e);
+            return EXIT_FAILURE;
+        },
+    };
+    let trace_path = matches.opt_str("T").map(PathBuf::from);
+    let profile_path = matches.opt_str("P").map(PathBuf::from);
     let pause = matches.opt_present("p");
     let start_save_path = matches.opt_str("p").map(PathBuf::from);
     let project_path = matches.opt_str("n").map(|name| {
@@ -141,26 +223,7 @@ fn xmain() -> i32 {
             })
     });
     let can_clear_temp_dir = temp_dir.is_none();
-    let replay = match matches
-        .opt_str("f")
-        .map(|filename| {
-            let filepath = PathBuf::from(&filename);
-            match filepath.extension().and_then(|x| x.to_str()) {
-                Some("bin") => match SaveState::from_file(&filepath, &mut savestate::Buffer::new()) {
-                    Ok(state) => Ok(state.into_replay()),
-                    Err(e) => Err(format!("couldn't load {:?}: {:?}", filepath, e)),
-                },
-
-                Some("gmtas") => match Replay::from_file(&filepath) {
-                    Ok(replay) => Ok(replay),
-                    Err(e) => Err(format!("couldn't load {:?}: {:?}", filepath, e)),
-                },
-
-                _ => Err("unknown filetype for -f, expected '.bin' or '.gmtas'".into()),
-            }
-        })
-        .transpose()
-    {
+    let mut replay = match matches.opt_str("f").map(|filename| load_replay(Path::new(&filename))).transpose() {
         Ok(r) => r,
         Err(e) => {
             eprintln!("{}", e);
@@ -168,6 +231,49 @@ fn xmain() -> i32 {
         },
     };
 
+    // Edited replays get written out, then played back straight away to check they don't desync
+    if editing {
+        let (replay, output) = match (&mut replay, matches.opt_str("edit-output").map(PathBuf::from)) {
+            (Some(replay), Some(output)) if output.extension().and_then(|x| x.to_str()) == Some("gmtas") => {
+                (replay, output)
+            },
+            (None, _) => {
+                eprintln!("--append, --splice and --shift need a replay to edit from -f");
+                return EXIT_FAILURE;
+            },
+            _ => {
+                eprintln!("--append, --splice and --shift need a .gmtas file to write to from --edit-output");
+                return EXIT_FAILURE;
+            },
+        };
+        if let Err(e) = edit_replay(replay, &matches) {
+            eprintln!("{}", e);
+            return EXIT_FAILURE;
+        }
+        if let Err(e) = replay.to_file(&output) {
+            eprintln!("couldn't write {:?}: {:?}", output, e);
+            return EXIT_FAILURE;
+        }
+        println!("Wrote edited replay of {} frames to {}", replay.frame_count(), output.display());
+    }
+
+    // A plain "rerecords" overlay takes the count from the project the replay was exported from
+    let capture_overlay = match matches.opt_str("O").map(|list| {
+        let project_dir = matches.opt_str("f").and_then(|f| PathBuf::from(f).parent().map(Path::to_path_buf));
+        CaptureOverlay::from_list(&list, project_dir.and_then(|dir| recording::project_rerecords(&dir)))
+    }) {
+        Some(Ok(_)) if !capture_recording => {
+            eprintln!("-O only does anything when capturing with -c");
+            return EXIT_FAILURE;
+        },
+        Some(Err(e)) => {
+            eprintln!("invalid overlay list for -O: {}", e);
+            return EXIT_FAILURE;
+        },
+        Some(Ok(overlay)) => Some(overlay),
+        None => None,
+    };
+
     let input = {
         if matches.free.len() == 1 {
             &matches.free[0]
@@ -198,17 +304,16 @@ fn xmain() -> i32 {
         println!("loading '{}'...", input);
     }
 
-    #[rustfmt::skip]
-    let assets = gm8exe::reader::from_exe(
-        &mut file,                              // mut exe: AsRef<[u8]>
-        if verbose {                            // logger: Option<Fn(&str)>
-            Some(|s: &str| println!("{}", s))
-        } else {
-            None
-        },
-        strict,                                 // strict: bool
-        multithread,                            // multithread: bool
+    let logger = if verbose { Some(|s: &str| println!("{}", s)) } else { None };
+    let is_project = matches!(
+        file_path.extension().and_then(|x| x.to_str()).map(|x| x.to_ascii_lowercase()).as_deref(),
+        Some("gmk" | "gm81")
     );
+    let assets = if is_project {
+        gm8exe::gmk::from_gmk(&file, logger, strict, multithread)
+    } else {
+        gm8exe::reader::from_exe(&mut file, logger, strict, multithread)
+    };
     let assets = match assets {
         Ok(assets) => assets,
         Err(err) => {
@@ -217,6 +322,19 @@ fn xmain() -> i32 {
         },
     };
 
+    if check {
+        let findings = compat::scan(&assets);
+        for finding in &findings {
+            println!("{}", finding);
+        }
+        if findings.is_empty() {
+            println!("no problems found");
+            return EXIT_SUCCESS;
+        }
+        println!("found {} problem(s)", findings.len());
+        return EXIT_FAILURE;
+    }
+
     let absolute_path = match file_path.canonicalize() {
         Ok(p) => p,
         Err(e) => {
@@ -253,6 +371,49 @@ fn xmain() -> i32 {
         },
     };
 
+    if let Some(dir) = &cd_dir {
+        if let Err(e) = components.cd.insert_disc(dir) {
+            eprintln!("Failed to load CD from {}: {}", dir.display(), e);
+            return EXIT_FAILURE;
+        }
+    }
+
+    if let Some(path) = &trace_path {
+        match Trace::create(path) {
+            Ok(trace) => components.trace = Some(trace),
+            Err(e) => {
+                eprintln!("Failed to create trace file {}: {}", path.display(), e);
+                return EXIT_FAILURE;
+            },
+        }
+    }
+
+    if let Some(port) = mplay_port {
+        components.mplay.set_discovery_port(port);
+    }
+
+    if let Some(path) = &profile_path {
+        components.profiler = Some(Profiler::new(path));
+    }
+    components.capture_overlay = capture_overlay;
+
+    // Ghosts are run by the TAS UI a frame at a time, and it only wants to know where the objects are
+    if let Some(list) = &ghost_objects {
+        if replay.is_none() {
+            eprintln!("--ghost needs a replay to run from -f");
+            return EXIT_FAILURE;
+        }
+        match GhostRecorder::from_list(&components, list) {
+            Ok(recorder) => components.ghost_recorder = Some(recorder),
+            Err(e) => {
+                eprintln!("invalid object list for --ghost: {}", e);
+                return EXIT_FAILURE;
+            },
+        }
+        components.window.set_visible(false);
+        components.audio.mute();
+    }
+
     let time_now = GameClock::SpoofedNanos(gml::datetime::now_as_nanos());
 
     if let Err(err) = if let Some(path) = project_path {
@@ -273,7 +434,7 @@ fn xmain() -> i32 {
             .map(|i| PathBuf::from(components.decode_str(i.name.as_ref()).into_owned()))
             .collect::<Vec<_>>();
         let result = if let Some(replay) = replay {
-            components.replay(replay, output_bin, start_save_path.as_ref())
+            components.replay(replay, output_bin, start_save_path.as_ref(), editing || ghost_objects.is_some())
         } else {
             components.clock = if spoof_time { time_now } else { GameClock::StartupEpoch(std::time::Instant::now()) };
             components.run()
@@ -1,9 +1,10 @@
 use crate::{
-    asset::{assert_ver, Error, PascalString, ReadPascalString},
+    asset::{assert_ver, Error, PascalString, ReadPascalString, WritePascalString},
     reader::inflate,
+    writer::deflate,
 };
-use byteorder::{ReadBytesExt, LE};
-use std::io::{self, Read, Seek, SeekFrom};
+use byteorder::{ReadBytesExt, WriteBytesExt, LE};
+use std::io::{self, Read, Seek, SeekFrom, Write};
 
 pub const VERSION: u32 = 700;
 
@@ -194,35 +195,7 @@ impl Extension {
 
         // Don't do decryption if there are no contents
         if contents_len != 0 {
-            // decryption setup
-            let mut char_table = [0u8; 0x200];
-            let mut seed1: i32 = seed1_raw as _;
-            let mut seed2: i32 = (seed1 % 0xFA) + 6;
-            seed1 /= 0xFA;
-            if seed1 < 0 {
-                seed1 += 100;
-            }
-            if seed2 < 0 {
-                seed2 += 100;
-            }
-            for (i, val) in char_table.iter_mut().enumerate() {
-                *val = (i % 256) as u8; // 0-255 repeating (twice)
-            }
-
-            // calculating char table - pass 1: pseudorandom byteswap
-            for i in 1..0x2711 {
-                let idx: usize = ((((i * seed2 as u32) + seed1 as u32) % 0xFE) + 1) as _;
-                let b1 = char_table[idx];
-                let b2 = char_table[idx + 1];
-                char_table[idx] = b2;
-                char_table[idx + 1] = b1;
-            }
-
-            // .. pass 2: use low half to scramble top half
-            for i in 0..0x100 {
-                let lo: u8 = char_table[i + 1];
-                char_table[lo as usize + 0x100] = (i as u8).wrapping_add(1);
-            }
+            let char_table = char_table(seed1_raw);
 
             // decrypt data chunk
             for byte in &mut reader.get_mut()[data_pos + 1..data_pos + contents_len] {
@@ -251,4 +224,98 @@ impl Extension {
 
         Ok(Extension { name, folder_name, files })
     }
+
+    /// Writes the extension in exe format, scrambling the file contents using the given seed.
+    pub fn write(&self, writer: &mut impl Write, seed: u32) -> io::Result<()> {
+        writer.write_u32::<LE>(VERSION)?;
+        writer.write_pas_string(&self.name)?;
+        writer.write_pas_string(&self.folder_name)?;
+
+        writer.write_u32::<LE>(self.files.len() as u32)?;
+        for file in &self.files {
+            writer.write_u32::<LE>(VERSION)?;
+            writer.write_pas_string(&file.name)?;
+            writer.write_u32::<LE>(file.kind as u32)?;
+            writer.write_pas_string(&file.initializer)?;
+            writer.write_pas_string(&file.finalizer)?;
+
+            writer.write_u32::<LE>(file.functions.len() as u32)?;
+            for function in &file.functions {
+                writer.write_u32::<LE>(VERSION)?;
+                writer.write_pas_string(&function.name)?;
+                writer.write_pas_string(&function.external_name)?;
+                writer.write_u32::<LE>(function.convention as u32)?;
+                writer.write_u32::<LE>(function.id)?;
+                writer.write_i32::<LE>(function.arg_count)?;
+                for kind in function.arg_types.iter() {
+                    writer.write_u32::<LE>(*kind as u32)?;
+                }
+                writer.write_u32::<LE>(function.return_type as u32)?;
+            }
+
+            writer.write_u32::<LE>(file.consts.len() as u32)?;
+            for constant in &file.consts {
+                writer.write_u32::<LE>(VERSION)?;
+                writer.write_pas_string(&constant.name)?;
+                writer.write_pas_string(&constant.value)?;
+            }
+        }
+
+        // Action libraries aren't stored, everything else is deflated back to back
+        let mut contents = Vec::new();
+        for file in self.files.iter().filter(|f| f.kind != FileKind::ActionLibrary) {
+            let data = deflate(&file.contents)?;
+            contents.write_u32::<LE>(data.len() as u32)?;
+            contents.extend_from_slice(&data);
+        }
+
+        // The reader's char table maps back through the top half, so encrypting goes through the bottom half.
+        // As with reading, the first byte is left as-is.
+        if !contents.is_empty() {
+            let char_table = char_table(seed);
+            for byte in &mut contents[1..] {
+                *byte = char_table[usize::from(*byte)];
+            }
+        }
+
+        writer.write_u32::<LE>(contents.len() as u32 + 4)?;
+        writer.write_u32::<LE>(seed)?;
+        writer.write_all(&contents)?;
+        Ok(())
+    }
+}
+
+/// Generates the substitution table used to scramble extension contents.
+/// The bottom half maps plain bytes to scrambled ones, and the top half maps them back.
+fn char_table(seed: u32) -> [u8; 0x200] {
+    let mut char_table = [0u8; 0x200];
+    let mut seed1: i32 = seed as _;
+    let mut seed2: i32 = (seed1 % 0xFA) + 6;
+    seed1 /= 0xFA;
+    if seed1 < 0 {
+        seed1 += 100;
+    }
+    if seed2 < 0 {
+        seed2 += 100;
+    }
+    for (i, val) in char_table.iter_mut().enumerate() {
+        *val = (i % 256) as u8; // 0-255 repeating (twice)
+    }
+
+    // calculating char table - pass 1: pseudorandom byteswap
+    for i in 1u32..0x2711 {
+        let idx: usize = ((i.wrapping_mul(seed2 as u32).wrapping_add(seed1 as u32) % 0xFE) + 1) as _;
+        let b1 = char_table[idx];
+        let b2 = char_table[idx + 1];
+        char_table[idx] = b2;
+        char_table[idx + 1] = b1;
+    }
+
+    // .. pass 2: use low half to scramble top half
+    for i in 0..0x100 {
+        let lo: u8 = char_table[i + 1];
+        char_table[lo as usize + 0x100] = (i as u8).wrapping_add(1);
+    }
+
+    char_table
 }
@@ -121,6 +121,9 @@ impl Asset for Font {
                 .write_u32::<LE>(self.range_start | ((self.aa_level % 0x100) << 24) | ((self.charset % 0x100) << 16))?,
         }
         writer.write_u32::<LE>(self.range_end)?;
+        for val in self.dmap.iter() {
+            writer.write_u32::<LE>(*val)?;
+        }
         writer.write_u32::<LE>(self.map_width)?;
         writer.write_u32::<LE>(self.map_height)?;
         writer.write_u32::<LE>(self.pixel_map.len() as u32)?; // TODO: len as u32
@@ -8,6 +8,22 @@ use std::io::{self, Read};
 pub const VERSION: u32 = 430;
 pub const VERSION_EVENT: u32 = 400;
 
+/// Short names for each event type, in the order they're stored in `Object::events`.
+pub const EVENT_NAMES: [&str; 12] = [
+    "create",
+    "destroy",
+    "alarm",
+    "step",
+    "collision",
+    "keyboard",
+    "mouse",
+    "other",
+    "draw",
+    "keypress",
+    "keyrelease",
+    "trigger",
+];
+
 pub struct Object {
     /// The asset name present in GML and the editor.
     pub name: PascalString,
@@ -247,7 +247,11 @@ impl Asset for Room {
 
     fn serialize_exe(&self, mut writer: impl io::Write, version: GameVersion) -> io::Result<()> {
         writer.write_pas_string(&self.name)?;
-        writer.write_u32::<LE>(VERSION)?;
+        writer.write_u32::<LE>(match (self.uses_810_features, self.uses_811_features) {
+            (_, true) => 811,
+            (true, false) => 810,
+            (false, false) => VERSION,
+        })?;
         writer.write_pas_string(&self.caption)?;
         writer.write_u32::<LE>(self.width)?;
         writer.write_u32::<LE>(self.height)?;
@@ -299,6 +303,14 @@ impl Asset for Room {
             writer.write_i32::<LE>(instance.object)?;
             writer.write_i32::<LE>(instance.id)?;
             writer.write_pas_string(&instance.creation_code)?;
+            if self.uses_810_features || self.uses_811_features {
+                writer.write_f64::<LE>(instance.xscale)?;
+                writer.write_f64::<LE>(instance.yscale)?;
+                writer.write_u32::<LE>(instance.blend)?;
+            }
+            if self.uses_811_features {
+                writer.write_f64::<LE>(instance.angle)?;
+            }
         }
         writer.write_u32::<LE>(self.tiles.len() as u32)?;
         for tile in &self.tiles {
@@ -311,6 +323,11 @@ impl Asset for Room {
             writer.write_u32::<LE>(tile.height)?;
             writer.write_i32::<LE>(tile.depth)?;
             writer.write_i32::<LE>(tile.id)?;
+            if self.uses_810_features || self.uses_811_features {
+                writer.write_f64::<LE>(tile.xscale)?;
+                writer.write_f64::<LE>(tile.yscale)?;
+                writer.write_u32::<LE>(tile.blend)?;
+            }
         }
         Ok(())
     }
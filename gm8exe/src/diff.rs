@@ -0,0 +1,651 @@
+//! Structural comparison between two games, such as two releases of the same game.
+//!
+//! Assets are matched up by ID. Anything containing GML gets a line-level diff of its code, and images and rooms
+//! get summaries of what changed in them rather than a full dump.
+
+use crate::{
+    asset::{
+        extension,
+        object::EVENT_NAMES,
+        room::{Instance, Tile},
+        CodeAction, Extension, IncludedFile, Object, PascalString, Path, Room,
+    },
+    AssetList, GameAssets,
+};
+use std::{collections::BTreeMap, fmt};
+
+/// Lines of unchanged code to show around each change when displaying a diff.
+const CONTEXT_LINES: usize = 2;
+
+/// Above this many cells, a code diff is reported as a complete replacement instead of being computed.
+const MAX_DIFF_CELLS: usize = 16_000_000;
+
+/// Every difference found between two games, in asset order.
+pub struct GameDiff {
+    pub changes: Vec<AssetChange>,
+}
+
+pub struct AssetChange {
+    /// The kind of asset, such as "sprite" or "room"
+    pub kind: &'static str,
+
+    /// The asset's ID, or its position in the list for things which don't have IDs
+    pub id: usize,
+
+    /// The asset's name in the newer game, or in the older one if it was removed
+    pub name: String,
+
+    pub change: Change,
+}
+
+pub enum Change {
+    Added,
+    Removed,
+    Modified(Vec<Detail>),
+}
+
+pub enum Detail {
+    /// A plain property changed, both values formatted for display
+    Property { name: String, old: String, new: String },
+
+    /// Some GML changed, such as a script, an event or a creation code
+    Code { location: String, lines: Vec<Line> },
+
+    /// Some pixels changed in an image which is the same size in both games
+    Pixels { image: String, changed: usize, total: usize },
+
+    /// Instances or tiles in a room changed, matched up by their IDs
+    Placements { kind: &'static str, added: usize, removed: usize, modified: usize },
+}
+
+/// One line of a code diff.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Line {
+    Same(String),
+    Added(String),
+    Removed(String),
+}
+
+/// Compares two games and returns everything that changed from `old` to `new`.
+pub fn diff(old: &GameAssets, new: &GameAssets) -> GameDiff {
+    let mut changes = Vec::new();
+
+    diff_list(
+        &mut changes,
+        "sprite",
+        &old.sprites,
+        &new.sprites,
+        |x| &x.name,
+        |details, old, new| {
+            property(details, "origin", (old.origin_x, old.origin_y), (new.origin_x, new.origin_y));
+            property(details, "frames", old.frames.len(), new.frames.len());
+            property(details, "per-frame masks", old.per_frame_colliders, new.per_frame_colliders);
+            for (i, (old, new)) in old.frames.iter().zip(&new.frames).enumerate() {
+                let image = format!("frame {}", i);
+                image_diff(details, image, (old.width, old.height, &old.data), (new.width, new.height, &new.data));
+            }
+        },
+    );
+
+    diff_list(
+        &mut changes,
+        "sound",
+        &old.sounds,
+        &new.sounds,
+        |x| &x.name,
+        |details, old, new| {
+            property(details, "kind", old.kind as u32, new.kind as u32);
+            property(details, "extension", old.extension.to_string(), new.extension.to_string());
+            property(details, "volume", old.volume, new.volume);
+            property(details, "pan", old.pan, new.pan);
+            property(details, "preload", old.preload, new.preload);
+            if old.data != new.data {
+                let size = |x: &Option<Box<[u8]>>| x.as_ref().map(|x| x.len()).unwrap_or(0);
+                details.push(Detail::Property {
+                    name: "data".into(),
+                    old: format!("{} bytes", size(&old.data)),
+                    new: format!("{} bytes", size(&new.data)),
+                });
+            }
+        },
+    );
+
+    diff_list(
+        &mut changes,
+        "background",
+        &old.backgrounds,
+        &new.backgrounds,
+        |x| &x.name,
+        |details, old, new| {
+            let empty = Box::default();
+            let old_data = old.data.as_ref().unwrap_or(&empty);
+            let new_data = new.data.as_ref().unwrap_or(&empty);
+            image_diff(details, "image".into(), (old.width, old.height, old_data), (new.width, new.height, new_data));
+        },
+    );
+
+    diff_list(
+        &mut changes,
+        "path",
+        &old.paths,
+        &new.paths,
+        |x| &x.name,
+        |details, old, new| {
+            property(details, "connection", old.connection as u32, new.connection as u32);
+            property(details, "precision", old.precision, new.precision);
+            property(details, "closed", old.closed, new.closed);
+            let points = |x: &Path| x.points.iter().map(|p| (p.x, p.y, p.speed)).collect::<Vec<_>>();
+            if points(old) != points(new) {
+                let describe = |x: &Path| format!("{} points", x.points.len());
+                details.push(Detail::Property { name: "points".into(), old: describe(old), new: describe(new) });
+            }
+        },
+    );
+
+    diff_list(
+        &mut changes,
+        "script",
+        &old.scripts,
+        &new.scripts,
+        |x| &x.name,
+        |details, old, new| {
+            code_diff(details, "code".into(), &old.source.to_string(), &new.source.to_string());
+        },
+    );
+
+    diff_list(
+        &mut changes,
+        "font",
+        &old.fonts,
+        &new.fonts,
+        |x| &x.name,
+        |details, old, new| {
+            property(details, "font", old.sys_name.to_string(), new.sys_name.to_string());
+            property(details, "size", old.size, new.size);
+            property(details, "bold", old.bold, new.bold);
+            property(details, "italic", old.italic, new.italic);
+            property(details, "range", (old.range_start, old.range_end), (new.range_start, new.range_end));
+            property(details, "charset", old.charset, new.charset);
+            property(details, "aa level", old.aa_level, new.aa_level);
+        },
+    );
+
+    diff_list(
+        &mut changes,
+        "timeline",
+        &old.timelines,
+        &new.timelines,
+        |x| &x.name,
+        |details, old, new| {
+            let old = old.moments.iter().map(|(m, x)| (*m, x)).collect::<BTreeMap<_, _>>();
+            let new = new.moments.iter().map(|(m, x)| (*m, x)).collect::<BTreeMap<_, _>>();
+            let mut moments = old.keys().chain(new.keys()).copied().collect::<Vec<_>>();
+            moments.sort_unstable();
+            moments.dedup();
+            for moment in moments {
+                let old = old.get(&moment).map(|x| actions_to_text(x)).unwrap_or_default();
+                let new = new.get(&moment).map(|x| actions_to_text(x)).unwrap_or_default();
+                code_diff(details, format!("moment {}", moment), &old, &new);
+            }
+        },
+    );
+
+    diff_list(
+        &mut changes,
+        "object",
+        &old.objects,
+        &new.objects,
+        |x| &x.name,
+        |details, old, new| {
+            property(details, "sprite", old.sprite_index, new.sprite_index);
+            property(details, "solid", old.solid, new.solid);
+            property(details, "visible", old.visible, new.visible);
+            property(details, "depth", old.depth, new.depth);
+            property(details, "persistent", old.persistent, new.persistent);
+            property(details, "parent", old.parent_index, new.parent_index);
+            property(details, "mask", old.mask_index, new.mask_index);
+            fn events(x: &Object) -> BTreeMap<(usize, u32), &Vec<CodeAction>> {
+                x.events
+                    .iter()
+                    .enumerate()
+                    .flat_map(|(ev_type, subs)| subs.iter().map(move |(sub, actions)| ((ev_type, *sub), actions)))
+                    .collect()
+            }
+            let (old, new) = (events(old), events(new));
+            let mut keys = old.keys().chain(new.keys()).copied().collect::<Vec<_>>();
+            keys.sort_unstable();
+            keys.dedup();
+            for key in keys {
+                let old = old.get(&key).map(|x| actions_to_text(x)).unwrap_or_default();
+                let new = new.get(&key).map(|x| actions_to_text(x)).unwrap_or_default();
+                let (ev_type, sub) = key;
+                let location = format!("{} event {}", EVENT_NAMES.get(ev_type).unwrap_or(&"unknown"), sub);
+                code_diff(details, location, &old, &new);
+            }
+        },
+    );
+
+    diff_list(
+        &mut changes,
+        "room",
+        &old.rooms,
+        &new.rooms,
+        |x| &x.name,
+        |details, old, new| {
+            property(details, "caption", old.caption.to_string(), new.caption.to_string());
+            property(details, "size", (old.width, old.height), (new.width, new.height));
+            property(details, "speed", old.speed, new.speed);
+            property(details, "persistent", old.persistent, new.persistent);
+            property(details, "colour", u32::from(old.bg_colour), u32::from(new.bg_colour));
+            property(details, "views enabled", old.views_enabled, new.views_enabled);
+            code_diff(details, "creation code".into(), &old.creation_code.to_string(), &new.creation_code.to_string());
+
+            fn instances(x: &Room) -> BTreeMap<i32, (String, &Instance)> {
+                x.instances
+                    .iter()
+                    .map(|i| {
+                        let key =
+                            format!("{} {} {} {} {} {} {}", i.object, i.x, i.y, i.xscale, i.yscale, i.blend, i.angle);
+                        (i.id, (key, i))
+                    })
+                    .collect()
+            }
+            let (old_instances, new_instances) = (instances(old), instances(new));
+            placements(details, "instances", &old_instances, &new_instances);
+            for (id, (_, new)) in &new_instances {
+                if let Some((_, old)) = old_instances.get(id) {
+                    let location = format!("instance {} creation code", id);
+                    code_diff(details, location, &old.creation_code.to_string(), &new.creation_code.to_string());
+                }
+            }
+
+            fn tiles(x: &Room) -> BTreeMap<i32, (String, &Tile)> {
+                x.tiles
+                    .iter()
+                    .map(|t| {
+                        let key = format!(
+                            "{} {} {} {} {} {} {} {} {} {} {}",
+                            t.source_bg,
+                            t.x,
+                            t.y,
+                            t.tile_x,
+                            t.tile_y,
+                            t.width,
+                            t.height,
+                            t.depth,
+                            t.xscale,
+                            t.yscale,
+                            t.blend,
+                        );
+                        (t.id, (key, t))
+                    })
+                    .collect()
+            }
+            placements(details, "tiles", &tiles(old), &tiles(new));
+        },
+    );
+
+    diff_list(
+        &mut changes,
+        "trigger",
+        &old.triggers,
+        &new.triggers,
+        |x| &x.name,
+        |details, old, new| {
+            property(details, "moment", old.moment as u32, new.moment as u32);
+            property(details, "constant", old.constant_name.to_string(), new.constant_name.to_string());
+            code_diff(details, "condition".into(), &old.condition.to_string(), &new.condition.to_string());
+        },
+    );
+
+    // These have no IDs, so they're matched up by name instead
+    let constants = |x: &GameAssets| {
+        x.constants.iter().enumerate().map(|(i, c)| (c.name.to_string(), (i, c.expression.to_string()))).collect()
+    };
+    diff_named(&mut changes, "constant", constants(old), constants(new), |details, old, new| {
+        property(details, "value", old, new);
+    });
+
+    fn included_files(x: &GameAssets) -> BTreeMap<String, (usize, &IncludedFile)> {
+        x.included_files.iter().enumerate().map(|(i, f)| (f.file_name.to_string(), (i, f))).collect()
+    }
+    diff_named(&mut changes, "included file", included_files(old), included_files(new), |details, old, new| {
+        if old.embedded_data != new.embedded_data {
+            let size = |x: &IncludedFile| x.embedded_data.as_ref().map(|x| x.len()).unwrap_or(0);
+            details.push(Detail::Property {
+                name: "data".into(),
+                old: format!("{} bytes", size(old)),
+                new: format!("{} bytes", size(new)),
+            });
+        }
+    });
+
+    fn extensions(x: &GameAssets) -> BTreeMap<String, (usize, &Extension)> {
+        x.extensions.iter().enumerate().map(|(i, e)| (e.name.to_string(), (i, e))).collect()
+    }
+    diff_named(&mut changes, "extension", extensions(old), extensions(new), |details, old, new| {
+        fn files(x: &Extension) -> BTreeMap<String, &extension::File> {
+            x.files.iter().map(|f| (f.name.to_string(), f)).collect()
+        }
+        let (old, new) = (files(old), files(new));
+        for (name, old_file) in &old {
+            match new.get(name) {
+                Some(new_file) => {
+                    if old_file.contents != new_file.contents {
+                        details.push(Detail::Property {
+                            name: format!("{} contents", name),
+                            old: format!("{} bytes", old_file.contents.len()),
+                            new: format!("{} bytes", new_file.contents.len()),
+                        });
+                    }
+                    let functions = |x: &extension::File| {
+                        x.functions.iter().map(|f| f.name.to_string()).collect::<Vec<_>>().join(", ")
+                    };
+                    property(details, &format!("{} functions", name), functions(old_file), functions(new_file));
+                },
+                None => {
+                    details.push(Detail::Property { name: name.clone(), old: "present".into(), new: "removed".into() })
+                },
+            }
+        }
+        for name in new.keys().filter(|x| !old.contains_key(*x)) {
+            details.push(Detail::Property { name: name.clone(), old: "absent".into(), new: "added".into() });
+        }
+    });
+
+    GameDiff { changes }
+}
+
+/// Compares two asset lists by ID, calling `compare` on assets which exist in both games.
+fn diff_list<T, N, F>(
+    changes: &mut Vec<AssetChange>,
+    kind: &'static str,
+    old: &AssetList<T>,
+    new: &AssetList<T>,
+    name: N,
+    compare: F,
+) where
+    N: Fn(&T) -> &PascalString,
+    F: Fn(&mut Vec<Detail>, &T, &T),
+{
+    for id in 0..old.len().max(new.len()) {
+        let old = old.get(id).and_then(|x| x.as_deref());
+        let new = new.get(id).and_then(|x| x.as_deref());
+        let (change, asset_name) = match (old, new) {
+            (None, None) => continue,
+            (None, Some(new)) => (Change::Added, name(new)),
+            (Some(old), None) => (Change::Removed, name(old)),
+            (Some(old), Some(new)) => {
+                let mut details = Vec::new();
+                property(&mut details, "name", name(old).to_string(), name(new).to_string());
+                compare(&mut details, old, new);
+                if details.is_empty() {
+                    continue;
+                }
+                (Change::Modified(details), name(new))
+            },
+        };
+        changes.push(AssetChange { kind, id, name: asset_name.to_string(), change });
+    }
+}
+
+/// Compares two lists of things which are identified by name, each along with its index in the list.
+fn diff_named<T, F>(
+    changes: &mut Vec<AssetChange>,
+    kind: &'static str,
+    old: BTreeMap<String, (usize, T)>,
+    new: BTreeMap<String, (usize, T)>,
+    compare: F,
+) where
+    F: Fn(&mut Vec<Detail>, &T, &T),
+{
+    for (name, (id, old_item)) in &old {
+        let change = match new.get(name) {
+            Some((_, new_item)) => {
+                let mut details = Vec::new();
+                compare(&mut details, old_item, new_item);
+                if details.is_empty() {
+                    continue;
+                }
+                Change::Modified(details)
+            },
+            None => Change::Removed,
+        };
+        changes.push(AssetChange { kind, id: *id, name: name.clone(), change });
+    }
+    for (name, (id, _)) in new.iter().filter(|(name, _)| !old.contains_key(*name)) {
+        changes.push(AssetChange { kind, id: *id, name: name.clone(), change: Change::Added });
+    }
+}
+
+fn property<T: fmt::Debug + PartialEq>(details: &mut Vec<Detail>, name: &str, old: T, new: T) {
+    if old != new {
+        details.push(Detail::Property { name: name.into(), old: format!("{:?}", old), new: format!("{:?}", new) });
+    }
+}
+
+fn image_diff(details: &mut Vec<Detail>, image: String, old: (u32, u32, &[u8]), new: (u32, u32, &[u8])) {
+    let (old_width, old_height, old_data) = old;
+    let (new_width, new_height, new_data) = new;
+    if (old_width, old_height) != (new_width, new_height) {
+        details.push(Detail::Property {
+            name: format!("{} size", image),
+            old: format!("{}x{}", old_width, old_height),
+            new: format!("{}x{}", new_width, new_height),
+        });
+    } else if old_data != new_data {
+        let changed = old_data.chunks(4).zip(new_data.chunks(4)).filter(|(a, b)| a != b).count();
+        details.push(Detail::Pixels { image, changed, total: old_data.len().max(new_data.len()) / 4 });
+    }
+}
+
+fn placements<T>(
+    details: &mut Vec<Detail>,
+    kind: &'static str,
+    old: &BTreeMap<i32, (String, T)>,
+    new: &BTreeMap<i32, (String, T)>,
+) {
+    let added = new.keys().filter(|id| !old.contains_key(id)).count();
+    let removed = old.keys().filter(|id| !new.contains_key(id)).count();
+    let modified = old.iter().filter(|(id, (a, _))| new.get(id).is_some_and(|(b, _)| a != b)).count();
+    if added + removed + modified > 0 {
+        details.push(Detail::Placements { kind, added, removed, modified });
+    }
+}
+
+fn code_diff(details: &mut Vec<Detail>, location: String, old: &str, new: &str) {
+    if old != new {
+        details.push(Detail::Code { location, lines: diff_lines(old, new) });
+    }
+}
+
+/// Writes out an action list as text so that it can be diffed.
+/// Code actions become their code, and other actions become a single line describing them.
+fn actions_to_text(actions: &[CodeAction]) -> String {
+    let mut out = String::new();
+    for action in actions {
+        if action.action_kind == 7 && action.execution_type == 2 {
+            out += &action.param_strings[0].to_string();
+        } else {
+            let args = action.param_strings.iter().take(action.param_count).map(|x| format!("{:?}", x.to_string()));
+            out += &format!(
+                "action {}:{} applies_to={} relative={} not={} ({})",
+                action.lib_id,
+                action.id,
+                action.applies_to,
+                action.is_relative,
+                action.invert_condition,
+                args.collect::<Vec<_>>().join(", "),
+            );
+        }
+        out += "\n";
+    }
+    out
+}
+
+/// Line-level diff of two texts, using the longest common subsequence of their lines.
+pub fn diff_lines(old: &str, new: &str) -> Vec<Line> {
+    let old = old.lines().collect::<Vec<_>>();
+    let new = new.lines().collect::<Vec<_>>();
+    let prefix = old.iter().zip(&new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..].iter().rev().zip(new[prefix..].iter().rev()).take_while(|(a, b)| a == b).count();
+    let old_mid = &old[prefix..old.len() - suffix];
+    let new_mid = &new[prefix..new.len() - suffix];
+
+    let mut lines = old[..prefix].iter().map(|x| Line::Same(x.to_string())).collect::<Vec<_>>();
+    if old_mid.len().saturating_mul(new_mid.len()) > MAX_DIFF_CELLS {
+        lines.extend(old_mid.iter().map(|x| Line::Removed(x.to_string())));
+        lines.extend(new_mid.iter().map(|x| Line::Added(x.to_string())));
+    } else {
+        // lcs[i][j] is the LCS length of old_mid[i..] and new_mid[j..]
+        let width = new_mid.len() + 1;
+        let mut lcs = vec![0u32; (old_mid.len() + 1) * width];
+        for i in (0..old_mid.len()).rev() {
+            for j in (0..new_mid.len()).rev() {
+                lcs[i * width + j] = if old_mid[i] == new_mid[j] {
+                    lcs[(i + 1) * width + j + 1] + 1
+                } else {
+                    lcs[(i + 1) * width + j].max(lcs[i * width + j + 1])
+                };
+            }
+        }
+        let (mut i, mut j) = (0, 0);
+        while i < old_mid.len() || j < new_mid.len() {
+            if i < old_mid.len() && j < new_mid.len() && old_mid[i] == new_mid[j] {
+                lines.push(Line::Same(old_mid[i].to_string()));
+                i += 1;
+                j += 1;
+            } else if j < new_mid.len() && (i == old_mid.len() || lcs[i * width + j + 1] > lcs[(i + 1) * width + j]) {
+                lines.push(Line::Added(new_mid[j].to_string()));
+                j += 1;
+            } else {
+                lines.push(Line::Removed(old_mid[i].to_string()));
+                i += 1;
+            }
+        }
+    }
+    lines.extend(old[old.len() - suffix..].iter().map(|x| Line::Same(x.to_string())));
+    lines
+}
+
+impl fmt::Display for GameDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.changes.is_empty() {
+            return writeln!(f, "No differences found.");
+        }
+        for change in &self.changes {
+            let symbol = match change.change {
+                Change::Added => '+',
+                Change::Removed => '-',
+                Change::Modified(_) => '~',
+            };
+            writeln!(f, "{} {} {} {}", symbol, change.kind, change.id, change.name)?;
+            if let Change::Modified(details) = &change.change {
+                for detail in details {
+                    write!(f, "{}", detail)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Detail {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Property { name, old, new } => writeln!(f, "    {}: {} -> {}", name, old, new),
+            Self::Pixels { image, changed, total } => {
+                writeln!(f, "    {}: {}/{} pixels changed", image, changed, total)
+            },
+            Self::Placements { kind, added, removed, modified } => {
+                writeln!(f, "    {}: {} added, {} removed, {} modified", kind, added, removed, modified)
+            },
+            Self::Code { location, lines } => {
+                writeln!(f, "    {}:", location)?;
+                // Only show changed lines and a bit of context around them
+                let near_change = |i: usize| {
+                    let start = i.saturating_sub(CONTEXT_LINES);
+                    lines[start..(i + CONTEXT_LINES + 1).min(lines.len())].iter().any(|x| !matches!(x, Line::Same(_)))
+                };
+                let mut skipped = false;
+                for (i, line) in lines.iter().enumerate() {
+                    if !near_change(i) {
+                        skipped = true;
+                        continue;
+                    }
+                    if skipped {
+                        writeln!(f, "      ...")?;
+                        skipped = false;
+                    }
+                    match line {
+                        Line::Same(x) => writeln!(f, "        {}", x)?,
+                        Line::Added(x) => writeln!(f, "      + {}", x)?,
+                        Line::Removed(x) => writeln!(f, "      - {}", x)?,
+                    }
+                }
+                Ok(())
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{diff_lines, Line, MAX_DIFF_CELLS};
+
+    /// Shows a diff in unified diff style, which is easier to compare against than a list of `Line`s.
+    fn diff(old: &str, new: &str) -> Vec<String> {
+        diff_lines(old, new)
+            .into_iter()
+            .map(|line| match line {
+                Line::Same(x) => format!(" {}", x),
+                Line::Added(x) => format!("+{}", x),
+                Line::Removed(x) => format!("-{}", x),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn unchanged() {
+        assert_eq!(diff("a\nb\nc", "a\nb\nc"), [" a", " b", " c"]);
+        assert!(diff("", "").is_empty());
+    }
+
+    #[test]
+    fn added_and_removed() {
+        assert_eq!(diff("a\nc", "a\nb\nc"), [" a", "+b", " c"]);
+        assert_eq!(diff("a\nb\nc", "a\nc"), [" a", "-b", " c"]);
+        assert_eq!(diff("", "a\nb"), ["+a", "+b"]);
+        assert_eq!(diff("a\nb", ""), ["-a", "-b"]);
+    }
+
+    #[test]
+    fn changed_line() {
+        assert_eq!(diff("a\nb\nc", "a\nx\nc"), [" a", "-b", "+x", " c"]);
+    }
+
+    #[test]
+    fn longest_common_subsequence() {
+        // The common prefix and suffix get trimmed first, but the middle still has to find "b" and "d"
+        assert_eq!(diff("x\na\nb\nc\nd\ny", "x\nb\ne\nd\na\ny"), [" x", "-a", " b", "-c", "+e", " d", "+a", " y"]);
+        assert_eq!(diff("b\na", "a\nb"), ["-b", " a", "+b"]);
+    }
+
+    #[test]
+    fn repeated_lines() {
+        assert_eq!(diff("a\na\na", "a\na"), [" a", " a", "-a"]);
+        assert_eq!(diff("a\nb\na\nb", "a\nb"), [" a", " b", "-a", "-b"]);
+    }
+
+    #[test]
+    fn too_big_to_diff() {
+        let size = (MAX_DIFF_CELLS as f64).sqrt() as usize + 1;
+        let old = (0..size).map(|i| format!("old {}", i)).collect::<Vec<_>>().join("\n");
+        let new = (0..size).map(|i| format!("new {}", i)).collect::<Vec<_>>().join("\n");
+        let lines = diff_lines(&format!("same\n{}", old), &format!("same\n{}", new));
+        assert_eq!(lines.len(), size * 2 + 1);
+        assert_eq!(lines[0], Line::Same("same".into()));
+        assert!(lines[1..=size].iter().all(|x| matches!(x, Line::Removed(_))));
+        assert!(lines[size + 1..].iter().all(|x| matches!(x, Line::Added(_))));
+    }
+}
@@ -184,3 +184,18 @@ where
 
     Ok(())
 }
+
+/// Applies GameMaker 8.0 protection in-place, the inverse of `decrypt`.
+/// `data` is the asset data which follows the length field, and `swap_table` must be a permutation of 0-255.
+pub fn encrypt(data: &mut [u8], swap_table: &[u8; 256]) {
+    // undo the second pass: the same swaps, in the opposite order
+    for i in 0..data.len() {
+        let b = i.saturating_sub(usize::from(swap_table[i & 0xFF]));
+        data.swap(i, b);
+    }
+
+    // undo the first pass, going forwards so each byte is keyed on the already-encrypted one before it
+    for i in 1..data.len() {
+        data[i] = swap_table[usize::from(data[i].wrapping_add(data[i - 1]).wrapping_add(i as u8))];
+    }
+}
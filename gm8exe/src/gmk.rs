@@ -0,0 +1,1096 @@
+//! Reader for GameMaker 8.0 and 8.1 project files (.gmk and .gm81).
+//!
+//! A project contains everything an executable does except for data the IDE generates at compile time,
+//! so that is reconstructed here: sprite collision maps are built from the collision settings,
+//! but fonts can't be, since there's no font rasteriser available, so projects with fonts are rejected.
+//! Extension packages are referenced by name only, so they have no files, and there's no DirectX DLL.
+
+use crate::{
+    asset::{
+        assert_ver,
+        included_file::ExportSetting,
+        path::{ConnectionKind, Point},
+        room,
+        sound::SoundFX,
+        sprite::{CollisionMap, Frame},
+        Background, CodeAction, Constant, Error, Extension, Font, IncludedFile, Object, PascalString, Path, ReadChunk,
+        ReadPascalString, Room, Script, Sound, SoundKind, Sprite, Timeline, Trigger, TriggerKind,
+    },
+    reader::{inflate, ReaderError},
+    settings::{GameHelpDialog, Settings},
+    AssetList, GameAssets, GameVersion,
+};
+use byteorder::{ReadBytesExt, LE};
+use rayon::prelude::{IntoParallelRefIterator, ParallelIterator};
+use std::io::{self, Read, Seek, SeekFrom};
+
+/// Magic number at the start of every GMK file.
+pub const MAGIC: u32 = 1234321;
+
+/// Marker the decompiler puts around creation code it generated to emulate 8.1-only room features.
+const COMPAT_HEADER: &str = "/* gm8.2 compat */\r\n";
+const COMPAT_FOOTER: &str = "/****************/\r\n\r\n";
+
+pub fn from_gmk<I, F>(gmk: I, logger: Option<F>, strict: bool, multithread: bool) -> Result<GameAssets, ReaderError>
+where
+    F: Copy + Fn(&str),
+    I: AsRef<[u8]>,
+{
+    let mut gmk = io::Cursor::new(gmk.as_ref());
+
+    // little helper thing
+    macro_rules! assert_ver {
+        ($ver: expr, $($expect: expr),+) => {{
+            let expected = [$($expect),+];
+            let got = $ver;
+            if strict && !expected.contains(&got) {
+                Err(ReaderError::AssetError(Error::VersionError { expected: expected[0], got }))
+            } else {
+                Ok(())
+            }
+        }};
+    }
+
+    // Header
+    if gmk.read_u32::<LE>()? != MAGIC {
+        return Err(ReaderError::UnknownFormat)
+    }
+    let game_ver = match gmk.read_u32::<LE>()? {
+        800 => GameVersion::GameMaker8_0,
+        810 => GameVersion::GameMaker8_1,
+        _ => return Err(ReaderError::UnknownFormat),
+    };
+    log!(logger, "Found GMK header for {:?}", game_ver);
+    let game_id = gmk.read_u32::<LE>()?;
+    let guid = [gmk.read_u32::<LE>()?, gmk.read_u32::<LE>()?, gmk.read_u32::<LE>()?, gmk.read_u32::<LE>()?];
+    log!(logger, "Game ID: {}", game_id);
+
+    // Game Settings
+    assert_ver!(gmk.read_u32::<LE>()?, 800, 810)?;
+    let (settings, ico_file_raw) = {
+        let mut cfg = read_block(&mut gmk)?;
+
+        fn read_image(cfg: &mut impl Read) -> Result<Option<Box<[u8]>>, ReaderError> {
+            if cfg.read_u32::<LE>()? != 0 {
+                let mut data = Vec::new();
+                read_block(cfg)?.read_to_end(&mut data)?;
+                Ok(Some(data.into_boxed_slice()))
+            } else {
+                Ok(None)
+            }
+        }
+
+        let fullscreen = cfg.read_u32::<LE>()? != 0;
+        let interpolate_pixels = cfg.read_u32::<LE>()? != 0;
+        let dont_draw_border = cfg.read_u32::<LE>()? != 0;
+        let display_cursor = cfg.read_u32::<LE>()? != 0;
+        let scaling = cfg.read_i32::<LE>()?;
+        let allow_resize = cfg.read_u32::<LE>()? != 0;
+        let window_on_top = cfg.read_u32::<LE>()? != 0;
+        let clear_colour = cfg.read_u32::<LE>()?;
+        let set_resolution = cfg.read_u32::<LE>()? != 0;
+        let colour_depth = cfg.read_u32::<LE>()?;
+        let resolution = cfg.read_u32::<LE>()?;
+        let frequency = cfg.read_u32::<LE>()?;
+        let dont_show_buttons = cfg.read_u32::<LE>()? != 0;
+        let (vsync, force_cpu_render) = match (game_ver, cfg.read_u32::<LE>()?) {
+            (GameVersion::GameMaker8_0, x) => (x != 0, true), // see 8.1.141 changelog
+            (GameVersion::GameMaker8_1, x) => ((x & 1) != 0, (x & (1 << 7)) != 0),
+        };
+        let disable_screensaver = cfg.read_u32::<LE>()? != 0;
+        let f4_fullscreen_toggle = cfg.read_u32::<LE>()? != 0;
+        let f1_help_menu = cfg.read_u32::<LE>()? != 0;
+        let esc_close_game = cfg.read_u32::<LE>()? != 0;
+        let f5_save_f6_load = cfg.read_u32::<LE>()? != 0;
+        let f9_screenshot = cfg.read_u32::<LE>()? != 0;
+        let treat_close_as_esc = cfg.read_u32::<LE>()? != 0;
+        let priority = cfg.read_u32::<LE>()?;
+        let freeze_on_lose_focus = cfg.read_u32::<LE>()? != 0;
+        let loading_bar = cfg.read_u32::<LE>()?;
+        let (backdata, frontdata) =
+            if loading_bar == 2 { (read_image(&mut cfg)?, read_image(&mut cfg)?) } else { (None, None) };
+        // The first bool is whether to show a custom image, the second is whether there's actually one to show
+        let custom_load_image = if cfg.read_u32::<LE>()? != 0 { read_image(&mut cfg)? } else { None };
+        let transparent = cfg.read_u32::<LE>()? != 0;
+        let translucency = cfg.read_u32::<LE>()?;
+        let scale_progress_bar = cfg.read_u32::<LE>()? != 0;
+        let ico_len = cfg.read_u32::<LE>()? as usize;
+        let ico_file_raw = if ico_len != 0 { Some(cfg.read_chunk(ico_len)?) } else { None };
+        let show_error_messages = cfg.read_u32::<LE>()? != 0;
+        let log_errors = cfg.read_u32::<LE>()? != 0;
+        let always_abort = cfg.read_u32::<LE>()? != 0;
+        let (zero_uninitialized_vars, error_on_uninitialized_args) = match (game_ver, cfg.read_u32::<LE>()?) {
+            (GameVersion::GameMaker8_0, x) => (x != 0, false),
+            (GameVersion::GameMaker8_1, x) => ((x & 1) != 0, (x & 2) != 0),
+        };
+        // The rest is author, version and copyright information, which doesn't go in the exe
+
+        log!(logger, " + Loaded settings structure");
+
+        let settings = Settings {
+            fullscreen,
+            scaling,
+            interpolate_pixels,
+            clear_colour,
+            allow_resize,
+            window_on_top,
+            dont_draw_border,
+            dont_show_buttons,
+            display_cursor,
+            freeze_on_lose_focus,
+            disable_screensaver,
+            force_cpu_render,
+            set_resolution,
+            colour_depth,
+            resolution,
+            frequency,
+            vsync,
+            esc_close_game,
+            treat_close_as_esc,
+            f1_help_menu,
+            f4_fullscreen_toggle,
+            f5_save_f6_load,
+            f9_screenshot,
+            priority,
+            custom_load_image,
+            transparent,
+            translucency,
+            loading_bar,
+            backdata,
+            frontdata,
+            scale_progress_bar,
+            show_error_messages,
+            log_errors,
+            always_abort,
+            zero_uninitialized_vars,
+            error_on_uninitialized_args,
+            swap_creation_events: false,
+        };
+        (settings, ico_file_raw)
+    };
+
+    // Triggers
+    assert_ver!(gmk.read_u32::<LE>()?, 800)?;
+    let triggers = get_assets(&mut gmk, |r| read_trigger(r, strict), multithread)?;
+    skip_timestamp(&mut gmk)?;
+    log!(logger, " + Read {} triggers", triggers.len());
+
+    // Constants
+    assert_ver!(gmk.read_u32::<LE>()?, 800)?;
+    let constant_count = gmk.read_u32::<LE>()? as usize;
+    let mut constants = Vec::with_capacity(constant_count);
+    for _ in 0..constant_count {
+        let name = gmk.read_pas_string()?;
+        let expression = gmk.read_pas_string()?;
+        log!(logger, " + Added constant '{}' (expression: {})", name, expression);
+        constants.push(Constant { name, expression });
+    }
+    skip_timestamp(&mut gmk)?;
+
+    macro_rules! read_list {
+        ($name: literal, [$($ver: expr),+], $read_fn: expr) => {{
+            assert_ver!(gmk.read_u32::<LE>()?, $($ver),+)?;
+            let list = get_assets(&mut gmk, $read_fn, multithread)?;
+            log!(logger, " + Read {} {}", list.iter().flatten().count(), $name);
+            list
+        }};
+    }
+
+    let sounds = read_list!("sounds", [800], |r| read_sound(r, strict));
+    let sprites = read_list!("sprites", [800], |r| read_sprite(r, strict));
+    let backgrounds = read_list!("backgrounds", [800], |r| read_background(r, strict));
+    let paths = read_list!("paths", [800, 420], |r| read_path(r, strict));
+    let scripts = read_list!("scripts", [800], |r| read_script(r, strict));
+    let fonts = read_list!("fonts", [800], |r| read_font(r, game_ver, strict));
+    if let Some(font) = fonts.iter().flatten().next() {
+        return Err(ReaderError::UnrenderedFont(font.name.to_string()))
+    }
+    let timelines = read_list!("timelines", [800], |r| read_timeline(r, game_ver, strict));
+    let objects = read_list!("objects", [800], |r| read_object(r, game_ver, strict));
+    let rooms = read_list!("rooms", [800], |r| read_room(r, game_ver, strict));
+
+    let last_instance_id = gmk.read_i32::<LE>()?;
+    let last_tile_id = gmk.read_i32::<LE>()?;
+
+    // Included Files
+    assert_ver!(gmk.read_u32::<LE>()?, 800)?;
+    let included_file_count = gmk.read_u32::<LE>()? as usize;
+    let mut included_files = Vec::with_capacity(included_file_count);
+    for _ in 0..included_file_count {
+        let file = read_included_file(&mut read_block(&mut gmk)?, strict)?;
+        log!(logger, " + Added included file '{}' (len: {})", file.file_name, file.source_length);
+        included_files.push(file);
+    }
+
+    // Extension packages - only the names are stored in a GMK
+    assert_ver!(gmk.read_u32::<LE>()?, 700)?;
+    let extension_count = gmk.read_u32::<LE>()? as usize;
+    let mut extensions = Vec::with_capacity(extension_count);
+    for _ in 0..extension_count {
+        let name = gmk.read_pas_string()?;
+        log!(logger, " + Added extension '{}' (files not available in a GMK)", name);
+        extensions.push(Extension { name, folder_name: PascalString::default(), files: Vec::new() });
+    }
+
+    // Help Dialog
+    assert_ver!(gmk.read_u32::<LE>()?, 800)?;
+    let help_dialog = {
+        let mut data = read_block(&mut gmk)?;
+        let bg_colour = data.read_u32::<LE>()?.into();
+        let new_window = data.read_u32::<LE>()? != 0;
+        let caption = data.read_pas_string()?;
+        let left = data.read_i32::<LE>()?;
+        let top = data.read_i32::<LE>()?;
+        let width = data.read_u32::<LE>()?;
+        let height = data.read_u32::<LE>()?;
+        let border = data.read_u32::<LE>()? != 0;
+        let resizable = data.read_u32::<LE>()? != 0;
+        let window_on_top = data.read_u32::<LE>()? != 0;
+        let freeze_game = data.read_u32::<LE>()? != 0;
+        skip_timestamp(&mut data)?;
+        let info = data.read_pas_string()?;
+        GameHelpDialog {
+            bg_colour,
+            new_window,
+            caption,
+            left,
+            top,
+            width,
+            height,
+            border,
+            resizable,
+            window_on_top,
+            freeze_game,
+            info,
+        }
+    };
+    log!(logger, " + Help Dialog: {:#?}", help_dialog);
+
+    // Action library initialization code
+    assert_ver!(gmk.read_u32::<LE>()?, 500)?;
+    let str_count = gmk.read_u32::<LE>()? as usize;
+    let mut library_init_strings = Vec::with_capacity(str_count);
+    for _ in 0..str_count {
+        library_init_strings.push(gmk.read_pas_string()?);
+    }
+    log!(logger, " + Read {} action library initialization strings", str_count);
+
+    // Room Order
+    assert_ver!(gmk.read_u32::<LE>()?, 700)?;
+    let ro_count = gmk.read_u32::<LE>()? as usize;
+    let mut room_order = Vec::with_capacity(ro_count);
+    for _ in 0..ro_count {
+        room_order.push(gmk.read_i32::<LE>()?);
+    }
+    log!(logger, " + Added Room Order LUT: {:?}", room_order);
+
+    // The resource tree follows, but it's only used by the IDE
+
+    Ok(GameAssets {
+        extensions,
+        sprites,
+        sounds,
+        backgrounds,
+        paths,
+        scripts,
+        fonts,
+        timelines,
+        objects,
+        triggers,
+        constants,
+        rooms,
+        included_files,
+
+        dx_dll: Vec::new(),
+        ico_file_raw,
+        version: game_ver,
+        help_dialog,
+        last_instance_id,
+        last_tile_id,
+        library_init_strings,
+        room_order,
+
+        settings,
+        game_id,
+        guid,
+    })
+}
+
+/// Reads a length-prefixed zlib block, returning a reader for the inflated data.
+fn read_block(src: &mut impl Read) -> io::Result<io::Cursor<Vec<u8>>> {
+    let len = src.read_u32::<LE>()? as usize;
+    let data = src.read_chunk(len)?;
+    let mut inflated = Vec::new();
+    inflate(&data).read_to_end(&mut inflated)?;
+    Ok(io::Cursor::new(inflated))
+}
+
+/// Skips over a "last changed" timestamp, which is an f64 in Delphi's TDateTime format.
+fn skip_timestamp(src: &mut impl Read) -> io::Result<()> {
+    src.read_f64::<LE>().map(|_| ())
+}
+
+/// Reads an asset list, where each asset is a zlib block starting with a bool for whether the asset exists.
+fn get_assets<T, F>(src: &mut io::Cursor<&[u8]>, deserializer: F, multithread: bool) -> Result<AssetList<T>, ReaderError>
+where
+    T: Send,
+    F: Fn(&mut io::Cursor<Vec<u8>>) -> Result<T, Error> + Sync,
+{
+    let count = src.read_u32::<LE>()? as usize;
+    let mut refs = Vec::with_capacity(count);
+    for _ in 0..count {
+        let len = src.read_u32::<LE>()? as usize;
+        let pos = src.position() as usize;
+        src.seek(SeekFrom::Current(len as i64))?;
+        refs.push(src.get_ref().get(pos..pos + len).ok_or(ReaderError::AssetError(Error::MalformedData))?);
+    }
+
+    let to_asset = |data: &[u8]| {
+        let mut inflated = Vec::new();
+        inflate(data).read_to_end(&mut inflated)?;
+        let mut data = io::Cursor::new(inflated);
+        match data.read_u32::<LE>() {
+            Ok(0) => Ok(None),
+            Ok(_) => Ok(Some(Box::new(deserializer(&mut data)?))),
+            Err(_) => Err(ReaderError::AssetError(Error::MalformedData)),
+        }
+    };
+
+    if multithread {
+        refs.par_iter().copied().map(to_asset).collect()
+    } else {
+        refs.iter().copied().map(to_asset).collect()
+    }
+}
+
+fn read_trigger(reader: &mut impl Read, strict: bool) -> Result<Trigger, Error> {
+    let version = reader.read_u32::<LE>()?;
+    if strict {
+        assert_ver(version, 800)?;
+    }
+    let name = reader.read_pas_string()?;
+    let condition = reader.read_pas_string()?;
+    let moment = TriggerKind::from(reader.read_u32::<LE>()?);
+    let constant_name = reader.read_pas_string()?;
+    Ok(Trigger { name, condition, moment, constant_name })
+}
+
+fn read_sound(reader: &mut impl Read, strict: bool) -> Result<Sound, Error> {
+    let name = reader.read_pas_string()?;
+    skip_timestamp(reader)?;
+    let version = reader.read_u32::<LE>()?;
+    if strict {
+        assert_ver(version, 800)?;
+    }
+    let kind = SoundKind::from(reader.read_u32::<LE>()?);
+    let extension = reader.read_pas_string()?;
+    let source = reader.read_pas_string()?;
+    let data = if reader.read_u32::<LE>()? != 0 {
+        let len = reader.read_u32::<LE>()? as usize;
+        Some(reader.read_chunk(len)?.into_boxed_slice())
+    } else {
+        None
+    };
+    let effects = reader.read_u32::<LE>()?;
+    let fx = SoundFX {
+        chorus: (effects & 0b1) != 0,
+        echo: (effects & 0b10) != 0,
+        flanger: (effects & 0b100) != 0,
+        gargle: (effects & 0b1000) != 0,
+        reverb: (effects & 0b10000) != 0,
+    };
+    let volume = reader.read_f64::<LE>()?;
+    let pan = reader.read_f64::<LE>()?;
+    let preload = reader.read_u32::<LE>()? != 0;
+    Ok(Sound { name, source, extension, data, kind, volume, pan, preload, fx })
+}
+
+fn read_sprite(reader: &mut impl Read, strict: bool) -> Result<Sprite, Error> {
+    let name = reader.read_pas_string()?;
+    skip_timestamp(reader)?;
+    let version = reader.read_u32::<LE>()?;
+    if strict {
+        assert_ver(version, 800)?;
+    }
+    let origin_x = reader.read_i32::<LE>()?;
+    let origin_y = reader.read_i32::<LE>()?;
+    let frame_count = reader.read_u32::<LE>()?;
+    let frames = (0..frame_count)
+        .map(|_| {
+            let version = reader.read_u32::<LE>()?;
+            if strict {
+                assert_ver(version, 800)?;
+            }
+            let width = reader.read_u32::<LE>()?;
+            let height = reader.read_u32::<LE>()?;
+            let data = if width * height != 0 {
+                let len = reader.read_u32::<LE>()? as usize;
+                reader.read_chunk(len)?.into_boxed_slice()
+            } else {
+                Box::default()
+            };
+            Ok(Frame { width, height, data })
+        })
+        .collect::<Result<Vec<_>, Error>>()?;
+
+    let shape = reader.read_u32::<LE>()?;
+    let tolerance = reader.read_u32::<LE>()?.min(255) as u8;
+    let per_frame_colliders = reader.read_u32::<LE>()? != 0;
+    let bbox_kind = reader.read_u32::<LE>()?;
+    let bbox_left = reader.read_u32::<LE>()?;
+    let bbox_right = reader.read_u32::<LE>()?;
+    let bbox_bottom = reader.read_u32::<LE>()?;
+    let bbox_top = reader.read_u32::<LE>()?;
+
+    let (colliders, per_frame_colliders) = match frames.first() {
+        Some(first) if first.width * first.height != 0 => {
+            let (width, height) = (first.width, first.height);
+            let alpha_map = |frames: &[Frame]| {
+                let mut data = vec![false; (width * height) as usize];
+                for frame in frames {
+                    for (i, pixel) in frame.data.chunks_exact(4).enumerate().take(data.len()) {
+                        data[i] |= pixel[3] > tolerance;
+                    }
+                }
+                data
+            };
+            let maps: Vec<Vec<bool>> = if per_frame_colliders {
+                frames.chunks(1).map(alpha_map).collect()
+            } else {
+                vec![alpha_map(&frames)]
+            };
+            let colliders = maps
+                .into_iter()
+                .map(|alpha| {
+                    let bbox = match bbox_kind {
+                        0 => auto_bbox(&alpha, width, height),
+                        1 => Bbox { left: 0, right: width - 1, top: 0, bottom: height - 1 },
+                        _ => Bbox {
+                            left: bbox_left.min(width - 1),
+                            right: bbox_right.min(width - 1),
+                            top: bbox_top.min(height - 1),
+                            bottom: bbox_bottom.min(height - 1),
+                        },
+                    };
+                    make_collider(&alpha, width, height, bbox, shape)
+                })
+                .collect();
+            (colliders, per_frame_colliders)
+        },
+        _ => (Vec::new(), false),
+    };
+
+    Ok(Sprite { name, origin_x, origin_y, frames, colliders, per_frame_colliders })
+}
+
+struct Bbox {
+    left: u32,
+    right: u32,
+    top: u32,
+    bottom: u32,
+}
+
+/// Finds the smallest box containing every pixel with collision.
+/// If there aren't any, the box is inverted so that nothing is inside it, like GM8 does.
+fn auto_bbox(data: &[bool], width: u32, height: u32) -> Bbox {
+    let mut bbox = Bbox { left: width - 1, right: 0, top: height - 1, bottom: 0 };
+    for y in 0..height {
+        for x in 0..width {
+            if data[(y * width + x) as usize] {
+                bbox.left = bbox.left.min(x);
+                bbox.right = bbox.right.max(x);
+                bbox.top = bbox.top.min(y);
+                bbox.bottom = bbox.bottom.max(y);
+            }
+        }
+    }
+    bbox
+}
+
+/// Builds a collision map for the given shape (0 = precise, 1 = rectangle, 2 = disk, 3 = diamond) within a box.
+fn make_collider(alpha: &[bool], width: u32, height: u32, bbox: Bbox, shape: u32) -> CollisionMap {
+    let mut data = vec![false; (width * height) as usize].into_boxed_slice();
+    let xcenter = f64::from(bbox.left + bbox.right) / 2.0;
+    let ycenter = f64::from(bbox.top + bbox.bottom) / 2.0;
+    let xrad = f64::from(bbox.right.saturating_sub(bbox.left)) / 2.0 + 0.5;
+    let yrad = f64::from(bbox.bottom.saturating_sub(bbox.top)) / 2.0 + 0.5;
+    for y in bbox.top..=bbox.bottom {
+        for x in bbox.left..=bbox.right {
+            let i = (y * width + x) as usize;
+            let xs = (f64::from(x) - xcenter) / xrad;
+            let ys = (f64::from(y) - ycenter) / yrad;
+            data[i] = match shape {
+                0 => alpha[i],
+                1 => true,
+                2 => xs * xs + ys * ys < 1.0,
+                _ => xs.abs() + ys.abs() < 1.0,
+            };
+        }
+    }
+    CollisionMap {
+        width,
+        height,
+        bbox_left: bbox.left,
+        bbox_right: bbox.right,
+        bbox_top: bbox.top,
+        bbox_bottom: bbox.bottom,
+        data,
+    }
+}
+
+fn read_background(reader: &mut impl Read, strict: bool) -> Result<Background, Error> {
+    let name = reader.read_pas_string()?;
+    skip_timestamp(reader)?;
+    let version = reader.read_u32::<LE>()?;
+    if strict {
+        assert_ver(version, 710)?;
+    }
+    // Tileset settings are only used by the room editor
+    for _ in 0..7 {
+        reader.read_u32::<LE>()?;
+    }
+    let version = reader.read_u32::<LE>()?;
+    if strict {
+        assert_ver(version, 800)?;
+    }
+    let width = reader.read_u32::<LE>()?;
+    let height = reader.read_u32::<LE>()?;
+    if width * height != 0 {
+        let len = reader.read_u32::<LE>()? as usize;
+        if len != 0 {
+            if len != (width as usize * height as usize * 4) {
+                return Err(Error::MalformedData)
+            }
+            let data = Some(reader.read_chunk(len)?.into_boxed_slice());
+            return Ok(Background { name, width, height, data })
+        }
+    }
+    Ok(Background { name, width: 0, height: 0, data: None })
+}
+
+fn read_path(reader: &mut impl Read, strict: bool) -> Result<Path, Error> {
+    let name = reader.read_pas_string()?;
+    skip_timestamp(reader)?;
+    let version = reader.read_u32::<LE>()?;
+    if strict {
+        assert_ver(version, 530)?;
+    }
+    let connection = ConnectionKind::from(reader.read_u32::<LE>()?);
+    let closed = reader.read_u32::<LE>()? != 0;
+    let precision = reader.read_u32::<LE>()?;
+    reader.read_i32::<LE>()?; // room to show in the path editor
+    reader.read_u32::<LE>()?; // snap x
+    reader.read_u32::<LE>()?; // snap y
+    let point_count = reader.read_u32::<LE>()? as usize;
+    let points = (0..point_count)
+        .map(|_| Ok(Point { x: reader.read_f64::<LE>()?, y: reader.read_f64::<LE>()?, speed: reader.read_f64::<LE>()? }))
+        .collect::<io::Result<_>>()?;
+    Ok(Path { name, connection, precision, closed, points })
+}
+
+fn read_script(reader: &mut impl Read, strict: bool) -> Result<Script, Error> {
+    let name = reader.read_pas_string()?;
+    skip_timestamp(reader)?;
+    let version = reader.read_u32::<LE>()?;
+    if strict {
+        assert_ver(version, 800)?;
+    }
+    let source = reader.read_pas_string()?;
+    Ok(Script { name, source })
+}
+
+fn read_font(reader: &mut impl Read, version: GameVersion, strict: bool) -> Result<Font, Error> {
+    let name = reader.read_pas_string()?;
+    skip_timestamp(reader)?;
+    let ver = reader.read_u32::<LE>()?;
+    if strict {
+        assert_ver(ver, 800)?;
+    }
+    let sys_name = reader.read_pas_string()?;
+    let size = reader.read_u32::<LE>()?;
+    let bold = reader.read_u32::<LE>()? != 0;
+    let italic = reader.read_u32::<LE>()? != 0;
+    let mut range_start = reader.read_u32::<LE>()?;
+    let range_end = reader.read_u32::<LE>()?;
+    let (aa_level, charset) = match version {
+        GameVersion::GameMaker8_0 => (0, 0),
+        GameVersion::GameMaker8_1 => {
+            let aa_level = (range_start & 0xFF000000) >> 24;
+            let charset = (range_start & 0x00FF0000) >> 16;
+            range_start &= 0x0000FFFF;
+            (aa_level, charset)
+        },
+    };
+
+    // The IDE renders the glyphs when compiling, which can't be done here, so from_gmk refuses any font
+    Ok(Font {
+        name,
+        sys_name,
+        size,
+        bold,
+        italic,
+        range_start,
+        range_end,
+        charset,
+        aa_level,
+        dmap: Box::new([0; 0x600]),
+        map_width: 0,
+        map_height: 0,
+        pixel_map: Box::new([]),
+    })
+}
+
+fn read_actions(reader: &mut impl Read, version: GameVersion, strict: bool) -> Result<Vec<CodeAction>, Error> {
+    let ver = reader.read_u32::<LE>()?;
+    if strict {
+        assert_ver(ver, 400)?;
+    }
+    let action_count = reader.read_u32::<LE>()?;
+    (0..action_count).map(|_| CodeAction::deserialize_exe(reader, version, strict)).collect()
+}
+
+fn read_timeline(reader: &mut impl Read, version: GameVersion, strict: bool) -> Result<Timeline, Error> {
+    let name = reader.read_pas_string()?;
+    skip_timestamp(reader)?;
+    let ver = reader.read_u32::<LE>()?;
+    if strict {
+        assert_ver(ver, 500)?;
+    }
+    let moment_count = reader.read_u32::<LE>()? as usize;
+    let moments = (0..moment_count)
+        .map(|_| Ok((reader.read_u32::<LE>()?, read_actions(reader, version, strict)?)))
+        .collect::<Result<_, Error>>()?;
+    Ok(Timeline { name, moments })
+}
+
+fn read_object(reader: &mut impl Read, version: GameVersion, strict: bool) -> Result<Object, Error> {
+    let name = reader.read_pas_string()?;
+    skip_timestamp(reader)?;
+    let ver = reader.read_u32::<LE>()?;
+    if strict {
+        assert_ver(ver, 430)?;
+    }
+    let sprite_index = reader.read_i32::<LE>()?;
+    let solid = reader.read_u32::<LE>()? != 0;
+    let visible = reader.read_u32::<LE>()? != 0;
+    let depth = reader.read_i32::<LE>()?;
+    let persistent = reader.read_u32::<LE>()? != 0;
+    let parent_index = reader.read_i32::<LE>()?;
+    let mask_index = reader.read_i32::<LE>()?;
+    let event_list_count = reader.read_u32::<LE>()?;
+    if event_list_count != 11 {
+        return Err(Error::MalformedData)
+    }
+    let mut events = Vec::with_capacity((event_list_count + 1) as usize);
+    for _ in 0..=event_list_count {
+        let mut sub_event_list = Vec::new();
+        while let Ok(index) = u32::try_from(reader.read_i32::<LE>()?) {
+            sub_event_list.push((index, read_actions(reader, version, strict)?));
+        }
+        events.push(sub_event_list);
+    }
+    Ok(Object { name, sprite_index, solid, visible, depth, persistent, parent_index, mask_index, events })
+}
+
+/// Splits off a compatibility block generated by the decompiler, returning its lines and the original code.
+fn split_compat(code: &PascalString) -> Option<(Vec<&str>, PascalString)> {
+    let code = std::str::from_utf8(&code.0).ok()?;
+    let rest = code.strip_prefix(COMPAT_HEADER)?;
+    let (block, original) = rest.split_once(COMPAT_FOOTER)?;
+    Some((block.lines().collect(), original.into()))
+}
+
+fn read_room(reader: &mut impl Read, version: GameVersion, strict: bool) -> Result<Room, Error> {
+    let name = reader.read_pas_string()?;
+    skip_timestamp(reader)?;
+    let ver = reader.read_u32::<LE>()?;
+    if strict {
+        assert_ver(ver, 541)?;
+    }
+    let caption = reader.read_pas_string()?;
+    let width = reader.read_u32::<LE>()?;
+    let height = reader.read_u32::<LE>()?;
+    reader.read_u32::<LE>()?; // snap x
+    reader.read_u32::<LE>()?; // snap y
+    reader.read_u32::<LE>()?; // isometric grid
+    let speed = reader.read_u32::<LE>()?;
+    let persistent = reader.read_u32::<LE>()? != 0;
+    let bg_colour = reader.read_u32::<LE>()?.into();
+    let (clear_screen, clear_region) = match (version, reader.read_u32::<LE>()?) {
+        (GameVersion::GameMaker8_0, x) => (x != 0, true),
+        (GameVersion::GameMaker8_1, x) => ((x & 0b01) != 0, (x & 0b10) == 0),
+    };
+    let mut creation_code = reader.read_pas_string()?;
+
+    let background_count = reader.read_u32::<LE>()? as usize;
+    let backgrounds = (0..background_count)
+        .map(|_| {
+            Ok(room::Background {
+                visible_on_start: reader.read_u32::<LE>()? != 0,
+                is_foreground: reader.read_u32::<LE>()? != 0,
+                source_bg: reader.read_i32::<LE>()?,
+                xoffset: reader.read_i32::<LE>()?,
+                yoffset: reader.read_i32::<LE>()?,
+                tile_horz: reader.read_u32::<LE>()? != 0,
+                tile_vert: reader.read_u32::<LE>()? != 0,
+                hspeed: reader.read_i32::<LE>()?,
+                vspeed: reader.read_i32::<LE>()?,
+                stretch: reader.read_u32::<LE>()? != 0,
+            })
+        })
+        .collect::<io::Result<_>>()?;
+
+    let views_enabled = reader.read_u32::<LE>()? != 0;
+    let view_count = reader.read_u32::<LE>()? as usize;
+    let views = (0..view_count)
+        .map(|_| {
+            Ok(room::View {
+                visible: reader.read_u32::<LE>()? != 0,
+                source_x: reader.read_i32::<LE>()?,
+                source_y: reader.read_i32::<LE>()?,
+                source_w: reader.read_u32::<LE>()?,
+                source_h: reader.read_u32::<LE>()?,
+                port_x: reader.read_i32::<LE>()?,
+                port_y: reader.read_i32::<LE>()?,
+                port_w: reader.read_u32::<LE>()?,
+                port_h: reader.read_u32::<LE>()?,
+                following: room::ViewFollowData {
+                    hborder: reader.read_i32::<LE>()?,
+                    vborder: reader.read_i32::<LE>()?,
+                    hspeed: reader.read_i32::<LE>()?,
+                    vspeed: reader.read_i32::<LE>()?,
+                    target: reader.read_i32::<LE>()?,
+                },
+            })
+        })
+        .collect::<io::Result<_>>()?;
+
+    let mut uses_810_features = false;
+    let mut uses_811_features = false;
+
+    let instance_count = reader.read_u32::<LE>()? as usize;
+    let mut instances = Vec::with_capacity(instance_count);
+    for _ in 0..instance_count {
+        let mut instance = room::Instance {
+            x: reader.read_i32::<LE>()?,
+            y: reader.read_i32::<LE>()?,
+            object: reader.read_i32::<LE>()?,
+            id: reader.read_i32::<LE>()?,
+            creation_code: reader.read_pas_string()?,
+            xscale: 1.0,
+            yscale: 1.0,
+            blend: u32::MAX,
+            angle: 0.0,
+        };
+        reader.read_u32::<LE>()?; // locked in editor
+        if let Some((lines, code)) = split_compat(&instance.creation_code) {
+            for line in lines {
+                let (var, value) = match line.strip_suffix(';').and_then(|l| l.split_once('=')) {
+                    Some(x) => x,
+                    None => continue,
+                };
+                match var {
+                    "image_xscale" => instance.xscale = value.parse().map_err(|_| Error::MalformedData)?,
+                    "image_yscale" => instance.yscale = value.parse().map_err(|_| Error::MalformedData)?,
+                    "image_blend" => instance.blend = value.parse().map_err(|_| Error::MalformedData)?,
+                    "image_angle" => {
+                        instance.angle = value.parse().map_err(|_| Error::MalformedData)?;
+                        uses_811_features = true;
+                    },
+                    _ => (),
+                }
+            }
+            instance.creation_code = code;
+            uses_810_features = true;
+        }
+        instances.push(instance);
+    }
+
+    let tile_count = reader.read_u32::<LE>()? as usize;
+    let mut tiles = Vec::with_capacity(tile_count);
+    for _ in 0..tile_count {
+        tiles.push(room::Tile {
+            x: reader.read_i32::<LE>()?,
+            y: reader.read_i32::<LE>()?,
+            source_bg: reader.read_i32::<LE>()?,
+            tile_x: reader.read_u32::<LE>()?,
+            tile_y: reader.read_u32::<LE>()?,
+            width: reader.read_u32::<LE>()?,
+            height: reader.read_u32::<LE>()?,
+            depth: reader.read_i32::<LE>()?,
+            id: reader.read_i32::<LE>()?,
+            xscale: 1.0,
+            yscale: 1.0,
+            blend: u32::MAX,
+        });
+        reader.read_u32::<LE>()?; // locked in editor
+    }
+
+    // Tile scales and blends are stored as function calls in the room's creation code
+    if let Some((lines, code)) = split_compat(&creation_code) {
+        for line in lines {
+            let call = line.strip_suffix(");").and_then(|l| l.split_once('('));
+            let (function, args) = match call {
+                Some((function, args)) => (function, args.split(',').collect::<Vec<_>>()),
+                None => continue,
+            };
+            let tile = match args.first().and_then(|id| id.parse::<i32>().ok()) {
+                Some(id) => tiles.iter_mut().find(|t| t.id == id),
+                None => None,
+            };
+            match (function, tile, args.as_slice()) {
+                ("tile_set_scale", Some(tile), [_, xscale, yscale]) => {
+                    tile.xscale = xscale.parse().map_err(|_| Error::MalformedData)?;
+                    tile.yscale = yscale.parse().map_err(|_| Error::MalformedData)?;
+                },
+                ("tile_set_blend", Some(tile), [_, blend]) => {
+                    tile.blend = blend.parse().map_err(|_| Error::MalformedData)?;
+                },
+                _ => (),
+            }
+        }
+        creation_code = code;
+        uses_810_features = true;
+    }
+
+    // The rest is room editor settings, which the exe doesn't need
+
+    Ok(Room {
+        name,
+        caption,
+        width,
+        height,
+        speed,
+        persistent,
+        bg_colour,
+        clear_screen,
+        clear_region,
+        creation_code,
+        backgrounds,
+        views_enabled,
+        views,
+        instances,
+        tiles,
+        uses_810_features,
+        uses_811_features,
+    })
+}
+
+fn read_included_file(reader: &mut impl Read, strict: bool) -> Result<IncludedFile, Error> {
+    skip_timestamp(reader)?;
+    let version = reader.read_u32::<LE>()?;
+    if strict {
+        assert_ver(version, 800)?;
+    }
+    let file_name = reader.read_pas_string()?;
+    let source_path = reader.read_pas_string()?;
+    let data_exists = reader.read_u32::<LE>()? != 0;
+    let source_length = reader.read_u32::<LE>()? as usize;
+    let stored_in_gmk = reader.read_u32::<LE>()? != 0;
+    let embedded_data = if stored_in_gmk && data_exists {
+        let len = reader.read_u32::<LE>()? as usize;
+        Some(reader.read_chunk(len)?.into_boxed_slice())
+    } else {
+        None
+    };
+    let export_flag = reader.read_u32::<LE>()?;
+    let custom_folder_path = reader.read_pas_string()?;
+    let export_settings = match export_flag {
+        0 => ExportSetting::NoExport,
+        1 => ExportSetting::TempFolder,
+        2 => ExportSetting::GameFolder,
+        _ => ExportSetting::CustomFolder(custom_folder_path),
+    };
+    let overwrite_file = reader.read_u32::<LE>()? != 0;
+    let free_memory = reader.read_u32::<LE>()? != 0;
+    let remove_at_end = reader.read_u32::<LE>()? != 0;
+    Ok(IncludedFile {
+        file_name,
+        source_path,
+        data_exists,
+        source_length,
+        stored_in_gmk,
+        embedded_data,
+        export_settings,
+        overwrite_file,
+        free_memory,
+        remove_at_end,
+    })
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::{asset::WritePascalString, writer::deflate};
+    use byteorder::WriteBytesExt;
+
+    fn u32s(out: &mut Vec<u8>, values: &[u32]) {
+        for value in values {
+            out.write_u32::<LE>(*value).unwrap();
+        }
+    }
+
+    fn string(out: &mut Vec<u8>, s: &str) {
+        out.write_pas_string(&s.into()).unwrap();
+    }
+
+    fn block(out: &mut Vec<u8>, data: &[u8]) {
+        let data = deflate(data).unwrap();
+        u32s(out, &[data.len() as u32]);
+        out.extend_from_slice(&data);
+    }
+
+    /// Writes an asset list, with `None` for a deleted asset.
+    fn list(out: &mut Vec<u8>, version: u32, assets: &[Option<Vec<u8>>]) {
+        u32s(out, &[version, assets.len() as u32]);
+        for asset in assets {
+            let mut data = Vec::new();
+            match asset {
+                Some(asset) => {
+                    u32s(&mut data, &[1]);
+                    data.extend_from_slice(asset);
+                },
+                None => u32s(&mut data, &[0]),
+            }
+            block(out, &data);
+        }
+    }
+
+    /// A small GM8.1 project with a script, a sprite and a room using the decompiler's compatibility block.
+    pub(crate) fn fixture() -> Vec<u8> {
+        project(&[])
+    }
+
+    /// The fixture project with the given font list.
+    fn project(fonts: &[Option<Vec<u8>>]) -> Vec<u8> {
+        let mut gmk = Vec::new();
+        u32s(&mut gmk, &[MAGIC, 810, 123456, 1, 2, 3, 4, 800]);
+
+        let mut settings = Vec::new();
+        // fullscreen to scaling, then allow_resize to frequency
+        u32s(&mut settings, &[0, 0, 0, 1, -1i32 as u32, 0, 1, 0x123456, 0, 0, 0, 0]);
+        // dont_show_buttons to loading_bar, with no custom images
+        u32s(&mut settings, &[0, 0, 1, 1, 1, 1, 1, 1, 1, 0, 0, 0, 0]);
+        // transparency, no icon, then error settings
+        u32s(&mut settings, &[0, 255, 1, 0, 1, 0, 0, 3]);
+        block(&mut gmk, &settings);
+
+        u32s(&mut gmk, &[800, 0]);
+        gmk.write_f64::<LE>(0.0).unwrap();
+        u32s(&mut gmk, &[800, 1]);
+        string(&mut gmk, "LIVES");
+        string(&mut gmk, "3");
+        gmk.write_f64::<LE>(0.0).unwrap();
+
+        list(&mut gmk, 800, &[]);
+
+        let mut sprite = Vec::new();
+        string(&mut sprite, "spr_dot");
+        sprite.write_f64::<LE>(0.0).unwrap();
+        u32s(&mut sprite, &[800, 1, 1, 1, 800, 2, 2, 16]);
+        sprite.extend_from_slice(&[0, 0, 0, 0, 255, 255, 255, 255, 0, 0, 0, 0, 0, 0, 0, 0]);
+        // precise, tolerance 0, shared collider, automatic bbox
+        u32s(&mut sprite, &[0, 0, 0, 0, 0, 1, 1, 0]);
+        list(&mut gmk, 800, &[None, Some(sprite)]);
+
+        list(&mut gmk, 800, &[]);
+        list(&mut gmk, 800, &[]);
+
+        let mut script = Vec::new();
+        string(&mut script, "scr_test");
+        script.write_f64::<LE>(0.0).unwrap();
+        u32s(&mut script, &[800]);
+        string(&mut script, "return argument0 + 1;");
+        list(&mut gmk, 800, &[Some(script)]);
+
+        list(&mut gmk, 800, fonts);
+        list(&mut gmk, 800, &[]);
+        list(&mut gmk, 800, &[]);
+
+        let mut room = Vec::new();
+        string(&mut room, "rm_test");
+        room.write_f64::<LE>(0.0).unwrap();
+        u32s(&mut room, &[541]);
+        string(&mut room, "Test");
+        // size, snap, isometric, speed, persistent, colour, clear flags
+        u32s(&mut room, &[320, 240, 16, 16, 0, 60, 0, 0, 1]);
+        string(&mut room, "");
+        // no backgrounds, views disabled with none set up, then one instance
+        u32s(&mut room, &[0, 0, 0, 1, 32, 48, 0, 100001]);
+        string(&mut room, &format!("{}image_xscale=2;\r\n{}hp = 3;", COMPAT_HEADER, COMPAT_FOOTER));
+        // locked, then no tiles
+        u32s(&mut room, &[0, 0]);
+        list(&mut gmk, 800, &[Some(room)]);
+
+        u32s(&mut gmk, &[100001, 10000000, 800, 0, 700, 1]);
+        string(&mut gmk, "Extension");
+
+        u32s(&mut gmk, &[800]);
+        let mut help = Vec::new();
+        u32s(&mut help, &[0xFFFFFF, 0]);
+        string(&mut help, "Help");
+        u32s(&mut help, &[-1i32 as u32, -1i32 as u32, 600, 400, 1, 1, 0, 1]);
+        help.write_f64::<LE>(0.0).unwrap();
+        string(&mut help, "Some help");
+        block(&mut gmk, &help);
+
+        u32s(&mut gmk, &[500, 0, 700, 1, 0]);
+        gmk
+    }
+
+    #[test]
+    fn read_fixture() {
+        let assets = from_gmk(fixture(), None::<fn(&str)>, true, false).unwrap();
+        assert!(matches!(assets.version, GameVersion::GameMaker8_1));
+        assert_eq!((assets.game_id, assets.guid), (123456, [1, 2, 3, 4]));
+        assert!(assets.settings.window_on_top);
+        assert!(assets.settings.zero_uninitialized_vars && assets.settings.error_on_uninitialized_args);
+        assert_eq!(assets.settings.clear_colour, 0x123456);
+        assert_eq!(assets.constants[0].name.0.as_ref(), b"LIVES");
+
+        assert!(assets.sprites[0].is_none());
+        let sprite = assets.sprites[1].as_ref().unwrap();
+        assert_eq!(sprite.name.0.as_ref(), b"spr_dot");
+        let collider = &sprite.colliders[0];
+        assert_eq!((collider.bbox_left, collider.bbox_right, collider.bbox_top, collider.bbox_bottom), (1, 1, 0, 0));
+        assert_eq!(collider.data.as_ref(), [false, true, false, false]);
+
+        let script = assets.scripts[0].as_ref().unwrap();
+        assert_eq!(script.source.0.as_ref(), b"return argument0 + 1;");
+
+        let room = assets.rooms[0].as_ref().unwrap();
+        assert_eq!((room.width, room.height, room.speed), (320, 240, 60));
+        assert!(room.clear_screen && room.clear_region);
+        assert!(room.uses_810_features && !room.uses_811_features);
+        let instance = &room.instances[0];
+        assert_eq!((instance.x, instance.y, instance.id, instance.xscale), (32, 48, 100001, 2.0));
+        assert_eq!(instance.creation_code.0.as_ref(), b"hp = 3;");
+
+        assert_eq!(assets.extensions[0].name.0.as_ref(), b"Extension");
+        assert_eq!(assets.help_dialog.info.0.as_ref(), b"Some help");
+        assert_eq!(assets.last_instance_id, 100001);
+        assert_eq!(assets.room_order, [0]);
+    }
+
+    #[test]
+    fn rejects_other_files() {
+        let mut gmk = fixture();
+        gmk[0] ^= 1;
+        assert!(matches!(from_gmk(gmk, None::<fn(&str)>, true, false), Err(ReaderError::UnknownFormat)));
+    }
+
+    #[test]
+    fn rejects_fonts() {
+        let mut font = Vec::new();
+        string(&mut font, "fnt_test");
+        font.write_f64::<LE>(0.0).unwrap();
+        u32s(&mut font, &[800]);
+        string(&mut font, "Arial");
+        u32s(&mut font, &[12, 0, 0, 32, 127]);
+        match from_gmk(project(&[None, Some(font)]), None::<fn(&str)>, true, false) {
+            Err(ReaderError::UnrenderedFont(name)) => assert_eq!(name, "fnt_test"),
+            _ => panic!("expected a font error"),
+        }
+        assert!(from_gmk(project(&[None]), None::<fn(&str)>, true, false).is_ok());
+    }
+}
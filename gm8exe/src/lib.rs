@@ -20,11 +20,14 @@ macro_rules! log {
 
 pub mod asset;
 pub mod def;
+pub mod diff;
 pub mod gamedata;
+pub mod gmk;
 pub mod reader;
 pub mod rsrc;
 pub mod settings;
 pub mod upx;
+pub mod writer;
 
 mod colour;
 
@@ -1,9 +1,9 @@
 use crate::{
     asset::*,
-    gamedata::{self, gm80},
+    gamedata::{self, antidec, gm80},
     rsrc,
     settings::{GameHelpDialog, Settings},
-    AssetList, GameAssets, GameVersion,
+    upx, AssetList, GameAssets, GameVersion,
 };
 use byteorder::{ReadBytesExt, LE};
 use flate2::bufread::ZlibDecoder;
@@ -20,6 +20,7 @@ pub enum ReaderError {
     IO(io::Error),
     PartialUPXPacking,
     UnknownFormat,
+    UnrenderedFont(String),
 }
 impl std::error::Error for ReaderError {}
 impl Display for ReaderError {
@@ -32,6 +33,9 @@ impl Display for ReaderError {
                 "looks upx protected, can't locate headers".into()
             },
             ReaderError::UnknownFormat => "unknown format, could not identify file".into(),
+            ReaderError::UnrenderedFont(name) => {
+                format!("font '{}' has no rendered glyphs, compile the project in GameMaker first", name)
+            },
         })
     }
 }
@@ -66,16 +70,21 @@ pub struct PESection {
     pub disk_address: u32,
 }
 
-pub fn from_exe<I, F>(mut exe: I, logger: Option<F>, strict: bool, multithread: bool) -> Result<GameAssets, ReaderError>
+/// The parts of a PE file's layout which matter for reading a game.
+struct PELayout {
+    sections: Vec<PESection>,
+
+    /// This is None if there is no UPX, otherwise it's (max_size, offset_on_disk)
+    upx_data: Option<(u32, u32)>,
+
+    rsrc_location: Option<u32>,
+}
+
+/// Checks the exe header and reads the section table, leaving the cursor just after it.
+fn read_pe_layout<F>(exe: &mut io::Cursor<&mut [u8]>, logger: Option<F>) -> Result<PELayout, ReaderError>
 where
     F: Copy + Fn(&str),
-    I: AsRef<[u8]> + AsMut<[u8]>,
 {
-    let exe = exe.as_mut();
-
-    // comfy wrapper for byteorder I/O
-    let mut exe = io::Cursor::new(exe);
-
     // verify executable header
     // Windows EXE must always start with "MZ"
     if exe.get_ref().get(0..2).unwrap_or(b"XX") != b"MZ" {
@@ -137,6 +146,61 @@ where
         sections.push(PESection { virtual_size, virtual_address, disk_size, disk_address })
     }
 
+    // Decide if UPX is in use based on PE section names
+    // This is None if there is no UPX, obviously, otherwise it's (max_size, offset_on_disk)
+    let upx_data: Option<(u32, u32)> = match upx0_virtual_len {
+        Some(len0) => upx1_data.map(|(len1, offset)| (len0 + len1, offset)),
+        None => None,
+    };
+
+    Ok(PELayout { sections, upx_data, rsrc_location })
+}
+
+/// Protection found on top of a game's data.
+pub struct Protection {
+    /// Whether the exe is packed with UPX
+    pub upx: bool,
+
+    /// The settings read from the antidec2 loader, if the game is protected by it
+    pub antidec: Option<antidec::Metadata>,
+}
+
+/// Detects UPX and antidec2 protection on an exe without reading the game itself.
+pub fn detect_protection(exe: &[u8]) -> Result<Protection, ReaderError> {
+    let mut exe = exe.to_vec();
+    let mut exe = io::Cursor::new(exe.as_mut_slice());
+    let logger: Option<fn(&str)> = None;
+    let layout = read_pe_layout(&mut exe, logger)?;
+
+    // This matches the order in which gamedata::find checks for things
+    let check = |exe: &mut io::Cursor<&mut [u8]>| -> io::Result<Option<antidec::Metadata>> {
+        match antidec::check80(exe)? {
+            Some(metadata) => Ok(Some(metadata)),
+            None => antidec::check81(exe),
+        }
+    };
+    let antidec = match layout.upx_data {
+        Some((max_size, disk_offset)) => {
+            let mut unpacked = upx::unpack(&mut exe, max_size, disk_offset, logger)?;
+            check(&mut io::Cursor::new(&mut *unpacked))?
+        },
+        None => check(&mut exe)?,
+    };
+    Ok(Protection { upx: layout.upx_data.is_some(), antidec })
+}
+
+pub fn from_exe<I, F>(mut exe: I, logger: Option<F>, strict: bool, multithread: bool) -> Result<GameAssets, ReaderError>
+where
+    F: Copy + Fn(&str),
+    I: AsRef<[u8]> + AsMut<[u8]>,
+{
+    let exe = exe.as_mut();
+
+    // comfy wrapper for byteorder I/O
+    let mut exe = io::Cursor::new(exe);
+
+    let PELayout { sections, upx_data, rsrc_location } = read_pe_layout(&mut exe, logger)?;
+
     let ico_file_raw = rsrc_location
         .map(|x| {
             let temp_pos = exe.position();
@@ -148,13 +212,6 @@ where
         .transpose()?
         .flatten();
 
-    // Decide if UPX is in use based on PE section names
-    // This is None if there is no UPX, obviously, otherwise it's (max_size, offset_on_disk)
-    let upx_data: Option<(u32, u32)> = match upx0_virtual_len {
-        Some(len0) => upx1_data.map(|(len1, offset)| (len0 + len1, offset)),
-        None => None,
-    };
-
     // Identify the game version in use and locate the gamedata header
     let game_ver = gamedata::find(&mut exe, logger, upx_data)?;
 
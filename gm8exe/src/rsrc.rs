@@ -1,6 +1,9 @@
 use crate::reader::PESection;
-use byteorder::{ReadBytesExt, WriteBytesExt, LE};
-use std::io::{self, Seek, SeekFrom};
+use byteorder::{ByteOrder, ReadBytesExt, WriteBytesExt, LE};
+use std::{
+    collections::BTreeMap,
+    io::{self, Read, Seek, SeekFrom},
+};
 
 /*
 /// A windows icon from the .rsrc header
@@ -83,7 +86,7 @@ pub fn find_icons(data: &mut io::Cursor<&mut [u8]>, pe_sections: &[PESection]) -
             let rva = data.read_u32::<LE>()?;
             let size = data.read_u32::<LE>()?;
 
-            if let Some(v) = extract_virtual_bytes(data, pe_sections, rva, size as usize)? {
+            if let Some(v) = extract_virtual_bytes(data.get_ref(), pe_sections, rva, size as usize) {
                 // Read the ico header
                 let mut ico_header = io::Cursor::new(&v);
                 ico_header.seek(SeekFrom::Current(4))?;
@@ -108,7 +111,7 @@ pub fn find_icons(data: &mut io::Cursor<&mut [u8]>, pe_sections: &[PESection]) -
                     // Match this ordinal name with an icon resource
                     for icon in &icons {
                         if icon.0 == ordinal as u32 && icon.2 >= 40 {
-                            if let Some(v) = extract_virtual_bytes(data, pe_sections, icon.1, icon.2 as usize)? {
+                            if let Some(v) = extract_virtual_bytes(data.get_ref(), pe_sections, icon.1, icon.2 as usize) {
                                 raw_file_body.extend_from_slice(&v);
                             }
                             break
@@ -125,22 +128,239 @@ pub fn find_icons(data: &mut io::Cursor<&mut [u8]>, pe_sections: &[PESection]) -
 }
 
 /// Extracts some bytes from the file from their location in the initialized exe's memory
-fn extract_virtual_bytes(
-    data: &mut io::Cursor<&mut [u8]>,
-    pe_sections: &[PESection],
-    rva: u32,
-    size: usize,
-) -> io::Result<Option<Vec<u8>>> {
+fn extract_virtual_bytes(data: &[u8], pe_sections: &[PESection], rva: u32, size: usize) -> Option<Vec<u8>> {
     for section in pe_sections {
         if rva >= section.virtual_address
-            && ((rva as usize) + size) < ((section.virtual_address + section.virtual_size) as usize)
+            && ((rva as usize) + size) <= ((section.virtual_address + section.virtual_size) as usize)
         {
             // data is in this section
             let offset_on_disk = rva - section.virtual_address;
             let data_location = (section.disk_address + offset_on_disk) as usize;
-            return Ok(data.get_ref().get(data_location..data_location + size).map(|chunk| chunk.to_vec()))
+            return data.get(data_location..data_location + size).map(|chunk| chunk.to_vec())
         }
     }
 
-    Ok(None)
+    None
+}
+
+/// An entry name in the resource tree. Named entries sort before numbered ones, as the PE format requires.
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord)]
+enum ResourceId {
+    Name(Vec<u16>),
+    Id(u32),
+}
+
+enum ResourceNode {
+    Dir(BTreeMap<ResourceId, ResourceNode>),
+    Leaf { code_page: u32, data: Vec<u8> },
+}
+
+/// Replaces the window icon in an exe with the given .ico file, rebuilding its .rsrc section.
+/// Every other resource is kept. Since the section will change size, it has to be the last one in the exe.
+pub fn replace_icons(exe: &mut Vec<u8>, ico: &[u8]) -> io::Result<()> {
+    fn invalid(msg: &str) -> io::Error {
+        io::Error::new(io::ErrorKind::InvalidData, msg)
+    }
+
+    // Locate the optional header and section table
+    let mut data = io::Cursor::new(&exe[..]);
+    data.set_position(0x3C);
+    let pe_header_loc = u64::from(data.read_u32::<LE>()?);
+    data.set_position(pe_header_loc + 6);
+    let section_count = data.read_u16::<LE>()?;
+    data.seek(SeekFrom::Current(12))?;
+    let optional_len = data.read_u16::<LE>()?;
+    let optional_header = pe_header_loc + 24;
+    let section_table = optional_header + u64::from(optional_len);
+
+    data.set_position(optional_header + 32);
+    let section_alignment = data.read_u32::<LE>()?.max(1);
+    let file_alignment = data.read_u32::<LE>()?.max(1);
+    data.set_position(optional_header + 92);
+    let rva_count = data.read_u32::<LE>()?;
+
+    let mut sections = Vec::with_capacity(usize::from(section_count));
+    let mut rsrc = None;
+    data.set_position(section_table);
+    for i in 0..section_count {
+        let mut name = [0u8; 8];
+        data.read_exact(&mut name)?;
+        let virtual_size = data.read_u32::<LE>()?;
+        let virtual_address = data.read_u32::<LE>()?;
+        let disk_size = data.read_u32::<LE>()?;
+        let disk_address = data.read_u32::<LE>()?;
+        data.seek(SeekFrom::Current(16))?;
+        if &name == b".rsrc\0\0\0" {
+            rsrc = Some(usize::from(i));
+        }
+        sections.push(PESection { virtual_size, virtual_address, disk_size, disk_address });
+    }
+    let rsrc = rsrc.ok_or_else(|| invalid("exe has no .rsrc section"))?;
+    let (rsrc_va, rsrc_disk) = (sections[rsrc].virtual_address, sections[rsrc].disk_address);
+    if sections.iter().any(|s| s.virtual_address > rsrc_va || s.disk_address > rsrc_disk) {
+        return Err(invalid(".rsrc isn't the last section in the exe, so it can't be resized"))
+    }
+
+    // Read the whole resource tree
+    fn read_dir(
+        data: &mut io::Cursor<&[u8]>,
+        sections: &[PESection],
+        base: u64,
+        offset: u32,
+        depth: usize,
+    ) -> io::Result<BTreeMap<ResourceId, ResourceNode>> {
+        let mut dir = BTreeMap::new();
+        data.set_position(base + u64::from(offset) + 12);
+        let count = u32::from(data.read_u16::<LE>()?) + u32::from(data.read_u16::<LE>()?);
+        for i in 0..count {
+            data.set_position(base + u64::from(offset) + 16 + u64::from(i) * 8);
+            let name = data.read_u32::<LE>()?;
+            let child = data.read_u32::<LE>()?;
+            let id = if name & 0x80000000 != 0 {
+                data.set_position(base + u64::from(name & 0x7FFFFFFF));
+                let len = data.read_u16::<LE>()?;
+                ResourceId::Name((0..len).map(|_| data.read_u16::<LE>()).collect::<io::Result<_>>()?)
+            } else {
+                ResourceId::Id(name)
+            };
+            let node = if child & 0x80000000 != 0 {
+                if depth >= 3 {
+                    return Err(io::Error::new(io::ErrorKind::InvalidData, "resource tree is too deep"))
+                }
+                ResourceNode::Dir(read_dir(data, sections, base, child & 0x7FFFFFFF, depth + 1)?)
+            } else {
+                data.set_position(base + u64::from(child));
+                let rva = data.read_u32::<LE>()?;
+                let size = data.read_u32::<LE>()?;
+                let code_page = data.read_u32::<LE>()?;
+                let leaf = extract_virtual_bytes(data.get_ref(), sections, rva, size as usize);
+                ResourceNode::Leaf { code_page, data: leaf.unwrap_or_default() }
+            };
+            dir.insert(id, node);
+        }
+        Ok(dir)
+    }
+    let mut tree = read_dir(&mut data, &sections, u64::from(rsrc_disk), 0, 1)?;
+
+    // Swap out the icons, keeping the name and language of the old icon group if there was one
+    let (group_name, language) = match tree.get(&ResourceId::Id(14)) {
+        Some(ResourceNode::Dir(names)) => match names.iter().next() {
+            Some((name, ResourceNode::Dir(languages))) => {
+                (name.clone(), languages.keys().next().cloned().unwrap_or(ResourceId::Id(0)))
+            },
+            _ => (ResourceId::Id(1), ResourceId::Id(0)),
+        },
+        _ => (ResourceId::Id(1), ResourceId::Id(0)),
+    };
+    let leaf = |data: Vec<u8>| {
+        let mut languages = BTreeMap::new();
+        languages.insert(language.clone(), ResourceNode::Leaf { code_page: 0, data });
+        ResourceNode::Dir(languages)
+    };
+
+    // An .ico file is a header followed by 16-byte entries pointing to each image, whereas an icon group
+    // has 14-byte entries which end in the ID of an RT_ICON resource instead of a file offset.
+    let mut ico_data = io::Cursor::new(ico);
+    ico_data.set_position(2);
+    if ico_data.read_u16::<LE>()? != 1 {
+        return Err(invalid("not an .ico file"))
+    }
+    let image_count = ico_data.read_u16::<LE>()?;
+    let mut group = ico[..6].to_vec();
+    let mut icons = BTreeMap::new();
+    for id in 1..=u32::from(image_count) {
+        let entry = ico_data.position() as usize;
+        ico_data.seek(SeekFrom::Current(8))?;
+        let size = ico_data.read_u32::<LE>()? as usize;
+        let offset = ico_data.read_u32::<LE>()? as usize;
+        let image = ico.get(offset..offset + size).ok_or_else(|| invalid(".ico image is out of bounds"))?;
+        group.extend_from_slice(&ico[entry..entry + 12]);
+        group.write_u16::<LE>(id as u16)?;
+        icons.insert(ResourceId::Id(id), leaf(image.to_vec()));
+    }
+    let mut groups = BTreeMap::new();
+    groups.insert(group_name, leaf(group));
+    tree.insert(ResourceId::Id(3), ResourceNode::Dir(icons));
+    tree.insert(ResourceId::Id(14), ResourceNode::Dir(groups));
+
+    // Lay out the new section: directory tables, then data entries, then names, then the data itself.
+    // Anything which points forwards gets patched in once its target has been placed.
+    fn write_dir<'a>(
+        out: &mut Vec<u8>,
+        dir: &'a BTreeMap<ResourceId, ResourceNode>,
+        names: &mut Vec<(usize, &'a [u16])>,
+        leaves: &mut Vec<(usize, u32, &'a [u8])>,
+    ) {
+        let named = dir.keys().filter(|id| matches!(id, ResourceId::Name(_))).count();
+        out.extend_from_slice(&[0; 12]);
+        out.extend_from_slice(&(named as u16).to_le_bytes());
+        out.extend_from_slice(&((dir.len() - named) as u16).to_le_bytes());
+        let table = out.len();
+        out.resize(table + dir.len() * 8, 0);
+        for (i, (id, node)) in dir.iter().enumerate() {
+            let entry = table + i * 8;
+            match id {
+                ResourceId::Name(name) => names.push((entry, name)),
+                ResourceId::Id(id) => LE::write_u32(&mut out[entry..], *id),
+            }
+            match node {
+                ResourceNode::Dir(children) => {
+                    let offset = out.len() as u32;
+                    LE::write_u32(&mut out[entry + 4..], offset | 0x80000000);
+                    write_dir(out, children, names, leaves);
+                },
+                ResourceNode::Leaf { code_page, data } => leaves.push((entry + 4, *code_page, data)),
+            }
+        }
+    }
+    let mut out = Vec::new();
+    let mut names = Vec::new();
+    let mut leaves = Vec::new();
+    write_dir(&mut out, &tree, &mut names, &mut leaves);
+    let mut data_entries = Vec::with_capacity(leaves.len());
+    for (entry, code_page, data) in &leaves {
+        let offset = out.len();
+        LE::write_u32(&mut out[*entry..], offset as u32);
+        data_entries.push(offset);
+        out.write_u32::<LE>(0)?; // rva, patched below
+        out.write_u32::<LE>(data.len() as u32)?;
+        out.write_u32::<LE>(*code_page)?;
+        out.write_u32::<LE>(0)?;
+    }
+    for (entry, name) in names {
+        let offset = out.len() as u32;
+        LE::write_u32(&mut out[entry..], offset | 0x80000000);
+        out.write_u16::<LE>(name.len() as u16)?;
+        for c in name {
+            out.write_u16::<LE>(*c)?;
+        }
+    }
+    for ((_, _, data), entry) in leaves.iter().zip(data_entries) {
+        out.resize((out.len() + 7) & !7, 0);
+        let offset = out.len() as u32;
+        LE::write_u32(&mut out[entry..], rsrc_va + offset);
+        out.extend_from_slice(data);
+    }
+
+    // Put the new section in place of the old one, keeping anything that was after it
+    let virtual_size = out.len() as u32;
+    let disk_size = virtual_size.div_ceil(file_alignment) * file_alignment;
+    out.resize(disk_size as usize, 0);
+    let old_end = (rsrc_disk + sections[rsrc].disk_size) as usize;
+    let overlay = exe.get(old_end..).unwrap_or(&[]).to_vec();
+    exe.truncate(rsrc_disk as usize);
+    exe.extend_from_slice(&out);
+    exe.extend_from_slice(&overlay);
+
+    // Update the section header, SizeOfImage and the resource data directory to match
+    let header = section_table as usize + rsrc * 40;
+    LE::write_u32(&mut exe[header + 8..], virtual_size);
+    LE::write_u32(&mut exe[header + 16..], disk_size);
+    let image_size = (rsrc_va + virtual_size).div_ceil(section_alignment) * section_alignment;
+    LE::write_u32(&mut exe[optional_header as usize + 56..], image_size);
+    if rva_count > 2 {
+        LE::write_u32(&mut exe[optional_header as usize + 96 + 2 * 8 + 4..], virtual_size);
+    }
+
+    Ok(())
 }
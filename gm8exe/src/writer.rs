@@ -0,0 +1,529 @@
+use crate::{
+    asset::{Asset, WritePascalString},
+    gamedata::{gm80, gm81},
+    rsrc,
+    settings::{GameHelpDialog, Settings},
+    AssetList, GameAssets, GameVersion,
+};
+use byteorder::{ReadBytesExt, WriteBytesExt, LE};
+use flate2::{write::ZlibEncoder, Compression};
+use rayon::prelude::{IntoParallelRefIterator, ParallelIterator};
+use std::{
+    fmt::{self, Display},
+    io::{self, Read, Write},
+};
+
+#[derive(Debug)]
+pub enum WriterError {
+    InvalidExeHeader,
+    IO(io::Error),
+    RunnerTooLong,
+    UnknownRunner,
+}
+impl std::error::Error for WriterError {}
+impl Display for WriterError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", match self {
+            WriterError::InvalidExeHeader => "invalid exe header".into(),
+            WriterError::IO(err) => format!("io error: {}", err),
+            WriterError::RunnerTooLong => "runner is too long for its game data offset".into(),
+            WriterError::UnknownRunner => "unknown runner, could not find where it loads game data from".into(),
+        })
+    }
+}
+
+impl From<io::Error> for WriterError {
+    fn from(err: io::Error) -> Self {
+        WriterError::IO(err)
+    }
+}
+
+/// Helper function for deflating data at the same compression level as GM8.
+pub(crate) fn deflate<I>(data: &I) -> io::Result<Vec<u8>>
+where
+    I: AsRef<[u8]> + ?Sized,
+{
+    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(data.as_ref())?;
+    encoder.finish()
+}
+
+/// Writes a length-prefixed block of deflated data.
+fn write_block(out: &mut Vec<u8>, data: &[u8]) -> io::Result<()> {
+    let data = deflate(data)?;
+    out.write_u32::<LE>(data.len() as u32)?;
+    out.extend_from_slice(&data);
+    Ok(())
+}
+
+/// Stands in for the random numbers GM8 uses for keys and garbage.
+/// It's seeded from the game ID, so writing the same assets twice produces the same exe.
+struct Noise(u32);
+
+impl Noise {
+    fn next(&mut self) -> u32 {
+        // xorshift32
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 17;
+        self.0 ^= self.0 << 5;
+        self.0
+    }
+}
+
+/// Where a runner looks for its game data: the position it starts searching from,
+/// plus the magic and version values it checks for, unless those checks are patched out.
+struct Loader {
+    header_start: usize,
+    magic: Option<u32>,
+    header_version: Option<u32>,
+}
+
+/// Reads the GM8.0 loading sequence, mirroring `gamedata::gm80::check`.
+fn find_loader_80(exe: &[u8]) -> io::Result<Option<Loader>> {
+    if exe.len() < 0x144AC0 + 4 {
+        return Ok(None)
+    }
+    let mut exe = io::Cursor::new(exe);
+    let mut check = |pos: u64, sequence: &[u8]| -> io::Result<bool> {
+        exe.set_position(pos);
+        let mut buf = vec![0u8; sequence.len()];
+        exe.read_exact(&mut buf)?;
+        Ok(buf == sequence)
+    };
+    if !check(0x000A49BE, &[0x8B, 0x45, 0xF4, 0xE8, 0x2A, 0xBD, 0xFD, 0xFF])? {
+        return Ok(None)
+    }
+    let magic_intact = check(0x000A49CB, &[0x0F, 0x85, 0x18, 0x01, 0x00, 0x00])?;
+    let header_version_intact = check(0x000A49E2, &[0x8B, 0xC6, 0xE8, 0x07, 0xBD, 0xFD, 0xFF])?
+        && check(0x000A49EE, &[0x0F, 0x85, 0xF5, 0x00, 0x00, 0x00])?;
+
+    exe.set_position(0x000A49C6);
+    let magic = match (exe.read_u8()?, magic_intact) {
+        (0x3D, true) => Some(exe.read_u32::<LE>()?),
+        (0x3D, false) | (0x90, _) => None,
+        _ => return Ok(None),
+    };
+    exe.set_position(0x000A49E9);
+    let header_version = match (exe.read_u8()?, header_version_intact) {
+        (0x3D, true) => Some(exe.read_u32::<LE>()?),
+        _ => None,
+    };
+    exe.set_position(0x144AC0);
+    let header_start = exe.read_u32::<LE>()? as usize;
+    Ok(Some(Loader { header_start, magic, header_version }))
+}
+
+/// Reads the GM8.1 loading sequence, mirroring `gamedata::gm81::check`.
+/// Runners without one get the default header position, which `check_lazy` finds.
+fn find_loader_81(exe: &[u8]) -> Result<Loader, WriterError> {
+    let default = Loader { header_start: 3800004, magic: Some(0xF7140067), header_version: None };
+    if exe.len() < 0x226D8A {
+        return Ok(default)
+    }
+    let mut exe = io::Cursor::new(exe);
+    exe.set_position(0x0010BB83);
+    let mut buf = [0u8; 8];
+    exe.read_exact(&mut buf)?;
+    if buf == [0x8B, 0x02, 0xC1, 0xE0, 0x10, 0x8B, 0x11, 0x81] {
+        // SUDALV's re-encryption depends on data in the runner which we can't reproduce
+        return Err(WriterError::UnknownRunner)
+    }
+    exe.set_position(0x00226CF3);
+    exe.read_exact(&mut buf)?;
+    if buf != [0xE8, 0x80, 0xF2, 0xDD, 0xFF, 0xC7, 0x45, 0xF0] {
+        return Ok(default)
+    }
+    let header_start = exe.read_u32::<LE>()? as usize;
+    exe.set_position(exe.position() + 125);
+    let mut buf = [0u8; 3];
+    exe.read_exact(&mut buf)?;
+    let magic = match buf {
+        [0x81, 0x7D, 0xEC] => {
+            let magic = exe.read_u32::<LE>()?;
+            if exe.read_u8()? == 0x74 { Some(magic) } else { None }
+        },
+        _ => None,
+    };
+    Ok(Loader { header_start, magic, header_version: None })
+}
+
+fn write_settings(out: &mut Vec<u8>, settings: &Settings, version: GameVersion) -> io::Result<()> {
+    fn write_data_maybe(cfg: &mut Vec<u8>, data: &Option<Box<[u8]>>) -> io::Result<()> {
+        match data {
+            Some(data) => {
+                cfg.write_u32::<LE>(1)?;
+                cfg.write_u32::<LE>(data.len() as u32)?;
+                cfg.extend_from_slice(data);
+            },
+            None => cfg.write_u32::<LE>(0)?,
+        }
+        Ok(())
+    }
+
+    let mut cfg = Vec::new();
+    cfg.write_u32::<LE>(settings.fullscreen.into())?;
+    cfg.write_u32::<LE>(settings.interpolate_pixels.into())?;
+    cfg.write_u32::<LE>(settings.dont_draw_border.into())?;
+    cfg.write_u32::<LE>(settings.display_cursor.into())?;
+    cfg.write_i32::<LE>(settings.scaling)?;
+    cfg.write_u32::<LE>(settings.allow_resize.into())?;
+    cfg.write_u32::<LE>(settings.window_on_top.into())?;
+    cfg.write_u32::<LE>(settings.clear_colour)?;
+    cfg.write_u32::<LE>(settings.set_resolution.into())?;
+    cfg.write_u32::<LE>(settings.colour_depth)?;
+    cfg.write_u32::<LE>(settings.resolution)?;
+    cfg.write_u32::<LE>(settings.frequency)?;
+    cfg.write_u32::<LE>(settings.dont_show_buttons.into())?;
+    match version {
+        GameVersion::GameMaker8_0 => cfg.write_u32::<LE>(settings.vsync.into())?,
+        GameVersion::GameMaker8_1 => {
+            cfg.write_u32::<LE>((u32::from(settings.force_cpu_render) << 7) | u32::from(settings.vsync))?
+        },
+    }
+    cfg.write_u32::<LE>(settings.disable_screensaver.into())?;
+    cfg.write_u32::<LE>(settings.f4_fullscreen_toggle.into())?;
+    cfg.write_u32::<LE>(settings.f1_help_menu.into())?;
+    cfg.write_u32::<LE>(settings.esc_close_game.into())?;
+    cfg.write_u32::<LE>(settings.f5_save_f6_load.into())?;
+    cfg.write_u32::<LE>(settings.f9_screenshot.into())?;
+    cfg.write_u32::<LE>(settings.treat_close_as_esc.into())?;
+    cfg.write_u32::<LE>(settings.priority)?;
+    cfg.write_u32::<LE>(settings.freeze_on_lose_focus.into())?;
+    cfg.write_u32::<LE>(settings.loading_bar)?;
+    if settings.loading_bar != 0 {
+        write_data_maybe(&mut cfg, &settings.backdata)?;
+        write_data_maybe(&mut cfg, &settings.frontdata)?;
+    }
+    write_data_maybe(&mut cfg, &settings.custom_load_image)?;
+    cfg.write_u32::<LE>(settings.transparent.into())?;
+    cfg.write_u32::<LE>(settings.translucency)?;
+    cfg.write_u32::<LE>(settings.scale_progress_bar.into())?;
+    cfg.write_u32::<LE>(settings.show_error_messages.into())?;
+    cfg.write_u32::<LE>(settings.log_errors.into())?;
+    cfg.write_u32::<LE>(settings.always_abort.into())?;
+    match version {
+        GameVersion::GameMaker8_0 => cfg.write_u32::<LE>(settings.zero_uninitialized_vars.into())?,
+        GameVersion::GameMaker8_1 => {
+            cfg.write_u32::<LE>(
+                (u32::from(settings.error_on_uninitialized_args) << 1) | u32::from(settings.zero_uninitialized_vars),
+            )?;
+            // Later 8.1 runners read a WebGL setting and the creation event order here, older ones stop early
+            cfg.write_u32::<LE>(0)?;
+            cfg.write_u32::<LE>(settings.swap_creation_events.into())?;
+        },
+    }
+    write_block(out, &cfg)
+}
+
+fn write_help_dialog(out: &mut Vec<u8>, help_dialog: &GameHelpDialog) -> io::Result<()> {
+    let mut data = Vec::new();
+    data.write_u32::<LE>(help_dialog.bg_colour.into())?;
+    data.write_u32::<LE>(help_dialog.new_window.into())?;
+    data.write_pas_string(&help_dialog.caption)?;
+    data.write_i32::<LE>(help_dialog.left)?;
+    data.write_i32::<LE>(help_dialog.top)?;
+    data.write_u32::<LE>(help_dialog.width)?;
+    data.write_u32::<LE>(help_dialog.height)?;
+    data.write_u32::<LE>(help_dialog.border.into())?;
+    data.write_u32::<LE>(help_dialog.resizable.into())?;
+    data.write_u32::<LE>(help_dialog.window_on_top.into())?;
+    data.write_u32::<LE>(help_dialog.freeze_game.into())?;
+    data.write_pas_string(&help_dialog.info)?;
+    write_block(out, &data)
+}
+
+/// Writes a list of assets, each in its own deflated block. Deleted assets are a block containing just a zero.
+fn write_assets<T>(out: &mut Vec<u8>, assets: &AssetList<T>, version: GameVersion, multithread: bool) -> io::Result<()>
+where
+    T: Asset + Sync,
+{
+    let to_block = |asset: &Option<Box<T>>| {
+        let mut data = Vec::new();
+        match asset {
+            Some(asset) => {
+                data.write_u32::<LE>(1)?;
+                asset.serialize_exe(&mut data, version)?;
+            },
+            None => data.write_u32::<LE>(0)?,
+        }
+        deflate(&data)
+    };
+
+    let blocks = if multithread {
+        assets.par_iter().map(to_block).collect::<io::Result<Vec<_>>>()?
+    } else {
+        assets.iter().map(to_block).collect::<io::Result<Vec<_>>>()?
+    };
+    out.write_u32::<LE>(blocks.len() as u32)?;
+    for block in blocks {
+        out.write_u32::<LE>(block.len() as u32)?;
+        out.extend_from_slice(&block);
+    }
+    Ok(())
+}
+
+/// Builds a game executable from a set of assets and a runner, which is a GM8.0 or GM8.1 exe with no game data
+/// attached. The runner's icon is replaced if the assets have one.
+pub fn to_exe<F>(
+    assets: &GameAssets,
+    runner: &[u8],
+    logger: Option<F>,
+    multithread: bool,
+) -> Result<Vec<u8>, WriterError>
+where
+    F: Copy + Fn(&str),
+{
+    let version = assets.version;
+    let mut noise = Noise(assets.game_id | 1);
+    let mut exe = runner.to_vec();
+
+    // Same checks as the reader: "MZ", then a PE header for i386
+    if exe.get(0..2) != Some(b"MZ") {
+        return Err(WriterError::InvalidExeHeader)
+    }
+    let mut header = io::Cursor::new(&exe);
+    header.set_position(0x3C);
+    let pe_header_loc = header.read_u32::<LE>()? as usize;
+    if exe.get(pe_header_loc..pe_header_loc + 6) != Some(b"PE\0\0\x4C\x01") {
+        return Err(WriterError::InvalidExeHeader)
+    }
+
+    if let Some(ico) = &assets.ico_file_raw {
+        rsrc::replace_icons(&mut exe, ico)?;
+        log!(logger, "Replaced runner icon ({} bytes)", ico.len());
+    }
+
+    // Work out where the header goes. If the runner checks for a magic number it searches for the header,
+    // otherwise it has to be exactly where the runner starts looking.
+    let loader = match version {
+        GameVersion::GameMaker8_0 => find_loader_80(&exe)?.ok_or(WriterError::UnknownRunner)?,
+        GameVersion::GameMaker8_1 => find_loader_81(&exe)?,
+    };
+    let header_pos = match (version, loader.magic) {
+        (_, None) if exe.len() > loader.header_start => return Err(WriterError::RunnerTooLong),
+        (_, None) => loader.header_start,
+        // The GM8.0 runner searches in steps of 10000 bytes
+        (GameVersion::GameMaker8_0, Some(_)) => {
+            let steps = exe.len().saturating_sub(loader.header_start).div_ceil(10000);
+            loader.header_start + steps * 10000
+        },
+        (GameVersion::GameMaker8_1, Some(_)) => exe.len().max(loader.header_start),
+    };
+    log!(logger, "Writing game data header at 0x{:X} (runner is 0x{:X} bytes)", header_pos, exe.len());
+    exe.resize(header_pos, 0);
+
+    match version {
+        GameVersion::GameMaker8_0 => {
+            exe.write_u32::<LE>(loader.magic.unwrap_or(1234321))?;
+            exe.write_u32::<LE>(loader.header_version.unwrap_or(800))?;
+            exe.write_u32::<LE>(0)?;
+            exe.write_u32::<LE>(0)?;
+        },
+        GameVersion::GameMaker8_1 => {
+            // The magic value is interleaved with an xor value the reader ignores; leaving that as zero
+            // makes sure the runner can't find a false match while searching through the padding before it.
+            let magic = loader.magic.unwrap_or(0xF7140067);
+            exe.write_u32::<LE>(magic & 0xFF00FF00)?;
+            exe.write_u32::<LE>(magic & 0x00FF00FF)?;
+            exe.write_u32::<LE>(noise.next())?; // hash key
+            exe.write_u32::<LE>(noise.next())?; // seed
+            exe.extend_from_slice(&[0; 20]);
+        },
+    }
+
+    write_settings(&mut exe, &assets.settings, version)?;
+
+    // Embedded DirectX DLL, which GMK files don't have, so it may be empty
+    exe.write_pas_string(&"D3DX8.dll".into())?;
+    exe.write_u32::<LE>(assets.dx_dll.len() as u32)?;
+    exe.extend_from_slice(&assets.dx_dll);
+
+    // Everything from here onwards gets the GM8.0 encryption
+    let mut data = Vec::new();
+    let garbage_dwords = noise.next() % 0x400;
+    data.write_u32::<LE>(garbage_dwords)?;
+    for _ in 0..garbage_dwords {
+        data.write_u32::<LE>(noise.next())?;
+    }
+    data.write_u32::<LE>(1)?; // pro flag
+    data.write_u32::<LE>(assets.game_id)?;
+    for part in assets.guid {
+        data.write_u32::<LE>(part)?;
+    }
+
+    data.write_u32::<LE>(700)?;
+    data.write_u32::<LE>(assets.extensions.len() as u32)?;
+    for extension in &assets.extensions {
+        extension.write(&mut data, noise.next())?;
+    }
+
+    data.write_u32::<LE>(800)?;
+    write_assets(&mut data, &assets.triggers, version, multithread)?;
+    data.write_u32::<LE>(800)?;
+    data.write_u32::<LE>(assets.constants.len() as u32)?;
+    for constant in &assets.constants {
+        data.write_pas_string(&constant.name)?;
+        data.write_pas_string(&constant.expression)?;
+    }
+    data.write_u32::<LE>(800)?;
+    write_assets(&mut data, &assets.sounds, version, multithread)?;
+    data.write_u32::<LE>(800)?;
+    write_assets(&mut data, &assets.sprites, version, multithread)?;
+    data.write_u32::<LE>(800)?;
+    write_assets(&mut data, &assets.backgrounds, version, multithread)?;
+    data.write_u32::<LE>(800)?;
+    write_assets(&mut data, &assets.paths, version, multithread)?;
+    data.write_u32::<LE>(800)?;
+    write_assets(&mut data, &assets.scripts, version, multithread)?;
+    data.write_u32::<LE>(800)?;
+    write_assets(&mut data, &assets.fonts, version, multithread)?;
+    data.write_u32::<LE>(800)?;
+    write_assets(&mut data, &assets.timelines, version, multithread)?;
+    data.write_u32::<LE>(800)?;
+    write_assets(&mut data, &assets.objects, version, multithread)?;
+    data.write_u32::<LE>(800)?;
+    write_assets(&mut data, &assets.rooms, version, multithread)?;
+    data.write_i32::<LE>(assets.last_instance_id)?;
+    data.write_i32::<LE>(assets.last_tile_id)?;
+    log!(logger, "Wrote {} rooms and their resources", assets.rooms.len());
+
+    // Included files don't have the "exists" flag other assets do
+    data.write_u32::<LE>(800)?;
+    data.write_u32::<LE>(assets.included_files.len() as u32)?;
+    for file in &assets.included_files {
+        let mut block = Vec::new();
+        file.serialize_exe(&mut block, version)?;
+        write_block(&mut data, &block)?;
+    }
+
+    data.write_u32::<LE>(800)?;
+    write_help_dialog(&mut data, &assets.help_dialog)?;
+
+    data.write_u32::<LE>(500)?;
+    data.write_u32::<LE>(assets.library_init_strings.len() as u32)?;
+    for string in &assets.library_init_strings {
+        data.write_pas_string(string)?;
+    }
+
+    data.write_u32::<LE>(700)?;
+    data.write_u32::<LE>(assets.room_order.len() as u32)?;
+    for room in &assets.room_order {
+        data.write_i32::<LE>(*room)?;
+    }
+
+    // The swap table is surrounded by two chunks of garbage
+    let mut swap_table = [0u8; 256];
+    for (i, val) in swap_table.iter_mut().enumerate() {
+        *val = i as u8;
+    }
+    for i in (1..256).rev() {
+        swap_table.swap(i, noise.next() as usize % (i + 1));
+    }
+    let garbage = [noise.next() % 0x100, noise.next() % 0x100];
+    exe.write_u32::<LE>(garbage[0])?;
+    exe.write_u32::<LE>(garbage[1])?;
+    for _ in 0..garbage[0] {
+        exe.write_u32::<LE>(noise.next())?;
+    }
+    exe.extend_from_slice(&swap_table);
+    for _ in 0..garbage[1] {
+        exe.write_u32::<LE>(noise.next())?;
+    }
+    exe.write_u32::<LE>(data.len() as u32)?;
+    gm80::encrypt(&mut data, &swap_table);
+    exe.extend_from_slice(&data);
+
+    // GM8.1's encryption is an xor stream over the rest of the file, so running the decryption encrypts it
+    if let GameVersion::GameMaker8_1 = version {
+        let mut cursor = io::Cursor::new(exe.as_mut_slice());
+        cursor.set_position(header_pos as u64 + 8);
+        gm81::decrypt(&mut cursor, None::<fn(&str)>, gm81::XorMethod::Normal)?;
+    }
+
+    log!(logger, "Finished writing exe ({} bytes)", exe.len());
+    Ok(exe)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{gmk, reader::from_exe};
+
+    /// The smallest exe that passes the header checks: "MZ", then a PE header for i386 with no sections.
+    fn runner(len: usize) -> Vec<u8> {
+        let mut exe = vec![0u8; len];
+        exe[..2].copy_from_slice(b"MZ");
+        exe[0x3C..0x40].copy_from_slice(&0x40u32.to_le_bytes());
+        exe[0x40..0x46].copy_from_slice(b"PE\0\0\x4C\x01");
+        exe
+    }
+
+    /// A GM8.0 runner with an intact loading sequence, which starts searching for the header at `header_start`.
+    fn runner_80(header_start: u32) -> Vec<u8> {
+        let mut exe = runner(0x144AC4);
+        let mut patch = |pos: usize, bytes: &[u8]| exe[pos..pos + bytes.len()].copy_from_slice(bytes);
+        patch(0xA49BE, &[0x8B, 0x45, 0xF4, 0xE8, 0x2A, 0xBD, 0xFD, 0xFF, 0x3D]);
+        patch(0xA49C7, &1234321u32.to_le_bytes());
+        patch(0xA49CB, &[0x0F, 0x85, 0x18, 0x01, 0x00, 0x00]);
+        patch(0xA49E2, &[0x8B, 0xC6, 0xE8, 0x07, 0xBD, 0xFD, 0xFF, 0x3D]);
+        patch(0xA49EA, &800u32.to_le_bytes());
+        patch(0xA49EE, &[0x0F, 0x85, 0xF5, 0x00, 0x00, 0x00]);
+        patch(0x144AC0, &header_start.to_le_bytes());
+        exe
+    }
+
+    fn fixture_assets(version: GameVersion) -> GameAssets {
+        let mut assets = gmk::from_gmk(gmk::tests::fixture(), None::<fn(&str)>, true, false).unwrap();
+        assets.version = version;
+        assets
+    }
+
+    /// Writes the assets, reads them back, then checks they're the same and that writing them again gives the same exe.
+    fn round_trip(assets: &GameAssets, runner: &[u8]) {
+        let exe = to_exe(assets, runner, None::<fn(&str)>, false).unwrap();
+        let read = from_exe(exe.clone(), None::<fn(&str)>, true, false).unwrap();
+
+        assert_eq!(std::mem::discriminant(&read.version), std::mem::discriminant(&assets.version));
+        assert_eq!((read.game_id, read.guid), (assets.game_id, assets.guid));
+        assert_eq!(read.settings.clear_colour, assets.settings.clear_colour);
+        assert_eq!(read.settings.window_on_top, assets.settings.window_on_top);
+        assert_eq!(read.constants[0].expression.0, assets.constants[0].expression.0);
+        assert_eq!(read.extensions[0].name.0, assets.extensions[0].name.0);
+        assert!(read.sprites[0].is_none());
+        let (sprite, expected) = (read.sprites[1].as_ref().unwrap(), assets.sprites[1].as_ref().unwrap());
+        assert_eq!(sprite.frames[0].data, expected.frames[0].data);
+        assert_eq!(sprite.colliders[0].data, expected.colliders[0].data);
+        assert_eq!(read.scripts[0].as_ref().unwrap().source.0, assets.scripts[0].as_ref().unwrap().source.0);
+        let (room, expected) = (read.rooms[0].as_ref().unwrap(), assets.rooms[0].as_ref().unwrap());
+        assert_eq!(room.uses_810_features, expected.uses_810_features);
+        assert_eq!(room.instances[0].xscale, expected.instances[0].xscale);
+        assert_eq!(room.instances[0].creation_code.0, expected.instances[0].creation_code.0);
+        assert_eq!(read.help_dialog.info.0, assets.help_dialog.info.0);
+        assert_eq!((read.last_instance_id, read.last_tile_id), (assets.last_instance_id, assets.last_tile_id));
+        assert_eq!(read.room_order, assets.room_order);
+
+        let rewritten = to_exe(&read, runner, None::<fn(&str)>, false).unwrap();
+        assert!(rewritten == exe, "writing the assets read from an exe should give the same exe");
+    }
+
+    #[test]
+    fn round_trip_81() {
+        // Runners without a loading sequence get the header at the default position, which the reader searches from
+        round_trip(&fixture_assets(GameVersion::GameMaker8_1), &runner(0x1000));
+    }
+
+    #[test]
+    fn round_trip_80() {
+        // Starting inside the runner makes the header go in the first 10000-byte step past its end
+        round_trip(&fixture_assets(GameVersion::GameMaker8_0), &runner_80(0x100000));
+    }
+
+    #[test]
+    fn rejects_bad_runners() {
+        let assets = fixture_assets(GameVersion::GameMaker8_0);
+        let result = to_exe(&assets, b"not an exe", None::<fn(&str)>, false);
+        assert!(matches!(result, Err(WriterError::InvalidExeHeader)));
+        let result = to_exe(&assets, &runner(0x1000), None::<fn(&str)>, false);
+        assert!(matches!(result, Err(WriterError::UnknownRunner)));
+    }
+}
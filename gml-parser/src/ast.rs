@@ -1,17 +1,19 @@
 use crate::{
     lexer::Lexer,
-    token::{Keyword, Operator, Separator, Token},
+    token::{Keyword, Operator, Separator, Span, Token},
 };
 
 use std::{
     error, fmt,
-    iter::{IntoIterator, Peekable},
+    iter::IntoIterator,
     ops::{Deref, DerefMut},
 };
 
 #[derive(Debug, PartialEq)]
 pub struct AST<'a>(Vec<Expr<'a>>);
 
+/// An expression or statement. Compound expressions carry the span of the source code they were parsed from,
+/// which covers any trailing semicolons on statements. Literals, groups and bare keywords don't have spans.
 #[derive(Debug, PartialEq)]
 pub enum Expr<'a> {
     LiteralIdentifier(&'a [u8]),
@@ -46,6 +48,8 @@ pub enum Expr<'a> {
 pub struct UnaryExpr<'a> {
     pub op: Operator,
     pub child: Expr<'a>,
+
+    pub span: Span,
 }
 
 #[derive(Debug, PartialEq)]
@@ -53,18 +57,24 @@ pub struct BinaryExpr<'a> {
     pub op: Operator,
     pub left: Expr<'a>,
     pub right: Expr<'a>,
+
+    pub span: Span,
 }
 
 #[derive(Debug, PartialEq)]
 pub struct FunctionExpr<'a> {
     pub name: &'a [u8],
     pub params: Vec<Expr<'a>>,
+
+    pub span: Span,
 }
 
 #[derive(Debug, PartialEq)]
 pub struct DoUntilExpr<'a> {
     pub cond: Expr<'a>,
     pub body: Expr<'a>,
+
+    pub span: Span,
 }
 
 #[derive(Debug, PartialEq)]
@@ -74,6 +84,8 @@ pub struct ForExpr<'a> {
     pub step: Expr<'a>,
 
     pub body: Expr<'a>,
+
+    pub span: Span,
 }
 
 #[derive(Debug, PartialEq)]
@@ -81,50 +93,88 @@ pub struct IfExpr<'a> {
     pub cond: Expr<'a>,
     pub body: Expr<'a>,
     pub else_body: Option<Expr<'a>>,
+
+    pub span: Span,
 }
 
 #[derive(Debug, PartialEq)]
 pub struct RepeatExpr<'a> {
     pub count: Expr<'a>,
     pub body: Expr<'a>,
+
+    pub span: Span,
 }
 
 #[derive(Debug, PartialEq)]
 pub struct SwitchExpr<'a> {
     pub input: Expr<'a>,
     pub body: Expr<'a>,
+
+    pub span: Span,
 }
 
 #[derive(Debug, PartialEq)]
 pub struct VarExpr<'a> {
     pub vars: Vec<&'a [u8]>,
+
+    pub span: Span,
 }
 
 #[derive(Debug, PartialEq)]
 pub struct GlobalVarExpr<'a> {
     pub vars: Vec<&'a [u8]>,
+
+    pub span: Span,
 }
 
 #[derive(Debug, PartialEq)]
 pub struct WithExpr<'a> {
     pub target: Expr<'a>,
     pub body: Expr<'a>,
+
+    pub span: Span,
 }
 
 #[derive(Debug, PartialEq)]
 pub struct WhileExpr<'a> {
     pub cond: Expr<'a>,
     pub body: Expr<'a>,
+
+    pub span: Span,
 }
 
 #[derive(Debug)]
 pub struct Error {
     pub message: String,
+
+    /// Where in the source code the error was found. At EOF, this is an empty span at the end of the source.
+    pub span: Span,
 }
 
 impl Error {
-    pub fn new(message: String) -> Self {
-        Error { message }
+    pub fn new(message: String, span: Span) -> Self {
+        Error { message, span }
+    }
+}
+
+impl<'a> Expr<'a> {
+    /// Returns the span of the source code this expression was parsed from, if it has one.
+    pub fn span(&self) -> Option<Span> {
+        match self {
+            Expr::Unary(unary) => Some(unary.span),
+            Expr::Binary(binary) => Some(binary.span),
+            Expr::DoUntil(dountil) => Some(dountil.span),
+            Expr::For(for_ex) => Some(for_ex.span),
+            Expr::Function(call) => Some(call.span),
+            Expr::If(if_ex) => Some(if_ex.span),
+            Expr::Repeat(repeat) => Some(repeat.span),
+            Expr::Switch(switch) => Some(switch.span),
+            Expr::Var(var) => Some(var.span),
+            Expr::GlobalVar(var) => Some(var.span),
+            Expr::With(with) => Some(with.span),
+            Expr::While(while_ex) => Some(while_ex.span),
+            _ => None,
+        }
     }
 }
 
@@ -195,31 +245,79 @@ impl<'a> fmt::Display for Expr<'a> {
 impl error::Error for Error {}
 impl fmt::Display for Error {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{}", self.message)
+        write!(f, "{} at {}", self.message, self.span)
     }
 }
 
 // TODO? This is not the prettiest.
 macro_rules! expect_token {
-    ( $token: expr, $($content: tt)* ) => ({
-        match $token {
+    ( $lex: expr, $($content: tt)* ) => ({
+        match $lex.next() {
             Some(Token::$($content)*) => {},
             Some(t) => {
                 return Err(Error::new(format!(
                     "Unexpected token {:?}; `{}` expected",
                     t, Token::$($content)*,
-                )));
+                ), $lex.span()));
             }
             None => {
                 return Err(Error::new(format!(
                     "Unexpected EOF; `{}` expected",
                     Token::$($content)*,
-                )));
+                ), $lex.span()));
             }
         }
     });
 }
 
+/// Token stream for the parser. Works like a Peekable, but also keeps track of where tokens came from.
+#[derive(Clone)]
+struct Tokens<'a> {
+    lexer: Lexer<'a>,
+    peeked: Option<(Option<Token<'a>>, Span)>,
+
+    /// Span of the token most recently taken with next().
+    span: Span,
+}
+
+impl<'a> Tokens<'a> {
+    fn new(source: &'a [u8]) -> Self {
+        let lexer = Lexer::new(source);
+        Self { span: lexer.span(), lexer, peeked: None }
+    }
+
+    fn fill(&mut self) -> &(Option<Token<'a>>, Span) {
+        let lexer = &mut self.lexer;
+        self.peeked.get_or_insert_with(|| (lexer.next(), lexer.span()))
+    }
+
+    fn next(&mut self) -> Option<Token<'a>> {
+        self.fill();
+        let (token, span) = self.peeked.take().unwrap();
+        self.span = span;
+        token
+    }
+
+    fn peek(&mut self) -> Option<&Token<'a>> {
+        self.fill().0.as_ref()
+    }
+
+    /// Returns the span of the token most recently taken.
+    fn span(&self) -> Span {
+        self.span
+    }
+
+    /// Returns the span of the next token, without taking it.
+    fn peek_span(&mut self) -> Span {
+        self.fill().1
+    }
+
+    /// Returns a span from the start of the given one to the end of the token most recently taken.
+    fn span_from(&self, start: Span) -> Span {
+        start.to(self.span)
+    }
+}
+
 impl<'a> Default for AST<'a> {
     fn default() -> Self {
         AST(Vec::new())
@@ -251,7 +349,7 @@ impl<'a> IntoIterator for AST<'a> {
 
 impl<'a> AST<'a> {
     pub fn new(source: &'a [u8]) -> Result<Self, Error> {
-        let mut lex = Lexer::new(source).peekable();
+        let mut lex = Tokens::new(source);
         let mut expressions = Vec::new();
 
         loop {
@@ -267,11 +365,11 @@ impl<'a> AST<'a> {
     }
 
     pub fn expression(source: &'a [u8]) -> Result<Expr<'a>, Error> {
-        let mut lex = Lexer::new(source).peekable();
+        let mut lex = Tokens::new(source);
         if lex.peek().is_some() { AST::read_binary_tree(&mut lex, None, false) } else { Ok(Expr::LiteralReal(0.0)) }
     }
 
-    fn read_line(lex: &mut Peekable<Lexer<'a>>) -> Result<Option<Expr<'a>>, Error> {
+    fn read_line(lex: &mut Tokens<'a>) -> Result<Option<Expr<'a>>, Error> {
         let token = loop {
             match lex.next() {
                 Some(Token::Separator(Separator::Semicolon)) => continue,
@@ -279,6 +377,7 @@ impl<'a> AST<'a> {
                 None => return Ok(None), // EOF
             }
         };
+        let head = lex.span();
 
         // Use token type to determine what logic we should apply here
         let ret = match token {
@@ -327,17 +426,18 @@ impl<'a> AST<'a> {
                                 }
                             }
 
+                            let span = lex.span_from(head);
                             match key {
-                                Keyword::Var => Ok(Some(Expr::Var(Box::new(VarExpr { vars })))),
-                                Keyword::GlobalVar => Ok(Some(Expr::GlobalVar(Box::new(GlobalVarExpr { vars })))),
+                                Keyword::Var => Ok(Some(Expr::Var(Box::new(VarExpr { vars, span })))),
+                                Keyword::GlobalVar => Ok(Some(Expr::GlobalVar(Box::new(GlobalVarExpr { vars, span })))),
                                 _ => unreachable!(),
                             }
                         } else {
                             // This doesn't do anything in GML. We could probably make it a NOP.
                             match key {
-                                Keyword::Var => Ok(Some(Expr::Var(Box::new(VarExpr { vars: vec![] })))),
+                                Keyword::Var => Ok(Some(Expr::Var(Box::new(VarExpr { vars: vec![], span: head })))),
                                 Keyword::GlobalVar => {
-                                    Ok(Some(Expr::GlobalVar(Box::new(GlobalVarExpr { vars: vec![] }))))
+                                    Ok(Some(Expr::GlobalVar(Box::new(GlobalVarExpr { vars: vec![], span: head }))))
                                 },
                                 _ => unreachable!(),
                             }
@@ -345,11 +445,12 @@ impl<'a> AST<'a> {
                     },
 
                     Keyword::Do => {
-                        let body = AST::read_group(lex)?
-                            .ok_or_else(|| Error::new("Unexpected EOF after 'do' keyword".to_string()))?;
-                        expect_token!(lex.next(), Keyword(Keyword::Until));
+                        let body = AST::read_group(lex)?.ok_or_else(|| {
+                            Error::new("Unexpected EOF after 'do' keyword".to_string(), lex.peek_span())
+                        })?;
+                        expect_token!(lex, Keyword(Keyword::Until));
                         let cond = AST::read_binary_tree(lex, None, false)?;
-                        Ok(Some(Expr::DoUntil(Box::new(DoUntilExpr { cond, body }))))
+                        Ok(Some(Expr::DoUntil(Box::new(DoUntilExpr { cond, body, span: lex.span_from(head) }))))
                     },
 
                     Keyword::If => {
@@ -357,24 +458,25 @@ impl<'a> AST<'a> {
                         if lex.peek() == Some(&Token::Separator(Separator::Then)) {
                             lex.next();
                         }
-                        let body = AST::read_group(lex)?
-                            .ok_or_else(|| Error::new("Unexpected EOF after 'if' condition".to_string()))?;
+                        let body = AST::read_group(lex)?.ok_or_else(|| {
+                            Error::new("Unexpected EOF after 'if' condition".to_string(), lex.peek_span())
+                        })?;
                         let else_body = if lex.peek() == Some(&Token::Keyword(Keyword::Else)) {
                             lex.next(); // consume 'else'
-                            Some(
-                                AST::read_group(lex)?
-                                    .ok_or_else(|| Error::new("Unexpected EOF after 'else' keyword".to_string()))?,
-                            )
+                            Some(AST::read_group(lex)?.ok_or_else(|| {
+                                Error::new("Unexpected EOF after 'else' keyword".to_string(), lex.peek_span())
+                            })?)
                         } else {
                             None
                         };
-                        Ok(Some(Expr::If(Box::new(IfExpr { cond, body, else_body }))))
+                        Ok(Some(Expr::If(Box::new(IfExpr { cond, body, else_body, span: lex.span_from(head) }))))
                     },
 
                     Keyword::For => {
-                        expect_token!(lex.next(), Separator(Separator::ParenLeft));
-                        let start = AST::read_line(lex)?
-                            .ok_or_else(|| Error::new("Unexpected EOF during 'for' params".to_string()))?;
+                        expect_token!(lex, Separator(Separator::ParenLeft));
+                        let start = AST::read_line(lex)?.ok_or_else(|| {
+                            Error::new("Unexpected EOF during 'for' params".to_string(), lex.peek_span())
+                        })?;
                         if lex.peek() == Some(&Token::Separator(Separator::Semicolon)) {
                             lex.next();
                         }
@@ -382,29 +484,33 @@ impl<'a> AST<'a> {
                         if lex.peek() == Some(&Token::Separator(Separator::Semicolon)) {
                             lex.next();
                         }
-                        let step = AST::read_line(lex)?
-                            .ok_or_else(|| Error::new("Unexpected EOF during 'for' params".to_string()))?;
+                        let step = AST::read_line(lex)?.ok_or_else(|| {
+                            Error::new("Unexpected EOF during 'for' params".to_string(), lex.peek_span())
+                        })?;
                         while lex.peek() == Some(&Token::Separator(Separator::Semicolon)) {
                             lex.next();
                         }
-                        expect_token!(lex.next(), Separator(Separator::ParenRight));
-                        let body = AST::read_group(lex)?
-                            .ok_or_else(|| Error::new("Unexpected EOF after 'for' params".to_string()))?;
-                        Ok(Some(Expr::For(Box::new(ForExpr { start, cond, step, body }))))
+                        expect_token!(lex, Separator(Separator::ParenRight));
+                        let body = AST::read_group(lex)?.ok_or_else(|| {
+                            Error::new("Unexpected EOF after 'for' params".to_string(), lex.peek_span())
+                        })?;
+                        Ok(Some(Expr::For(Box::new(ForExpr { start, cond, step, body, span: lex.span_from(head) }))))
                     },
 
                     Keyword::Repeat => {
                         let count = AST::read_binary_tree(lex, None, false)?;
-                        let body = AST::read_group(lex)?
-                            .ok_or_else(|| Error::new("Unexpected EOF after 'repeat' condition".to_string()))?;
-                        Ok(Some(Expr::Repeat(Box::new(RepeatExpr { count, body }))))
+                        let body = AST::read_group(lex)?.ok_or_else(|| {
+                            Error::new("Unexpected EOF after 'repeat' condition".to_string(), lex.peek_span())
+                        })?;
+                        Ok(Some(Expr::Repeat(Box::new(RepeatExpr { count, body, span: lex.span_from(head) }))))
                     },
 
                     Keyword::Switch => {
                         let input = AST::read_binary_tree(lex, None, false)?;
-                        let body = AST::read_line(lex)?
-                            .ok_or_else(|| Error::new("Unexpected EOF after 'switch' condition".to_string()))?;
-                        Ok(Some(Expr::Switch(Box::new(SwitchExpr { input, body }))))
+                        let body = AST::read_line(lex)?.ok_or_else(|| {
+                            Error::new("Unexpected EOF after 'switch' condition".to_string(), lex.peek_span())
+                        })?;
+                        Ok(Some(Expr::Switch(Box::new(SwitchExpr { input, body, span: lex.span_from(head) }))))
                     },
 
                     Keyword::With => {
@@ -412,9 +518,10 @@ impl<'a> AST<'a> {
                         if lex.peek() == Some(&Token::Keyword(Keyword::Do)) {
                             lex.next();
                         }
-                        let body = AST::read_group(lex)?
-                            .ok_or_else(|| Error::new("Unexpected EOF after 'with' condition".to_string()))?;
-                        Ok(Some(Expr::With(Box::new(WithExpr { target, body }))))
+                        let body = AST::read_group(lex)?.ok_or_else(|| {
+                            Error::new("Unexpected EOF after 'with' condition".to_string(), lex.peek_span())
+                        })?;
+                        Ok(Some(Expr::With(Box::new(WithExpr { target, body, span: lex.span_from(head) }))))
                     },
 
                     Keyword::While => {
@@ -422,19 +529,20 @@ impl<'a> AST<'a> {
                         if lex.peek() == Some(&Token::Keyword(Keyword::Do)) {
                             lex.next();
                         }
-                        let body = AST::read_group(lex)?
-                            .ok_or_else(|| Error::new("Unexpected EOF after 'while' condition".to_string()))?;
-                        Ok(Some(Expr::While(Box::new(WhileExpr { cond, body }))))
+                        let body = AST::read_group(lex)?.ok_or_else(|| {
+                            Error::new("Unexpected EOF after 'while' condition".to_string(), lex.peek_span())
+                        })?;
+                        Ok(Some(Expr::While(Box::new(WhileExpr { cond, body, span: lex.span_from(head) }))))
                     },
 
                     Keyword::Case => {
                         let expr = AST::read_binary_tree(lex, None, false)?;
-                        expect_token!(lex.next(), Separator(Separator::Colon));
+                        expect_token!(lex, Separator(Separator::Colon));
                         Ok(Some(Expr::Case(Box::new(expr))))
                     },
 
                     Keyword::Default => {
-                        expect_token!(lex.next(), Separator(Separator::Colon));
+                        expect_token!(lex, Separator(Separator::Colon));
                         Ok(Some(Expr::Default))
                     },
 
@@ -449,7 +557,7 @@ impl<'a> AST<'a> {
                         Ok(Some(Expr::Return(Box::new(val))))
                     },
 
-                    _ => return Err(Error::new(format!("Invalid Keyword at beginning of expression: {:?}", key))),
+                    _ => return Err(Error::new(format!("Invalid Keyword at beginning of expression: {:?}", key), head)),
                 }
             },
 
@@ -459,7 +567,8 @@ impl<'a> AST<'a> {
                 let next_token = match lex.peek() {
                     Some(t) => t,
                     None => {
-                        return Err(Error::new(format!("Stray identifier at EOF: {:?}", String::from_utf8_lossy(id))))
+                        let message = format!("Stray identifier at EOF: {:?}", String::from_utf8_lossy(id));
+                        return Err(Error::new(message, lex.peek_span()))
                     },
                 };
                 match next_token {
@@ -483,7 +592,7 @@ impl<'a> AST<'a> {
                                 },
                                 _ => match AST::read_line(lex) {
                                     Ok(Some(e)) => inner_expressions.push(e),
-                                    Ok(None) => break Err(Error::new("Unclosed brace at EOF".to_string())),
+                                    Ok(None) => break Err(Error::new("Unclosed brace at EOF".to_string(), lex.span())),
                                     Err(e) => break Err(e),
                                 },
                             }
@@ -498,11 +607,13 @@ impl<'a> AST<'a> {
                     },
 
                     // Default
-                    _ => return Err(Error::new(format!("Invalid Separator at beginning of expression: {:?}", sep))),
+                    _ => {
+                        return Err(Error::new(format!("Invalid Separator at beginning of expression: {:?}", sep), head))
+                    },
                 }
             },
 
-            _ => return Err(Error::new(format!("Invalid token at beginning of expression: {:?}", token))),
+            _ => return Err(Error::new(format!("Invalid token at beginning of expression: {:?}", token), head)),
         };
 
         // skip over trailing semicolons
@@ -513,7 +624,7 @@ impl<'a> AST<'a> {
         ret
     }
 
-    fn read_group(lex: &mut Peekable<Lexer<'a>>) -> Result<Option<Expr<'a>>, Error> {
+    fn read_group(lex: &mut Tokens<'a>) -> Result<Option<Expr<'a>>, Error> {
         match lex.peek() {
             Some(Token::Separator(Separator::Semicolon)) => {
                 while lex.peek() == Some(&Token::Separator(Separator::Semicolon)) {
@@ -527,27 +638,27 @@ impl<'a> AST<'a> {
     }
 
     fn read_binary_tree(
-        lex: &mut Peekable<Lexer<'a>>,
+        lex: &mut Tokens<'a>,
         first_token: Option<Token<'a>>, // Sometimes we've already parsed the first token, so it should be put here.
         expect_assignment: bool,        // Do we expect the first op to be an assignment?
     ) -> Result<Expr<'a>, Error> {
-        let (val, op) = AST::read_binary_tree_recursive(lex, first_token, expect_assignment, 0)?;
+        let (val, _, op) = AST::read_binary_tree_recursive(lex, first_token, expect_assignment, 0)?;
         if let Some(stray_op) = op {
-            Err(Error::new(format!("read_binary_tree has stray operator: {:?}", stray_op)))
+            Err(Error::new(format!("read_binary_tree has stray operator: {:?}", stray_op), lex.span()))
         } else {
             Ok(val)
         }
     }
 
     fn read_binary_tree_recursive(
-        lex: &mut Peekable<Lexer<'a>>,
+        lex: &mut Tokens<'a>,
         first_token: Option<Token<'a>>, // Sometimes we've already parsed the first token, so it should be put here.
         expect_assignment: bool,        // Do we expect the first op to be an assignment?
         lowest_prec: u8,                // We are not allowed to go below this operator precedence in this tree.
                                         // If we do, we'll return the next op.
-    ) -> Result<(Expr<'a>, Option<Operator>), Error> {
+    ) -> Result<(Expr<'a>, Span, Option<Operator>), Error> {
         // Get the first expression before any operators
-        let mut lhs = AST::read_btree_expression(lex, first_token)?;
+        let (mut lhs, mut span) = AST::read_btree_expression(lex, first_token)?;
 
         // Check if the next token is an operator
         let next_token = lex.peek();
@@ -568,54 +679,65 @@ impl<'a> AST<'a> {
                     if let Some(precedence) = AST::get_op_precedence(&op) {
                         // this op is invalid if an assignment is expected
                         if expect_assignment {
-                            break Err(Error::new(format!("Invalid operator {:?} found, expected assignment", op)))
+                            let message = format!("Invalid operator {:?} found, expected assignment", op);
+                            break Err(Error::new(message, lex.span()))
                         }
                         // If this op has lower prec than we're allowed to read, we have to return it here.
                         if precedence < lowest_prec {
-                            break Ok((lhs, Some(op)))
+                            break Ok((lhs, span, Some(op)))
                         }
                         // We're allowed to use the next operator. Let's read an RHS to put on after it.
                         // We limit this tree to current precedence + 1 to prevent it using operators of our
                         // current precedence.  This way, 1/2/3 is correctly built as (1/2)/3 rather than 1/(2/3).
-                        let (rhs, next_op) = AST::read_binary_tree_recursive(lex, None, false, precedence + 1)?;
+                        let (rhs, rhs_span, next_op) =
+                            AST::read_binary_tree_recursive(lex, None, false, precedence + 1)?;
+                        span = span.to(rhs_span);
                         if let Some(next_op) = next_op {
                             // There's another operator even after the RHS.
                             if let Some(next_prec) = AST::get_op_precedence(&next_op) {
                                 if next_prec < lowest_prec {
                                     // This next op is lower than we're allowed to go, so we must return it
                                     break Ok((
-                                        Expr::Binary(Box::new(BinaryExpr { op, left: lhs, right: rhs })),
+                                        Expr::Binary(Box::new(BinaryExpr { op, left: lhs, right: rhs, span })),
+                                        span,
                                         Some(next_op),
                                     ))
                                 } else {
                                     // Update LHS by sticking RHS onto it,
                                     // set op to the new operator, and go round again.
-                                    lhs = Expr::Binary(Box::new(BinaryExpr { op, left: lhs, right: rhs }));
+                                    lhs = Expr::Binary(Box::new(BinaryExpr { op, left: lhs, right: rhs, span }));
                                     op = next_op;
                                 }
                             } else {
                                 // Precedence would already have been checked by the returning function.
-                                break Err(Error::new(format!(
-                                    "read_binary_tree_recursive returned invalid operator: {}",
-                                    next_op
-                                )))
+                                break Err(Error::new(
+                                    format!("read_binary_tree_recursive returned invalid operator: {}", next_op),
+                                    lex.span(),
+                                ))
                             }
                         } else {
                             // No more operators so let's put our lhs and rhs together.
-                            break Ok((Expr::Binary(Box::new(BinaryExpr { op, left: lhs, right: rhs })), None))
+                            break Ok((
+                                Expr::Binary(Box::new(BinaryExpr { op, left: lhs, right: rhs, span })),
+                                span,
+                                None,
+                            ))
                         }
                     } else {
                         // this op is invalid if assignment not expected, OR if it's a unary operator
                         // (those have no precedence so they pass the previous test.)
                         if !expect_assignment || op == Operator::Not || op == Operator::Complement {
-                            break Err(Error::new(format!("Invalid operator {:?} found, expected evaluable", op)))
+                            let message = format!("Invalid operator {:?} found, expected evaluable", op);
+                            break Err(Error::new(message, lex.span()))
                         } else {
                             // No need to do precedence on an assignment, so just grab RHS and return
-                            let (rhs, stray_op) = AST::read_binary_tree_recursive(lex, None, false, lowest_prec)?;
+                            let (rhs, rhs_span, stray_op) =
+                                AST::read_binary_tree_recursive(lex, None, false, lowest_prec)?;
+                            let span = span.to(rhs_span);
                             break if let Some(op) = stray_op {
-                                Err(Error::new(format!("Stray operator {:?} in expression", op)))
+                                Err(Error::new(format!("Stray operator {:?} in expression", op), lex.span()))
                             } else {
-                                Ok((Expr::Binary(Box::new(BinaryExpr { op, left: lhs, right: rhs })), None))
+                                Ok((Expr::Binary(Box::new(BinaryExpr { op, left: lhs, right: rhs, span })), span, None))
                             }
                         }
                     }
@@ -623,21 +745,27 @@ impl<'a> AST<'a> {
             },
             _ => {
                 if expect_assignment {
-                    Err(Error::new(format!("Invalid token {:?} when expecting assignment operator", next_token)))
+                    let message = format!("Invalid token {:?} when expecting assignment operator", next_token);
+                    Err(Error::new(message, lex.peek_span()))
                 } else {
-                    Ok((lhs, None))
+                    Ok((lhs, span, None))
                 }
             },
         }
     }
 
-    fn read_btree_expression(lex: &mut Peekable<Lexer<'a>>, first_token: Option<Token<'a>>) -> Result<Expr<'a>, Error> {
+    fn read_btree_expression(
+        lex: &mut Tokens<'a>,
+        first_token: Option<Token<'a>>,
+    ) -> Result<(Expr<'a>, Span), Error> {
         // Get first token and match it
-        let mut lhs = match if first_token.is_some() { first_token } else { lex.next() } {
+        let token = if first_token.is_some() { first_token } else { lex.next() };
+        let head = lex.span();
+        let mut lhs = match token {
             Some(Token::Separator(ref sep)) if *sep == Separator::ParenLeft => {
                 let binary_tree = AST::read_binary_tree(lex, None, false)?;
                 if lex.next() != Some(Token::Separator(Separator::ParenRight)) {
-                    return Err(Error::new("Unclosed parenthesis in binary tree".to_string()))
+                    return Err(Error::new("Unclosed parenthesis in binary tree".to_string(), lex.span()))
                 } else {
                     binary_tree
                 }
@@ -645,9 +773,10 @@ impl<'a> AST<'a> {
             Some(Token::Operator(op)) => {
                 if op == Operator::Add || op == Operator::Subtract || op == Operator::Not || op == Operator::Complement
                 {
-                    Expr::Unary(Box::new(UnaryExpr { op, child: AST::read_btree_expression(lex, None)? }))
+                    let (child, _) = AST::read_btree_expression(lex, None)?;
+                    Expr::Unary(Box::new(UnaryExpr { op, child, span: lex.span_from(head) }))
                 } else {
-                    return Err(Error::new(format!("Invalid unary operator {:?} in expression", op)))
+                    return Err(Error::new(format!("Invalid unary operator {:?} in expression", op), lex.span()))
                 }
             },
             Some(Token::Identifier(t)) => {
@@ -660,8 +789,8 @@ impl<'a> AST<'a> {
 
             Some(Token::Real(t)) => Expr::LiteralReal(t),
             Some(Token::String(t)) => Expr::LiteralString(t),
-            Some(t) => return Err(Error::new(format!("Invalid token while scanning binary tree: {:?}", t))),
-            None => return Err(Error::new("Found EOF unexpectedly while reading binary tree".to_string())),
+            Some(t) => return Err(Error::new(format!("Invalid token while scanning binary tree: {:?}", t), lex.span())),
+            None => return Err(Error::new("Found EOF unexpectedly while reading binary tree".to_string(), lex.span())),
         };
 
         // Do we need to amend this LHS at all?
@@ -685,11 +814,13 @@ impl<'a> AST<'a> {
                                     }
                                 },
                                 Some(t) => {
-                                    return Err(Error::new(format!("Invalid token {:?}, expected expression", t)))
+                                    let message = format!("Invalid token {:?}, expected expression", t);
+                                    return Err(Error::new(message, lex.span()))
                                 },
                                 None => {
                                     return Err(Error::new(
                                         "Found EOF unexpectedly while reading array accessor".to_string(),
+                                        lex.span(),
                                     ))
                                 },
                             }
@@ -699,6 +830,7 @@ impl<'a> AST<'a> {
                         op: Operator::Index,
                         left: lhs,
                         right: Expr::Group(dimensions),
+                        span: lex.span_from(head),
                     }));
                 },
 
@@ -709,20 +841,27 @@ impl<'a> AST<'a> {
                             op: Operator::Deref,
                             left: lhs,
                             right: Expr::LiteralIdentifier(id),
+                            span: lex.span_from(head),
                         })),
-                        Some(t) => return Err(Error::new(format!("Unexpected token {:?} following deref", t))),
-                        None => return Err(Error::new("Found EOF unexpectedly while reading binary tree".to_string())),
+                        Some(t) => {
+                            return Err(Error::new(format!("Unexpected token {:?} following deref", t), lex.span()))
+                        },
+                        None => {
+                            let message = "Found EOF unexpectedly while reading binary tree".to_string();
+                            return Err(Error::new(message, lex.span()))
+                        },
                     }
                 },
                 _ => break,
             }
         }
 
-        Ok(lhs)
+        Ok((lhs, lex.span_from(head)))
     }
 
-    fn read_function_call(lex: &mut Peekable<Lexer<'a>>, function_name: &'a [u8]) -> Result<Expr<'a>, Error> {
-        expect_token!(lex.next(), Separator(Separator::ParenLeft));
+    fn read_function_call(lex: &mut Tokens<'a>, function_name: &'a [u8]) -> Result<Expr<'a>, Error> {
+        let head = lex.span();
+        expect_token!(lex, Separator(Separator::ParenLeft));
 
         let mut params = Vec::new();
         if lex.peek() == Some(&Token::Separator(Separator::ParenRight)) {
@@ -739,15 +878,20 @@ impl<'a> AST<'a> {
                             break
                         }
                     },
-                    Some(t) => return Err(Error::new(format!("Invalid token {:?}, expected expression", t))),
-                    None => return Err(Error::new("Found EOF unexpectedly while reading function call".to_string())),
+                    Some(t) => {
+                        return Err(Error::new(format!("Invalid token {:?}, expected expression", t), lex.span()))
+                    },
+                    None => {
+                        let message = "Found EOF unexpectedly while reading function call".to_string();
+                        return Err(Error::new(message, lex.span()))
+                    },
                 }
             }
         }
-        Ok(Expr::Function(Box::new(FunctionExpr { name: function_name, params })))
+        Ok(Expr::Function(Box::new(FunctionExpr { name: function_name, params, span: lex.span_from(head) })))
     }
 
-    fn get_op_precedence(op: &Operator) -> Option<u8> {
+    pub(crate) fn get_op_precedence(op: &Operator) -> Option<u8> {
         match op {
             Operator::Add => Some(4),
             Operator::Subtract => Some(4),
@@ -789,11 +933,80 @@ impl<'a> AST<'a> {
 mod tests {
     use super::*;
 
+    /// Resets every span in a tree, so testcases about the shape of a tree don't need to spell them out.
+    fn clear_spans(expr: &mut Expr) {
+        match expr {
+            Expr::Unary(unary) => {
+                unary.span = Span::default();
+                clear_spans(&mut unary.child);
+            },
+            Expr::Binary(binary) => {
+                binary.span = Span::default();
+                clear_spans(&mut binary.left);
+                clear_spans(&mut binary.right);
+            },
+            Expr::DoUntil(dountil) => {
+                dountil.span = Span::default();
+                clear_spans(&mut dountil.cond);
+                clear_spans(&mut dountil.body);
+            },
+            Expr::For(for_ex) => {
+                for_ex.span = Span::default();
+                clear_spans(&mut for_ex.start);
+                clear_spans(&mut for_ex.cond);
+                clear_spans(&mut for_ex.step);
+                clear_spans(&mut for_ex.body);
+            },
+            Expr::Function(call) => {
+                call.span = Span::default();
+                call.params.iter_mut().for_each(clear_spans);
+            },
+            Expr::Group(group) => group.iter_mut().for_each(clear_spans),
+            Expr::If(if_ex) => {
+                if_ex.span = Span::default();
+                clear_spans(&mut if_ex.cond);
+                clear_spans(&mut if_ex.body);
+                if_ex.else_body.iter_mut().for_each(clear_spans);
+            },
+            Expr::Repeat(repeat) => {
+                repeat.span = Span::default();
+                clear_spans(&mut repeat.count);
+                clear_spans(&mut repeat.body);
+            },
+            Expr::Switch(switch) => {
+                switch.span = Span::default();
+                clear_spans(&mut switch.input);
+                clear_spans(&mut switch.body);
+            },
+            Expr::Var(var) => var.span = Span::default(),
+            Expr::GlobalVar(var) => var.span = Span::default(),
+            Expr::With(with) => {
+                with.span = Span::default();
+                clear_spans(&mut with.target);
+                clear_spans(&mut with.body);
+            },
+            Expr::While(while_ex) => {
+                while_ex.span = Span::default();
+                clear_spans(&mut while_ex.cond);
+                clear_spans(&mut while_ex.body);
+            },
+            Expr::Case(e) | Expr::Return(e) => clear_spans(e),
+            Expr::LiteralIdentifier(_)
+            | Expr::LiteralReal(_)
+            | Expr::LiteralString(_)
+            | Expr::Default
+            | Expr::Continue
+            | Expr::Break
+            | Expr::Exit => (),
+        }
+    }
+
     /// Helper function for all the AST testcases.
     fn assert_ast(input: &str, expected_output: Option<Vec<Expr>>) {
         match AST::new(input.as_bytes()) {
-            Ok(ast) => {
+            Ok(mut ast) => {
                 if let Some(e) = expected_output {
+                    ast.iter_mut().for_each(clear_spans);
                     assert_eq!(*ast, e);
                 }
             },
@@ -816,6 +1029,7 @@ mod tests {
                 op: Operator::Assign,
                 left: Expr::LiteralIdentifier(b"a"),
                 right: Expr::LiteralReal(1.0),
+                span: Span::default(),
             }))]),
         )
     }
@@ -829,6 +1043,7 @@ mod tests {
                 op: Operator::AssignAdd,
                 left: Expr::LiteralIdentifier(b"b"),
                 right: Expr::LiteralReal(2.0),
+                span: Span::default(),
             }))]),
         )
     }
@@ -842,6 +1057,7 @@ mod tests {
                 op: Operator::AssignSubtract,
                 left: Expr::LiteralIdentifier(b"c"),
                 right: Expr::LiteralReal(3.0),
+                span: Span::default(),
             }))]),
         )
     }
@@ -855,6 +1071,7 @@ mod tests {
                 op: Operator::AssignMultiply,
                 left: Expr::LiteralIdentifier(b"d"),
                 right: Expr::LiteralReal(4.0),
+                span: Span::default(),
             }))]),
         )
     }
@@ -868,6 +1085,7 @@ mod tests {
                 op: Operator::AssignDivide,
                 left: Expr::LiteralIdentifier(b"e"),
                 right: Expr::LiteralReal(5.0),
+                span: Span::default(),
             }))]),
         )
     }
@@ -881,6 +1099,7 @@ mod tests {
                 op: Operator::AssignBitwiseAnd,
                 left: Expr::LiteralIdentifier(b"f"),
                 right: Expr::LiteralReal(6.0),
+                span: Span::default(),
             }))]),
         )
     }
@@ -894,6 +1113,7 @@ mod tests {
                 op: Operator::AssignBitwiseOr,
                 left: Expr::LiteralIdentifier(b"g"),
                 right: Expr::LiteralReal(7.0),
+                span: Span::default(),
             }))]),
         )
     }
@@ -907,6 +1127,7 @@ mod tests {
                 op: Operator::AssignBitwiseXor,
                 left: Expr::LiteralIdentifier(b"h"),
                 right: Expr::LiteralReal(8.0),
+                span: Span::default(),
             }))]),
         )
     }
@@ -946,10 +1167,13 @@ mod tests {
                         op: Operator::Deref,
                         left: Expr::LiteralIdentifier(b"a"),
                         right: Expr::LiteralIdentifier(b"b"),
+                        span: Span::default(),
                     })),
                     right: Expr::Group(vec![Expr::LiteralIdentifier(b"c")]),
+                    span: Span::default(),
                 })),
                 right: Expr::LiteralIdentifier(b"d"),
+                span: Span::default(),
             }))]),
         );
     }
@@ -973,14 +1197,19 @@ mod tests {
                                     op: Operator::Deref,
                                     left: Expr::LiteralIdentifier(b"a"),
                                     right: Expr::LiteralIdentifier(b"b"),
+                                    span: Span::default(),
                                 })),
                                 right: Expr::Group(vec![Expr::LiteralIdentifier(b"c")]),
+                                span: Span::default(),
                             })),
                             right: Expr::LiteralIdentifier(b"d"),
+                            span: Span::default(),
                         })),
                         right: Expr::LiteralIdentifier(b"e"),
+                        span: Span::default(),
                     })),
                     right: Expr::Group(vec![Expr::LiteralIdentifier(b"f"), Expr::LiteralIdentifier(b"g")]),
+                    span: Span::default(),
                 })),
                 right: Expr::Binary(Box::new(BinaryExpr {
                     op: Operator::Deref,
@@ -988,9 +1217,12 @@ mod tests {
                         op: Operator::Index,
                         left: Expr::LiteralIdentifier(b"h"),
                         right: Expr::Group(vec![Expr::LiteralIdentifier(b"i"), Expr::LiteralIdentifier(b"j")]),
+                        span: Span::default(),
                     })),
                     right: Expr::LiteralIdentifier(b"k"),
+                    span: Span::default(),
                 })),
+                span: Span::default(),
             }))]),
         );
     }
@@ -1008,10 +1240,13 @@ mod tests {
                         op: Operator::Add,
                         left: Expr::LiteralIdentifier(b"a"),
                         right: Expr::LiteralReal(1.0),
+                        span: Span::default(),
                     })),
                     right: Expr::LiteralIdentifier(b"x"),
+                    span: Span::default(),
                 })),
                 right: Expr::LiteralReal(400.0),
+                span: Span::default(),
             }))]),
         );
     }
@@ -1028,7 +1263,9 @@ mod tests {
                     op: Operator::Equal,
                     left: Expr::LiteralIdentifier(b"b"),
                     right: Expr::LiteralIdentifier(b"c"),
+                    span: Span::default(),
                 })),
+                span: Span::default(),
             }))]),
         );
     }
@@ -1048,14 +1285,18 @@ mod tests {
                             op: Operator::Equal,
                             left: Expr::LiteralIdentifier(b"a"),
                             right: Expr::LiteralIdentifier(b"b"),
+                            span: Span::default(),
                         })),
                         right: Expr::LiteralIdentifier(b"c"),
+                        span: Span::default(),
                     })),
                     right: Expr::Group(vec![Expr::Binary(Box::new(BinaryExpr {
                         op: Operator::Equal,
                         left: Expr::LiteralIdentifier(b"d"),
                         right: Expr::LiteralIdentifier(b"e"),
+                        span: Span::default(),
                     }))]),
+                    span: Span::default(),
                 })),
                 right: Expr::Binary(Box::new(BinaryExpr {
                     op: Operator::Equal,
@@ -1066,10 +1307,14 @@ mod tests {
                             op: Operator::Equal,
                             left: Expr::LiteralIdentifier(b"g"),
                             right: Expr::LiteralIdentifier(b"h"),
+                            span: Span::default(),
                         }))]),
+                        span: Span::default(),
                     })),
                     right: Expr::LiteralIdentifier(b"i"),
+                    span: Span::default(),
                 })),
+                span: Span::default(),
             }))]),
         );
     }
@@ -1089,7 +1334,12 @@ mod tests {
             Some(vec![Expr::Binary(Box::new(BinaryExpr {
                 op: Operator::Assign,
                 left: Expr::LiteralIdentifier(b"a"),
-                right: Expr::Unary(Box::new(UnaryExpr { op: Operator::Add, child: Expr::LiteralReal(1.0) })),
+                right: Expr::Unary(Box::new(UnaryExpr {
+                    op: Operator::Add,
+                    child: Expr::LiteralReal(1.0),
+                    span: Span::default(),
+                })),
+                span: Span::default(),
             }))]),
         )
     }
@@ -1102,7 +1352,12 @@ mod tests {
             Some(vec![Expr::Binary(Box::new(BinaryExpr {
                 op: Operator::Assign,
                 left: Expr::LiteralIdentifier(b"a"),
-                right: Expr::Unary(Box::new(UnaryExpr { op: Operator::Subtract, child: Expr::LiteralReal(1.0) })),
+                right: Expr::Unary(Box::new(UnaryExpr {
+                    op: Operator::Subtract,
+                    child: Expr::LiteralReal(1.0),
+                    span: Span::default(),
+                })),
+                span: Span::default(),
             }))]),
         )
     }
@@ -1115,7 +1370,12 @@ mod tests {
             Some(vec![Expr::Binary(Box::new(BinaryExpr {
                 op: Operator::Assign,
                 left: Expr::LiteralIdentifier(b"a"),
-                right: Expr::Unary(Box::new(UnaryExpr { op: Operator::Complement, child: Expr::LiteralReal(1.0) })),
+                right: Expr::Unary(Box::new(UnaryExpr {
+                    op: Operator::Complement,
+                    child: Expr::LiteralReal(1.0),
+                    span: Span::default(),
+                })),
+                span: Span::default(),
             }))]),
         )
     }
@@ -1128,7 +1388,12 @@ mod tests {
             Some(vec![Expr::Binary(Box::new(BinaryExpr {
                 op: Operator::Assign,
                 left: Expr::LiteralIdentifier(b"a"),
-                right: Expr::Unary(Box::new(UnaryExpr { op: Operator::Not, child: Expr::LiteralReal(1.0) })),
+                right: Expr::Unary(Box::new(UnaryExpr {
+                    op: Operator::Not,
+                    child: Expr::LiteralReal(1.0),
+                    span: Span::default(),
+                })),
+                span: Span::default(),
             }))]),
         )
     }
@@ -1160,20 +1425,34 @@ mod tests {
                                                 op: Operator::Deref,
                                                 left: Expr::LiteralIdentifier(b"b"),
                                                 right: Expr::LiteralIdentifier(b"c"),
+                                                span: Span::default(),
                                             })),
                                             right: Expr::Group(vec![Expr::Unary(Box::new(UnaryExpr {
                                                 op: Operator::Add,
                                                 child: Expr::LiteralIdentifier(b"d"),
+                                                span: Span::default(),
                                             }))]),
+                                            span: Span::default(),
                                         })),
+                                        span: Span::default(),
                                     })),
+                                    span: Span::default(),
                                 })),
+                                span: Span::default(),
                             })),
+                            span: Span::default(),
                         })),
                         right: Expr::LiteralReal(2.0),
+                        span: Span::default(),
                     })),
-                    right: Expr::Unary(Box::new(UnaryExpr { op: Operator::Subtract, child: Expr::LiteralReal(3.0) })),
+                    right: Expr::Unary(Box::new(UnaryExpr {
+                        op: Operator::Subtract,
+                        child: Expr::LiteralReal(3.0),
+                        span: Span::default(),
+                    })),
+                    span: Span::default(),
                 })),
+                span: Span::default(),
             }))]),
         )
     }
@@ -1192,8 +1471,11 @@ mod tests {
                         op: Operator::Add,
                         left: Expr::LiteralIdentifier(b"b"),
                         right: Expr::LiteralReal(1.0),
+                        span: Span::default(),
                     })),
+                    span: Span::default(),
                 })),
+                span: Span::default(),
             }))]),
         )
     }
@@ -1206,10 +1488,19 @@ mod tests {
             Some(vec![Expr::Function(Box::new(FunctionExpr {
                 name: b"instance_create",
                 params: vec![
-                    Expr::Function(Box::new(FunctionExpr { name: b"random", params: vec![Expr::LiteralReal(800.0)] })),
-                    Expr::Function(Box::new(FunctionExpr { name: b"random", params: vec![Expr::LiteralReal(608.0)] })),
+                    Expr::Function(Box::new(FunctionExpr {
+                        name: b"random",
+                        params: vec![Expr::LiteralReal(800.0)],
+                        span: Span::default(),
+                    })),
+                    Expr::Function(Box::new(FunctionExpr {
+                        name: b"random",
+                        params: vec![Expr::LiteralReal(608.0)],
+                        span: Span::default(),
+                    })),
                     Expr::LiteralIdentifier(b"apple"),
                 ],
+                span: Span::default(),
             }))]),
         )
     }
@@ -1224,29 +1515,35 @@ mod tests {
                     op: Operator::Assign,
                     left: Expr::LiteralIdentifier(b"i"),
                     right: Expr::LiteralReal(0.0),
+                    span: Span::default(),
                 })),
                 cond: Expr::Binary(Box::new(BinaryExpr {
                     op: Operator::LessThan,
                     left: Expr::LiteralIdentifier(b"i"),
                     right: Expr::LiteralReal(10.0),
+                    span: Span::default(),
                 })),
                 step: Expr::Binary(Box::new(BinaryExpr {
                     op: Operator::AssignAdd,
                     left: Expr::LiteralIdentifier(b"i"),
                     right: Expr::LiteralReal(1.0),
+                    span: Span::default(),
                 })),
                 body: Expr::Group(vec![
                     Expr::Binary(Box::new(BinaryExpr {
                         op: Operator::Assign,
                         left: Expr::LiteralIdentifier(b"a"),
                         right: Expr::LiteralReal(1.0),
+                        span: Span::default(),
                     })),
                     Expr::Binary(Box::new(BinaryExpr {
                         op: Operator::Assign,
                         left: Expr::LiteralIdentifier(b"b"),
                         right: Expr::LiteralIdentifier(b"c"),
+                        span: Span::default(),
                     })),
                 ]),
+                span: Span::default(),
             }))]),
         )
     }
@@ -1261,22 +1558,27 @@ mod tests {
                     op: Operator::Assign,
                     left: Expr::LiteralIdentifier(b"i"),
                     right: Expr::LiteralReal(0.0),
+                    span: Span::default(),
                 })),
                 cond: Expr::Binary(Box::new(BinaryExpr {
                     op: Operator::LessThan,
                     left: Expr::LiteralIdentifier(b"i"),
                     right: Expr::LiteralReal(10.0),
+                    span: Span::default(),
                 })),
                 step: Expr::Binary(Box::new(BinaryExpr {
                     op: Operator::AssignAdd,
                     left: Expr::LiteralIdentifier(b"i"),
                     right: Expr::LiteralReal(1.0),
+                    span: Span::default(),
                 })),
                 body: Expr::Binary(Box::new(BinaryExpr {
                     op: Operator::Assign,
                     left: Expr::LiteralIdentifier(b"c"),
                     right: Expr::LiteralReal(3.0),
+                    span: Span::default(),
                 })),
+                span: Span::default(),
             }))]),
         )
     }
@@ -1291,22 +1593,27 @@ mod tests {
                     op: Operator::Assign,
                     left: Expr::LiteralIdentifier(b"i"),
                     right: Expr::LiteralReal(0.0),
+                    span: Span::default(),
                 })),
                 cond: Expr::Binary(Box::new(BinaryExpr {
                     op: Operator::LessThan,
                     left: Expr::LiteralIdentifier(b"i"),
                     right: Expr::LiteralReal(10.0),
+                    span: Span::default(),
                 })),
                 step: Expr::Binary(Box::new(BinaryExpr {
                     op: Operator::AssignAdd,
                     left: Expr::LiteralIdentifier(b"i"),
                     right: Expr::LiteralReal(1.0),
+                    span: Span::default(),
                 })),
                 body: Expr::Group(vec![Expr::Binary(Box::new(BinaryExpr {
                     op: Operator::Assign,
                     left: Expr::LiteralIdentifier(b"d"),
                     right: Expr::LiteralReal(4.0),
+                    span: Span::default(),
                 }))]),
+                span: Span::default(),
             }))]),
         )
     }
@@ -1319,6 +1626,7 @@ mod tests {
                 op: Operator::Assign,
                 left: Expr::LiteralIdentifier(b"a"),
                 right: Expr::LiteralReal(1.0),
+                span: Span::default(),
             }))]),
         );
     }
@@ -1341,25 +1649,31 @@ mod tests {
                     op: Operator::Equal,
                     left: Expr::LiteralIdentifier(b"a"),
                     right: Expr::LiteralReal(1.0),
+                    span: Span::default(),
                 })),
                 body: Expr::Group(vec![Expr::Binary(Box::new(BinaryExpr {
                     op: Operator::Assign,
                     left: Expr::LiteralIdentifier(b"a"),
                     right: Expr::LiteralReal(2.0),
+                    span: Span::default(),
                 }))]),
                 else_body: Some(Expr::If(Box::new(IfExpr {
                     cond: Expr::Binary(Box::new(BinaryExpr {
                         op: Operator::Equal,
                         left: Expr::LiteralIdentifier(b"a"),
                         right: Expr::LiteralReal(2.0),
+                        span: Span::default(),
                     })),
                     body: Expr::Group(vec![Expr::Binary(Box::new(BinaryExpr {
                         op: Operator::Assign,
                         left: Expr::LiteralIdentifier(b"a"),
                         right: Expr::LiteralReal(4.0),
+                        span: Span::default(),
                     }))]),
                     else_body: None,
+                    span: Span::default(),
                 }))),
+                span: Span::default(),
             }))]),
         );
     }
@@ -1370,8 +1684,8 @@ mod tests {
             // var syntax - basic constructions
             "var a; var b, c",
             Some(vec![
-                Expr::Var(Box::new(VarExpr { vars: vec![b"a"] })),
-                Expr::Var(Box::new(VarExpr { vars: vec![b"b", b"c"] })),
+                Expr::Var(Box::new(VarExpr { vars: vec![b"a"], span: Span::default() })),
+                Expr::Var(Box::new(VarExpr { vars: vec![b"b", b"c"], span: Span::default() })),
             ]),
         )
     }
@@ -1382,10 +1696,10 @@ mod tests {
             // var syntax - unusual valid constructions
             "var; var a,b,; var c,var",
             Some(vec![
-                Expr::Var(Box::new(VarExpr { vars: vec![] })),
-                Expr::Var(Box::new(VarExpr { vars: vec![b"a", b"b"] })),
-                Expr::Var(Box::new(VarExpr { vars: vec![b"c"] })),
-                Expr::Var(Box::new(VarExpr { vars: vec![] })),
+                Expr::Var(Box::new(VarExpr { vars: vec![], span: Span::default() })),
+                Expr::Var(Box::new(VarExpr { vars: vec![b"a", b"b"], span: Span::default() })),
+                Expr::Var(Box::new(VarExpr { vars: vec![b"c"], span: Span::default() })),
+                Expr::Var(Box::new(VarExpr { vars: vec![], span: Span::default() })),
             ]),
         )
     }
@@ -1403,8 +1717,12 @@ mod tests {
         assert_ast(
             "var a instance_create instance_destroy ()",
             Some(vec![
-                Expr::Var(Box::new(VarExpr { vars: vec![b"a", b"instance_create"] })),
-                Expr::Function(Box::new(FunctionExpr { name: b"instance_destroy", params: vec![] })),
+                Expr::Var(Box::new(VarExpr { vars: vec![b"a", b"instance_create"], span: Span::default() })),
+                Expr::Function(Box::new(FunctionExpr {
+                    name: b"instance_destroy",
+                    params: vec![],
+                    span: Span::default(),
+                })),
             ]),
         )
     }
@@ -1415,15 +1733,17 @@ mod tests {
         assert_ast(
             "var a b global.g = 0",
             Some(vec![
-                Expr::Var(Box::new(VarExpr { vars: vec![b"a", b"b"] })),
+                Expr::Var(Box::new(VarExpr { vars: vec![b"a", b"b"], span: Span::default() })),
                 Expr::Binary(Box::new(BinaryExpr {
                     op: Operator::Assign,
                     left: Expr::Binary(Box::new(BinaryExpr {
                         op: Operator::Deref,
                         left: Expr::LiteralIdentifier(b"global"),
                         right: Expr::LiteralIdentifier(b"g"),
+                        span: Span::default(),
                     })),
                     right: Expr::LiteralReal(0.0),
+                    span: Span::default(),
                 })),
             ]),
         )
@@ -1444,12 +1764,19 @@ mod tests {
     #[test]
     fn expression_with_operators() {
         // expression - unary and binary operators
+        let mut expr = AST::expression(b"1 * -2").unwrap();
+        clear_spans(&mut expr);
         assert_eq!(
-            AST::expression(b"1 * -2").unwrap(),
+            expr,
             Expr::Binary(Box::new(BinaryExpr {
                 op: Operator::Multiply,
                 left: Expr::LiteralReal(1.0),
-                right: Expr::Unary(Box::new(UnaryExpr { op: Operator::Subtract, child: Expr::LiteralReal(2.0) })),
+                right: Expr::Unary(Box::new(UnaryExpr {
+                    op: Operator::Subtract,
+                    child: Expr::LiteralReal(2.0),
+                    span: Span::default(),
+                })),
+                span: Span::default(),
             }))
         );
     }
@@ -1459,4 +1786,49 @@ mod tests {
         // expression with extra code after it - extra code should be dropped
         assert_eq!(AST::expression(b"0; a=1; game_end()").unwrap(), Expr::LiteralReal(0.0));
     }
+
+    #[test]
+    fn spans() {
+        // spans - statements, nested expressions and function calls across lines
+        let ast = AST::new(b"a = 1;\nif b {\n  c(d[0], e.f)\n}").unwrap();
+        let (assign, if_ex) = match &ast[..] {
+            [Expr::Binary(assign), Expr::If(if_ex)] => (assign, if_ex),
+            _ => panic!("unexpected tree: {:?}", ast),
+        };
+        assert_eq!(assign.span, Span { start: 0, end: 5, line: 1, column: 1 });
+        assert_eq!(if_ex.span, Span { start: 7, end: 30, line: 2, column: 1 });
+
+        let call = match &if_ex.body {
+            Expr::Group(group) => match &group[..] {
+                [Expr::Function(call)] => call,
+                _ => panic!("unexpected group: {:?}", group),
+            },
+            e => panic!("unexpected body: {:?}", e),
+        };
+        assert_eq!(call.span, Span { start: 16, end: 28, line: 3, column: 3 });
+        assert_eq!(call.params[0].span(), Some(Span { start: 18, end: 22, line: 3, column: 5 }));
+        assert_eq!(call.params[1].span(), Some(Span { start: 24, end: 27, line: 3, column: 11 }));
+    }
+
+    #[test]
+    fn spans_binary_tree() {
+        // spans - binary trees cover both operands, and a unary operator covers its child
+        let expr = AST::expression(b"-x * (y + 2)").unwrap();
+        let binary = match &expr {
+            Expr::Binary(binary) => binary,
+            e => panic!("unexpected tree: {:?}", e),
+        };
+        assert_eq!(binary.span, Span { start: 0, end: 12, line: 1, column: 1 });
+        assert_eq!(binary.left.span(), Some(Span { start: 0, end: 2, line: 1, column: 1 }));
+        assert_eq!(binary.right.span(), Some(Span { start: 6, end: 11, line: 1, column: 7 }));
+    }
+
+    #[test]
+    fn spans_errors() {
+        // spans - errors point at the offending token, or the end of the source at EOF
+        let error = AST::new(b"a = 1;\n/* comment */ b = ]").unwrap_err();
+        assert_eq!(error.span, Span { start: 25, end: 26, line: 2, column: 19 });
+        let error = AST::new(b"if a {\n  b = 1\n").unwrap_err();
+        assert_eq!(error.span, Span { start: 15, end: 15, line: 3, column: 1 });
+    }
 }
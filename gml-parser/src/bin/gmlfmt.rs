@@ -0,0 +1,146 @@
+use gml_parser::{
+    ast::AST,
+    lexer::Lexer,
+    printer::{self, BraceStyle, Style},
+};
+use std::{
+    env, fs,
+    io::{self, Read, Write},
+    process,
+};
+
+static USAGE: &str = "\
+Usage: gmlfmt [options] [FILE...]
+
+Reformats GML code. Reads from stdin if no files are given, and prints to stdout unless -w is given.
+Comments are not kept, as the parser discards them, so -w refuses to rewrite files which have any.
+
+Options:
+    -h, --help          print this help message
+    -w, --write         overwrite the given files instead of printing them
+    -i, --indent N      indent with N spaces (default 4)
+    -t, --tabs          indent with tabs
+    -a, --allman        put opening braces on their own line
+    -b, --begin-end     write blocks with begin and end
+    -p, --pascal        write assignments as :=
+    -d, --diamond       write != as <>
+    -l, --lf            end lines with LF instead of CRLF";
+
+fn fail(message: impl std::fmt::Display) -> ! {
+    eprintln!("gmlfmt: {}", message);
+    process::exit(1)
+}
+
+fn format(name: &str, source: &[u8], style: &Style) -> Vec<u8> {
+    match AST::new(source) {
+        Ok(ast) => printer::print(&ast, style),
+        Err(e) => fail(format_args!("{}: {}", name, e)),
+    }
+}
+
+/// Checks for comments, which the printer would drop.
+fn has_comments(source: &[u8]) -> bool {
+    // The lexer skips comments along with whitespace, so anything else between tokens has to be a comment
+    let mut lexer = Lexer::new(source);
+    let mut end = 0;
+    loop {
+        let token = lexer.next();
+        let span = lexer.span();
+        if source[end..span.start].iter().any(|&ch| ch > b' ') {
+            return true
+        }
+        if token.is_none() {
+            return false
+        }
+        end = span.end;
+    }
+}
+
+fn main() {
+    let mut style = Style::default();
+    let mut write = false;
+    let mut files = Vec::new();
+
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                return
+            },
+            "-w" | "--write" => write = true,
+            "-i" | "--indent" => {
+                let count = args
+                    .next()
+                    .and_then(|n| n.parse::<usize>().ok())
+                    .unwrap_or_else(|| fail("--indent expects a number of spaces"));
+                style.indent = " ".repeat(count);
+            },
+            "-t" | "--tabs" => style.indent = "\t".into(),
+            "-a" | "--allman" => style.braces = BraceStyle::NextLine,
+            "-b" | "--begin-end" => style.begin_end = true,
+            "-p" | "--pascal" => style.pascal_assign = true,
+            "-d" | "--diamond" => style.diamond = true,
+            "-l" | "--lf" => style.newline = "\n".into(),
+            _ if arg.starts_with('-') && arg != "-" => fail(format_args!("unknown option {}\n\n{}", arg, USAGE)),
+            _ => files.push(arg),
+        }
+    }
+
+    if files.is_empty() {
+        if write {
+            fail("--write needs at least one file");
+        }
+        let mut source = Vec::new();
+        if let Err(e) = io::stdin().read_to_end(&mut source) {
+            fail(format_args!("couldn't read stdin: {}", e));
+        }
+        let output = format("<stdin>", &source, &style);
+        let _ = io::stdout().write_all(&output);
+        return
+    }
+
+    let sources = files
+        .iter()
+        .map(|file| fs::read(file).unwrap_or_else(|e| fail(format_args!("couldn't read {}: {}", file, e))))
+        .collect::<Vec<_>>();
+    if write {
+        // Check everything first so nothing gets rewritten if any file would lose its comments
+        if let Some(file) = files.iter().zip(&sources).find(|(_, source)| has_comments(source)).map(|(x, _)| x) {
+            fail(format_args!("{} has comments, which would be lost, so it wasn't rewritten", file));
+        }
+    }
+
+    for (file, source) in files.iter().zip(&sources) {
+        let output = format(file, source, &style);
+        if write {
+            if let Err(e) = fs::write(file, &output) {
+                fail(format_args!("couldn't write {}: {}", file, e));
+            }
+        } else {
+            let _ = io::stdout().write_all(&output);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_comments() {
+        assert!(has_comments(b"// at the start\nx = 1;"));
+        assert!(has_comments(b"x = 1; // at the end"));
+        assert!(has_comments(b"x = 1;\r\n/* between\r\nlines */\r\ny = 2;"));
+        assert!(has_comments(b"x = /**/ 1;"));
+        assert!(has_comments(b"x = 1; /* unterminated"));
+    }
+
+    #[test]
+    fn no_comments() {
+        assert!(!has_comments(b""));
+        assert!(!has_comments(b"  \r\n\t"));
+        assert!(!has_comments(b"x = 1 / 2;\r\ny /= 3;"));
+        assert!(!has_comments(b"s = \"// not a comment\" + '/* nor this */';"));
+    }
+}
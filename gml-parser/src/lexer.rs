@@ -1,4 +1,4 @@
-use crate::token::{Keyword, Operator, Separator, Token};
+use crate::token::{Keyword, Operator, Separator, Span, Token};
 
 use std::{
     iter::{Copied, Enumerate, Peekable},
@@ -12,7 +12,14 @@ pub struct Lexer<'a> {
     /// GML source code to return references to.
     src: &'a [u8],
 
-    line_hint: usize,
+    /// Span of the token most recently returned.
+    span: Span,
+
+    /// Byte offset where the current token started.
+    token_start: usize,
+
+    /// How far into the source line numbers have been counted, and the line and line start at that point.
+    located: (usize, usize, usize),
 
     /// Iterator over the source code as raw bytes.
     iter: Peekable<Enumerate<Copied<slice::Iter<'a, u8>>>>,
@@ -21,38 +28,57 @@ pub struct Lexer<'a> {
 impl<'a> Lexer<'a> {
     /// Creates a new Lexer over GML source code.
     pub fn new(src: &'a [u8]) -> Self {
-        Lexer { src, line_hint: 1, iter: src.iter().copied().enumerate().peekable() }
+        Lexer {
+            src,
+            span: Span { start: 0, end: 0, line: 1, column: 1 },
+            token_start: 0,
+            located: (0, 1, 0),
+            iter: src.iter().copied().enumerate().peekable(),
+        }
     }
 
-    /// Returns the current line number in the source code.
+    /// Returns the line number of the token most recently returned.
     pub fn line(&self) -> usize {
-        self.line_hint
+        self.span.line
+    }
+
+    /// Returns the span of the token most recently returned.
+    /// Once the source code runs out, this is an empty span at the end of it.
+    pub fn span(&self) -> Span {
+        self.span
     }
 
     /// Fast-forwards the internal iterator to the next token, skipping over whitespace.
-    /// Returns how many lines (LF) were skipped in the process.
-    fn fast_forward(&mut self) -> usize {
-        let mut lines_skipped: usize = 0;
+    fn fast_forward(&mut self) {
         loop {
             match self.iter.peek() {
                 Some(&(_, ch)) if ch <= b' ' => {
-                    if ch == b'\n' {
-                        lines_skipped += 1;
-                    }
                     self.iter.next();
                 },
-                _ => break lines_skipped,
+                _ => break,
             }
         }
     }
-}
 
-impl<'a> Iterator for Lexer<'a> {
-    type Item = Token<'a>;
+    /// Works out the span from a byte offset up to the current position.
+    /// Offsets must never go backwards between calls, as lines are only counted once.
+    fn span_from(&mut self, start: usize) -> Span {
+        let end = self.iter.peek().map_or(self.src.len(), |&(i, _)| i);
+        let (located, mut line, mut line_start) = self.located;
+        for (i, &ch) in self.src[located..start].iter().enumerate() {
+            if ch == b'\n' {
+                line += 1;
+                line_start = located + i + 1;
+            }
+        }
+        self.located = (start, line, line_start);
+        Span { start, end, line, column: start - line_start + 1 }
+    }
 
-    fn next(&mut self) -> Option<Self::Item> {
+    /// Reads the next token, skipping over whitespace and comments.
+    fn read_token(&mut self) -> Option<Token<'a>> {
         // locate next token
-        self.line_hint = self.fast_forward();
+        self.fast_forward();
 
         /// Helper function to reconstruct our byte slices to a string easily.
         /// This is fine since we operate on something that is a &str in a first place,
@@ -63,6 +89,7 @@ impl<'a> Iterator for Lexer<'a> {
         }
 
         let head = *self.iter.peek()?;
+        self.token_start = head.0;
 
         #[allow(clippy::match_overlapping_arm)] // quotes overlap with the catch-all ASCII
         Some(match head.1 {
@@ -288,7 +315,7 @@ impl<'a> Iterator for Lexer<'a> {
                                         },
                                     }
                                 }
-                                return self.next()
+                                return self.read_token()
                             },
 
                             _ => return Some(Token::Operator(op)),
@@ -338,7 +365,7 @@ impl<'a> Iterator for Lexer<'a> {
                                 },
                             }
                         }
-                        return self.next()
+                        return self.read_token()
                     } else if op == Operator::LessThan && ch2 == b'>' {
                         // <> is the same as != (let's call it a diamond)
 
@@ -370,4 +397,15 @@ impl<'a> Iterator for Lexer<'a> {
     }
 }
 
+impl<'a> Iterator for Lexer<'a> {
+    type Item = Token<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let token = self.read_token();
+        let start = if token.is_some() { self.token_start } else { self.src.len() };
+        self.span = self.span_from(start);
+        token
+    }
+}
+
 // The lexer is intrinsically tested via the AST tests.
@@ -0,0 +1,572 @@
+use crate::{
+    ast::{BinaryExpr, Expr, AST},
+    token::Operator,
+};
+
+use std::io::Write;
+
+/// How printed code should be laid out.
+#[derive(Clone, Debug)]
+pub struct Style {
+    /// Text for one level of indentation.
+    pub indent: String,
+
+    /// Text to end each line with. GM8 itself uses CRLF.
+    pub newline: String,
+
+    /// Where the opening brace of a block goes.
+    pub braces: BraceStyle,
+
+    /// Write blocks with `begin` and `end` instead of braces.
+    pub begin_end: bool,
+
+    /// Write assignments with the pascal-style `:=` instead of `=`.
+    pub pascal_assign: bool,
+
+    /// Write `<>` instead of `!=`.
+    pub diamond: bool,
+}
+
+impl Default for Style {
+    fn default() -> Self {
+        Self {
+            indent: "    ".into(),
+            newline: "\r\n".into(),
+            braces: BraceStyle::SameLine,
+            begin_end: false,
+            pascal_assign: false,
+            diamond: false,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum BraceStyle {
+    /// `if (a) {`
+    SameLine,
+
+    /// `if (a)` with the brace on the line below.
+    NextLine,
+}
+
+/// Where an expression is being printed, for the benefit of a Rewrite.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Place {
+    /// Anywhere a value is read.
+    Value,
+
+    /// The left side of an assignment.
+    Assigned,
+
+    /// The instance on the left of a `.`
+    Owner,
+
+    /// The variable name on the right of a `.`
+    Field,
+
+    /// The target of a `with` statement.
+    WithTarget,
+}
+
+/// What a name which isn't part of an expression refers to, for the benefit of a Rewrite.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Name {
+    /// The name of a function or script being called.
+    Function,
+
+    /// A variable being declared with `var` or `globalvar`.
+    Declared,
+}
+
+/// Lets a caller replace parts of the code as it's printed, such as the deobfuscator renaming variables.
+pub trait Rewrite {
+    /// Returns text to print in place of an expression, or None to print it as normal.
+    /// Text which is printed in place of an expression is never wrapped in parentheses.
+    fn expr(&mut self, _expr: &Expr, _place: Place) -> Option<Vec<u8>> {
+        None
+    }
+
+    /// Returns text to print in place of a name, or None to print it as it is.
+    fn name(&mut self, _name: &[u8], _kind: Name) -> Option<Vec<u8>> {
+        None
+    }
+}
+
+impl Rewrite for () {}
+
+/// Writes an AST back out as GML source code.
+pub struct Printer<'s, 'r> {
+    style: &'s Style,
+    rewrite: &'r mut dyn Rewrite,
+    output: Vec<u8>,
+    indent: usize,
+}
+
+/// Prints a list of statements, such as a whole script.
+pub fn print(code: &[Expr], style: &Style) -> Vec<u8> {
+    let mut rewrite = ();
+    let mut printer = Printer::new(style, &mut rewrite);
+    printer.statements(code);
+    printer.finish()
+}
+
+/// Prints a single expression, such as a trigger condition.
+pub fn print_expression(expr: &Expr, style: &Style) -> Vec<u8> {
+    let mut rewrite = ();
+    let mut printer = Printer::new(style, &mut rewrite);
+    printer.expression(expr);
+    printer.finish()
+}
+
+impl<'s, 'r> Printer<'s, 'r> {
+    pub fn new(style: &'s Style, rewrite: &'r mut dyn Rewrite) -> Self {
+        Self { style, rewrite, output: Vec::new(), indent: 0 }
+    }
+
+    /// Prints a list of statements, one per line.
+    pub fn statements(&mut self, code: &[Expr]) {
+        // Statements after a case label are indented one level further than the label
+        let mut in_case = false;
+        for stmt in code {
+            if matches!(stmt, Expr::Case(_) | Expr::Default) {
+                if in_case {
+                    self.indent -= 1;
+                }
+                in_case = true;
+                self.write_indent();
+                self.statement(stmt, true);
+                self.indent += 1;
+            } else {
+                self.write_indent();
+                self.statement(stmt, true);
+            }
+        }
+        if in_case {
+            self.indent -= 1;
+        }
+    }
+
+    /// Prints a single expression.
+    pub fn expression(&mut self, expr: &Expr) {
+        self.value(expr, Place::Value, false);
+    }
+
+    /// Returns everything printed so far.
+    pub fn finish(self) -> Vec<u8> {
+        self.output
+    }
+
+    fn write(&mut self, text: &str) {
+        self.output.extend_from_slice(text.as_bytes());
+    }
+
+    fn write_indent(&mut self) {
+        for _ in 0..self.indent {
+            self.output.extend_from_slice(self.style.indent.as_bytes());
+        }
+    }
+
+    fn newline(&mut self) {
+        self.output.extend_from_slice(self.style.newline.as_bytes());
+    }
+
+    /// Writes a statement starting at the current position. If `terminate` is set, it's followed by
+    /// a semicolon where one is needed and a line break. Otherwise it's left open, as in a for loop's header.
+    fn statement(&mut self, stmt: &Expr, terminate: bool) {
+        match stmt {
+            Expr::DoUntil(dountil) => {
+                self.write("do");
+                self.block(&dountil.body);
+                self.continue_block();
+                self.write("until (");
+                self.expression(&dountil.cond);
+                self.write(")");
+            },
+            Expr::For(for_ex) => {
+                self.write("for (");
+                self.statement(&for_ex.start, false);
+                self.write("; ");
+                self.expression(&for_ex.cond);
+                self.write("; ");
+                self.statement(&for_ex.step, false);
+                self.write(")");
+                self.block(&for_ex.body);
+            },
+            Expr::Group(group) => {
+                self.open_brace();
+                self.newline();
+                self.indent += 1;
+                self.statements(group);
+                self.indent -= 1;
+                self.write_indent();
+                self.close_brace();
+            },
+            Expr::If(if_ex) => {
+                self.write("if (");
+                self.expression(&if_ex.cond);
+                self.write(")");
+                self.block(&if_ex.body);
+                if let Some(else_body) = &if_ex.else_body {
+                    self.continue_block();
+                    self.write("else");
+                    if let Expr::If(_) = else_body {
+                        // else-if chains stay flat rather than nesting a block per condition
+                        self.write(" ");
+                        return self.statement(else_body, terminate)
+                    }
+                    self.block(else_body);
+                }
+            },
+            Expr::Repeat(repeat) => self.header_and_block("repeat", &repeat.count, &repeat.body),
+            Expr::Switch(switch) => self.header_and_block("switch", &switch.input, &switch.body),
+            Expr::While(while_ex) => self.header_and_block("while", &while_ex.cond, &while_ex.body),
+            Expr::With(with) => {
+                self.write("with (");
+                self.value(&with.target, Place::WithTarget, false);
+                self.write(")");
+                self.block(&with.body);
+            },
+            Expr::Var(var) => self.declaration("var", &var.vars),
+            Expr::GlobalVar(var) => self.declaration("globalvar", &var.vars),
+            Expr::Case(value) => {
+                self.write("case ");
+                self.expression(value);
+                self.write(":");
+            },
+            Expr::Default => self.write("default:"),
+            Expr::Continue => self.write("continue"),
+            Expr::Break => self.write("break"),
+            Expr::Exit => self.write("exit"),
+            Expr::Return(value) => {
+                self.write("return ");
+                self.expression(value);
+            },
+            Expr::Binary(binary) if is_assignment(binary.op) => {
+                self.value(&binary.left, Place::Assigned, false);
+                self.write(" ");
+                self.operator(binary.op);
+                self.write(" ");
+                self.expression(&binary.right);
+            },
+            _ => self.expression(stmt),
+        }
+        if terminate {
+            let needs_semicolon = !matches!(
+                stmt,
+                Expr::For(_)
+                    | Expr::Group(_)
+                    | Expr::If(_)
+                    | Expr::Repeat(_)
+                    | Expr::Switch(_)
+                    | Expr::While(_)
+                    | Expr::With(_)
+                    | Expr::Case(_)
+                    | Expr::Default
+            );
+            if needs_semicolon {
+                self.write(";");
+            }
+            self.newline();
+        }
+    }
+
+    fn header_and_block(&mut self, keyword: &str, value: &Expr, body: &Expr) {
+        self.write(keyword);
+        self.write(" (");
+        self.expression(value);
+        self.write(")");
+        self.block(body);
+    }
+
+    fn declaration(&mut self, keyword: &str, vars: &[&[u8]]) {
+        self.write(keyword);
+        for (i, name) in vars.iter().enumerate() {
+            self.write(if i == 0 { " " } else { ", " });
+            self.name(name, Name::Declared);
+        }
+    }
+
+    /// Writes the body of a control statement as a block, after its header.
+    /// The cursor is left after the closing brace.
+    fn block(&mut self, body: &Expr) {
+        match self.style.braces {
+            BraceStyle::SameLine => self.write(" "),
+            BraceStyle::NextLine => {
+                self.newline();
+                self.write_indent();
+            },
+        }
+        if let Expr::Group(_) = body {
+            self.statement(body, false);
+        } else {
+            self.open_brace();
+            self.newline();
+            self.indent += 1;
+            self.statements(std::slice::from_ref(body));
+            self.indent -= 1;
+            self.write_indent();
+            self.close_brace();
+        }
+    }
+
+    /// Moves on from a closing brace to something which continues the same statement, like `else`.
+    fn continue_block(&mut self) {
+        match self.style.braces {
+            BraceStyle::SameLine => self.write(" "),
+            BraceStyle::NextLine => {
+                self.newline();
+                self.write_indent();
+            },
+        }
+    }
+
+    fn open_brace(&mut self) {
+        self.write(if self.style.begin_end { "begin" } else { "{" });
+    }
+
+    fn close_brace(&mut self) {
+        self.write(if self.style.begin_end { "end" } else { "}" });
+    }
+
+    fn name(&mut self, name: &[u8], kind: Name) {
+        match self.rewrite.name(name, kind) {
+            Some(text) => self.output.extend_from_slice(&text),
+            None => self.output.extend_from_slice(name),
+        }
+    }
+
+    fn operator(&mut self, op: Operator) {
+        match op {
+            Operator::Assign if self.style.pascal_assign => self.write(":="),
+            Operator::NotEqual if self.style.diamond => self.write("<>"),
+            _ => {
+                let _ = write!(self.output, "{}", op);
+            },
+        }
+    }
+
+    /// Writes an expression, giving the Rewrite a chance to replace it first.
+    /// If it isn't replaced and `wrap` is set, it's wrapped in parentheses.
+    fn value(&mut self, expr: &Expr, place: Place, wrap: bool) {
+        if let Some(text) = self.rewrite.expr(expr, place) {
+            self.output.extend_from_slice(&text);
+        } else if wrap {
+            self.write("(");
+            self.expr(expr);
+            self.write(")");
+        } else {
+            self.expr(expr);
+        }
+    }
+
+    fn expr(&mut self, expr: &Expr) {
+        match expr {
+            Expr::LiteralIdentifier(name) => self.output.extend_from_slice(name),
+            Expr::LiteralReal(real) => {
+                let _ = write!(self.output, "{}", real);
+            },
+            Expr::LiteralString(string) => self.string(string),
+            Expr::Unary(unary) => {
+                self.operator(unary.op);
+                // Unary operators bind tighter than any binary operator
+                let wrap = matches!(&unary.child, Expr::Binary(b) if !is_postfix(b.op));
+                self.value(&unary.child, Place::Value, wrap);
+            },
+            Expr::Binary(binary) => match binary.op {
+                Operator::Index => {
+                    self.value(&binary.left, Place::Value, !can_be_postfixed(&binary.left));
+                    self.write("[");
+                    match &binary.right {
+                        Expr::Group(dimensions) => {
+                            for (i, dimension) in dimensions.iter().enumerate() {
+                                if i != 0 {
+                                    self.write(", ");
+                                }
+                                self.expression(dimension);
+                            }
+                        },
+                        index => self.expression(index),
+                    }
+                    self.write("]");
+                },
+                Operator::Deref => {
+                    self.value(&binary.left, Place::Owner, !can_be_postfixed(&binary.left));
+                    self.write(".");
+                    self.value(&binary.right, Place::Field, false);
+                },
+                op => {
+                    self.value(&binary.left, Place::Value, needs_parens(binary, &binary.left, false));
+                    self.write(" ");
+                    self.operator(op);
+                    self.write(" ");
+                    self.value(&binary.right, Place::Value, needs_parens(binary, &binary.right, true));
+                },
+            },
+            Expr::Function(call) => {
+                self.name(call.name, Name::Function);
+                self.write("(");
+                for (i, param) in call.params.iter().enumerate() {
+                    if i != 0 {
+                        self.write(", ");
+                    }
+                    self.expression(param);
+                }
+                self.write(")");
+            },
+            stmt => self.statement(stmt, false),
+        }
+    }
+
+    /// Writes a string literal. GML has no escape sequences, so a string containing both kinds of quote
+    /// has to be written as several literals added together.
+    fn string(&mut self, string: &[u8]) {
+        let has_double = string.contains(&b'"');
+        if !has_double || !string.contains(&b'\'') {
+            let quote = if has_double { b'\'' } else { b'"' };
+            self.output.push(quote);
+            self.output.extend_from_slice(string);
+            self.output.push(quote);
+        } else {
+            self.write("(");
+            let mut rest = string;
+            while !rest.is_empty() {
+                // Runs of double quotes go in single quotes, and everything else goes in double quotes
+                let in_double = rest[0] != b'"';
+                let len = rest.iter().position(|&x| (x != b'"') != in_double).unwrap_or(rest.len());
+                if rest.len() != string.len() {
+                    self.write(" + ");
+                }
+                let quote = if in_double { b'"' } else { b'\'' };
+                self.output.push(quote);
+                self.output.extend_from_slice(&rest[..len]);
+                self.output.push(quote);
+                rest = &rest[len..];
+            }
+            self.write(")");
+        }
+    }
+}
+
+fn is_assignment(op: Operator) -> bool {
+    matches!(
+        op,
+        Operator::Assign
+            | Operator::AssignAdd
+            | Operator::AssignSubtract
+            | Operator::AssignMultiply
+            | Operator::AssignDivide
+            | Operator::AssignBitwiseAnd
+            | Operator::AssignBitwiseOr
+            | Operator::AssignBitwiseXor
+    )
+}
+
+fn is_postfix(op: Operator) -> bool {
+    matches!(op, Operator::Index | Operator::Deref)
+}
+
+/// Whether an expression can have `.` or `[]` written straight after it.
+fn can_be_postfixed(expr: &Expr) -> bool {
+    match expr {
+        Expr::LiteralIdentifier(_) => true,
+        Expr::Binary(binary) => is_postfix(binary.op),
+        _ => false,
+    }
+}
+
+/// Whether one side of a binary expression needs parentheses to keep GM8's precedence and left-associativity.
+fn needs_parens(parent: &BinaryExpr, child: &Expr, is_right: bool) -> bool {
+    match child {
+        Expr::Binary(binary) if !is_postfix(binary.op) => {
+            match (AST::get_op_precedence(&parent.op), AST::get_op_precedence(&binary.op)) {
+                (Some(parent), Some(child)) => child < parent || (is_right && child == parent),
+                _ => true,
+            }
+        },
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Helper function for the printer testcases. Also checks that the output parses back into the same code.
+    fn assert_print(input: &str, style: &Style, expected_output: &str) {
+        let ast = AST::new(input.as_bytes()).unwrap();
+        let output = print(&ast, style);
+        assert_eq!(String::from_utf8_lossy(&output), expected_output);
+        let reparsed = AST::new(&output).unwrap();
+        assert_eq!(print(&reparsed, style), output);
+    }
+
+    fn lf() -> Style {
+        Style { newline: "\n".into(), ..Style::default() }
+    }
+
+    #[test]
+    fn precedence() {
+        assert_print(
+            "a = (1 + 2) * 3 - (4 - 5) - 6 / (7 * 8); b = -(c + d) && !e.f[1, 2] or (g | h) mod 2",
+            &lf(),
+            "a = (1 + 2) * 3 - (4 - 5) - 6 / (7 * 8);\nb = -(c + d) && !e.f[1, 2] || (g | h) mod 2;\n",
+        )
+    }
+
+    #[test]
+    fn blocks() {
+        assert_print(
+            "if a b = 1 else if c begin d() end else e = 2; while x do y += 1; repeat 3 {}",
+            &lf(),
+            concat!(
+                "if (a) {\n    b = 1;\n} else if (c) {\n    d();\n} else {\n    e = 2;\n}\n",
+                "while (x) {\n    y += 1;\n}\n",
+                "repeat (3) {\n}\n",
+            ),
+        )
+    }
+
+    #[test]
+    fn loops() {
+        assert_print(
+            concat!(
+                "for (i = 0; i < 10; i += 1) { var j; globalvar k, l; with other j = i.x } ",
+                "do i -= 1 until i <= 0 return (1).x",
+            ),
+            &lf(),
+            concat!(
+                "for (i = 0; i < 10; i += 1) {\n    var j;\n    globalvar k, l;\n    with (other) {\n",
+                "        j = i.x;\n    }\n}\n",
+                "do {\n    i -= 1;\n} until (i <= 0);\n",
+                "return (1).x;\n",
+            ),
+        )
+    }
+
+    #[test]
+    fn gm8_style() {
+        let style = Style {
+            indent: "\t".into(),
+            braces: BraceStyle::NextLine,
+            begin_end: true,
+            pascal_assign: true,
+            diamond: true,
+            ..lf()
+        };
+        assert_print(
+            "switch a { case 1: b = c != d; break; default: exit }",
+            &style,
+            "switch (a)\nbegin\n\tcase 1:\n\t\tb := c <> d;\n\t\tbreak;\n\tdefault:\n\t\texit;\nend\n",
+        )
+    }
+
+    #[test]
+    fn strings() {
+        let style = lf();
+        assert_eq!(print_expression(&Expr::LiteralString(b"say \"hi\""), &style), b"'say \"hi\"'");
+        assert_eq!(
+            print_expression(&Expr::LiteralString(b"it's \"x\"\""), &style),
+            b"(\"it's \" + '\"' + \"x\" + '\"\"')",
+        );
+    }
+}
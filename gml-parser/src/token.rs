@@ -175,6 +175,36 @@ pub enum Separator {
     Then,
 }
 
+/// A region of GML source code, used to point at where a token or expression came from.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "runner-serde-derives", derive(Serialize, Deserialize))]
+pub struct Span {
+    /// Byte offset of the first byte.
+    pub start: usize,
+
+    /// Byte offset just past the last byte.
+    pub end: usize,
+
+    /// Line of the first byte, starting from 1.
+    pub line: usize,
+
+    /// Column of the first byte within its line, in bytes, starting from 1.
+    pub column: usize,
+}
+
+impl Span {
+    /// Returns a span covering everything from the start of this one to the end of another.
+    pub fn to(self, other: Span) -> Span {
+        Span { end: other.end, ..self }
+    }
+}
+
+impl fmt::Display for Span {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}, column {}", self.line, self.column)
+    }
+}
+
 impl<'a> fmt::Display for Token<'a> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {